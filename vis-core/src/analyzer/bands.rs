@@ -0,0 +1,324 @@
+//! Logarithmic Bands
+use crate::analyzer;
+
+/// Builder for LogBands
+#[derive(Debug, Default)]
+pub struct LogBandsBuilder {
+    /// Number of bands
+    ///
+    /// Ignored if [`per_octave`](#structfield.per_octave) is set.  Defaults to `30`, can also
+    /// be set from config as `"audio.bands.count"`.
+    pub count: Option<usize>,
+
+    /// Number of bands per octave, eg. `1` for octave or `3` for third-octave bands
+    ///
+    /// The count is derived from the range, rounded to whole bands.  Can also be set from
+    /// config as `"audio.bands.per_octave"`.
+    pub per_octave: Option<usize>,
+
+    /// Lower edge of the first band
+    ///
+    /// Defaults to `20.0`, can also be set from config as `"audio.bands.lowest"`.
+    pub lowest: Option<analyzer::Frequency>,
+
+    /// Upper edge of the last band
+    ///
+    /// Defaults to the Nyquist frequency of the analyzer, can also be set from config as
+    /// `"audio.bands.highest"`.
+    pub highest: Option<analyzer::Frequency>,
+}
+
+impl LogBandsBuilder {
+    /// Create a new LogBandsBuilder
+    pub fn new() -> LogBandsBuilder {
+        Default::default()
+    }
+
+    /// Set the number of bands
+    pub fn count(&mut self, count: usize) -> &mut LogBandsBuilder {
+        self.count = Some(count);
+        self
+    }
+
+    /// Set the number of bands per octave
+    pub fn per_octave(&mut self, per_octave: usize) -> &mut LogBandsBuilder {
+        self.per_octave = Some(per_octave);
+        self
+    }
+
+    /// Set the frequency range covered by the bands
+    pub fn range(
+        &mut self,
+        lowest: analyzer::Frequency,
+        highest: analyzer::Frequency,
+    ) -> &mut LogBandsBuilder {
+        self.lowest = Some(lowest);
+        self.highest = Some(highest);
+        self
+    }
+
+    /// Build LogBands around a fourier analyzer
+    pub fn build(&mut self, analyzer: analyzer::FourierAnalyzer) -> LogBands {
+        LogBands::from_builder(self, analyzer)
+    }
+}
+
+/// Weights of the bins making up a band, `weights[i]` belongs to bin `start + i`
+#[derive(Debug, Clone)]
+struct Band {
+    start: usize,
+    weights: Vec<analyzer::Sample>,
+}
+
+/// Logarithmically spaced bands on top of a fourier analyzer
+///
+/// Band edges are spaced by a constant ratio between the lowest and highest frequency, as
+/// in classic spectrum bar displays.  Each band is the mean magnitude `|z|` of the bins
+/// inside it, independent of the analyzer's
+/// [`MagnitudeMode`](../fourier/enum.MagnitudeMode.html).  Low bands narrower than the
+/// bin spacing often contain no bin at all, those are interpolated linearly between the
+/// two bins around their center.
+///
+/// Like the [`MelAnalyzer`](../mel/struct.MelAnalyzer.html)'s filterbank, the bands are
+/// computed once and again only if the bin frequencies change.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let samples = analyzer::SampleBuffer::new(32000, 8000);
+/// let fourier = analyzer::FourierBuilder::new().plan();
+/// let mut bands = analyzer::LogBandsBuilder::new()
+///     .per_octave(3)
+///     .range(25.0, 1600.0)
+///     .build(fourier);
+///
+/// bands.analyze(&samples);
+/// assert_eq!(bands.bands(0).len(), 18);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LogBands {
+    lowest: analyzer::Frequency,
+    highest: Option<analyzer::Frequency>,
+
+    analyzer: analyzer::FourierAnalyzer,
+    frequencies: Vec<analyzer::Frequency>,
+    weights: Vec<Band>,
+    bands: [Vec<analyzer::Sample>; 2],
+}
+
+impl LogBands {
+    /// Create LogBands from a builder config
+    pub fn from_builder(build: &LogBandsBuilder, analyzer: analyzer::FourierAnalyzer) -> LogBands {
+        let lowest = build
+            .lowest
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.bands.lowest", 20.0));
+        let highest = build
+            .highest
+            .or_else(|| crate::CONFIG.get("audio.bands.highest"));
+
+        let per_octave = build
+            .per_octave
+            .or_else(|| crate::CONFIG.get("audio.bands.per_octave"));
+        let count = match per_octave {
+            Some(per_octave) => {
+                let octaves = (highest.unwrap_or_else(|| analyzer.highest()) / lowest).log2();
+                ((octaves * per_octave as f32).round() as usize).max(1)
+            }
+            None => build
+                .count
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.bands.count", 30)),
+        };
+
+        let mut bands = LogBands {
+            lowest,
+            highest,
+
+            analyzer,
+            frequencies: Vec::new(),
+            weights: Vec::new(),
+            bands: [vec![0.0; count], vec![0.0; count]],
+        };
+        bands.plan();
+        bands
+    }
+
+    /// Compute the bins of each band for the current bin frequencies
+    fn plan(&mut self) {
+        self.frequencies = self.analyzer.frequencies();
+        let spacing = self.analyzer.bin_frequency(1);
+        let last = self.frequencies.len() - 1;
+
+        self.weights = (0..self.count())
+            .map(|i| {
+                let (low, high) = self.edges(i);
+                let start = self.frequencies.partition_point(|&f| f < low);
+                let end = self.frequencies.partition_point(|&f| f < high);
+                if end > start {
+                    let weight = 1.0 / (end - start) as analyzer::Sample;
+                    return Band {
+                        start,
+                        weights: vec![weight; end - start],
+                    };
+                }
+
+                let position = self.center(i) / spacing;
+                let below = (position.floor() as usize).min(last - 1);
+                let t = (position - below as f32).clamp(0.0, 1.0) as analyzer::Sample;
+                Band {
+                    start: below,
+                    weights: vec![1.0 - t, t],
+                }
+            })
+            .collect();
+    }
+
+    /// Return the number of bands
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.bands[0].len()
+    }
+
+    /// Return the wrapped fourier analyzer
+    #[inline]
+    pub fn analyzer(&self) -> &analyzer::FourierAnalyzer {
+        &self.analyzer
+    }
+
+    /// Return the wrapped fourier analyzer mutably, eg. to change the downsampling
+    #[inline]
+    pub fn analyzer_mut(&mut self) -> &mut analyzer::FourierAnalyzer {
+        &mut self.analyzer
+    }
+
+    /// Return the lower and upper edge of band `i`
+    pub fn edges(&self, i: usize) -> (analyzer::Frequency, analyzer::Frequency) {
+        let highest = self.highest.unwrap_or_else(|| self.analyzer.highest());
+        let ratio = highest / self.lowest;
+        let edge = |i: usize| self.lowest * ratio.powf(i as f32 / self.count() as f32);
+
+        // The outer edges are exact, so the range is covered without rounding errors
+        let lower = if i == 0 { self.lowest } else { edge(i) };
+        let upper = if i + 1 == self.count() {
+            highest
+        } else {
+            edge(i + 1)
+        };
+        (lower, upper)
+    }
+
+    /// Return the geometric center frequency of band `i`
+    pub fn center(&self, i: usize) -> analyzer::Frequency {
+        let (low, high) = self.edges(i);
+        (low * high).sqrt()
+    }
+
+    /// Analyze a `SampleBuffer` and compute the bands of both channels
+    pub fn analyze(&mut self, buf: &analyzer::SampleBuffer) -> [&[analyzer::Sample]; 2] {
+        self.analyzer.analyze(buf);
+        if self.analyzer.fft_len() / 2 + 1 != self.frequencies.len()
+            || self.analyzer.bin_frequency(1) != self.frequencies[1]
+        {
+            self.plan();
+        }
+
+        let (left, right) = self.analyzer.output();
+        for (bands, output) in self.bands.iter_mut().zip([left, right]) {
+            for (band, weights) in bands.iter_mut().zip(self.weights.iter()) {
+                *band = weights
+                    .weights
+                    .iter()
+                    .zip(output[weights.start..].iter())
+                    .map(|(w, c)| w * c.norm())
+                    .sum();
+            }
+        }
+
+        [&self.bands[0], &self.bands[1]]
+    }
+
+    /// Return the bands of a channel computed during the last analysis
+    ///
+    /// Band `0` is the lowest, `channel` is `0` for left and `1` for right.
+    #[inline]
+    pub fn bands(&self, channel: usize) -> &[analyzer::Sample] {
+        &self.bands[channel]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fourier() -> analyzer::FourierAnalyzer {
+        crate::test_config();
+        analyzer::FourierBuilder::new()
+            .rate(8000)
+            .length(512)
+            .window(analyzer::window::hanning)
+            .downsample(1)
+            .plan()
+    }
+
+    #[test]
+    fn test_edges() {
+        let bands = LogBandsBuilder::new()
+            .count(12)
+            .range(30.0, 3840.0)
+            .build(fourier());
+        assert_eq!(bands.count(), 12);
+        assert_eq!(bands.edges(0).0, 30.0);
+        assert_eq!(bands.edges(11).1, 3840.0);
+
+        // Contiguous with a constant ratio, 7 octaves in 12 bands
+        let ratio = (2.0f32).powf(7.0 / 12.0);
+        for i in 0..12 {
+            let (low, high) = bands.edges(i);
+            assert!((high / low - ratio).abs() < 1e-3, "{}: {} {}", i, low, high);
+            if i > 0 {
+                assert_eq!(bands.edges(i - 1).1, low);
+            }
+        }
+
+        let octaves = LogBandsBuilder::new()
+            .per_octave(3)
+            .range(25.0, 3200.0)
+            .build(fourier());
+        assert_eq!(octaves.count(), 21);
+        assert!((octaves.edges(3).0 - 50.0).abs() < 1e-3);
+
+        // The range defaults to the Nyquist frequency
+        let full = LogBandsBuilder::new().count(8).build(fourier());
+        assert_eq!(full.edges(7).1, 4000.0);
+    }
+
+    #[test]
+    fn test_bands() {
+        let mut bands = LogBandsBuilder::new()
+            .count(16)
+            .range(20.0, 4000.0)
+            .build(fourier());
+        let buf = analyzer::SampleBuffer::new(512, 8000);
+        buf.push(&crate::helpers::signal::sine(8000, 1000.0, 512.0 / 8000.0));
+        bands.analyze(&buf);
+
+        let left = bands.bands(0);
+        assert_eq!(left, bands.bands(1));
+        let strongest = (0..16)
+            .max_by(|&a, &b| left[a].partial_cmp(&left[b]).unwrap())
+            .unwrap();
+        let (low, high) = bands.edges(strongest);
+        assert!(low <= 1000.0 && high > 1000.0, "{} {}", low, high);
+
+        // The lowest bands are narrower than the 15.6 Hz bins, but not empty
+        assert!(bands.edges(0).1 - bands.edges(0).0 < 8000.0 / 512.0);
+        let lowest = &bands.weights[0];
+        assert_eq!(lowest.weights.len(), 2);
+        assert!((lowest.weights.iter().sum::<analyzer::Sample>() - 1.0).abs() < 1e-6);
+
+        // Silence is silent
+        buf.push(&vec![[0.0; 2]; 512]);
+        bands.analyze(&buf);
+        assert!(bands.bands(0).iter().all(|&b| b == 0.0));
+    }
+}
@@ -0,0 +1,295 @@
+//! Spectrum Bars
+use crate::analyzer;
+
+/// Builder for BarState
+#[derive(Debug, Default)]
+pub struct BarsBuilder {
+    /// Number of bars
+    ///
+    /// Defaults to `32`, can also be set from config as `"audio.bars.count"`.
+    pub count: Option<usize>,
+
+    /// Lower edge of the first bar
+    ///
+    /// Defaults to `20.0`, can also be set from config as `"audio.bars.lowest"`.
+    pub lowest: Option<analyzer::Frequency>,
+
+    /// Upper edge of the last bar
+    ///
+    /// Defaults to the highest frequency of the spectrum, can also be set from config as
+    /// `"audio.bars.highest"`.
+    pub highest: Option<analyzer::Frequency>,
+
+    /// Gate threshold at 1 kHz
+    ///
+    /// Bars below their threshold are zeroed, so quiet regions go dark independently of
+    /// loud ones.  Defaults to `0.0` (no gating), can also be set from config as
+    /// `"audio.bars.gate"`.
+    pub gate: Option<analyzer::SignalStrength>,
+
+    /// Factor the gate threshold changes by per octave
+    ///
+    /// Values below `1.0` lower the threshold for high frequencies, where levels are usually
+    /// lower.  Defaults to `1.0` (flat), can also be set from config as
+    /// `"audio.bars.gate_tilt"`.
+    pub gate_tilt: Option<f32>,
+}
+
+impl BarsBuilder {
+    /// Create a new BarsBuilder
+    pub fn new() -> BarsBuilder {
+        Default::default()
+    }
+
+    /// Set the number of bars
+    pub fn count(&mut self, count: usize) -> &mut BarsBuilder {
+        self.count = Some(count);
+        self
+    }
+
+    /// Set the frequency range covered by the bars
+    pub fn range(
+        &mut self,
+        lowest: analyzer::Frequency,
+        highest: analyzer::Frequency,
+    ) -> &mut BarsBuilder {
+        self.lowest = Some(lowest);
+        self.highest = Some(highest);
+        self
+    }
+
+    /// Set the gate threshold at 1 kHz and its change per octave
+    pub fn gate(&mut self, threshold: analyzer::SignalStrength, tilt: f32) -> &mut BarsBuilder {
+        self.gate = Some(threshold);
+        self.gate_tilt = Some(tilt);
+        self
+    }
+
+    /// Build the BarState
+    pub fn build(&mut self) -> BarState {
+        BarState::from_builder(self)
+    }
+}
+
+/// Bars of a spectrum
+///
+/// Groups the buckets of a spectrum into logarithmically spaced bars, so every octave gets
+/// the same number of bars.  Each bar shows the strongest bucket inside it, bars narrower
+/// than a bucket show the bucket at their center.  With a gate set, bars below the
+/// threshold at their center frequency are zeroed.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// let mut bars = analyzer::BarsBuilder::new()
+///     .count(16)
+///     .range(50.0, 4000.0)
+///     .build();
+///
+/// let spectrum = analyzer::Spectrum::new(vec![1.0; 256], 0.0, 4000.0);
+/// assert_eq!(bars.update(&spectrum), &[1.0; 16]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BarState {
+    lowest: analyzer::Frequency,
+    highest: Option<analyzer::Frequency>,
+    gate: analyzer::SignalStrength,
+    gate_tilt: f32,
+
+    bars: Vec<analyzer::SignalStrength>,
+    previous: Vec<analyzer::SignalStrength>,
+}
+
+impl BarState {
+    /// Create a BarState from a builder config
+    pub fn from_builder(build: &BarsBuilder) -> BarState {
+        let count = build
+            .count
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.bars.count", 32));
+
+        BarState {
+            lowest: build
+                .lowest
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.bars.lowest", 20.0)),
+            highest: build
+                .highest
+                .or_else(|| crate::CONFIG.get("audio.bars.highest")),
+            gate: build
+                .gate
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.bars.gate", 0.0)),
+            gate_tilt: build
+                .gate_tilt
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.bars.gate_tilt", 1.0)),
+
+            bars: vec![0.0; count],
+            previous: vec![0.0; count],
+        }
+    }
+
+    /// Return the number of bars
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.bars.len()
+    }
+
+    /// Return the bars computed during the last update
+    #[inline]
+    pub fn bars(&self) -> &[analyzer::SignalStrength] {
+        &self.bars
+    }
+
+    /// Return the rate of change of each bar per second
+    ///
+    /// Computed from the last two updates, which happened `dt` seconds apart.  Rising
+    /// bars have a positive velocity, falling ones a negative.
+    pub fn velocities(&self, dt: f32) -> impl Iterator<Item = analyzer::SignalStrength> + '_ {
+        self.bars
+            .iter()
+            .zip(self.previous.iter())
+            .map(move |(current, previous)| (current - previous) / dt)
+    }
+
+    /// Return the lower and upper edge of bar `i` for a spectrum ending at `highest`
+    pub fn edges(
+        &self,
+        i: usize,
+        highest: analyzer::Frequency,
+    ) -> (analyzer::Frequency, analyzer::Frequency) {
+        let highest = self.highest.unwrap_or(highest);
+        let ratio = highest / self.lowest;
+        let count = self.bars.len() as f32;
+
+        (
+            self.lowest * ratio.powf(i as f32 / count),
+            self.lowest * ratio.powf((i + 1) as f32 / count),
+        )
+    }
+
+    /// Return the gate threshold of bar `i` for a spectrum ending at `highest`
+    pub fn threshold(&self, i: usize, highest: analyzer::Frequency) -> analyzer::SignalStrength {
+        let (low, high) = self.edges(i, highest);
+        let center = (low * high).sqrt();
+
+        self.gate * self.gate_tilt.powf((center / 1000.0).log2())
+    }
+
+    /// Compute the bars of a spectrum
+    pub fn update<S: analyzer::spectrum::Storage>(
+        &mut self,
+        spectrum: &analyzer::Spectrum<S>,
+    ) -> &[analyzer::SignalStrength] {
+        self.previous.copy_from_slice(&self.bars);
+
+        for i in 0..self.bars.len() {
+            let (low, high) = self.edges(i, spectrum.highest());
+
+            let max = (0..spectrum.len())
+                .filter(|&j| {
+                    let f = spectrum.id_to_freq(j);
+                    f >= low && f < high
+                })
+                .map(|j| spectrum[j])
+                .fold(None, |max: Option<analyzer::SignalStrength>, v| {
+                    Some(max.map_or(v, |m| m.max(v)))
+                });
+
+            let bar = max.unwrap_or_else(|| {
+                let center = (low * high).sqrt();
+                if center >= spectrum.lowest() && center <= spectrum.highest() {
+                    spectrum[center]
+                } else {
+                    0.0
+                }
+            });
+
+            self.bars[i] = if bar < self.threshold(i, spectrum.highest()) {
+                0.0
+            } else {
+                bar
+            };
+        }
+
+        &self.bars
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bars() {
+        crate::test_config();
+        let mut spectrum = analyzer::Spectrum::new(vec![0.0; 801], 0.0, 8000.0);
+        spectrum[100.0] = 1.0;
+        spectrum[1000.0] = 0.5;
+
+        let mut bars = BarsBuilder::new().count(10).range(10.0, 10000.0).build();
+        bars.update(&spectrum);
+
+        // Three decades, 10/3 bars per decade
+        assert_eq!(bars.edges(0, 8000.0).0, 10.0);
+        assert!((bars.edges(9, 8000.0).1 - 10000.0).abs() < 1e-2);
+        assert_eq!(bars.bars()[3], 1.0);
+        assert_eq!(bars.bars()[6], 0.5);
+        assert_eq!(bars.bars().iter().filter(|&&b| b != 0.0).count(), 2);
+    }
+
+    #[test]
+    fn test_gate() {
+        let mut spectrum = analyzer::Spectrum::new(vec![0.0; 801], 0.0, 8000.0);
+        spectrum[100.0] = 1.0;
+        spectrum[1000.0] = 0.2;
+        spectrum[5000.0] = 0.2;
+
+        // Threshold halves every octave: ~3.3 at 100 Hz, 0.5 at 1 kHz, ~0.05 at 5 kHz
+        let mut bars = BarsBuilder::new()
+            .count(10)
+            .range(10.0, 10000.0)
+            .gate(0.5, 0.5)
+            .build();
+        bars.update(&spectrum);
+
+        assert!(bars.threshold(3, 8000.0) > 1.0);
+        assert_eq!(bars.bars()[3], 0.0);
+        assert_eq!(bars.bars()[6], 0.0);
+        assert_eq!(bars.bars()[8], 0.2);
+
+        // A flat gate lets the bass through
+        let mut bars = BarsBuilder::new()
+            .count(10)
+            .range(10.0, 10000.0)
+            .gate(0.5, 1.0)
+            .build();
+        bars.update(&spectrum);
+
+        assert_eq!(bars.bars()[3], 1.0);
+        assert_eq!(bars.bars()[6], 0.0);
+        assert_eq!(bars.bars()[8], 0.0);
+    }
+
+    #[test]
+    fn test_velocities() {
+        let mut bars = BarsBuilder::new()
+            .count(10)
+            .range(10.0, 10000.0)
+            .gate(0.0, 1.0)
+            .build();
+
+        let mut spectrum = analyzer::Spectrum::new(vec![0.0; 801], 0.0, 8000.0);
+        spectrum[1000.0] = 0.5;
+        bars.update(&spectrum);
+        bars.update(&spectrum);
+        assert!(bars.velocities(0.1).all(|v| v == 0.0));
+
+        // The 100 Hz bar rises, the 1 kHz bar falls
+        spectrum[100.0] = 1.0;
+        spectrum[1000.0] = 0.25;
+        bars.update(&spectrum);
+        let velocities = bars.velocities(0.1).collect::<Vec<_>>();
+        assert_eq!(velocities[3], 10.0);
+        assert_eq!(velocities[6], -2.5);
+        assert_eq!(velocities[0], 0.0);
+    }
+}
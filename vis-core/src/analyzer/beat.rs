@@ -42,6 +42,19 @@ pub struct BeatBuilder {
     ///
     /// Defaults to `8000` or `"audio.rate"`.
     pub rate: Option<usize>,
+
+    /// Minimum time in seconds between two beats
+    ///
+    /// Defaults to `0.0`, can also be set from config as `"audio.beat.refractory"`.
+    pub refractory: Option<f32>,
+
+    /// Volume, relative to the last beat's peak, the signal must fall below before the next
+    /// beat can be detected
+    ///
+    /// Together with `trigger` this forms a hysteresis which prevents a single hit with a
+    /// bumpy decay from triggering twice.  `1.0` disables the hysteresis.  Defaults to `1.0`,
+    /// can also be set from config as `"audio.beat.release"`.
+    pub release: Option<analyzer::SignalStrength>,
 }
 
 impl BeatBuilder {
@@ -90,6 +103,18 @@ impl BeatBuilder {
         self
     }
 
+    /// Set the refractory time
+    pub fn refractory(&mut self, refractory: f32) -> &mut BeatBuilder {
+        self.refractory = Some(refractory);
+        self
+    }
+
+    /// Set the release threshold
+    pub fn release(&mut self, release: analyzer::SignalStrength) -> &mut BeatBuilder {
+        self.release = Some(release);
+        self
+    }
+
     /// Build the detector
     pub fn build(&mut self) -> BeatDetector {
         BeatDetector::from_builder(self)
@@ -101,6 +126,7 @@ impl BeatBuilder {
 /// # Example
 /// ```
 /// # use vis_core::analyzer;
+/// # vis_core::default_config();
 /// # let samples = analyzer::SampleBuffer::new(32000, 8000);
 /// let mut beat = analyzer::BeatBuilder::new()
 ///     .decay(2000.0)
@@ -117,6 +143,13 @@ pub struct BeatDetector {
     decay: analyzer::SignalStrength,
     trigger: analyzer::SignalStrength,
     range: (analyzer::Frequency, analyzer::Frequency),
+    refractory: f32,
+    release: analyzer::SignalStrength,
+
+    start: std::time::Instant,
+    armed: bool,
+    last_beat_time: f32,
+    last_beat_peak: analyzer::SignalStrength,
 
     last_volume: analyzer::SignalStrength,
     last_delta: analyzer::SignalStrength,
@@ -125,6 +158,7 @@ pub struct BeatDetector {
     last_peak: analyzer::SignalStrength,
     last_valley: analyzer::SignalStrength,
 
+    groove: crate::helpers::Groove,
     analyzer: analyzer::FourierAnalyzer,
 }
 
@@ -145,6 +179,17 @@ impl BeatDetector {
                     crate::CONFIG.get_or("audio.beat.high", 100.0),
                 )
             }),
+            refractory: build
+                .refractory
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.beat.refractory", 0.0)),
+            release: build
+                .release
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.beat.release", 1.0)),
+
+            start: std::time::Instant::now(),
+            armed: true,
+            last_beat_time: f32::NEG_INFINITY,
+            last_beat_peak: 0.0,
 
             last_volume: 0.0,
             last_delta: 0.0,
@@ -153,8 +198,11 @@ impl BeatDetector {
             last_peak: 0.0,
             last_valley: 0.0,
 
+            groove: crate::helpers::Groove::new(16),
             analyzer: analyzer::FourierBuilder {
-                window: Some(analyzer::window::nuttall),
+                window: Some(analyzer::window::nuttall.into()),
+                pad_to: Some(0),
+                max_length: Some(0),
                 length: Some(
                     build
                         .fourier_length
@@ -170,6 +218,10 @@ impl BeatDetector {
                         .rate
                         .unwrap_or_else(|| crate::CONFIG.get_or("audio.rate", 8000)),
                 ),
+                // Trigger and decay are tuned for squared magnitudes
+                magnitude_mode: Some(analyzer::MagnitudeMode::Squared),
+                alignment: Some(analyzer::Alignment::End),
+                ..Default::default()
             }
             .plan(),
         }
@@ -180,10 +232,27 @@ impl BeatDetector {
         self.last_volume
     }
 
+    /// Return how steady the intervals between the last beats were
+    ///
+    /// See [`Groove::groove_stability`](../../helpers/struct.Groove.html#method.groove_stability).
+    pub fn groove_stability(&self) -> f32 {
+        self.groove.groove_stability()
+    }
+
     /// Detect a beat
     ///
-    /// Returns true if this cycle is a beat and false otherwise.
+    /// Returns true if this cycle is a beat and false otherwise.  The refractory time is
+    /// measured from the detector's creation, use [`detect_at`](#method.detect_at) to supply
+    /// timestamps yourself.
     pub fn detect(&mut self, samples: &analyzer::SampleBuffer) -> bool {
+        let time = crate::helpers::time(self.start);
+        self.detect_at(samples, time)
+    }
+
+    /// Detect a beat at `time` seconds
+    ///
+    /// Returns true if this cycle is a beat and false otherwise.
+    pub fn detect_at(&mut self, samples: &analyzer::SampleBuffer, time: f32) -> bool {
         self.analyzer.analyze(samples);
         let volume = self
             .analyzer
@@ -192,16 +261,28 @@ impl BeatDetector {
             .mean();
 
         // Decay beat_delta to allow quieter beats to be detected
-        self.last_beat_delta = self.last_beat_delta * self.decay;
+        self.last_beat_delta *= self.decay;
         let delta = volume - self.last_volume;
 
+        // Re-arm once the signal dropped far enough below the last beat
+        if !self.armed && volume < self.last_beat_peak * self.release {
+            self.armed = true;
+        }
+
         let isbeat = if delta < 0.0 && self.last_delta > 0.0 {
             self.last_peak = self.last_volume;
             let beat_delta = self.last_peak - self.last_valley;
 
             // Check if the peak is big enough
-            if beat_delta > (self.last_beat_delta * self.trigger) {
+            if beat_delta > (self.last_beat_delta * self.trigger)
+                && self.armed
+                && time - self.last_beat_time >= self.refractory
+            {
                 self.last_beat_delta = self.last_beat_delta.max(beat_delta);
+                self.last_beat_time = time;
+                self.last_beat_peak = self.last_peak;
+                self.armed = volume < self.last_peak * self.release;
+                self.groove.onset(time);
                 true
             } else {
                 false
@@ -222,3 +303,557 @@ impl BeatDetector {
         isbeat
     }
 }
+
+/// Statistic of the flux history an onset is compared against
+///
+/// Defaults to [`Median`](#variant.Median), which ignores the previous onsets themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnsetThreshold {
+    /// Median of the history
+    #[default]
+    Median,
+    /// Mean of the history
+    Mean,
+}
+
+impl std::str::FromStr for OnsetThreshold {
+    type Err = ();
+
+    /// Get the threshold statistic for the specified name
+    fn from_str(name: &str) -> Result<OnsetThreshold, ()> {
+        match name {
+            "median" => Ok(OnsetThreshold::Median),
+            "mean" => Ok(OnsetThreshold::Mean),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Builder for OnsetDetector
+#[derive(Debug, Default)]
+pub struct OnsetBuilder {
+    /// Number of past frames the threshold adapts to
+    ///
+    /// Defaults to `32`, can also be set from config as `"audio.onset.history"`.
+    pub history: Option<usize>,
+
+    /// Statistic of the history used as threshold
+    ///
+    /// Defaults to `Median`, can also be set from config as `"audio.onset.statistic"`
+    /// (`"median"` or `"mean"`).
+    pub statistic: Option<OnsetThreshold>,
+
+    /// Factor the flux must exceed the statistic by
+    ///
+    /// Defaults to `1.5`, can also be set from config as `"audio.onset.threshold"`.
+    pub threshold: Option<f32>,
+
+    /// Flux added to the threshold, so noise on a quiet signal is no onset
+    ///
+    /// Depends on the scale of the spectra.  Defaults to `0.0`, can also be set from config
+    /// as `"audio.onset.offset"`.
+    pub offset: Option<f32>,
+
+    /// Minimum number of frames between two onsets
+    ///
+    /// Defaults to `4`, can also be set from config as `"audio.onset.min_interval"`.
+    pub min_interval: Option<usize>,
+}
+
+impl OnsetBuilder {
+    /// Create a new OnsetBuilder
+    pub fn new() -> OnsetBuilder {
+        Default::default()
+    }
+
+    /// Set the history length
+    pub fn history(&mut self, history: usize) -> &mut OnsetBuilder {
+        self.history = Some(history);
+        self
+    }
+
+    /// Set the threshold statistic
+    pub fn statistic(&mut self, statistic: OnsetThreshold) -> &mut OnsetBuilder {
+        self.statistic = Some(statistic);
+        self
+    }
+
+    /// Set the threshold factor
+    pub fn threshold(&mut self, threshold: f32) -> &mut OnsetBuilder {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Set the threshold offset
+    pub fn offset(&mut self, offset: f32) -> &mut OnsetBuilder {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Set the minimum interval between onsets
+    pub fn min_interval(&mut self, min_interval: usize) -> &mut OnsetBuilder {
+        self.min_interval = Some(min_interval);
+        self
+    }
+
+    /// Build the detector
+    pub fn build(&mut self) -> OnsetDetector {
+        OnsetDetector::from_builder(self)
+    }
+}
+
+/// An onset detector with an adaptive threshold
+///
+/// Unlike the [`BeatDetector`](struct.BeatDetector.html), this consumes spectra computed
+/// elsewhere, one per frame.  The spectral flux of each frame, ie. the summed growth of all
+/// bins, is an onset if it exceeds the median (or mean) of the last `history` frames times
+/// `threshold` plus `offset`, and at least `min_interval` frames passed since the last
+/// onset.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let samples = analyzer::SampleBuffer::new(32000, 8000);
+/// # let mut mel = analyzer::MelBuilder::new().build(analyzer::FourierBuilder::new().plan());
+/// let mut onsets = analyzer::OnsetBuilder::new()
+///     .history(32)
+///     .threshold(1.5)
+///     .min_interval(4)
+///     .build();
+///
+/// mel.analyze(&samples);
+/// let isonset = onsets.process(mel.bands(0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct OnsetDetector {
+    history: usize,
+    statistic: OnsetThreshold,
+    threshold: f32,
+    offset: f32,
+    min_interval: usize,
+
+    previous: Vec<analyzer::Sample>,
+    fluxes: std::collections::VecDeque<f32>,
+    sorted: Vec<f32>,
+    frame: usize,
+    last_onset: Option<usize>,
+    flux: f32,
+}
+
+impl OnsetDetector {
+    /// Create an OnsetDetector from a builder config
+    pub fn from_builder(build: &OnsetBuilder) -> OnsetDetector {
+        let history = build
+            .history
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.onset.history", 32));
+        OnsetDetector {
+            history,
+            statistic: build.statistic.unwrap_or_else(|| {
+                crate::CONFIG
+                    .get_or("audio.onset.statistic", "median".to_string())
+                    .parse()
+                    .expect("Selected onset statistic not found!")
+            }),
+            threshold: build
+                .threshold
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.onset.threshold", 1.5)),
+            offset: build
+                .offset
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.onset.offset", 0.0)),
+            min_interval: build
+                .min_interval
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.onset.min_interval", 4)),
+
+            previous: Vec::new(),
+            fluxes: std::collections::VecDeque::with_capacity(history),
+            sorted: Vec::with_capacity(history),
+            frame: 0,
+            last_onset: None,
+            flux: 0.0,
+        }
+    }
+
+    /// Return the spectral flux of the last frame
+    #[inline]
+    pub fn flux(&self) -> f32 {
+        self.flux
+    }
+
+    /// Return the current threshold, the flux of the next frame has to exceed it
+    pub fn current_threshold(&mut self) -> f32 {
+        if self.fluxes.is_empty() {
+            return self.offset;
+        }
+
+        let statistic = match self.statistic {
+            OnsetThreshold::Mean => self.fluxes.iter().sum::<f32>() / self.fluxes.len() as f32,
+            OnsetThreshold::Median => {
+                self.sorted.clear();
+                self.sorted.extend(self.fluxes.iter());
+                let middle = self.sorted.len() / 2;
+                *self
+                    .sorted
+                    .select_nth_unstable_by(middle, |a, b| {
+                        a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .1
+            }
+        };
+        statistic * self.threshold + self.offset
+    }
+
+    /// Feed the spectrum of the next frame
+    ///
+    /// Returns true if this frame is an onset.  The first frame, and the first after the
+    /// number of bins changed, never is.
+    pub fn process(&mut self, spectrum: &[analyzer::Sample]) -> bool {
+        let frame = self.frame;
+        self.frame += 1;
+
+        if self.previous.len() != spectrum.len() {
+            self.previous.clear();
+            self.previous.extend_from_slice(spectrum);
+            self.flux = 0.0;
+            return false;
+        }
+
+        let flux = spectrum
+            .iter()
+            .zip(self.previous.iter())
+            .map(|(s, p)| (s - p).max(0.0))
+            .sum::<analyzer::Sample>();
+        self.flux = analyzer::samples::to_f32(flux);
+        self.previous.copy_from_slice(spectrum);
+
+        let isonset = self.flux > 0.0
+            && self.flux > self.current_threshold()
+            && self
+                .last_onset
+                .is_none_or(|last| frame - last >= self.min_interval);
+        if isonset {
+            self.last_onset = Some(frame);
+        }
+
+        if self.fluxes.len() == self.history {
+            self.fluxes.pop_front();
+        }
+        self.fluxes.push_back(self.flux);
+
+        isonset
+    }
+}
+
+/// Builder for TempoEstimator
+#[derive(Debug, Default)]
+pub struct TempoBuilder {
+    /// Number of onsets the estimate is based on
+    ///
+    /// Defaults to `16`, can also be set from config as `"audio.tempo.history"`.
+    pub history: Option<usize>,
+
+    /// Minimum number of onsets before a tempo is estimated
+    ///
+    /// Defaults to `4`, can also be set from config as `"audio.tempo.min_onsets"`.
+    pub min_onsets: Option<usize>,
+
+    /// Range of the estimated tempo in BPM
+    ///
+    /// Intervals outside are folded in by octaves, so the range should span at least one.
+    /// It must be positive and ascending.  Defaults to `60 - 180`, can also be set from config as `"audio.tempo.low"` and
+    /// `"audio.tempo.high"`.
+    pub range: Option<(f32, f32)>,
+}
+
+impl TempoBuilder {
+    /// Create a new TempoBuilder
+    pub fn new() -> TempoBuilder {
+        Default::default()
+    }
+
+    /// Set the number of remembered onsets
+    pub fn history(&mut self, history: usize) -> &mut TempoBuilder {
+        self.history = Some(history);
+        self
+    }
+
+    /// Set the minimum number of onsets
+    pub fn min_onsets(&mut self, min_onsets: usize) -> &mut TempoBuilder {
+        self.min_onsets = Some(min_onsets);
+        self
+    }
+
+    /// Set the tempo range
+    pub fn range(&mut self, low: f32, high: f32) -> &mut TempoBuilder {
+        self.range = Some((low, high));
+        self
+    }
+
+    /// Build the estimator
+    pub fn build(&mut self) -> TempoEstimator {
+        TempoEstimator::from_builder(self)
+    }
+}
+
+/// Width of the interval histogram bins in BPM
+const TEMPO_BIN: f32 = 2.0;
+
+/// Relative deviation from the histogram peak of intervals which refine the estimate
+const TEMPO_TOLERANCE: f32 = 0.08;
+
+/// Tempo estimation from onset times
+///
+/// Intervals between consecutive onsets are folded into the tempo range by octaves and
+/// histogrammed.  The estimate is the mean period of all intervals close to the peak of
+/// the histogram, which averages out the quantization of onsets to analysis frames.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let samples = analyzer::SampleBuffer::new(32000, 8000);
+/// # let mut mel = analyzer::MelBuilder::new().build(analyzer::FourierBuilder::new().plan());
+/// let mut onsets = analyzer::OnsetBuilder::new().build();
+/// let mut tempo = analyzer::TempoBuilder::new()
+///     .range(60.0, 180.0)
+///     .build();
+///
+/// mel.analyze(&samples);
+/// tempo.process(&samples, onsets.process(mel.bands(0)));
+/// if let Some(bpm) = tempo.bpm() {
+///     println!("{} BPM", bpm);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TempoEstimator {
+    history: usize,
+    min_onsets: usize,
+    range: (f32, f32),
+
+    onsets: std::collections::VecDeque<f64>,
+}
+
+impl TempoEstimator {
+    /// Create a TempoEstimator from a builder config
+    pub fn from_builder(build: &TempoBuilder) -> TempoEstimator {
+        let history = build
+            .history
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.tempo.history", 16));
+        let range = build.range.unwrap_or_else(|| {
+            (
+                crate::CONFIG.get_or("audio.tempo.low", 60.0),
+                crate::CONFIG.get_or("audio.tempo.high", 180.0),
+            )
+        });
+        assert!(
+            range.0 > 0.0 && range.1 > range.0,
+            "Tempo range must be positive and ascending!"
+        );
+
+        TempoEstimator {
+            history,
+            min_onsets: build
+                .min_onsets
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.tempo.min_onsets", 4)),
+            range,
+
+            onsets: std::collections::VecDeque::with_capacity(history),
+        }
+    }
+
+    /// Register an onset at `time` seconds
+    pub fn onset(&mut self, time: f64) {
+        if self.onsets.len() == self.history {
+            self.onsets.pop_front();
+        }
+        self.onsets.push_back(time);
+    }
+
+    /// Feed the onset decision of the frame analyzed from `buf`
+    ///
+    /// Onsets are timed by the number of samples pushed to the buffer and its rate, so the
+    /// estimate is independent of how often frames are analyzed.
+    pub fn process(&mut self, buf: &analyzer::SampleBuffer, isonset: bool) {
+        if isonset {
+            self.onset(buf.pushed() as f64 / buf.rate() as f64);
+        }
+    }
+
+    /// Forget all onsets, eg. when the track changes
+    pub fn reset(&mut self) {
+        self.onsets.clear();
+    }
+
+    /// Fold the interval `i` in seconds into the tempo range
+    ///
+    /// Returns the tempo in BPM and the number of beats the interval spans.
+    fn fold(&self, i: f64) -> Option<(f32, f32)> {
+        if i <= 0.0 {
+            return None;
+        }
+
+        let (mut bpm, mut beats) = ((60.0 / i) as f32, 1.0);
+        while bpm < self.range.0 {
+            bpm *= 2.0;
+            beats *= 2.0;
+        }
+        while bpm >= self.range.1 {
+            bpm /= 2.0;
+            beats /= 2.0;
+        }
+        if bpm >= self.range.0 {
+            Some((bpm, beats))
+        } else {
+            None
+        }
+    }
+
+    /// Return the estimated tempo in BPM
+    ///
+    /// `None` until `min_onsets` onsets were registered.
+    pub fn bpm(&self) -> Option<f32> {
+        if self.onsets.len() < self.min_onsets.max(2) {
+            return None;
+        }
+
+        let intervals = self
+            .onsets
+            .iter()
+            .zip(self.onsets.iter().skip(1))
+            .filter_map(|(a, b)| Some((b - a, self.fold(b - a)?)))
+            .collect::<Vec<_>>();
+
+        let mut histogram = vec![0; ((self.range.1 - self.range.0) / TEMPO_BIN).ceil() as usize];
+        let last = histogram.len() - 1;
+        for (_, (bpm, _)) in intervals.iter() {
+            histogram[(((bpm - self.range.0) / TEMPO_BIN) as usize).min(last)] += 1;
+        }
+        let peak = (0..histogram.len()).max_by_key(|&b| histogram[b])?;
+        if histogram[peak] == 0 {
+            return None;
+        }
+        let center = self.range.0 + (peak as f32 + 0.5) * TEMPO_BIN;
+
+        let (seconds, beats) = intervals
+            .iter()
+            .filter(|(_, (bpm, _))| (bpm - center).abs() <= center * TEMPO_TOLERANCE)
+            .fold((0.0, 0.0), |(seconds, beats), (i, (_, b))| {
+                (seconds + i, beats + *b as f64)
+            });
+
+        Some((60.0 * beats / seconds) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bursts every `period` frames, decaying in between, on top of low noise
+    fn pattern(period: usize, frames: usize) -> Vec<Vec<analyzer::Sample>> {
+        let mut noise = crate::helpers::signal::Noise::new(3);
+        (0..frames)
+            .map(|i| {
+                let burst = 0.5f64.powi((i % period) as i32) as analyzer::Sample;
+                (0..16).map(|_| burst + noise.next().abs() * 0.01).collect()
+            })
+            .collect()
+    }
+
+    fn onsets(detector: &mut OnsetDetector, frames: &[Vec<analyzer::Sample>]) -> Vec<usize> {
+        frames
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| detector.process(f))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[test]
+    fn test_onset_period() {
+        crate::test_config();
+        for statistic in [OnsetThreshold::Median, OnsetThreshold::Mean] {
+            let mut detector = OnsetBuilder::new()
+                .history(32)
+                .statistic(statistic)
+                .threshold(1.5)
+                .offset(0.5)
+                .min_interval(4)
+                .build();
+
+            let found = onsets(&mut detector, &pattern(10, 200));
+            assert_eq!(found.len(), 19, "{:?}: {:?}", statistic, found);
+            assert!(found.iter().all(|i| i % 10 == 0), "{:?}", found);
+        }
+    }
+
+    #[test]
+    fn test_onset_interval() {
+        crate::test_config();
+        let mut detector = OnsetBuilder::new()
+            .history(32)
+            .threshold(1.5)
+            .offset(0.5)
+            .min_interval(15)
+            .build();
+
+        // Every other burst is too close to the last onset
+        let found = onsets(&mut detector, &pattern(10, 200));
+        assert!(found.windows(2).all(|w| w[1] - w[0] == 20), "{:?}", found);
+        assert_eq!(found.len(), 10);
+    }
+
+    /// Onsets at `bpm`, quantized to frames of 128 samples at 8 kHz
+    fn tempo(bpm: f64, skip: impl Fn(usize) -> bool) -> TempoEstimator {
+        let mut tempo = TempoBuilder::new()
+            .history(16)
+            .min_onsets(4)
+            .range(60.0, 180.0)
+            .build();
+        let buf = analyzer::SampleBuffer::new(128, 8000);
+
+        let period = 60.0 / bpm * 8000.0 / 128.0;
+        let mut next = 0;
+        for frame in 0..2000 {
+            buf.push(&[[0.0; 2]; 128]);
+            let isonset = frame == (next as f64 * period).round() as usize;
+            if isonset {
+                next += 1;
+            }
+            tempo.process(&buf, isonset && !skip(next));
+        }
+        tempo
+    }
+
+    #[test]
+    fn test_tempo() {
+        crate::test_config();
+        let bpm = tempo(120.0, |_| false).bpm().unwrap();
+        assert!((bpm - 120.0).abs() < 2.0, "{}", bpm);
+
+        // Slow and fast tempos are folded into the range
+        let bpm = tempo(45.0, |_| false).bpm().unwrap();
+        assert!((bpm - 90.0).abs() < 2.0, "{}", bpm);
+        let bpm = tempo(200.0, |_| false).bpm().unwrap();
+        assert!((bpm - 100.0).abs() < 2.0, "{}", bpm);
+    }
+
+    #[test]
+    fn test_tempo_missed() {
+        crate::test_config();
+        // Every third onset is missed
+        let bpm = tempo(120.0, |i| i % 3 == 0).bpm().unwrap();
+        assert!((bpm - 120.0).abs() < 2.0, "{}", bpm);
+
+        let mut tempo = TempoBuilder::new().min_onsets(4).build();
+        for i in 0..3 {
+            tempo.onset(i as f64 * 0.5);
+        }
+        assert_eq!(tempo.bpm(), None);
+        tempo.onset(1.5);
+        assert_eq!(tempo.bpm(), Some(120.0));
+
+        tempo.reset();
+        assert_eq!(tempo.bpm(), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! Chromagram
+use crate::analyzer;
+
+/// Builder for ChromaAnalyzer
+#[derive(Debug, Default)]
+pub struct ChromaBuilder {
+    /// Frequency of A4 the pitch classes are tuned to
+    ///
+    /// Defaults to `440.0`, can also be set from config as `"audio.chroma.tuning"`.
+    pub tuning: Option<analyzer::Frequency>,
+
+    /// Frequency range folded into the pitch classes
+    ///
+    /// Low bins are too wide to tell semitones apart.  Defaults to `50 Hz - 5000 Hz`, can
+    /// also be set from config as `"audio.chroma.low"` and `"audio.chroma.high"`.
+    pub range: Option<(analyzer::Frequency, analyzer::Frequency)>,
+}
+
+impl ChromaBuilder {
+    /// Create a new ChromaBuilder
+    pub fn new() -> ChromaBuilder {
+        Default::default()
+    }
+
+    /// Set the frequency of A4
+    pub fn tuning(&mut self, tuning: analyzer::Frequency) -> &mut ChromaBuilder {
+        self.tuning = Some(tuning);
+        self
+    }
+
+    /// Set the frequency range
+    pub fn range(
+        &mut self,
+        low: analyzer::Frequency,
+        high: analyzer::Frequency,
+    ) -> &mut ChromaBuilder {
+        self.range = Some((low, high));
+        self
+    }
+
+    /// Build the ChromaAnalyzer around a fourier analyzer
+    pub fn build(&mut self, analyzer: analyzer::FourierAnalyzer) -> ChromaAnalyzer {
+        ChromaAnalyzer::from_builder(self, analyzer)
+    }
+}
+
+/// Pitch class profile on top of a fourier analyzer
+///
+/// The power `|z|²` of each transform bin in range is added to the pitch class
+/// nearest its center frequency, independent of the analyzer's
+/// [`MagnitudeMode`](../fourier/enum.MagnitudeMode.html).  Class `0` is C, like
+/// [`PitchClass`](../note/struct.PitchClass.html), and the strongest class is normalized
+/// to `1.0`.
+///
+/// Like the [`MelAnalyzer`](../mel/struct.MelAnalyzer.html)'s filterbank, the mapping of
+/// bins to pitch classes is computed once and again only if the bin frequencies change.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let samples = analyzer::SampleBuffer::new(32000, 8000);
+/// let fourier = analyzer::FourierBuilder::new().length(2048).plan();
+/// let mut chroma = analyzer::ChromaBuilder::new()
+///     .tuning(440.0)
+///     .range(50.0, 4000.0)
+///     .build(fourier);
+///
+/// chroma.analyze(&samples);
+/// let a = chroma.chroma(0)[9];
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChromaAnalyzer {
+    tuning: analyzer::Frequency,
+    range: (analyzer::Frequency, analyzer::Frequency),
+
+    analyzer: analyzer::FourierAnalyzer,
+    frequencies: Vec<analyzer::Frequency>,
+    classes: Vec<Option<u8>>,
+    chroma: [[analyzer::Sample; 12]; 2],
+}
+
+impl ChromaAnalyzer {
+    /// Create a ChromaAnalyzer from a builder config
+    pub fn from_builder(
+        build: &ChromaBuilder,
+        analyzer: analyzer::FourierAnalyzer,
+    ) -> ChromaAnalyzer {
+        let mut chroma = ChromaAnalyzer {
+            tuning: build
+                .tuning
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.chroma.tuning", 440.0)),
+            range: build.range.unwrap_or_else(|| {
+                (
+                    crate::CONFIG.get_or("audio.chroma.low", 50.0),
+                    crate::CONFIG.get_or("audio.chroma.high", 5000.0),
+                )
+            }),
+
+            analyzer,
+            frequencies: Vec::new(),
+            classes: Vec::new(),
+            chroma: [[0.0; 12]; 2],
+        };
+        chroma.plan();
+        chroma
+    }
+
+    /// Compute the pitch class of each bin for the current bin frequencies
+    fn plan(&mut self) {
+        self.frequencies = self.analyzer.frequencies();
+        self.classes = self
+            .frequencies
+            .iter()
+            .map(|&f| {
+                if f < self.range.0 || f > self.range.1 {
+                    return None;
+                }
+                // A is 9 semitones above C
+                let semitones = (12.0 * (f / self.tuning).log2()).round() as i32 + 9;
+                Some(semitones.rem_euclid(12) as u8)
+            })
+            .collect();
+    }
+
+    /// Return the wrapped fourier analyzer
+    #[inline]
+    pub fn analyzer(&self) -> &analyzer::FourierAnalyzer {
+        &self.analyzer
+    }
+
+    /// Return the wrapped fourier analyzer mutably, eg. to change the downsampling
+    #[inline]
+    pub fn analyzer_mut(&mut self) -> &mut analyzer::FourierAnalyzer {
+        &mut self.analyzer
+    }
+
+    /// Analyze a `SampleBuffer` and compute the chroma of both channels
+    pub fn analyze(&mut self, buf: &analyzer::SampleBuffer) -> [&[analyzer::Sample; 12]; 2] {
+        self.analyzer.analyze(buf);
+        if self.analyzer.fft_len() / 2 + 1 != self.frequencies.len()
+            || self.analyzer.bin_frequency(1) != self.frequencies[1]
+        {
+            self.plan();
+        }
+
+        let (left, right) = self.analyzer.output();
+        for (chroma, output) in self.chroma.iter_mut().zip([left, right]) {
+            *chroma = [0.0; 12];
+            for (class, c) in self.classes.iter().zip(output.iter()) {
+                if let Some(class) = class {
+                    chroma[*class as usize] += c.norm_sqr();
+                }
+            }
+
+            let max = chroma.iter().cloned().fold(0.0, analyzer::Sample::max);
+            if max > 0.0 {
+                for c in chroma.iter_mut() {
+                    *c /= max;
+                }
+            }
+        }
+
+        [&self.chroma[0], &self.chroma[1]]
+    }
+
+    /// Return the chroma of a channel computed during the last analysis
+    ///
+    /// Class `0` is C, `channel` is `0` for left and `1` for right.  Silence is all zeros.
+    #[inline]
+    pub fn chroma(&self, channel: usize) -> &[analyzer::Sample; 12] {
+        &self.chroma[channel]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chroma(tuning: analyzer::Frequency, freq: analyzer::Frequency) -> [analyzer::Sample; 12] {
+        crate::test_config();
+        let fourier = analyzer::FourierBuilder::new()
+            .rate(8000)
+            .length(2048)
+            .window(analyzer::window::hanning)
+            .downsample(1)
+            .plan();
+        let mut chroma = ChromaBuilder::new()
+            .tuning(tuning)
+            .range(50.0, 4000.0)
+            .build(fourier);
+
+        let buf = analyzer::SampleBuffer::new(2048, 8000);
+        buf.push(&crate::helpers::signal::sine(8000, freq, 2048.0 / 8000.0));
+        chroma.analyze(&buf);
+        assert_eq!(chroma.chroma(0), chroma.chroma(1));
+        *chroma.chroma(0)
+    }
+
+    fn strongest(chroma: &[analyzer::Sample; 12]) -> usize {
+        (0..12)
+            .max_by(|&a, &b| chroma[a].partial_cmp(&chroma[b]).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_a440() {
+        let a = chroma(440.0, 440.0);
+        assert_eq!(strongest(&a), 9);
+        assert_eq!(a[9], 1.0);
+        let rest = a.iter().sum::<analyzer::Sample>() - a[9];
+        assert!(rest < 0.01, "{:?}", a);
+
+        // An octave up and down is the same class
+        assert_eq!(strongest(&chroma(440.0, 880.0)), 9);
+        assert_eq!(strongest(&chroma(440.0, 220.0)), 9);
+
+        // Middle C and the semitone above A
+        assert_eq!(strongest(&chroma(440.0, 261.63)), 0);
+        assert_eq!(strongest(&chroma(440.0, 466.16)), 10);
+    }
+
+    #[test]
+    fn test_tuning() {
+        // Half a semitone off A440 is A when tuned to it
+        assert_eq!(strongest(&chroma(427.5, 427.5)), 9);
+        assert_eq!(strongest(&chroma(453.0, 453.0)), 9);
+
+        let fourier = analyzer::FourierBuilder::new().rate(8000).plan();
+        let mut silent = ChromaBuilder::new().build(fourier);
+        silent.analyze(&analyzer::SampleBuffer::new(8000, 8000));
+        assert_eq!(silent.chroma(0), &[0.0; 12]);
+    }
+}
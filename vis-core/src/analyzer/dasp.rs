@@ -0,0 +1,80 @@
+//! `dasp` Interoperability
+//!
+//! Enable the `dasp` feature to push [`dasp`](https://docs.rs/dasp) frames of any sample
+//! format and channel count into a `SampleBuffer` and to read it back as a `dasp::Signal`.
+//! Stereo frames of this crate (`[Sample; 2]`) already are `dasp` frames, so are spectrum
+//! buckets `dasp` samples.
+//!
+//! # Example
+//! ```
+//! # use vis_core::analyzer;
+//! use dasp::Signal;
+//!
+//! let buffer = analyzer::SampleBuffer::new(4, 8000);
+//! buffer.push_frames(&[[16384i16; 2], [0; 2]]);
+//!
+//! let frames = buffer.signal().take(4).collect::<Vec<_>>();
+//! assert_eq!(frames[2], [0.5; 2]);
+//! ```
+use crate::analyzer::{Sample, SampleBuffer};
+use dasp::sample::{Sample as _, ToSample};
+
+/// Signal over a snapshot of a `SampleBuffer`
+pub type SnapshotSignal = dasp::signal::FromIterator<std::vec::IntoIter<[Sample; 2]>>;
+
+/// Convert a `dasp` frame to a stereo sample
+///
+/// Mono frames are copied to both channels, only the first two channels of frames with
+/// more channels are used.
+pub fn to_stereo<F>(frame: F) -> [Sample; 2]
+where
+    F: dasp::Frame,
+    F::Sample: ToSample<Sample>,
+{
+    let mut channels = frame.channels().map(|s| s.to_sample::<Sample>());
+    let left = channels.next().unwrap_or(0.0);
+    let right = channels.next().unwrap_or(left);
+
+    [left, right]
+}
+
+impl SampleBuffer {
+    /// Push a slice of `dasp` frames to the buffer
+    ///
+    /// See [`to_stereo`](fn.to_stereo.html) for how frames are converted.
+    pub fn push_frames<F>(&self, frames: &[F])
+    where
+        F: dasp::Frame,
+        F::Sample: ToSample<Sample>,
+    {
+        self.push(&frames.iter().map(|f| to_stereo(*f)).collect::<Vec<_>>());
+    }
+
+    /// Return a `dasp::Signal` over a snapshot of the buffer, oldest sample first
+    pub fn signal(&self) -> SnapshotSignal {
+        dasp::signal::from_iter(self.snapshot())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dasp::Signal;
+
+    #[test]
+    fn test_round_trip() {
+        let buffer = SampleBuffer::new(4, 8000);
+        let frames: [[Sample; 2]; 4] = [[0.1, -0.1], [0.2, -0.2], [0.3, -0.3], [0.4, -0.4]];
+
+        buffer.push_frames(&frames);
+        assert_eq!(buffer.snapshot(), frames);
+        assert_eq!(buffer.signal().take(4).collect::<Vec<_>>(), frames);
+
+        buffer.push_frames(&[[i16::MIN, 0], [0, i16::MIN]]);
+        buffer.push_frames(&[[0.25f64], [-0.5f64]]);
+        assert_eq!(
+            buffer.snapshot(),
+            [[-1.0, 0.0], [0.0, -1.0], [0.25, 0.25], [-0.5, -0.5]]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Envelope Following
+use crate::analyzer;
+
+/// Builder for EnvelopeFollower
+#[derive(Debug, Default)]
+pub struct EnvelopeBuilder {
+    /// Time constant for rising levels in milliseconds
+    ///
+    /// Defaults to `10.0`, can also be set from config as `"audio.envelope.attack"`.
+    pub attack: Option<f32>,
+
+    /// Time constant for falling levels in milliseconds
+    ///
+    /// Defaults to `100.0`, can also be set from config as `"audio.envelope.release"`.
+    pub release: Option<f32>,
+}
+
+impl EnvelopeBuilder {
+    /// Create a new EnvelopeBuilder
+    pub fn new() -> EnvelopeBuilder {
+        Default::default()
+    }
+
+    /// Set the attack time constant in milliseconds
+    pub fn attack(&mut self, attack: f32) -> &mut EnvelopeBuilder {
+        self.attack = Some(attack);
+        self
+    }
+
+    /// Set the release time constant in milliseconds
+    pub fn release(&mut self, release: f32) -> &mut EnvelopeBuilder {
+        self.release = Some(release);
+        self
+    }
+
+    /// Build the follower
+    pub fn build(&mut self) -> EnvelopeFollower {
+        EnvelopeFollower::from_builder(self)
+    }
+}
+
+/// Per-channel amplitude envelope of the time-domain signal
+///
+/// A one-pole follower on the absolute sample values with separate time constants for
+/// rising and falling levels.  Each call to [`process`](#method.process) consumes the
+/// samples pushed since the previous one, so the envelope is sample-accurate no matter
+/// how often it is called.  Much cheaper than a transform for simple pulsing effects.
+///
+/// After a step the envelope covers `1 - 1/e` (about 63%) of the distance to the new
+/// level within one time constant.  Coefficients are computed for the rate of the
+/// `SampleBuffer` and recomputed if it changes.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let samples = analyzer::SampleBuffer::new(8000, 8000);
+/// let mut envelope = analyzer::EnvelopeBuilder::new()
+///     .attack(5.0)
+///     .release(200.0)
+///     .build();
+///
+/// let (left, right) = envelope.process(&samples);
+/// ```
+#[derive(Debug, Clone)]
+pub struct EnvelopeFollower {
+    attack: f32,
+    release: f32,
+
+    rate: usize,
+    coefficients: (f64, f64),
+    pushed: u64,
+    envelope: [f64; 2],
+}
+
+impl EnvelopeFollower {
+    /// Create an EnvelopeFollower from a builder config
+    pub fn from_builder(build: &EnvelopeBuilder) -> EnvelopeFollower {
+        EnvelopeFollower {
+            attack: build
+                .attack
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.envelope.attack", 10.0)),
+            release: build
+                .release
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.envelope.release", 100.0)),
+
+            rate: 0,
+            coefficients: (0.0, 0.0),
+            pushed: 0,
+            envelope: [0.0; 2],
+        }
+    }
+
+    /// Compute the coefficients for `rate`
+    fn plan(&mut self, rate: usize) {
+        let coefficient = |ms: f32| {
+            let samples = ms as f64 / 1000.0 * rate as f64;
+            if samples > 0.0 {
+                (-1.0 / samples).exp()
+            } else {
+                0.0
+            }
+        };
+
+        self.rate = rate;
+        self.coefficients = (coefficient(self.attack), coefficient(self.release));
+    }
+
+    /// Follow the samples pushed to `buf` since the last call and return the envelopes
+    pub fn process(&mut self, buf: &analyzer::SampleBuffer) -> (f32, f32) {
+        if buf.rate() != self.rate {
+            self.plan(buf.rate());
+        }
+
+        let (pushed, samples) = buf.since(self.pushed);
+        self.pushed = pushed;
+
+        let (attack, release) = self.coefficients;
+        for s in samples.iter() {
+            for (envelope, x) in self.envelope.iter_mut().zip(s.iter()) {
+                let x = (analyzer::samples::to_f32(*x) as f64).abs();
+                let coefficient = if x > *envelope { attack } else { release };
+                *envelope = x + coefficient * (*envelope - x);
+            }
+        }
+
+        self.envelope()
+    }
+
+    /// Return the envelopes of the left and right channel after the last call
+    #[inline]
+    pub fn envelope(&self) -> (f32, f32) {
+        (self.envelope[0] as f32, self.envelope[1] as f32)
+    }
+
+    /// Drop the envelope to silence
+    pub fn reset(&mut self) {
+        self.envelope = [0.0; 2];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step() {
+        crate::test_config();
+        let mut envelope = EnvelopeBuilder::new().attack(10.0).release(50.0).build();
+        let buf = analyzer::SampleBuffer::new(8000, 8000);
+        assert_eq!(envelope.process(&buf), (0.0, 0.0));
+
+        // One attack time constant is 80 samples, pushed in chunks
+        for _ in 0..4 {
+            buf.push(&[[1.0, -0.5]; 20]);
+            envelope.process(&buf);
+        }
+        let (left, right) = envelope.envelope();
+        let expected = 1.0 - (-1.0f32).exp();
+        assert!((left - expected).abs() < 0.01, "{}", left);
+        assert!((right - 0.5 * expected).abs() < 0.01, "{}", right);
+
+        // Settles, then decays by 1/e over one release time constant
+        buf.push(&[[1.0, -0.5]; 800]);
+        let (left, _) = envelope.process(&buf);
+        assert!(left > 0.999);
+        buf.push(&[[0.0; 2]; 400]);
+        let (left, right) = envelope.process(&buf);
+        assert!((left - (-1.0f32).exp()).abs() < 0.01, "{}", left);
+        assert!((right - 0.5 * (-1.0f32).exp()).abs() < 0.01, "{}", right);
+
+        // Nothing new, nothing changes
+        assert_eq!(envelope.process(&buf), (left, right));
+        envelope.reset();
+        assert_eq!(envelope.envelope(), (0.0, 0.0));
+    }
+}
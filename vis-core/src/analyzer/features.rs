@@ -0,0 +1,166 @@
+//! Scalar Spectral Features
+use crate::analyzer;
+
+/// Scalar features of the spectrum on top of a fourier analyzer
+///
+/// Features are computed from the
+/// [`magnitudes`](../fourier/struct.FourierAnalyzer.html#method.magnitudes) of the last
+/// transform, independent of the analyzer's
+/// [`MagnitudeMode`](../fourier/enum.MagnitudeMode.html).  Meant for mapping the character
+/// of the sound to eg. colors or brightness.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let samples = analyzer::SampleBuffer::new(32000, 8000);
+/// let fourier = analyzer::FourierBuilder::new().plan();
+/// let mut features = analyzer::SpectralFeatures::new(fourier);
+///
+/// features.analyze(&samples);
+/// let brightness = features.spectral_centroid(0);
+/// let change = features.spectral_flux(0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SpectralFeatures {
+    analyzer: analyzer::FourierAnalyzer,
+    previous: [Vec<analyzer::SignalStrength>; 2],
+}
+
+impl SpectralFeatures {
+    /// Create SpectralFeatures around a fourier analyzer
+    pub fn new(analyzer: analyzer::FourierAnalyzer) -> SpectralFeatures {
+        SpectralFeatures {
+            analyzer,
+            previous: [Vec::new(), Vec::new()],
+        }
+    }
+
+    /// Return the wrapped fourier analyzer
+    #[inline]
+    pub fn analyzer(&self) -> &analyzer::FourierAnalyzer {
+        &self.analyzer
+    }
+
+    /// Return the wrapped fourier analyzer mutably, eg. to change the downsampling
+    #[inline]
+    pub fn analyzer_mut(&mut self) -> &mut analyzer::FourierAnalyzer {
+        &mut self.analyzer
+    }
+
+    /// Analyze a `SampleBuffer` with the wrapped analyzer
+    pub fn analyze(&mut self, buf: &analyzer::SampleBuffer) {
+        self.analyzer.analyze(buf);
+    }
+
+    /// Return the spectral centroid of `channel` (`0` is left) in Hz
+    ///
+    /// The magnitude-weighted mean frequency of the last transform, higher for brighter
+    /// sounds.  Silence has a centroid of `0.0`.
+    pub fn spectral_centroid(&self, channel: usize) -> analyzer::Frequency {
+        let (left, right) = self.analyzer.output();
+        let output = if channel == 0 { left } else { right };
+        let (weighted, total) =
+            output
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(weighted, total), (bin, c)| {
+                    let m = c.norm() as analyzer::SignalStrength;
+                    (weighted + self.analyzer.bin_frequency(bin) * m, total + m)
+                });
+
+        if total > 0.0 {
+            weighted / total
+        } else {
+            0.0
+        }
+    }
+
+    /// Return the spectral flux of `channel` (`0` is left)
+    ///
+    /// The summed growth of all bins since the previous call for the same channel, so call
+    /// it once per analysis.  The first call, and the first after the number of bins
+    /// changed, returns `0.0`.
+    pub fn spectral_flux(&mut self, channel: usize) -> analyzer::SignalStrength {
+        let (left, right) = self.analyzer.output();
+        let output = if channel == 0 { left } else { right };
+        let previous = &mut self.previous[channel];
+
+        if previous.len() != output.len() {
+            previous.clear();
+            previous.extend(output.iter().map(|c| c.norm() as analyzer::SignalStrength));
+            return 0.0;
+        }
+
+        let mut flux = 0.0;
+        for (p, c) in previous.iter_mut().zip(output.iter()) {
+            let m = c.norm() as analyzer::SignalStrength;
+            flux += (m - *p).max(0.0);
+            *p = m;
+        }
+        flux
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features() -> (SpectralFeatures, analyzer::SampleBuffer) {
+        crate::test_config();
+        let fourier = analyzer::FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .window(analyzer::window::hanning)
+            .downsample(1)
+            .plan();
+        (
+            SpectralFeatures::new(fourier),
+            analyzer::SampleBuffer::new(256, 8000),
+        )
+    }
+
+    #[test]
+    fn test_centroid() {
+        let (mut features, buf) = features();
+        features.analyze(&buf);
+        assert_eq!(features.spectral_centroid(0), 0.0);
+
+        // A rising sweep raises the centroid
+        let mut last = 0.0;
+        for freq in [200.0, 500.0, 1000.0, 2000.0, 3000.0] {
+            buf.push(&crate::helpers::signal::sine(8000, freq, 256.0 / 8000.0));
+            features.analyze(&buf);
+
+            let centroid = features.spectral_centroid(0);
+            assert!(centroid > last, "{} <= {}", centroid, last);
+            assert!((centroid - freq).abs() < 100.0, "{} Hz: {}", freq, centroid);
+            last = centroid;
+        }
+    }
+
+    #[test]
+    fn test_flux() {
+        let (mut features, buf) = features();
+        features.analyze(&buf);
+        assert_eq!(features.spectral_flux(0), 0.0);
+        features.analyze(&buf);
+        assert_eq!(features.spectral_flux(0), 0.0);
+
+        // An onset spikes, a steady tone doesn't
+        let sine = crate::helpers::signal::sine(8000, 1000.0, 256.0 / 8000.0);
+        buf.push(&sine);
+        features.analyze(&buf);
+        let onset = features.spectral_flux(0);
+        assert!(onset > 10.0, "{}", onset);
+
+        buf.push(&sine);
+        features.analyze(&buf);
+        assert!(features.spectral_flux(0) < onset * 0.01);
+
+        // Fading out has no flux
+        buf.push(&vec![[0.0; 2]; 256]);
+        features.analyze(&buf);
+        assert_eq!(features.spectral_flux(0), 0.0);
+    }
+}
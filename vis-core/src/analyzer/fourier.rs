@@ -5,7 +5,10 @@ use crate::analyzer;
 /// Window functions
 ///
 /// A window-function in this case takes a size and should return a `Vec` of that length filled
-/// with the precomputed window coefficients.  The following are available by default:
+/// with the precomputed window coefficients.  Parametrized windows have a `_with` variant
+/// returning a closure, which can be passed to
+/// [`FourierBuilder::window`](../struct.FourierBuilder.html#method.window).  The following are
+/// available by default:
 ///
 /// * [None / Rectangle](fn.none.html)
 ///
@@ -25,89 +28,603 @@ use crate::analyzer;
 /// * [Nuttall](fn.nuttall.html)
 ///
 /// ![Nuttall Window](https://upload.wikimedia.org/wikipedia/commons/thumb/a/a4/Window_function_and_frequency_response_-_Nuttall_%28continuous_first_derivative%29.svg/512px-Window_function_and_frequency_response_-_Nuttall_%28continuous_first_derivative%29.svg.png)
+/// * [Flat Top](fn.flattop.html)
+/// * [Kaiser](fn.kaiser.html), [Gaussian](fn.gaussian.html) and [Tukey](fn.tukey.html),
+///   which take a shape parameter
 pub mod window {
+    use crate::analyzer::Sample;
+
+    pub(super) const PI: Sample = std::f64::consts::PI as Sample;
+
+    /// A window function, possibly carrying parameters
+    #[derive(Clone)]
+    pub struct Window(std::sync::Arc<dyn Fn(usize) -> Vec<Sample> + Send + Sync>);
+
+    impl Window {
+        /// Wrap a window function
+        pub fn new<F: Fn(usize) -> Vec<Sample> + Send + Sync + 'static>(f: F) -> Window {
+            Window(std::sync::Arc::new(f))
+        }
+
+        /// Compute the coefficients for a window of `size`
+        pub fn coefficients(&self, size: usize) -> Vec<Sample> {
+            (self.0)(size)
+        }
+    }
+
+    impl<F: Fn(usize) -> Vec<Sample> + Send + Sync + 'static> From<F> for Window {
+        fn from(f: F) -> Window {
+            Window::new(f)
+        }
+    }
+
+    impl std::fmt::Debug for Window {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "Window({:p})", &*self.0)
+        }
+    }
+
+    /// Position of sample `i` in a window of `size`, from `0.0` to `1.0`
+    fn position(i: usize, size: usize) -> Sample {
+        if size > 1 {
+            i as Sample / (size - 1) as Sample
+        } else {
+            0.5
+        }
+    }
+
+    /// Modified Bessel function of the first kind, order zero
+    fn bessel_i0(x: Sample) -> Sample {
+        let mut sum = 1.0;
+        let mut term = 1.0;
+        for k in 1..50 {
+            term *= (x / (2.0 * k as Sample)).powi(2);
+            sum += term;
+            if term < sum * 1e-9 {
+                break;
+            }
+        }
+        sum
+    }
+
     /// Blackman Window
     ///
     /// ![Blackman Window](https://upload.wikimedia.org/wikipedia/commons/thumb/3/38/Window_function_and_frequency_response_-_Blackman.svg/512px-Window_function_and_frequency_response_-_Blackman.svg.png)
-    pub fn blackman(size: usize) -> Vec<f32> {
-        apodize::blackman_iter(size).map(|f| f as f32).collect()
+    pub fn blackman(size: usize) -> Vec<Sample> {
+        apodize::blackman_iter(size).map(|f| f as Sample).collect()
     }
 
     /// Hamming Window
     ///
     /// ![Hamming Window](https://upload.wikimedia.org/wikipedia/commons/thumb/7/76/Window_function_and_frequency_response_-_Hamming_%28alpha_%3D_0.53836%29.svg/512px-Window_function_and_frequency_response_-_Hamming_%28alpha_%3D_0.53836%29.svg.png)
-    pub fn hamming(size: usize) -> Vec<f32> {
-        apodize::hamming_iter(size).map(|f| f as f32).collect()
+    pub fn hamming(size: usize) -> Vec<Sample> {
+        apodize::hamming_iter(size).map(|f| f as Sample).collect()
     }
 
     /// Hanning Window
     ///
     /// ![Hanning Window](https://upload.wikimedia.org/wikipedia/commons/thumb/b/b3/Window_function_and_frequency_response_-_Hann.svg/512px-Window_function_and_frequency_response_-_Hann.svg.png)
-    pub fn hanning(size: usize) -> Vec<f32> {
-        apodize::hanning_iter(size).map(|f| f as f32).collect()
+    pub fn hanning(size: usize) -> Vec<Sample> {
+        apodize::hanning_iter(size).map(|f| f as Sample).collect()
     }
 
     /// No window function / Rectangle window
     ///
     /// ![Rectangle Window](https://upload.wikimedia.org/wikipedia/commons/thumb/6/6a/Window_function_and_frequency_response_-_Rectangular.svg/512px-Window_function_and_frequency_response_-_Rectangular.svg.png)
-    pub fn none(size: usize) -> Vec<f32> {
+    pub fn none(size: usize) -> Vec<Sample> {
         vec![1.0; size]
     }
 
     /// Nuttall Window
     ///
     /// ![Nuttall Window](https://upload.wikimedia.org/wikipedia/commons/thumb/a/a4/Window_function_and_frequency_response_-_Nuttall_%28continuous_first_derivative%29.svg/512px-Window_function_and_frequency_response_-_Nuttall_%28continuous_first_derivative%29.svg.png)
-    pub fn nuttall(size: usize) -> Vec<f32> {
-        apodize::nuttall_iter(size).map(|f| f as f32).collect()
+    pub fn nuttall(size: usize) -> Vec<Sample> {
+        apodize::nuttall_iter(size).map(|f| f as Sample).collect()
     }
 
     /// Sine Window
     ///
     /// ![Sine Window](https://upload.wikimedia.org/wikipedia/commons/thumb/e/e5/Window_function_and_frequency_response_-_Cosine.svg/512px-Window_function_and_frequency_response_-_Cosine.svg.png)
-    pub fn sine(size: usize) -> Vec<f32> {
+    pub fn sine(size: usize) -> Vec<Sample> {
         (0..size)
-            .map(|i| (i as f32 / (size - 1) as f32 * std::f32::consts::PI).sin())
+            .map(|i| (i as Sample / (size - 1) as Sample * PI).sin())
             .collect()
     }
 
     /// Triangular Window
     ///
     /// ![Triangular Window](https://upload.wikimedia.org/wikipedia/commons/thumb/5/5b/Window_function_and_frequency_response_-_Triangular.svg/512px-Window_function_and_frequency_response_-_Triangular.svg.png)
-    pub fn triangular(size: usize) -> Vec<f32> {
-        apodize::triangular_iter(size).map(|f| f as f32).collect()
+    pub fn triangular(size: usize) -> Vec<Sample> {
+        apodize::triangular_iter(size)
+            .map(|f| f as Sample)
+            .collect()
+    }
+
+    /// Flat Top Window
+    ///
+    /// Very wide main lobe, but the amplitude of a sinusoid is measured accurately wherever
+    /// it falls between two buckets.
+    pub fn flattop(size: usize) -> Vec<Sample> {
+        const A: [Sample; 5] = [
+            0.215_578_95,
+            0.416_631_58,
+            0.277_263_16,
+            0.083_578_95,
+            0.006_947_37,
+        ];
+
+        (0..size)
+            .map(|i| {
+                let x = 2.0 * PI * position(i, size);
+                A[0] - A[1] * x.cos() + A[2] * (2.0 * x).cos() - A[3] * (3.0 * x).cos()
+                    + A[4] * (4.0 * x).cos()
+            })
+            .collect()
+    }
+
+    /// Kaiser Window
+    ///
+    /// `beta` trades main lobe width for side lobe level: `0.0` is the rectangle window,
+    /// `8.6` is similar to the Blackman window.
+    pub fn kaiser(size: usize, beta: Sample) -> Vec<Sample> {
+        (0..size)
+            .map(|i| {
+                let x = 2.0 * position(i, size) - 1.0;
+                bessel_i0(beta * (1.0 - x * x).max(0.0).sqrt()) / bessel_i0(beta)
+            })
+            .collect()
+    }
+
+    /// Kaiser Window with a fixed `beta`, see [`kaiser`](fn.kaiser.html)
+    pub fn kaiser_with(beta: Sample) -> impl Fn(usize) -> Vec<Sample> + Clone + Send + Sync {
+        move |size| kaiser(size, beta)
+    }
+
+    /// Gaussian Window
+    ///
+    /// `sigma` is the standard deviation relative to half the window, usually `0.5` or less.
+    pub fn gaussian(size: usize, sigma: Sample) -> Vec<Sample> {
+        (0..size)
+            .map(|i| {
+                let x = (2.0 * position(i, size) - 1.0) / sigma;
+                (-0.5 * x * x).exp()
+            })
+            .collect()
+    }
+
+    /// Gaussian Window with a fixed `sigma`, see [`gaussian`](fn.gaussian.html)
+    pub fn gaussian_with(sigma: Sample) -> impl Fn(usize) -> Vec<Sample> + Clone + Send + Sync {
+        move |size| gaussian(size, sigma)
+    }
+
+    /// Tukey Window
+    ///
+    /// Flat in the middle with cosine tapers, `alpha` is the fraction of the window which is
+    /// tapered: `0.0` is the rectangle window, `1.0` the Hanning window.
+    pub fn tukey(size: usize, alpha: Sample) -> Vec<Sample> {
+        (0..size)
+            .map(|i| {
+                let x = position(i, size);
+                let edge = x.min(1.0 - x);
+                if alpha <= 0.0 || edge >= alpha / 2.0 {
+                    1.0
+                } else {
+                    0.5 * (1.0 - (2.0 * PI * edge / alpha).cos())
+                }
+            })
+            .collect()
+    }
+
+    /// Tukey Window with a fixed `alpha`, see [`tukey`](fn.tukey.html)
+    pub fn tukey_with(alpha: Sample) -> impl Fn(usize) -> Vec<Sample> + Clone + Send + Sync {
+        move |size| tukey(size, alpha)
     }
 
     /// Get the window function for the specified name
-    pub fn from_str(name: &str) -> Option<fn(usize) -> Vec<f32>> {
+    ///
+    /// Parametrized windows take their parameter after a colon, eg. `"kaiser:8.6"`.
+    /// Without one, Kaiser uses a `beta` of `8.6`, Gaussian a `sigma` of `0.4` and Tukey an
+    /// `alpha` of `0.5`.
+    pub fn from_str(name: &str) -> Option<Window> {
+        let (name, param) = match name.split_once(':') {
+            Some((name, param)) => (name, Some(param.trim().parse::<Sample>().ok()?)),
+            None => (name, None),
+        };
+
+        let fixed = |f: fn(usize) -> Vec<Sample>| param.is_none().then(|| Window::new(f));
         match name {
-            "blackman" => Some(blackman),
-            "hamming" => Some(hamming),
-            "hanning" => Some(hanning),
-            "none" => Some(none),
-            "nuttall" => Some(nuttall),
-            "sine" => Some(sine),
-            "triangular" => Some(triangular),
+            "blackman" => fixed(blackman),
+            "hamming" => fixed(hamming),
+            "hanning" => fixed(hanning),
+            "none" => fixed(none),
+            "nuttall" => fixed(nuttall),
+            "sine" => fixed(sine),
+            "triangular" => fixed(triangular),
+            "flattop" => fixed(flattop),
+            "kaiser" => Some(Window::new(kaiser_with(param.unwrap_or(8.6)))),
+            "gaussian" => Some(Window::new(gaussian_with(param.unwrap_or(0.4)))),
+            "tukey" => Some(Window::new(tukey_with(param.unwrap_or(0.5)))),
             _ => None,
         }
     }
 }
 
+/// Conversion from the complex fourier output to a signal strength
+///
+/// Defaults to [`Magnitude`](#variant.Magnitude).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MagnitudeMode {
+    /// Magnitude `|z|`
+    #[default]
+    Magnitude,
+    /// Squared magnitude `|z|²`
+    Squared,
+    /// Real part `Re(z)`
+    Real,
+    /// Logarithmic magnitude `ln(1 + |z|)`
+    Log,
+}
+
+/// Point of the analyzed window a [`timestamp`](struct.FourierAnalyzer.html#method.timestamp)
+/// refers to
+///
+/// Defaults to [`Center`](#variant.Center), where the spectrum is most representative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Alignment {
+    /// Oldest sample of the window
+    Start,
+    /// Middle of the window
+    #[default]
+    Center,
+    /// Newest sample of the window
+    End,
+}
+
+impl std::str::FromStr for Alignment {
+    type Err = ();
+
+    /// Get the alignment for the specified name
+    fn from_str(name: &str) -> Result<Alignment, ()> {
+        match name {
+            "start" => Ok(Alignment::Start),
+            "center" => Ok(Alignment::Center),
+            "end" => Ok(Alignment::End),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Channels fed into the two transforms
+///
+/// Defaults to [`LeftRight`](#variant.LeftRight).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StereoMode {
+    /// Left channel first, right channel second
+    #[default]
+    LeftRight,
+    /// Mid `(l + r) / 2` first, side `(l - r) / 2` second
+    MidSide,
+}
+
+impl std::str::FromStr for StereoMode {
+    type Err = ();
+
+    /// Get the stereo mode for the specified name
+    fn from_str(name: &str) -> Result<StereoMode, ()> {
+        match name {
+            "leftright" | "lr" => Ok(StereoMode::LeftRight),
+            "midside" | "ms" => Ok(StereoMode::MidSide),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Scaling of the transform output, compensating for the window
+///
+/// Windows attenuate the signal, so without normalization levels depend on the selected
+/// window.  Defaults to [`None`](#variant.None).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Normalization {
+    /// Raw transform output
+    #[default]
+    None,
+    /// Divide by half the sum of the window coefficients
+    ///
+    /// A sinusoid of amplitude `1.0` centered on a bucket has a magnitude of `1.0`.
+    Amplitude,
+    /// Divide by the square root of `fft_len` times the sum of the squared window coefficients
+    ///
+    /// The squared magnitudes add up to half the mean square of the windowed signal, eg.
+    /// `0.25` for a sinusoid of amplitude `1.0`, independent of the window and padding.
+    Energy,
+}
+
+impl Normalization {
+    /// Return the factor the transform output is scaled by
+    pub fn scale(self, window: &[Sample], fft_len: usize) -> Sample {
+        match self {
+            Normalization::None => 1.0,
+            Normalization::Amplitude => 2.0 / window.iter().sum::<Sample>(),
+            Normalization::Energy => {
+                1.0 / (fft_len as Sample * window.iter().map(|w| w * w).sum::<Sample>()).sqrt()
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Normalization {
+    type Err = ();
+
+    /// Get the normalization for the specified name
+    fn from_str(name: &str) -> Result<Normalization, ()> {
+        match name {
+            "none" => Ok(Normalization::None),
+            "amplitude" => Ok(Normalization::Amplitude),
+            "energy" => Ok(Normalization::Energy),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Implementation of the fourier transform
+///
+/// The analyzer asks the backend whether a transform length is supported before planning
+/// it and falls back to the nearest supported length otherwise, see
+/// [`FourierAnalyzer::fft_len`](struct.FourierAnalyzer.html#method.fft_len).  The default
+/// [`RustFft`](struct.RustFft.html) supports all lengths.
+pub trait FftBackend: std::fmt::Debug + Send + Sync {
+    /// Return whether transforms of `len` points can be planned
+    fn supports(&self, _len: usize) -> bool {
+        true
+    }
+
+    /// Plan a forward transform of `len` real points
+    fn plan(&self, len: usize) -> std::sync::Arc<dyn realfft::RealToComplex<Sample>>;
+}
+
+/// Planner shared by all analyzers, which caches plans by length
+static PLANNER: parking_lot::Mutex<Option<realfft::RealFftPlanner<Sample>>> =
+    parking_lot::Mutex::new(None);
+
+/// Run `f` with the shared planner
+pub(crate) fn with_planner<R>(f: impl FnOnce(&mut realfft::RealFftPlanner<Sample>) -> R) -> R {
+    f(PLANNER
+        .lock()
+        .get_or_insert_with(realfft::RealFftPlanner::new))
+}
+
+/// Backend using `rustfft`, through its real-valued wrapper `realfft`
+///
+/// Plans are cached for the whole process, so analyzers of the same transform length
+/// share one plan.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RustFft;
+
+impl FftBackend for RustFft {
+    fn plan(&self, len: usize) -> std::sync::Arc<dyn realfft::RealToComplex<Sample>> {
+        with_planner(|planner| planner.plan_fft_forward(len))
+    }
+}
+
+/// Shortest transform for which both channels are transformed concurrently
+///
+/// Spawning the thread costs more than a shorter transform takes.
+const PARALLEL_MIN_LEN: usize = 4096;
+
+/// Design the anti-aliasing low-pass for downsampling by `factor`
+///
+/// Cutoff at the downsampled Nyquist frequency, see [`windowed_sinc`].
+fn lowpass(factor: usize) -> Vec<Sample> {
+    windowed_sinc(0.5 / factor as Sample, 16 * factor + 1)
+}
+
+/// Design a Blackman windowed sinc low-pass with `taps` coefficients
+///
+/// `cutoff` is given relative to the sample rate.  The taps are normalized to unity gain
+/// at DC.
+pub(crate) fn windowed_sinc(cutoff: Sample, taps: usize) -> Vec<Sample> {
+    let center = (taps / 2) as Sample;
+
+    let mut h = window::blackman(taps)
+        .into_iter()
+        .enumerate()
+        .map(|(i, w)| {
+            let x = 2.0 * cutoff * (i as Sample - center);
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (window::PI * x).sin() / (window::PI * x)
+            };
+            sinc * w
+        })
+        .collect::<Vec<_>>();
+    let sum = h.iter().sum::<Sample>();
+    for c in h.iter_mut() {
+        *c /= sum;
+    }
+    h
+}
+
+/// Error when the backend supports no transform length near the requested one
+///
+/// Lengths up to four times as far from the requested one as it is from zero are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsupportedLength(pub usize);
+
+impl std::fmt::Display for UnsupportedLength {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "No supported transform length near {}", self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedLength {}
+
+/// Return the supported length closest to `len` which is at least `min`
+///
+/// Ties are resolved towards the longer transform.
+fn supported_len(
+    backend: &dyn FftBackend,
+    len: usize,
+    min: usize,
+) -> Result<usize, UnsupportedLength> {
+    if backend.supports(len) {
+        return Ok(len);
+    }
+
+    let n = (1..=len * 4)
+        .flat_map(|d| [Some(len + d), len.checked_sub(d)])
+        .flatten()
+        .find(|&n| n >= min.max(1) && backend.supports(n))
+        .ok_or(UnsupportedLength(len))?;
+    log::warn!(
+        "Transform length {} is not supported, using {} instead",
+        len,
+        n
+    );
+    Ok(n)
+}
+
+/// Largest prime factor of transform lengths considered fast
+///
+/// `rustfft` handles any length, but lengths with larger prime factors fall back to much
+/// slower algorithms.
+const FAST_MAX_FACTOR: usize = 7;
+
+/// Return whether `len` has no prime factor larger than `FAST_MAX_FACTOR`
+fn is_fast_len(mut len: usize) -> bool {
+    if len == 0 {
+        return false;
+    }
+    for factor in 2..=FAST_MAX_FACTOR {
+        while len.is_multiple_of(factor) {
+            len /= factor;
+        }
+    }
+    len == 1
+}
+
+/// Return the shortest fast length supported by the backend which is at least `len`
+///
+/// Searches up to four times `len`, like [`supported_len`].
+fn fast_len(backend: &dyn FftBackend, len: usize) -> Option<usize> {
+    (len..=len * 4).find(|&n| is_fast_len(n) && backend.supports(n))
+}
+
+/// Negotiate window length and padding with the backend
+///
+/// When padding, only the padded length is adjusted, otherwise the window length is.  Slow
+/// transform lengths are padded to the next fast one if `pad_to_fast` is set and warned
+/// about otherwise.
+fn negotiate(
+    backend: &dyn FftBackend,
+    length: usize,
+    pad_to: usize,
+    pad_to_fast: bool,
+) -> Result<(usize, usize), UnsupportedLength> {
+    let (length, pad_to) = if pad_to > length {
+        (length, supported_len(backend, pad_to, length)?)
+    } else {
+        let length = supported_len(backend, length, 1)?;
+        (length, pad_to.min(length))
+    };
+
+    let fft_len = pad_to.max(length);
+    let fast = if pad_to_fast && !is_fast_len(fft_len) {
+        fast_len(backend, fft_len)
+    } else {
+        None
+    };
+    Ok(if is_fast_len(fft_len) {
+        (length, pad_to)
+    } else if let Some(fast) = fast {
+        log::debug!("Padding transform length {} to {}", fft_len, fast);
+        (length, fast)
+    } else {
+        log::warn!(
+            "Transform length {} has a prime factor larger than {}, the transform will be slow",
+            fft_len,
+            FAST_MAX_FACTOR
+        );
+        (length, pad_to)
+    })
+}
+
+impl MagnitudeMode {
+    /// Reduce a single complex bucket
+    #[inline]
+    pub fn apply(self, c: rustfft::num_complex::Complex<Sample>) -> analyzer::SignalStrength {
+        (match self {
+            MagnitudeMode::Magnitude => c.norm(),
+            MagnitudeMode::Squared => c.norm_sqr(),
+            MagnitudeMode::Real => c.re,
+            MagnitudeMode::Log => c.norm().ln_1p(),
+        }) as analyzer::SignalStrength
+    }
+}
+
+impl std::str::FromStr for MagnitudeMode {
+    type Err = ();
+
+    /// Get the magnitude mode for the specified name
+    fn from_str(name: &str) -> Result<MagnitudeMode, ()> {
+        match name {
+            "magnitude" => Ok(MagnitudeMode::Magnitude),
+            "squared" => Ok(MagnitudeMode::Squared),
+            "real" => Ok(MagnitudeMode::Real),
+            "log" => Ok(MagnitudeMode::Log),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Function mapping a stereo frame to the sample fed into the transform
+///
+/// See [`FourierBuilder::channel_fn`](struct.FourierBuilder.html#method.channel_fn).
+#[derive(Clone)]
+pub struct ChannelFn(pub std::sync::Arc<dyn Fn([Sample; 2]) -> Sample + Send + Sync>);
+
+impl std::fmt::Debug for ChannelFn {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ChannelFn({:p})", &*self.0)
+    }
+}
+
 /// Builder for FourierAnalyzer
 #[derive(Debug, Default)]
 pub struct FourierBuilder {
-    /// Length of the fourier transform
+    /// Length of the analyzed window
     ///
-    /// Most efficient if this is a power of two
+    /// This many (downsampled) samples are read from the `SampleBuffer` and windowed.
+    /// Unless padding is requested with [`pad_to`](#structfield.pad_to), this is also the
+    /// length of the fourier transform.  Most efficient if this is a power of two
     ///
     /// Can also be set from config as `"audio.fourier.length"`.
     pub length: Option<usize>,
 
+    /// Zero-pad the window to this length before transforming
+    ///
+    /// Padding interpolates the spectrum: the transform has more, narrower spaced buckets,
+    /// but the frequency resolution is still limited by the window length.  Values not
+    /// larger than `length` disable padding.  Use
+    /// [`FourierAnalyzer::fft_len`](struct.FourierAnalyzer.html#method.fft_len) for bucket
+    /// math.
+    ///
+    /// Can also be set from config as `"audio.fourier.pad_to"`.
+    pub pad_to: Option<usize>,
+
+    /// Longest window length passed to
+    /// [`FourierAnalyzer::set_length`](struct.FourierAnalyzer.html#method.set_length)
+    ///
+    /// Buffers are allocated for this length up front.  *Experimental*.  Defaults to
+    /// `length`, can also be set from config as `"audio.fourier.max_length"`.
+    pub max_length: Option<usize>,
+
     /// Window Function
     ///
     /// A few window functions are defined in the [`window`](window/index.html) module.
     ///
     /// Can also be set from config as `"audio.fourier.window"`.
-    pub window: Option<fn(usize) -> Vec<f32>>,
+    pub window: Option<window::Window>,
 
     /// Downsampling factor
     ///
@@ -116,10 +633,76 @@ pub struct FourierBuilder {
 
     /// Rate of the captured data
     ///
-    /// `FourierAnalyzer` will panic if the `SampleBuffer`'s rate does not match.
+    /// If the `SampleBuffer`'s rate does not match (eg. because the recorder captures at the
+    /// device's native rate), `FourierAnalyzer` scales the downsampling factor so the analyzed
+    /// rate stays close to `rate / downsample`.
     ///
     /// Can also be set from config as `"audio.rate"`.
     pub rate: Option<usize>,
+
+    /// Conversion from complex buckets to signal strengths
+    ///
+    /// Can also be set from config as `"audio.fourier.magnitude"`.
+    pub magnitude_mode: Option<MagnitudeMode>,
+
+    /// Point of the analyzed window reported as its timestamp
+    ///
+    /// Can also be set from config as `"audio.fourier.alignment"`.
+    pub alignment: Option<Alignment>,
+
+    /// Fourier transform implementation
+    ///
+    /// Defaults to [`RustFft`](struct.RustFft.html).
+    pub backend: Option<std::sync::Arc<dyn FftBackend>>,
+
+    /// Channel processing before the transform
+    ///
+    /// Maps each `[left, right]` frame to a single sample, eg. `l - r` for the side
+    /// channel.  Both spectra then show the result.  Defaults to analyzing left and right
+    /// separately, takes precedence over the [`stereo_mode`](#structfield.stereo_mode).
+    pub channel_fn: Option<ChannelFn>,
+
+    /// Channels analyzed as the first and second spectrum
+    ///
+    /// With [`MidSide`](enum.StereoMode.html#variant.MidSide), all accessors for the left
+    /// channel return the mid and those for the right channel the side spectrum.  Defaults to
+    /// [`LeftRight`](enum.StereoMode.html#variant.LeftRight), can also be set from config as
+    /// `"audio.fourier.stereo_mode"`.
+    pub stereo_mode: Option<StereoMode>,
+
+    /// Low-pass filter the samples before downsampling
+    ///
+    /// Without it, content above the downsampled Nyquist frequency aliases into the
+    /// spectrum.  The filter cuts off at the downsampled Nyquist frequency and delays the
+    /// signal by `8 * downsample` samples.  Defaults to `false`, can also be set from config
+    /// as `"audio.fourier.antialias"`.
+    pub antialias: Option<bool>,
+
+    /// Scaling of the transform output
+    ///
+    /// Can also be set from config as `"audio.fourier.normalize"`.
+    pub normalize: Option<Normalization>,
+
+    /// Transform both channels on the calling thread
+    ///
+    /// Otherwise transforms of at least 4096 points run on two threads.  Defaults to
+    /// `false`, can also be set from config as `"audio.fourier.single_thread"`.
+    pub single_thread: Option<bool>,
+
+    /// Remove the DC offset of each analyzed frame
+    ///
+    /// Subtracts the window-weighted mean of each channel, so a biased capture device does
+    /// not show up as a spike in the lowest buckets.  Defaults to `false`, can also be set
+    /// from config as `"audio.fourier.remove_dc"`.
+    pub remove_dc: Option<bool>,
+
+    /// Zero-pad slow transform lengths to the next fast one
+    ///
+    /// Lengths with a prime factor larger than 7 are much slower to transform.  If set, such
+    /// lengths are padded like with [`pad_to`](#structfield.pad_to), otherwise a warning is
+    /// logged.  Defaults to `false`, can also be set from config as
+    /// `"audio.fourier.pad_to_fast"`.
+    pub pad_to_fast: Option<bool>,
 }
 
 impl FourierBuilder {
@@ -134,9 +717,30 @@ impl FourierBuilder {
         self
     }
 
+    /// Set the length to zero-pad the window to
+    pub fn pad_to(&mut self, length: usize) -> &mut FourierBuilder {
+        self.pad_to = Some(length);
+        self
+    }
+
+    /// Enable or disable padding slow transform lengths to fast ones
+    pub fn pad_to_fast(&mut self, enabled: bool) -> &mut FourierBuilder {
+        self.pad_to_fast = Some(enabled);
+        self
+    }
+
+    /// Set the longest window length used with variable length analysis
+    pub fn max_length(&mut self, length: usize) -> &mut FourierBuilder {
+        self.max_length = Some(length);
+        self
+    }
+
     /// Set the window function
-    pub fn window(&mut self, f: fn(usize) -> Vec<f32>) -> &mut FourierBuilder {
-        self.window = Some(f);
+    ///
+    /// Takes plain window functions like `window::hanning`, closures like
+    /// `window::kaiser_with(8.6)` and windows from `window::from_str`.
+    pub fn window<W: Into<window::Window>>(&mut self, window: W) -> &mut FourierBuilder {
+        self.window = Some(window.into());
         self
     }
 
@@ -152,23 +756,176 @@ impl FourierBuilder {
         self
     }
 
+    /// Set the conversion from complex buckets to signal strengths
+    pub fn magnitude_mode(&mut self, mode: MagnitudeMode) -> &mut FourierBuilder {
+        self.magnitude_mode = Some(mode);
+        self
+    }
+
+    /// Set the point of the window timestamps refer to
+    pub fn alignment(&mut self, alignment: Alignment) -> &mut FourierBuilder {
+        self.alignment = Some(alignment);
+        self
+    }
+
+    /// Set the fourier transform implementation
+    pub fn backend(&mut self, backend: std::sync::Arc<dyn FftBackend>) -> &mut FourierBuilder {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Set a function combining both channels into the analyzed signal
+    pub fn channel_fn(
+        &mut self,
+        f: Box<dyn Fn([Sample; 2]) -> Sample + Send + Sync>,
+    ) -> &mut FourierBuilder {
+        self.channel_fn = Some(ChannelFn(f.into()));
+        self
+    }
+
+    /// Set the channels to analyze
+    pub fn stereo_mode(&mut self, mode: StereoMode) -> &mut FourierBuilder {
+        self.stereo_mode = Some(mode);
+        self
+    }
+
+    /// Set the scaling of the transform output
+    pub fn normalize(&mut self, normalization: Normalization) -> &mut FourierBuilder {
+        self.normalize = Some(normalization);
+        self
+    }
+
+    /// Transform both channels sequentially on the calling thread
+    pub fn single_thread(&mut self, single_thread: bool) -> &mut FourierBuilder {
+        self.single_thread = Some(single_thread);
+        self
+    }
+
+    /// Enable or disable the low-pass filter before downsampling
+    pub fn antialias(&mut self, enabled: bool) -> &mut FourierBuilder {
+        self.antialias = Some(enabled);
+        self
+    }
+
+    /// Enable or disable removing the DC offset
+    pub fn remove_dc(&mut self, enabled: bool) -> &mut FourierBuilder {
+        self.remove_dc = Some(enabled);
+        self
+    }
+
     /// Plan the fourier transform and prepare buffers
+    ///
+    /// If the backend does not support the requested length, the nearest supported one is
+    /// used and a warning is logged.
+    ///
+    /// # Panics
+    /// If the backend supports no length near the requested one, see
+    /// [`try_plan`](#method.try_plan).
     pub fn plan(&mut self) -> FourierAnalyzer {
+        self.try_plan()
+            .unwrap_or_else(|e| panic!("Can't plan fourier transform: {}", e))
+    }
+
+    /// Plan the fourier transform, failing if the backend supports no nearby length
+    pub fn try_plan(&mut self) -> Result<FourierAnalyzer, UnsupportedLength> {
         let length = self
             .length
             .unwrap_or_else(|| crate::CONFIG.get_or("audio.fourier.length", 512));
-        let window = (self.window.unwrap_or_else(|| {
+        let pad_to = self
+            .pad_to
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.fourier.pad_to", 0));
+        let max_length = self
+            .max_length
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.fourier.max_length", 0));
+        let pad_to_fast = self
+            .pad_to_fast
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.fourier.pad_to_fast", false));
+        let window = self.window.clone().unwrap_or_else(|| {
             window::from_str(&crate::CONFIG.get_or("audio.fourier.window", "none".to_string()))
                 .expect("Selected window type not found!")
-        }))(length);
+        });
         let downsample = self
             .downsample
             .unwrap_or_else(|| crate::CONFIG.get_or("audio.fourier.downsample", 5));
         let rate = self
             .rate
             .unwrap_or_else(|| crate::CONFIG.get_or("audio.rate", 8000));
+        let magnitude_mode = self.magnitude_mode.unwrap_or_else(|| {
+            crate::CONFIG
+                .get_or("audio.fourier.magnitude", "magnitude".to_string())
+                .parse()
+                .expect("Selected magnitude mode not found!")
+        });
+        let alignment = self.alignment.unwrap_or_else(|| {
+            crate::CONFIG
+                .get_or("audio.fourier.alignment", "center".to_string())
+                .parse()
+                .expect("Selected alignment not found!")
+        });
+
+        let backend = self
+            .backend
+            .clone()
+            .unwrap_or_else(|| std::sync::Arc::new(RustFft));
+
+        let mut fa = FourierAnalyzer::new(
+            length,
+            max_length.max(length),
+            pad_to,
+            pad_to_fast,
+            window,
+            downsample,
+            rate,
+            magnitude_mode,
+            backend,
+        )?;
+        fa.alignment = alignment;
+        fa.channel_fn = self.channel_fn.clone();
+        fa.stereo_mode = self.stereo_mode.unwrap_or_else(|| {
+            crate::CONFIG
+                .get_or("audio.fourier.stereo_mode", "leftright".to_string())
+                .parse()
+                .expect("Selected stereo mode not found!")
+        });
+        fa.antialias = self
+            .antialias
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.fourier.antialias", false));
+        fa.single_thread = self
+            .single_thread
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.fourier.single_thread", false));
+        fa.remove_dc = self
+            .remove_dc
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.fourier.remove_dc", false));
+        fa.normalization = self.normalize.unwrap_or_else(|| {
+            crate::CONFIG
+                .get_or("audio.fourier.normalize", "none".to_string())
+                .parse()
+                .expect("Selected normalization not found!")
+        });
+        Ok(fa)
+    }
+}
+
+/// Window coefficients and transform for one window length
+#[derive(Clone)]
+struct Plan {
+    length: usize,
+    window: Vec<Sample>,
+    fft: std::sync::Arc<dyn realfft::RealToComplex<Sample>>,
+}
 
-        FourierAnalyzer::new(length, window, downsample, rate)
+impl Plan {
+    fn new(
+        length: usize,
+        fft_len: usize,
+        window: &window::Window,
+        backend: &dyn FftBackend,
+    ) -> Plan {
+        Plan {
+            length,
+            window: window.coefficients(length),
+            fft: backend.plan(fft_len),
+        }
     }
 }
 
@@ -177,6 +934,7 @@ impl FourierBuilder {
 /// # Example
 /// ```
 /// # use vis_core::analyzer::fourier::*;
+/// # vis_core::default_config();
 /// let analyzer = FourierBuilder::new()
 ///     .length(512)
 ///     .window(window::nuttall)
@@ -187,68 +945,138 @@ impl FourierBuilder {
 #[derive(Clone)]
 pub struct FourierAnalyzer {
     length: usize,
+    max_length: usize,
+    pad_to: usize,
+    pad_to_fast: bool,
+    fft_len: usize,
     buckets: usize,
-    window: Vec<Sample>,
+    window_fn: window::Window,
     downsample: usize,
+    magnitude_mode: MagnitudeMode,
+    alignment: Alignment,
+    backend: std::sync::Arc<dyn FftBackend>,
+    channel_fn: Option<ChannelFn>,
+    stereo_mode: StereoMode,
+    antialias: bool,
+    normalization: Normalization,
+    single_thread: bool,
+    remove_dc: bool,
+    /// Anti-aliasing filter taps, designed for the current downsampling factor on demand
+    lowpass: Vec<Sample>,
+    /// Undecimated samples read for filtering
+    raw: Vec<[Sample; 2]>,
 
     rate: usize,
     lowest: analyzer::Frequency,
     highest: analyzer::Frequency,
 
-    fft: std::sync::Arc<dyn rustfft::Fft<Sample>>,
+    /// Plans for all lengths used so far, the active one is `plans[plan]`
+    plans: Vec<Plan>,
+    plan: usize,
 
-    input: [Vec<rustfft::num_complex::Complex<Sample>>; 2],
-    output: Vec<rustfft::num_complex::Complex<Sample>>,
+    input: [Vec<Sample>; 2],
+    output: [Vec<rustfft::num_complex::Complex<Sample>>; 2],
 
     spectra: [analyzer::Spectrum<Vec<analyzer::SignalStrength>>; 2],
     average: analyzer::Spectrum<Vec<analyzer::SignalStrength>>,
+    reference: Option<analyzer::Spectrum<Vec<analyzer::SignalStrength>>>,
+
+    /// Number of samples pushed up to the end of the last analyzed window
+    window_end: u64,
 }
 
 impl std::fmt::Debug for FourierAnalyzer {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "FourierAnalyzer {{ length: {:?}, downsample: {:?}, lowest: {:?}, highest: {:?} }}",
-            self.length, self.downsample, self.lowest, self.highest,
+            "FourierAnalyzer {{ length: {:?}, fft_len: {:?}, downsample: {:?}, lowest: {:?}, highest: {:?} }}",
+            self.length, self.fft_len, self.downsample, self.lowest, self.highest,
         )
     }
 }
 
 impl FourierAnalyzer {
-    fn new(length: usize, window: Vec<f32>, downsample: usize, rate: usize) -> FourierAnalyzer {
-        use rustfft::num_traits::Zero;
-
-        let fft = rustfft::FftPlanner::new().plan_fft_forward(length);
-        let buckets = length / 2;
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        length: usize,
+        max_length: usize,
+        pad_to: usize,
+        pad_to_fast: bool,
+        window_fn: window::Window,
+        downsample: usize,
+        rate: usize,
+        magnitude_mode: MagnitudeMode,
+        backend: std::sync::Arc<dyn FftBackend>,
+    ) -> Result<FourierAnalyzer, UnsupportedLength> {
+        let (length, fft_pad) = negotiate(&*backend, length, pad_to, pad_to_fast)?;
+        let max_length = max_length.max(length);
+        let fft_len = fft_pad.max(length);
+        let plan = Plan::new(length, fft_len, &window_fn, &*backend);
+        let max_fft_len = pad_to.max(max_length);
+        let max_fft_len = if pad_to_fast {
+            fast_len(&*backend, max_fft_len).unwrap_or(max_fft_len)
+        } else {
+            max_fft_len
+        };
+        let buckets = fft_len / 2;
 
         let downsampled_rate = rate as f32 / downsample as f32;
-        let lowest = downsampled_rate / length as f32;
+        let lowest = downsampled_rate / fft_len as f32;
         let highest = downsampled_rate / 2.0;
 
+        let spectrum = || {
+            let mut buf = Vec::with_capacity(max_fft_len / 2);
+            buf.resize(buckets, 0.0);
+            analyzer::Spectrum::new(buf, lowest, highest)
+        };
+
         let fa = FourierAnalyzer {
             length,
+            max_length,
+            pad_to,
+            pad_to_fast,
+            fft_len,
             buckets,
-            window,
+            window_fn,
             downsample,
+            magnitude_mode,
+            alignment: Alignment::default(),
+            backend,
+            channel_fn: None,
+            stereo_mode: StereoMode::LeftRight,
+            antialias: false,
+            normalization: Normalization::None,
+            single_thread: false,
+            remove_dc: false,
+            lowpass: Vec::new(),
+            raw: Vec::new(),
 
             rate,
             lowest,
             highest,
 
-            fft,
+            plans: vec![plan],
+            plan: 0,
 
-            input: [Vec::with_capacity(length), Vec::with_capacity(length)],
-            output: vec![rustfft::num_complex::Complex::zero(); length],
-
-            spectra: [
-                analyzer::Spectrum::new(vec![0.0; buckets], lowest, highest),
-                analyzer::Spectrum::new(vec![0.0; buckets], lowest, highest),
+            input: [
+                Vec::with_capacity(max_fft_len),
+                Vec::with_capacity(max_fft_len),
+            ],
+            output: [
+                Vec::with_capacity(max_fft_len),
+                Vec::with_capacity(max_fft_len),
             ],
-            average: analyzer::Spectrum::new(vec![0.0; buckets], lowest, highest),
+
+            spectra: [spectrum(), spectrum()],
+            average: spectrum(),
+            reference: None,
+
+            window_end: 0,
         };
 
         log::debug!("FourierAnalyzer({:p}):", &fa);
-        log::debug!("    Fourier Length      = {:8}", length);
+        log::debug!("    Window Length       = {:8}", length);
+        log::debug!("    Fourier Length      = {:8}", fft_len);
         log::debug!("    Buckets             = {:8}", buckets);
         log::debug!(
             "    Downsampled Rate    = {:8} ({} / {})",
@@ -256,22 +1084,49 @@ impl FourierAnalyzer {
             rate,
             downsample,
         );
+        log::debug!("    Magnitude Mode      = {:?}", magnitude_mode);
         log::debug!("    Lowest  Frequency   = {:8.3} Hz", lowest);
         log::debug!("    Highest Frequency   = {:8.3} Hz", highest);
 
-        fa
+        Ok(fa)
     }
 
-    /// Return the number of buckets
+    /// Return the length of the analyzed window
+    ///
+    /// This is the number of (downsampled) samples read from the `SampleBuffer`.
     #[inline]
-    pub fn buckets(&self) -> usize {
-        self.buckets
+    pub fn length(&self) -> usize {
+        self.length
     }
 
-    /// Return the frequency of the lowest bucket
+    /// Return the length of the planned fourier transform
+    ///
+    /// Differs from [`length`](#method.length) if the window is zero-padded.  Buckets are
+    /// spaced `rate / downsample / fft_len` apart.  Both can differ from the requested
+    /// lengths if the [`FftBackend`](trait.FftBackend.html) does not support them, the
+    /// transform length also if slow lengths are
+    /// [padded](struct.FourierBuilder.html#structfield.pad_to_fast).
     #[inline]
-    pub fn lowest(&self) -> analyzer::Frequency {
-        self.lowest
+    pub fn fft_len(&self) -> usize {
+        self.fft_len
+    }
+
+    /// Return the downsampling factor
+    #[inline]
+    pub fn downsample(&self) -> usize {
+        self.downsample
+    }
+
+    /// Return the number of buckets
+    #[inline]
+    pub fn buckets(&self) -> usize {
+        self.buckets
+    }
+
+    /// Return the frequency of the lowest bucket
+    #[inline]
+    pub fn lowest(&self) -> analyzer::Frequency {
+        self.lowest
     }
 
     /// Return the frequency of the highest bucket
@@ -280,6 +1135,131 @@ impl FourierAnalyzer {
         self.highest
     }
 
+    /// Return the center frequency of transform bin `bin`
+    ///
+    /// Bins are spaced `effective_rate / fft_len` apart, where the effective rate is the
+    /// `SampleBuffer`'s rate divided by the downsampling factor.  Downsampling therefore
+    /// lowers the Nyquist limit to `effective_rate / 2`, which is the frequency of bin
+    /// `fft_len / 2`, the last one in [`output`](#method.output).
+    pub fn bin_frequency(&self, bin: usize) -> analyzer::Frequency {
+        bin as analyzer::Frequency * self.rate as analyzer::Frequency
+            / self.downsample as analyzer::Frequency
+            / self.fft_len as analyzer::Frequency
+    }
+
+    /// Return the center frequencies of all `fft_len / 2 + 1` bins
+    ///
+    /// Matches the bins of [`output`](#method.output) and
+    /// [`magnitudes`](#method.magnitudes).
+    pub fn frequencies(&self) -> Vec<analyzer::Frequency> {
+        (0..self.fft_len / 2 + 1)
+            .map(|bin| self.bin_frequency(bin))
+            .collect()
+    }
+
+    /// Change the downsampling factor
+    ///
+    /// Takes effect on the next [`analyze`](#method.analyze), which allows zooming into the
+    /// lower frequencies live.  The `SampleBuffer` needs to hold at least
+    /// `length * factor` samples; if it does not, `analyze` reduces the factor to the
+    /// largest one which fits.
+    pub fn set_downsample(&mut self, factor: usize) {
+        assert!(factor > 0, "Downsampling factor must be at least 1!");
+
+        self.downsample = factor;
+        self.update_range();
+    }
+
+    /// Change the window length
+    ///
+    /// *Experimental*: Allows adapting the window per frame, eg. longer windows for
+    /// sustained sections and shorter ones for transients.  `length` must not exceed the
+    /// builder's [`max_length`](struct.FourierBuilder.html#structfield.max_length).  No buffers
+    /// are reallocated, but the window and transform are planned the first time each length
+    /// is used.  Lengths the backend does not support are replaced by the nearest supported
+    /// one, if there is none the current length is kept and a warning is logged.
+    pub fn set_length(&mut self, length: usize) {
+        assert!(
+            length > 0 && length <= self.max_length,
+            "Window length must be between 1 and {}!",
+            self.max_length
+        );
+        let (length, pad_to) =
+            match negotiate(&*self.backend, length, self.pad_to, self.pad_to_fast) {
+                Ok(negotiated) => negotiated,
+                Err(e) => {
+                    log::warn!("{}, keeping length {}", e, self.length);
+                    return;
+                }
+            };
+        if length == self.length {
+            return;
+        }
+
+        self.length = length;
+        self.fft_len = pad_to.max(length);
+        self.buckets = self.fft_len / 2;
+
+        self.plan = match self.plans.iter().position(|p| p.length == length) {
+            Some(plan) => plan,
+            None => {
+                self.plans.push(Plan::new(
+                    length,
+                    self.fft_len,
+                    &self.window_fn,
+                    &*self.backend,
+                ));
+                self.plans.len() - 1
+            }
+        };
+
+        self.update_range();
+    }
+
+    /// Analyze a `SampleBuffer` with a window of `length` samples
+    ///
+    /// See [`set_length`](#method.set_length).
+    pub fn analyze_with_length(
+        &mut self,
+        buf: &analyzer::SampleBuffer,
+        length: usize,
+    ) -> [analyzer::Spectrum<&[analyzer::SignalStrength]>; 2] {
+        self.set_length(length);
+        self.analyze(buf)
+    }
+
+    /// Recompute the frequency range after the rate or downsampling changed
+    fn update_range(&mut self) {
+        let downsampled_rate = self.rate as f32 / self.downsample as f32;
+        self.lowest = downsampled_rate / self.fft_len as f32;
+        self.highest = downsampled_rate / 2.0;
+
+        for s in self.spectra.iter_mut() {
+            s.resize(self.buckets, self.lowest, self.highest);
+        }
+        self.average.resize(self.buckets, self.lowest, self.highest);
+
+        log::debug!("FourierAnalyzer({:p}): Changed range", &self);
+        log::debug!(
+            "    Downsampled Rate    = {:8} ({} / {})",
+            downsampled_rate,
+            self.rate,
+            self.downsample,
+        );
+    }
+
+    /// Adapt to a buffer with a different rate than planned
+    ///
+    /// The downsampling factor is scaled so the analyzed rate stays as close as possible to
+    /// the one this analyzer was planned for.
+    fn adapt_rate(&mut self, rate: usize) {
+        let downsampled_rate = self.rate as f32 / self.downsample as f32;
+
+        self.downsample = ((rate as f32 / downsampled_rate).round() as usize).max(1);
+        self.rate = rate;
+        self.update_range();
+    }
+
     /// Analyze a `SampleBuffer`
     ///
     /// Returns the left and right channel data as spectra
@@ -289,41 +1269,269 @@ impl FourierAnalyzer {
     ) -> [analyzer::Spectrum<&[analyzer::SignalStrength]>; 2] {
         log::trace!("FourierAnalyzer({:p}): Analyzing ...", &self);
 
-        assert_eq!(
-            buf.rate(),
-            self.rate,
-            "Samplerate of buffer does not match!"
-        );
+        if buf.rate() != self.rate {
+            self.adapt_rate(buf.rate());
+        }
+        if self.length * self.downsample > buf.size() {
+            let downsample = (buf.size() / self.length).max(1);
+            log::warn!(
+                "FourierAnalyzer({:p}): Buffer too small for downsampling by {}, using {}",
+                &self,
+                self.downsample,
+                downsample,
+            );
+            self.set_downsample(downsample);
+        }
+
+        let plan = &self.plans[self.plan];
 
         // Copy samples to left and right buffer
         self.input[0].clear();
         self.input[1].clear();
-        for ([l, r], window) in buf
-            .iter(self.length, self.downsample)
-            .zip(self.window.iter())
-        {
-            self.input[0].push(rustfft::num_complex::Complex::new(l * window, 0.0));
-            self.input[1].push(rustfft::num_complex::Complex::new(r * window, 0.0));
+        let input = &mut self.input;
+        let channel_fn = &self.channel_fn;
+        let stereo_mode = self.stereo_mode;
+        let mut push = |[l, r]: [Sample; 2], window: Sample| {
+            let (l, r) = match (channel_fn, stereo_mode) {
+                (Some(ChannelFn(f)), _) => {
+                    let x = f([l, r]);
+                    (x, x)
+                }
+                (None, StereoMode::LeftRight) => (l, r),
+                (None, StereoMode::MidSide) => ((l + r) * 0.5, (l - r) * 0.5),
+            };
+            input[0].push(l * window);
+            input[1].push(r * window);
+        };
+
+        if self.antialias && self.downsample > 1 {
+            if self.lowpass.len() != 16 * self.downsample + 1 {
+                self.lowpass = lowpass(self.downsample);
+            }
+
+            // Read enough history for the first filtered sample, if the buffer has it
+            let span = self.length * self.downsample;
+            let history = (self.lowpass.len() - 1).min(buf.size() - span);
+            let samples = buf.iter(span + history, 1);
+            self.window_end = samples.pushed();
+            self.raw.clear();
+            self.raw.extend(samples);
+
+            for (j, window) in plan.window.iter().enumerate() {
+                let newest = history + j * self.downsample;
+                let filtered = self
+                    .lowpass
+                    .iter()
+                    .zip(self.raw[..=newest].iter().rev())
+                    .fold([0.0; 2], |[l, r], (h, s)| [l + h * s[0], r + h * s[1]]);
+                push(filtered, *window);
+            }
+        } else {
+            let samples = buf.iter(self.length, self.downsample);
+            self.window_end = samples.pushed();
+            for (sample, window) in samples.zip(plan.window.iter()) {
+                push(sample, *window);
+            }
         }
 
-        debug_assert_eq!(self.input[0].len(), self.window.len());
-        debug_assert_eq!(self.input[1].len(), self.window.len());
+        debug_assert_eq!(self.input[0].len(), plan.window.len());
+        debug_assert_eq!(self.input[1].len(), plan.window.len());
+
+        // Subtract the weighted mean, which leaves the windowed frame without DC
+        if self.remove_dc {
+            let window_sum = plan.window.iter().sum::<Sample>();
+            if window_sum != 0.0 {
+                for input in self.input.iter_mut() {
+                    let mean = input.iter().sum::<Sample>() / window_sum;
+                    for (x, w) in input.iter_mut().zip(plan.window.iter()) {
+                        *x -= mean * w;
+                    }
+                }
+            }
+        }
 
-        self.output.copy_from_slice(&self.input[0]);
-        self.fft.process(&mut self.output);
-        for (s, o) in self.spectra[0].iter_mut().zip(self.output.iter()) {
-            *s = o.norm_sqr();
+        // Zero-pad to the transform length
+        for input in self.input.iter_mut() {
+            input.resize(self.fft_len, 0.0);
         }
 
-        self.output.copy_from_slice(&self.input[1]);
-        self.fft.process(&mut self.output);
-        for (s, o) in self.spectra[1].iter_mut().zip(self.output.iter()) {
-            *s = o.norm_sqr();
+        let scale = self.normalization.scale(&plan.window, self.fft_len);
+
+        // Each channel is transformed into its own buffer, so both stay available.  The
+        // input is used as scratch space.
+        let fft_len = self.fft_len;
+        let mode = self.magnitude_mode;
+        let [input_l, input_r] = &mut self.input;
+        let [output_l, output_r] = &mut self.output;
+        let [spectrum_l, spectrum_r] = &mut self.spectra;
+        let transform =
+            |input: &mut Vec<Sample>,
+             output: &mut Vec<rustfft::num_complex::Complex<Sample>>,
+             spectrum: &mut analyzer::Spectrum<Vec<analyzer::SignalStrength>>| {
+                output.resize(fft_len / 2 + 1, rustfft::num_complex::Complex::default());
+                plan.fft
+                    .process(input, output)
+                    .expect("Transform buffers have the planned length");
+                if scale != 1.0 {
+                    for o in output.iter_mut() {
+                        *o *= scale;
+                    }
+                }
+                for (s, o) in spectrum.iter_mut().zip(output.iter()) {
+                    *s = mode.apply(*o);
+                }
+            };
+
+        if !self.single_thread && fft_len >= PARALLEL_MIN_LEN {
+            std::thread::scope(|scope| {
+                scope.spawn(|| transform(input_r, output_r, spectrum_r));
+                transform(input_l, output_l, spectrum_l);
+            });
+        } else {
+            transform(input_l, output_l, spectrum_l);
+            transform(input_r, output_r, spectrum_r);
         }
 
         [self.spectra[0].as_ref(), self.spectra[1].as_ref()]
     }
 
+    /// Return the sum of the coefficients of the current window
+    ///
+    /// This is the gain of the window for a constant signal, half of it for a sinusoid
+    /// centered on a bucket.
+    pub fn window_sum(&self) -> Sample {
+        self.plans[self.plan].window.iter().sum()
+    }
+
+    /// Return the scaling of the transform output
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// Return the point of the window timestamps refer to
+    pub fn alignment(&self) -> Alignment {
+        self.alignment
+    }
+
+    /// Number of samples (before downsampling) between the timestamp and the end of the window
+    fn offset(&self) -> f64 {
+        let span = (self.length * self.downsample) as f64;
+
+        match self.alignment {
+            Alignment::Start => span,
+            Alignment::Center => span / 2.0,
+            Alignment::End => 0.0,
+        }
+    }
+
+    /// Return the time in seconds from the timestamp to the newest analyzed sample
+    ///
+    /// This is how far features lag behind the audio, due to the window length.
+    pub fn latency(&self) -> f32 {
+        (self.offset() / self.rate as f64) as f32
+    }
+
+    /// Return the timestamp of the last analyzed window in seconds
+    ///
+    /// Timestamps are in stream time, ie. the number of samples pushed to the buffer
+    /// divided by its rate, and refer to the point selected with the
+    /// [`alignment`](struct.FourierBuilder.html#structfield.alignment).  Use this
+    /// instead of the time of the analysis for synchronizing with the audio.  Windows
+    /// reaching back before the first pushed sample have negative timestamps.
+    pub fn timestamp(&self) -> f64 {
+        (self.window_end as f64 - self.offset()) / self.rate as f64
+    }
+
+    /// Get the complex output of the last transform for the left and right channel
+    ///
+    /// Contains the non-redundant `fft_len / 2 + 1` buckets, from DC up to the Nyquist
+    /// frequency.
+    pub fn output(
+        &self,
+    ) -> (
+        &[rustfft::num_complex::Complex<Sample>],
+        &[rustfft::num_complex::Complex<Sample>],
+    ) {
+        (&self.output[0], &self.output[1])
+    }
+
+    /// Return the magnitudes `|z|` of the last transform of `channel` (`0` is left)
+    ///
+    /// Contains the same `fft_len / 2 + 1` buckets as [`output`](#method.output),
+    /// independent of the [`MagnitudeMode`](enum.MagnitudeMode.html).
+    pub fn magnitudes(&self, channel: usize) -> Vec<analyzer::SignalStrength> {
+        self.output[channel]
+            .iter()
+            .map(|c| c.norm() as analyzer::SignalStrength)
+            .collect()
+    }
+
+    /// Return the [`magnitudes`](#method.magnitudes) of `channel` in dB, clamped at
+    /// `floor_db`
+    pub fn magnitudes_db(&self, channel: usize, floor_db: f32) -> Vec<analyzer::SignalStrength> {
+        self.output[channel]
+            .iter()
+            .map(|c| (20.0 * (c.norm() as analyzer::SignalStrength).log10()).max(floor_db))
+            .collect()
+    }
+
+    /// Return the dominant frequency of the last transform of `channel` in Hz
+    ///
+    /// Finds the bin with the largest [`magnitude`](#method.magnitudes) and fits a parabola
+    /// through it and its neighbors, so the estimate is not limited to the
+    /// [`bin_frequency`](#method.bin_frequency) grid.  Bins at either end are returned
+    /// without interpolation.
+    pub fn peak_frequency(&self, channel: usize) -> analyzer::Frequency {
+        let magnitudes = self.magnitudes(channel);
+        let peak = match (0..magnitudes.len()).max_by(|&a, &b| {
+            magnitudes[a]
+                .partial_cmp(&magnitudes[b])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        }) {
+            Some(peak) => peak,
+            None => return 0.0,
+        };
+
+        let offset = if peak > 0 && peak + 1 < magnitudes.len() {
+            let (a, b, c) = (magnitudes[peak - 1], magnitudes[peak], magnitudes[peak + 1]);
+            let denom = a - 2.0 * b + c;
+            if denom != 0.0 {
+                0.5 * (a - c) / denom
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        };
+
+        self.bin_frequency(peak) + offset * self.bin_frequency(1)
+    }
+
+    /// Multiply the last transform of both channels by per-bin `weights`
+    ///
+    /// `weights` are linear amplitude gains for the `fft_len / 2 + 1` bins of
+    /// [`output`](#method.output), eg. from
+    /// [`weighting::a_weighting`](../weighting/fn.a_weighting.html) of the
+    /// [`frequencies`](#method.frequencies).  The output and the spectra are updated,
+    /// magnitudes are weighted according to the [`MagnitudeMode`](enum.MagnitudeMode.html).
+    pub fn apply_weighting(&mut self, weights: &[analyzer::SignalStrength]) {
+        assert_eq!(
+            weights.len(),
+            self.fft_len / 2 + 1,
+            "Weights must have one gain per bin!"
+        );
+
+        let mode = self.magnitude_mode;
+        for (output, spectrum) in self.output.iter_mut().zip(self.spectra.iter_mut()) {
+            for (o, w) in output.iter_mut().zip(weights.iter()) {
+                *o *= *w as Sample;
+            }
+            for (s, o) in spectrum.iter_mut().zip(output.iter()) {
+                *s = mode.apply(*o);
+            }
+        }
+    }
+
     /// Get the left channels spectral data from the last transform
     pub fn left(&self) -> analyzer::Spectrum<&[analyzer::SignalStrength]> {
         self.spectra[0].as_ref()
@@ -340,6 +1548,346 @@ impl FourierAnalyzer {
 
         self.average.as_ref()
     }
+
+    /// Hold the current average spectrum as a reference
+    ///
+    /// The reference stays constant while the analyzer keeps updating, eg. to compare
+    /// before and after tuning an EQ.  Replaces a previous reference.
+    pub fn freeze(&mut self) {
+        analyzer::average_spectrum(&mut self.average, &self.spectra);
+        self.reference = Some(self.average.clone());
+    }
+
+    /// Drop the reference
+    pub fn unfreeze(&mut self) {
+        self.reference = None;
+    }
+
+    /// Get the reference captured by [`freeze`](#method.freeze)
+    pub fn reference(&self) -> Option<analyzer::Spectrum<&[analyzer::SignalStrength]>> {
+        self.reference.as_ref().map(|r| r.as_ref())
+    }
+
+    /// Calculate the difference between the average spectrum and the reference
+    ///
+    /// Returns `None` if there is no reference or if it was captured with a different
+    /// range (eg. before a call to [`set_downsample`](#method.set_downsample)).
+    pub fn difference(&mut self) -> Option<analyzer::Spectrum<Vec<analyzer::SignalStrength>>> {
+        analyzer::average_spectrum(&mut self.average, &self.spectra);
+
+        let reference = self.reference.as_ref()?;
+        if reference.len() != self.average.len()
+            || reference.lowest() != self.average.lowest()
+            || reference.highest() != self.average.highest()
+        {
+            return None;
+        }
+
+        Some(analyzer::Spectrum::new(
+            self.average
+                .iter()
+                .zip(reference.iter())
+                .map(|(a, r)| a - r)
+                .collect(),
+            self.average.lowest(),
+            self.average.highest(),
+        ))
+    }
+
+    /// Create an [`InverseFourier`](struct.InverseFourier.html) matching this analyzer
+    ///
+    /// Uses the current window, padding and normalization, so the
+    /// [`output`](#method.output) of an analysis every `hop` (downsampled) samples is
+    /// resynthesized at the downsampled rate.
+    pub fn inverse(&self, hop: usize) -> InverseFourier {
+        let plan = &self.plans[self.plan];
+
+        InverseFourier::new(
+            plan.window.clone(),
+            self.fft_len,
+            hop,
+            1.0 / self.normalization.scale(&plan.window, self.fft_len),
+            crate::CONFIG.get_or("audio.buffer", 16000),
+        )
+    }
+}
+
+/// Builder for InverseFourier
+#[derive(Debug, Default)]
+pub struct InverseBuilder {
+    /// Length of the synthesized window
+    ///
+    /// Must match the forward transform.  Can also be set from config as
+    /// `"audio.fourier.length"`.
+    pub length: Option<usize>,
+
+    /// Length of the transform, if the forward window was zero-padded
+    ///
+    /// Values not larger than `length` disable padding.  Can also be set from config as
+    /// `"audio.fourier.pad_to"`.
+    pub pad_to: Option<usize>,
+
+    /// Synthesis window, should be the analysis window
+    ///
+    /// Can also be set from config as `"audio.fourier.window"`.
+    pub window: Option<window::Window>,
+
+    /// Number of samples between two consecutive frames
+    ///
+    /// Defaults to half the length.
+    pub hop: Option<usize>,
+
+    /// Number of output samples kept until they are read, older ones are dropped
+    ///
+    /// Defaults to `16000`, can also be set from config as `"audio.buffer"`.
+    pub buffer_size: Option<usize>,
+}
+
+impl InverseBuilder {
+    /// Create a new InverseBuilder
+    pub fn new() -> InverseBuilder {
+        Default::default()
+    }
+
+    /// Set the window length
+    pub fn length(&mut self, length: usize) -> &mut InverseBuilder {
+        self.length = Some(length);
+        self
+    }
+
+    /// Set the padded transform length
+    pub fn pad_to(&mut self, length: usize) -> &mut InverseBuilder {
+        self.pad_to = Some(length);
+        self
+    }
+
+    /// Set the synthesis window
+    pub fn window<W: Into<window::Window>>(&mut self, window: W) -> &mut InverseBuilder {
+        self.window = Some(window.into());
+        self
+    }
+
+    /// Set the hop between frames
+    pub fn hop(&mut self, hop: usize) -> &mut InverseBuilder {
+        self.hop = Some(hop);
+        self
+    }
+
+    /// Set the size of the output buffer
+    pub fn buffer_size(&mut self, buffer_size: usize) -> &mut InverseBuilder {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    /// Plan the inverse transform and prepare buffers
+    pub fn plan(&mut self) -> InverseFourier {
+        let length = self
+            .length
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.fourier.length", 512));
+        let pad_to = self
+            .pad_to
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.fourier.pad_to", 0));
+        let window = self.window.clone().unwrap_or_else(|| {
+            window::from_str(&crate::CONFIG.get_or("audio.fourier.window", "none".to_string()))
+                .expect("Selected window type not found!")
+        });
+        let buffer_size = self
+            .buffer_size
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.buffer", 16000));
+
+        InverseFourier::new(
+            window.coefficients(length),
+            pad_to.max(length),
+            self.hop.unwrap_or((length / 2).max(1)),
+            1.0,
+            buffer_size,
+        )
+    }
+}
+
+/// Inverse fourier transform with weighted overlap-add
+///
+/// Turns one (possibly modified) spectrum per frame back into samples, eg. for effects
+/// which filter the spectrum.  Each frame is transformed back, multiplied with the
+/// synthesis window and added to the previous frames `hop` samples later.  The sum is
+/// divided by the overlapping squared windows, so a spectrum analyzed with the same window
+/// every `hop` samples is reconstructed exactly, for any window whose overlaps do not sum
+/// to zero.
+///
+/// Each frame completes `hop` samples, which are kept in an output ring buffer until they
+/// are [`read`](#method.read).  They lag `length - hop` samples behind the newest analyzed
+/// sample, the first `length - hop` samples are faded in.  Use
+/// [`FourierAnalyzer::inverse`](struct.FourierAnalyzer.html#method.inverse) to match a
+/// forward analyzer, including its normalization.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let samples = analyzer::SampleBuffer::new(512, 8000);
+/// let mut analyzer = analyzer::FourierBuilder::new()
+///     .length(512)
+///     .window(analyzer::window::hanning)
+///     .downsample(1)
+///     .plan();
+/// let mut inverse = analyzer.inverse(128);
+///
+/// // Every 128 pushed samples
+/// analyzer.analyze(&samples);
+/// inverse.process(analyzer.output().0);
+///
+/// let mut out = vec![0.0; 128];
+/// assert_eq!(inverse.read(&mut out), 128);
+/// ```
+#[derive(Clone)]
+pub struct InverseFourier {
+    fft_len: usize,
+    hop: usize,
+    window: Vec<Sample>,
+    scale: Sample,
+    ifft: std::sync::Arc<dyn realfft::ComplexToReal<Sample>>,
+
+    spectrum: Vec<rustfft::num_complex::Complex<Sample>>,
+    frame: Vec<Sample>,
+    /// Overlapping frames, the first `hop` samples are completed by the next frame
+    overlap: Vec<Sample>,
+    /// Sum of the overlapping squared windows for each position within a hop
+    norm: Vec<Sample>,
+    output: std::collections::VecDeque<Sample>,
+    buffer_size: usize,
+}
+
+impl std::fmt::Debug for InverseFourier {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "InverseFourier {{ length: {:?}, fft_len: {:?}, hop: {:?} }}",
+            self.window.len(),
+            self.fft_len,
+            self.hop,
+        )
+    }
+}
+
+impl InverseFourier {
+    fn new(
+        window: Vec<Sample>,
+        fft_len: usize,
+        hop: usize,
+        scale: Sample,
+        buffer_size: usize,
+    ) -> InverseFourier {
+        let length = window.len();
+        assert!(
+            hop > 0 && hop <= length,
+            "Hop must be between 1 and the window length {}!",
+            length
+        );
+
+        let norm = (0..hop)
+            .map(|i| {
+                window
+                    .iter()
+                    .skip(i)
+                    .step_by(hop)
+                    .map(|w| w * w)
+                    .sum::<Sample>()
+            })
+            .collect();
+
+        log::debug!("InverseFourier:");
+        log::debug!("    Window Length       = {:8}", length);
+        log::debug!("    Fourier Length      = {:8}", fft_len);
+        log::debug!("    Hop                 = {:8}", hop);
+
+        InverseFourier {
+            fft_len,
+            hop,
+            window,
+            scale,
+            ifft: with_planner(|planner| planner.plan_fft_inverse(fft_len)),
+
+            spectrum: Vec::with_capacity(fft_len / 2 + 1),
+            frame: vec![0.0; fft_len],
+            overlap: vec![0.0; length],
+            norm,
+            output: std::collections::VecDeque::with_capacity(buffer_size),
+            buffer_size,
+        }
+    }
+
+    /// Return the length of the synthesized window
+    #[inline]
+    pub fn length(&self) -> usize {
+        self.window.len()
+    }
+
+    /// Return the number of samples completed by each frame
+    #[inline]
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// Add the next frame, given as the `fft_len / 2 + 1` buckets of a real transform
+    ///
+    /// Completes [`hop`](#method.hop) output samples.
+    pub fn process(&mut self, spectrum: &[rustfft::num_complex::Complex<Sample>]) {
+        assert_eq!(
+            spectrum.len(),
+            self.fft_len / 2 + 1,
+            "Spectrum must have fft_len / 2 + 1 buckets!"
+        );
+
+        // The transform of a real signal has no imaginary DC or Nyquist part
+        self.spectrum.clear();
+        self.spectrum.extend_from_slice(spectrum);
+        self.spectrum[0].im = 0.0;
+        if self.fft_len.is_multiple_of(2) {
+            self.spectrum[self.fft_len / 2].im = 0.0;
+        }
+        self.ifft
+            .process(&mut self.spectrum, &mut self.frame)
+            .expect("Transform buffers have the planned length");
+
+        let scale = self.scale / self.fft_len as Sample;
+        for ((o, f), w) in self
+            .overlap
+            .iter_mut()
+            .zip(self.frame.iter())
+            .zip(self.window.iter())
+        {
+            *o += f * w * scale;
+        }
+
+        for (o, n) in self.overlap[..self.hop].iter().zip(self.norm.iter()) {
+            if self.output.len() == self.buffer_size {
+                self.output.pop_front();
+            }
+            self.output.push_back(if *n > 0.0 { o / n } else { 0.0 });
+        }
+        self.overlap.rotate_left(self.hop);
+        let length = self.overlap.len();
+        for o in self.overlap[length - self.hop..].iter_mut() {
+            *o = 0.0;
+        }
+    }
+
+    /// Return the number of output samples which were not read yet
+    #[inline]
+    pub fn available(&self) -> usize {
+        self.output.len()
+    }
+
+    /// Move the oldest output samples into `out`
+    ///
+    /// Returns the number of samples written, at most `out.len()`.
+    pub fn read(&mut self, out: &mut [Sample]) -> usize {
+        let n = out.len().min(self.output.len());
+        for (o, s) in out.iter_mut().zip(self.output.drain(..n)) {
+            *o = s;
+        }
+        n
+    }
 }
 
 #[cfg(test)]
@@ -348,6 +1896,7 @@ mod tests {
 
     #[test]
     fn test_init() {
+        crate::test_config();
         FourierBuilder::new()
             .rate(8000)
             .length(512)
@@ -358,6 +1907,7 @@ mod tests {
 
     #[test]
     fn test_analyze() {
+        crate::test_config();
         let mut analyzer = FourierBuilder::new()
             .rate(8000)
             .length(512)
@@ -371,4 +1921,950 @@ mod tests {
 
         analyzer.analyze(&buf);
     }
+
+    #[test]
+    fn test_native_rate() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(512)
+            .window(window::from_str("hanning").unwrap())
+            .downsample(1)
+            .plan();
+
+        // Captured at 48kHz, but the analyzer was planned for 8kHz
+        let buf = crate::analyzer::SampleBuffer::new(4096, 48000);
+        buf.push(
+            &(0..4096)
+                .map(|i| {
+                    let s =
+                        (i as f32 / 48000.0 * 1000.0 * 2.0 * std::f32::consts::PI).sin() as Sample;
+                    [s, s]
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        analyzer.analyze(&buf);
+        let spectrum = analyzer.average();
+
+        assert_eq!(spectrum.highest(), 4000.0);
+
+        let (freq, _) = spectrum.find_maxima_alloc()[0];
+        assert!((freq - 1000.0).abs() < 1.5 * 8000.0 / 512.0, "{}", freq);
+    }
+
+    #[test]
+    fn test_pad_to() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(512)
+            .pad_to(2048)
+            .window(window::from_str("hanning").unwrap())
+            .downsample(1)
+            .plan();
+
+        assert_eq!(analyzer.length(), 512);
+        assert_eq!(analyzer.fft_len(), 2048);
+        assert_eq!(analyzer.buckets(), 1024);
+        assert_eq!(analyzer.lowest(), 8000.0 / 2048.0);
+
+        let buf = crate::analyzer::SampleBuffer::new(512, 8000);
+        buf.push(&crate::helpers::signal::sine(8000, 1000.0, 512.0 / 8000.0));
+        analyzer.analyze(&buf);
+
+        let (freq, _) = analyzer.average().find_maxima_alloc()[0];
+        assert!((freq - 1000.0).abs() < 2.0 * 8000.0 / 2048.0, "{}", freq);
+
+        let unpadded = FourierBuilder::new()
+            .rate(8000)
+            .length(512)
+            .pad_to(256)
+            .window(window::from_str("hanning").unwrap())
+            .downsample(1)
+            .plan();
+        assert_eq!(unpadded.fft_len(), 512);
+    }
+
+    #[test]
+    fn test_pad_to_interpolation() {
+        crate::test_config();
+        let buf = crate::analyzer::SampleBuffer::new(256, 8000);
+        // Between bins 32 and 33 of the unpadded transform
+        buf.push(&crate::helpers::signal::sine(8000, 1010.0, 256.0 / 8000.0));
+
+        let strongest = |pad_to| {
+            let mut analyzer = FourierBuilder::new()
+                .rate(8000)
+                .length(256)
+                .pad_to(pad_to)
+                .window(window::hanning)
+                .downsample(1)
+                .plan();
+            analyzer.analyze(&buf);
+            let magnitudes = analyzer.magnitudes(0);
+            let bin = (0..magnitudes.len())
+                .max_by(|&a, &b| magnitudes[a].partial_cmp(&magnitudes[b]).unwrap())
+                .unwrap();
+            (analyzer.bin_frequency(bin), magnitudes)
+        };
+
+        let (coarse, unpadded) = strongest(0);
+        let (fine, padded) = strongest(2048);
+        assert_eq!(unpadded.len(), 129);
+        assert_eq!(padded.len(), 1025);
+        assert!((coarse - 1010.0).abs() > 5.0, "{}", coarse);
+        assert!((fine - 1010.0).abs() < 8000.0 / 2048.0, "{}", fine);
+
+        // The main lobe spans many buckets and falls off smoothly on both sides
+        let peak = (fine / (8000.0 / 2048.0)).round() as usize;
+        for i in 0..16 {
+            assert!(padded[peak + i] >= padded[peak + i + 1]);
+            assert!(padded[peak - i] >= padded[peak - i - 1]);
+        }
+        // Every 8th padded bucket is an unpadded one
+        for bin in 20..40 {
+            assert!((padded[bin * 8] - unpadded[bin]).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_set_downsample() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .window(window::from_str("hanning").unwrap())
+            .downsample(1)
+            .plan();
+
+        let buf = crate::analyzer::SampleBuffer::new(1024, 8000);
+        buf.push(&crate::helpers::signal::sine(8000, 500.0, 1024.0 / 8000.0));
+
+        analyzer.analyze(&buf);
+        assert_eq!(analyzer.highest(), 4000.0);
+        let before = analyzer.left().id_to_freq(8);
+
+        analyzer.set_downsample(4);
+        assert_eq!(analyzer.highest(), 1000.0);
+        analyzer.analyze(&buf);
+        let after = analyzer.left().id_to_freq(8);
+        assert!(
+            (after * 4.0 - before).abs() < 1e-3,
+            "{} != {} / 4",
+            after,
+            before
+        );
+
+        let (freq, _) = analyzer.average().find_maxima_alloc()[0];
+        assert!((freq - 500.0).abs() < 2.0 * 2000.0 / 256.0, "{}", freq);
+
+        // Buffer only holds 1024 samples
+        analyzer.set_downsample(8);
+        analyzer.analyze(&buf);
+        assert_eq!(analyzer.downsample(), 4);
+    }
+
+    #[test]
+    fn test_variable_length() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(512)
+            .max_length(512)
+            .window(window::from_str("hanning").unwrap())
+            .downsample(1)
+            .plan();
+
+        let buf = crate::analyzer::SampleBuffer::new(512, 8000);
+        buf.push(&crate::helpers::signal::sine(8000, 1000.0, 512.0 / 8000.0));
+
+        let ptr = analyzer.left().iter().as_slice().as_ptr();
+        for length in [256, 512, 256, 512].iter().cloned() {
+            let [left, _] = analyzer.analyze_with_length(&buf, length);
+            assert_eq!(left.len(), length / 2);
+            assert_eq!(left.iter().as_slice().as_ptr(), ptr);
+
+            assert_eq!(analyzer.length(), length);
+            assert_eq!(analyzer.lowest(), 8000.0 / length as f32);
+
+            let (freq, _) = analyzer.average().find_maxima_alloc()[0];
+            assert!(
+                (freq - 1000.0).abs() < 2.0 * 8000.0 / length as f32,
+                "{}",
+                freq
+            );
+        }
+    }
+
+    #[test]
+    fn test_freeze() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .window(window::from_str("hanning").unwrap())
+            .downsample(1)
+            .plan();
+        let buf = crate::analyzer::SampleBuffer::new(256, 8000);
+
+        assert!(analyzer.reference().is_none());
+        assert!(analyzer.difference().is_none());
+
+        buf.push(&crate::helpers::signal::sine(8000, 500.0, 256.0 / 8000.0));
+        analyzer.analyze(&buf);
+        analyzer.freeze();
+        let frozen = analyzer
+            .reference()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            frozen,
+            analyzer.average().iter().cloned().collect::<Vec<_>>()
+        );
+
+        buf.push(&crate::helpers::signal::sine(8000, 2000.0, 256.0 / 8000.0));
+        analyzer.analyze(&buf);
+        let live = analyzer.average().iter().cloned().collect::<Vec<_>>();
+        assert_ne!(live, frozen);
+        assert_eq!(
+            analyzer
+                .reference()
+                .unwrap()
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>(),
+            frozen
+        );
+
+        let difference = analyzer.difference().unwrap();
+        assert!(difference[2000.0] > 0.0);
+        assert!(difference[500.0] < 0.0);
+
+        analyzer.unfreeze();
+        assert!(analyzer.reference().is_none());
+    }
+
+    #[test]
+    fn test_magnitude_modes() {
+        let c = rustfft::num_complex::Complex::new(3.0, 4.0);
+
+        assert_eq!(MagnitudeMode::Magnitude.apply(c), 5.0);
+        assert_eq!(MagnitudeMode::Squared.apply(c), 25.0);
+        assert_eq!(MagnitudeMode::Real.apply(c), 3.0);
+        assert!((MagnitudeMode::Log.apply(c) - 6.0f32.ln()).abs() < 1e-6);
+
+        assert_eq!(MagnitudeMode::default(), MagnitudeMode::Magnitude);
+    }
+
+    #[test]
+    fn test_timestamp() {
+        crate::test_config();
+        let buf = crate::analyzer::SampleBuffer::new(4000, 8000);
+        buf.push(&[[0.0; 2]; 1000]);
+
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .downsample(2)
+            .plan();
+        assert_eq!(analyzer.alignment(), Alignment::Center);
+        analyzer.analyze(&buf);
+
+        // The window spans samples 488 to 1000, its middle is sample 744
+        assert_eq!(analyzer.timestamp(), 744.0 / 8000.0);
+        assert_eq!(analyzer.latency(), 256.0 / 8000.0);
+
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .downsample(2)
+            .alignment(Alignment::End)
+            .plan();
+        analyzer.analyze(&buf);
+        assert_eq!(analyzer.timestamp(), 1000.0 / 8000.0);
+        assert_eq!(analyzer.latency(), 0.0);
+    }
+
+    /// Backend only supporting powers of two
+    #[derive(Debug)]
+    struct PowerOfTwo;
+
+    impl FftBackend for PowerOfTwo {
+        fn supports(&self, len: usize) -> bool {
+            len.is_power_of_two()
+        }
+
+        fn plan(&self, len: usize) -> std::sync::Arc<dyn realfft::RealToComplex<Sample>> {
+            assert!(self.supports(len), "Unsupported length {}", len);
+            RustFft.plan(len)
+        }
+    }
+
+    #[test]
+    fn test_unsupported_length() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(300)
+            .max_length(1024)
+            .downsample(1)
+            .backend(std::sync::Arc::new(PowerOfTwo))
+            .plan();
+        assert_eq!(analyzer.length(), 256);
+        assert_eq!(analyzer.fft_len(), 256);
+
+        let buf = crate::analyzer::SampleBuffer::new(1024, 8000);
+        buf.push(&crate::helpers::signal::sine(8000, 1000.0, 1024.0 / 8000.0));
+        analyzer.analyze(&buf);
+        let average = analyzer.average();
+        let max = (0..average.len())
+            .max_by(|&a, &b| average[a].partial_cmp(&average[b]).unwrap())
+            .unwrap();
+        assert!((average.id_to_freq(max) - 1000.0).abs() <= 8000.0 / 256.0);
+
+        // Padding is adjusted instead of the window
+        analyzer.set_length(700);
+        assert_eq!(analyzer.length(), 512);
+        let analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(200)
+            .pad_to(600)
+            .backend(std::sync::Arc::new(PowerOfTwo))
+            .plan();
+        assert_eq!(analyzer.length(), 200);
+        assert_eq!(analyzer.fft_len(), 512);
+    }
+
+    /// Backend supporting a single length
+    #[derive(Debug)]
+    struct Only(usize);
+
+    impl FftBackend for Only {
+        fn supports(&self, len: usize) -> bool {
+            len == self.0
+        }
+
+        fn plan(&self, len: usize) -> std::sync::Arc<dyn realfft::RealToComplex<Sample>> {
+            assert!(self.supports(len), "Unsupported length {}", len);
+            RustFft.plan(len)
+        }
+    }
+
+    #[test]
+    fn test_no_supported_length() {
+        crate::test_config();
+        let result = FourierBuilder::new()
+            .rate(8000)
+            .length(16)
+            .downsample(1)
+            .backend(std::sync::Arc::new(Only(256)))
+            .try_plan();
+        assert_eq!(result.err(), Some(UnsupportedLength(16)));
+
+        // Switching to an unsupported length keeps the current one
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .max_length(256)
+            .downsample(1)
+            .backend(std::sync::Arc::new(Only(256)))
+            .try_plan()
+            .unwrap();
+        analyzer.set_length(16);
+        assert_eq!(analyzer.length(), 256);
+        assert_eq!(analyzer.fft_len(), 256);
+    }
+
+    #[test]
+    fn test_stereo() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .window(window::hanning)
+            .downsample(1)
+            .plan();
+
+        // 500 Hz on the left, 2 kHz on the right
+        let left = crate::helpers::signal::sine(8000, 500.0, 256.0 / 8000.0);
+        let right = crate::helpers::signal::sine(8000, 2000.0, 256.0 / 8000.0);
+        let buf = crate::analyzer::SampleBuffer::new(256, 8000);
+        buf.push(
+            &left
+                .iter()
+                .zip(right.iter())
+                .map(|(l, r)| [l[0], r[0]])
+                .collect::<Vec<_>>(),
+        );
+        analyzer.analyze(&buf);
+
+        let (l, r) = analyzer.output();
+        assert_eq!(l.len(), 129);
+        assert_ne!(l, r);
+        assert!(l[16].norm() > 10.0 * l[64].norm());
+        assert!(r[64].norm() > 10.0 * r[16].norm());
+
+        assert_ne!(
+            analyzer.left().iter().collect::<Vec<_>>(),
+            analyzer.right().iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        crate::test_config();
+        let expected = if cfg!(feature = "f64-samples") { 8 } else { 4 };
+        assert_eq!(std::mem::size_of::<Sample>(), expected);
+
+        let mut recorder = crate::recorder::signal::SignalBuilder::new()
+            .rate(8000)
+            .buffer_size(4096)
+            .tone(crate::recorder::signal::Waveform::Sine, 1000.0, 1.0)
+            .create();
+        crate::recorder::Recorder::sync(&mut recorder, 4096.0 / 8000.0);
+
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(4096)
+            .window(window::none)
+            .downsample(1)
+            .plan();
+        analyzer.analyze(crate::recorder::Recorder::sample_buffer(&recorder));
+
+        // 1000 Hz falls onto bin 512, everything else is rounding noise
+        let magnitudes = analyzer.magnitudes(0);
+        let floor = magnitudes
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != 512)
+            .fold(0.0, |max, (_, m)| m.max(max))
+            / magnitudes[512];
+        let limit = if cfg!(feature = "f64-samples") {
+            1e-9
+        } else {
+            1e-3
+        };
+        assert!(floor < limit, "{}", floor);
+    }
+
+    #[test]
+    fn test_magnitudes() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .window(window::hanning)
+            .downsample(1)
+            .plan();
+        let buf = crate::analyzer::SampleBuffer::new(256, 8000);
+        buf.push(&crate::helpers::signal::sine(8000, 1000.0, 256.0 / 8000.0));
+        analyzer.analyze(&buf);
+
+        // Buckets are 31.25 Hz apart, starting at DC
+        let magnitudes = analyzer.magnitudes(0);
+        assert_eq!(magnitudes.len(), 129);
+        let peak = (0..magnitudes.len())
+            .max_by(|&a, &b| magnitudes[a].partial_cmp(&magnitudes[b]).unwrap())
+            .unwrap();
+        assert_eq!(peak, 32);
+
+        let db = analyzer.magnitudes_db(0, -60.0);
+        assert!((db[32] - 20.0 * magnitudes[32].log10()).abs() < 1e-4);
+        assert!(db.iter().all(|&v| v >= -60.0));
+        assert_eq!(db[100], -60.0);
+    }
+
+    #[test]
+    fn test_peak_frequency() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .window(window::hanning)
+            .downsample(1)
+            .plan();
+        let buf = crate::analyzer::SampleBuffer::new(256, 8000);
+
+        // 31.25 Hz bins, 1010 Hz lies between bins 32 and 33
+        buf.push(&crate::helpers::signal::sine(8000, 1010.0, 256.0 / 8000.0));
+        analyzer.analyze(&buf);
+
+        let width = analyzer.bin_frequency(1);
+        let peak = analyzer.peak_frequency(0);
+        assert!((peak - 1010.0).abs() < width / 2.0, "{}", peak);
+        assert!((peak - 1010.0).abs() < (analyzer.bin_frequency(32) - 1010.0).abs());
+    }
+
+    #[test]
+    fn test_real_transform() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(200)
+            .pad_to(256)
+            .window(window::nuttall)
+            .downsample(2)
+            .plan();
+        let noise = crate::helpers::signal::white_noise(8000, 0.1, 7);
+        let buf = crate::analyzer::SampleBuffer::new(800, 8000);
+        buf.push(&noise);
+        analyzer.analyze(&buf);
+
+        // Same input through a complex transform
+        let window = window::nuttall(200);
+        let mut complex = buf
+            .iter(200, 2)
+            .zip(window.iter())
+            .map(|([l, _], w)| rustfft::num_complex::Complex::new(l * w, 0.0))
+            .chain(std::iter::repeat(rustfft::num_complex::Complex::default()))
+            .take(256)
+            .collect::<Vec<_>>();
+        rustfft::FftPlanner::new()
+            .plan_fft_forward(256)
+            .process(&mut complex);
+
+        let (real, _) = analyzer.output();
+        assert_eq!(real.len(), 129);
+        for (r, c) in real.iter().zip(complex.iter()) {
+            assert!((r - c).norm() < 1e-4, "{} != {}", r, c);
+        }
+        for (s, c) in analyzer.left().iter().zip(complex.iter()) {
+            assert!((s - crate::analyzer::samples::to_f32(c.norm())).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_bin_frequency() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .downsample(4)
+            .plan();
+
+        // 2 kHz effective rate
+        assert_eq!(analyzer.bin_frequency(0), 0.0);
+        assert_eq!(analyzer.bin_frequency(128), 1000.0);
+        let frequencies = analyzer.frequencies();
+        assert_eq!(frequencies.len(), 129);
+        assert_eq!(frequencies[1], 7.8125);
+
+        // Changing the downsampling moves the Nyquist limit
+        analyzer.set_downsample(2);
+        assert_eq!(analyzer.bin_frequency(128), 2000.0);
+    }
+
+    #[test]
+    fn test_pad_to_fast() {
+        crate::test_config();
+        assert!(is_fast_len(1024) && is_fast_len(1000) && is_fast_len(2 * 3 * 5 * 7));
+        assert!(!is_fast_len(1009) && !is_fast_len(2 * 11) && !is_fast_len(0));
+
+        // 1009 is prime, only warned about by default
+        let slow = FourierBuilder::new()
+            .rate(8000)
+            .length(1009)
+            .downsample(1)
+            .plan();
+        assert_eq!(slow.length(), 1009);
+        assert_eq!(slow.fft_len(), 1009);
+
+        let mut padded = FourierBuilder::new()
+            .rate(8000)
+            .length(1009)
+            .max_length(2000)
+            .downsample(1)
+            .pad_to_fast(true)
+            .plan();
+        assert_eq!(padded.length(), 1009);
+        assert_eq!(padded.fft_len(), 1024);
+        assert_eq!(padded.lowest(), 8000.0 / 1024.0);
+
+        let buf = crate::analyzer::SampleBuffer::new(2000, 8000);
+        buf.push(&crate::helpers::signal::sine(8000, 1000.0, 0.25));
+        padded.analyze(&buf);
+        assert!((padded.peak_frequency(0) - 1000.0).abs() < 8000.0 / 1024.0);
+
+        // Fast lengths are left alone, padding follows variable lengths
+        padded.set_length(1000);
+        assert_eq!(padded.fft_len(), 1000);
+        padded.set_length(1997);
+        assert_eq!(padded.fft_len(), 2000);
+    }
+
+    #[test]
+    fn test_shared_plans() {
+        crate::test_config();
+        let build = |length| {
+            FourierBuilder::new()
+                .rate(8000)
+                .length(length)
+                .downsample(1)
+                .plan()
+        };
+        let fft = |analyzer: &FourierAnalyzer| analyzer.plans[analyzer.plan].fft.clone();
+
+        let (a, b, c) = (build(384), build(384), build(768));
+        assert!(std::sync::Arc::ptr_eq(&fft(&a), &fft(&b)));
+        assert!(!std::sync::Arc::ptr_eq(&fft(&a), &fft(&c)));
+
+        // Also across threads
+        let d = std::thread::spawn(move || build(384)).join().unwrap();
+        assert!(std::sync::Arc::ptr_eq(&fft(&a), &fft(&d)));
+    }
+
+    #[test]
+    fn test_channel_fn() {
+        crate::test_config();
+        let buf = crate::analyzer::SampleBuffer::new(256, 8000);
+        // 500 Hz in the mid, 2000 Hz in the side channel
+        let samples = crate::helpers::signal::sine(8000, 500.0, 0.032)
+            .into_iter()
+            .zip(crate::helpers::signal::sine(8000, 2000.0, 0.032))
+            .map(|([m, _], [s, _])| [m + s, m - s])
+            .collect::<Vec<_>>();
+        buf.push(&samples);
+
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .downsample(1)
+            .channel_fn(Box::new(|[l, r]| l - r))
+            .plan();
+        analyzer.analyze(&buf);
+
+        // 31.25 Hz per bin
+        let side = analyzer.magnitudes(0);
+        assert!(side[64] > 100.0);
+        assert!(side[16] < 1e-2);
+        assert_eq!(side, analyzer.magnitudes(1));
+    }
+
+    #[test]
+    fn test_mid_side() {
+        crate::test_config();
+        let buf = crate::analyzer::SampleBuffer::new(256, 8000);
+        let analyzer = || {
+            FourierBuilder::new()
+                .rate(8000)
+                .length(256)
+                .window(window::hanning)
+                .downsample(1)
+                .stereo_mode(StereoMode::MidSide)
+                .plan()
+        };
+
+        // Mono has no side
+        buf.push(&crate::helpers::signal::sine(8000, 1000.0, 0.032));
+        let mut mono = analyzer();
+        mono.analyze(&buf);
+        let plain = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .window(window::hanning)
+            .downsample(1)
+            .plan()
+            .analyze(&buf)[0]
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(mono.left().iter().cloned().collect::<Vec<_>>(), plain);
+        assert!(mono.magnitudes(1).iter().all(|&m| m < 1e-6));
+
+        // A fully inverted channel is all side
+        let samples = crate::helpers::signal::sine(8000, 1000.0, 0.032)
+            .into_iter()
+            .map(|[l, _]| [l, -l])
+            .collect::<Vec<_>>();
+        buf.push(&samples);
+        let mut side = analyzer();
+        side.analyze(&buf);
+        assert!(side.magnitudes(0).iter().all(|&m| m < 1e-6));
+        assert!(side.magnitudes(1)[32] > 10.0);
+
+        assert_eq!("ms".parse(), Ok(StereoMode::MidSide));
+        assert_eq!("leftright".parse(), Ok(StereoMode::LeftRight));
+    }
+
+    #[test]
+    fn test_antialias() {
+        crate::test_config();
+        let peak = |freq, antialias| {
+            let buf = crate::analyzer::SampleBuffer::new(2048, 8000);
+            buf.push(&crate::helpers::signal::sine(8000, freq, 0.256));
+            let mut analyzer = FourierBuilder::new()
+                .rate(8000)
+                .length(256)
+                .downsample(4)
+                .window(window::hanning)
+                .antialias(antialias)
+                .plan();
+            analyzer.analyze(&buf);
+            analyzer.magnitudes(0).into_iter().fold(0.0, f32::max)
+        };
+
+        // 1750 Hz is above the downsampled Nyquist of 1000 Hz and aliases to 250 Hz
+        let aliased = peak(1750.0, false);
+        let filtered = peak(1750.0, true);
+        assert!(
+            20.0 * (filtered / aliased).log10() < -40.0,
+            "{} vs {}",
+            filtered,
+            aliased
+        );
+
+        // The pass band is untouched
+        assert!((peak(250.0, true) / peak(250.0, false) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_parametrized_windows() {
+        let sum = |w: &[Sample]| w.iter().sum::<Sample>();
+
+        // Limits match simpler windows
+        assert!(window::kaiser(64, 0.0)
+            .iter()
+            .all(|&w| (w - 1.0).abs() < 1e-6));
+        assert!(window::tukey(64, 0.0).iter().all(|&w| w == 1.0));
+        let tukey = window::tukey(65, 1.0);
+        let hanning = window::hanning(65);
+        assert!(tukey
+            .iter()
+            .zip(hanning.iter())
+            .all(|(a, b)| (a - b).abs() < 1e-4));
+
+        // Symmetric, peaking in the middle
+        for w in [
+            window::kaiser(65, 8.6),
+            window::gaussian(65, 0.4),
+            window::flattop(65),
+        ] {
+            assert!((w[32] - 1.0).abs() < 1e-3, "{}", w[32]);
+            assert!((w[10] - w[54]).abs() < 1e-5);
+        }
+        assert!(sum(&window::kaiser(64, 12.0)) < sum(&window::kaiser(64, 4.0)));
+
+        // Specifiers
+        let kaiser = window::from_str("kaiser:4.0").unwrap();
+        assert_eq!(kaiser.coefficients(32), window::kaiser(32, 4.0));
+        assert_eq!(
+            window::from_str("tukey").unwrap().coefficients(32),
+            window::tukey(32, 0.5)
+        );
+        assert!(window::from_str("kaiser:beta").is_none());
+        assert!(window::from_str("hanning:2").is_none());
+        assert_eq!(
+            window::from_str("flattop").unwrap().coefficients(16),
+            window::flattop(16)
+        );
+    }
+
+    #[test]
+    fn test_normalization() {
+        crate::test_config();
+        let buf = crate::analyzer::SampleBuffer::new(256, 8000);
+        buf.push(&crate::helpers::signal::sine(8000, 1000.0, 0.032));
+
+        let windows: [fn(usize) -> Vec<Sample>; 5] = [
+            window::none,
+            window::hanning,
+            window::blackman,
+            window::nuttall,
+            window::flattop,
+        ];
+        for w in windows {
+            let mut analyzer = FourierBuilder::new()
+                .rate(8000)
+                .length(256)
+                .downsample(1)
+                .window(w)
+                .normalize(Normalization::Amplitude)
+                .plan();
+            analyzer.analyze(&buf);
+
+            let peak = analyzer.magnitudes(0).into_iter().fold(0.0, f32::max);
+            assert!((peak - 1.0).abs() < 1e-2, "{}", peak);
+            assert!((analyzer.window_sum() - w(256).iter().sum::<Sample>()).abs() < 1e-3);
+        }
+
+        // Noise power does not depend on the window
+        buf.push(&crate::helpers::signal::white_noise(8000, 0.032, 9));
+        let power = |w: fn(usize) -> Vec<Sample>| {
+            let mut analyzer = FourierBuilder::new()
+                .rate(8000)
+                .length(256)
+                .downsample(1)
+                .window(w)
+                .normalize(Normalization::Energy)
+                .plan();
+            analyzer.analyze(&buf);
+            analyzer.magnitudes(0).iter().map(|m| m * m).sum::<f32>()
+        };
+        let ratio = power(window::hanning) / power(window::none);
+        assert!((ratio - 1.0).abs() < 0.2, "{}", ratio);
+
+        // Half the mean square of a sinusoid, for any window
+        buf.push(&crate::helpers::signal::sine(8000, 1000.0, 0.032));
+        for w in windows {
+            let energy = power(w);
+            assert!((energy - 0.25).abs() < 1e-3, "{}", energy);
+        }
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .pad_to(1024)
+            .downsample(1)
+            .window(window::hanning)
+            .normalize(Normalization::Energy)
+            .plan();
+        analyzer.analyze(&buf);
+        let energy = analyzer.magnitudes(0).iter().map(|m| m * m).sum::<f32>();
+        assert!((energy - 0.25).abs() < 1e-3, "{}", energy);
+    }
+
+    #[test]
+    fn test_parallel() {
+        crate::test_config();
+        let buf = crate::analyzer::SampleBuffer::new(8192, 8000);
+        let left = crate::helpers::signal::white_noise(8000, 1.024, 11);
+        let right = crate::helpers::signal::white_noise(8000, 1.024, 12);
+        buf.push(
+            &left
+                .iter()
+                .zip(right.iter())
+                .map(|([l, _], [r, _])| [*l, *r])
+                .collect::<Vec<_>>(),
+        );
+
+        let analyze = |single_thread| {
+            let mut analyzer = FourierBuilder::new()
+                .rate(8000)
+                .length(8192)
+                .downsample(1)
+                .window(window::hanning)
+                .single_thread(single_thread)
+                .plan();
+            analyzer.analyze(&buf);
+            let (l, r) = analyzer.output();
+            (
+                l.to_vec(),
+                r.to_vec(),
+                analyzer.left().iter().cloned().collect::<Vec<_>>(),
+            )
+        };
+
+        let parallel = analyze(false);
+        assert_eq!(parallel, analyze(true));
+        assert_ne!(parallel.0, parallel.1);
+    }
+
+    #[test]
+    fn test_inverse() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .window(window::hanning)
+            .downsample(1)
+            .normalize(Normalization::Amplitude)
+            .plan();
+        let buf = crate::analyzer::SampleBuffer::new(256, 8000);
+        let noise = crate::helpers::signal::white_noise(8000, 0.512, 3);
+
+        for hop in [64, 128] {
+            let mut inverse = analyzer.inverse(hop);
+            let mut out = vec![0.0; noise.len()];
+            let mut written = 0;
+            for chunk in noise.chunks(hop) {
+                buf.push(chunk);
+                analyzer.analyze(&buf);
+                inverse.process(analyzer.output().0);
+                written += inverse.read(&mut out[written..]);
+            }
+            assert_eq!(written, noise.len());
+            assert_eq!(inverse.available(), 0);
+
+            // Delayed by the overlap, after fading in
+            let delay = 256 - hop;
+            for (o, n) in out[delay..].iter().zip(noise.iter()) {
+                assert!((o - n[0]).abs() < 1e-4, "{} != {}", o, n[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_inverse_padded() {
+        crate::test_config();
+        let mut analyzer = FourierBuilder::new()
+            .rate(8000)
+            .length(200)
+            .pad_to(256)
+            .window(window::hanning)
+            .downsample(1)
+            .plan();
+        let mut inverse = InverseBuilder::new()
+            .length(200)
+            .pad_to(256)
+            .window(window::hanning)
+            .hop(50)
+            .buffer_size(100)
+            .plan();
+        assert_eq!(inverse.length(), analyzer.length());
+
+        let buf = crate::analyzer::SampleBuffer::new(256, 8000);
+        let sine = crate::helpers::signal::sine(8000, 1000.0, 0.1);
+        for chunk in sine.chunks(50) {
+            buf.push(chunk);
+            analyzer.analyze(&buf);
+            inverse.process(analyzer.output().0);
+        }
+
+        // Only the newest samples are kept
+        let mut out = vec![0.0; 200];
+        assert_eq!(inverse.read(&mut out), 100);
+        let end = sine.len() - 150;
+        for (o, s) in out[..100].iter().zip(sine[end - 100..end].iter()) {
+            assert!((o - s[0]).abs() < 1e-4, "{} != {}", o, s[0]);
+        }
+    }
+
+    #[test]
+    fn test_remove_dc() {
+        crate::test_config();
+        let biased = crate::helpers::signal::sine(8000, 1000.0, 256.0 / 8000.0)
+            .iter()
+            .map(|[l, r]| [l * 0.5 + 0.3, r * 0.5 - 0.2])
+            .collect::<Vec<_>>();
+        let buf = crate::analyzer::SampleBuffer::new(256, 8000);
+        buf.push(&biased);
+
+        let analyze = |remove_dc| {
+            let mut analyzer = FourierBuilder::new()
+                .rate(8000)
+                .length(256)
+                .window(window::hanning)
+                .downsample(1)
+                .remove_dc(remove_dc)
+                .plan();
+            analyzer.analyze(&buf);
+            (analyzer.magnitudes(0), analyzer.magnitudes(1))
+        };
+
+        let (left, right) = analyze(false);
+        let (left_dc, right_dc) = analyze(true);
+        assert!(
+            left[0] > 30.0 && right[0] > 20.0,
+            "{} {}",
+            left[0],
+            right[0]
+        );
+        assert!(left_dc[0] < 1e-3 && right_dc[0] < 1e-3);
+        assert!(left_dc[1] < left[1] * 0.01);
+
+        // The tone is untouched
+        assert!((left_dc[32] - left[32]).abs() < 1e-3);
+    }
 }
@@ -0,0 +1,215 @@
+//! Automatic Gain
+use crate::analyzer;
+
+/// Builder for AutoGain
+#[derive(Debug, Default)]
+pub struct AutoGainBuilder {
+    /// Level the input should be amplified to
+    ///
+    /// Defaults to `1.0`, can also be set from config as `"audio.gain.target"`.
+    pub target: Option<analyzer::SignalStrength>,
+
+    /// Fraction of the tracked peak level that remains after one second
+    ///
+    /// The lower this is, the faster the gain rises after a loud passage.  Defaults to
+    /// `0.5`, can also be set from config as `"audio.gain.release"`.
+    pub release: Option<f32>,
+
+    /// Upper limit for the gain
+    ///
+    /// Defaults to `100.0`, can also be set from config as `"audio.gain.max"`.
+    pub max_gain: Option<f32>,
+
+    /// Gain at startup
+    ///
+    /// Defaults to `1.0`, can also be set from config as `"audio.gain.initial"`.
+    pub initial_gain: Option<f32>,
+
+    /// Duration in seconds over which the gain ramps from `initial_gain` to its
+    /// steady-state value
+    ///
+    /// Defaults to `2.0`, can also be set from config as `"audio.gain.warmup"`.
+    pub warmup: Option<f32>,
+}
+
+impl AutoGainBuilder {
+    /// Create a new AutoGainBuilder
+    pub fn new() -> AutoGainBuilder {
+        Default::default()
+    }
+
+    /// Set the target level
+    pub fn target(&mut self, target: analyzer::SignalStrength) -> &mut AutoGainBuilder {
+        self.target = Some(target);
+        self
+    }
+
+    /// Set the release of the tracked level
+    pub fn release(&mut self, release: f32) -> &mut AutoGainBuilder {
+        self.release = Some(release);
+        self
+    }
+
+    /// Set the maximum gain
+    pub fn max_gain(&mut self, max_gain: f32) -> &mut AutoGainBuilder {
+        self.max_gain = Some(max_gain);
+        self
+    }
+
+    /// Set the initial gain
+    pub fn initial_gain(&mut self, initial_gain: f32) -> &mut AutoGainBuilder {
+        self.initial_gain = Some(initial_gain);
+        self
+    }
+
+    /// Set the warm-up duration in seconds
+    pub fn warmup(&mut self, warmup: f32) -> &mut AutoGainBuilder {
+        self.warmup = Some(warmup);
+        self
+    }
+
+    /// Build the AutoGain
+    pub fn build(&mut self) -> AutoGain {
+        AutoGain::from_builder(self)
+    }
+}
+
+/// Automatic gain control
+///
+/// Tracks the peak level of the input and computes a gain which brings it to the target
+/// level.  During the warm-up period the gain ramps up from a conservative initial value
+/// so a quiet intro is not blasted to full scale.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// let mut gain = analyzer::AutoGainBuilder::new()
+///     .target(1.0)
+///     .release(0.5)
+///     .max_gain(100.0)
+///     .initial_gain(1.0)
+///     .warmup(2.0)
+///     .build();
+///
+/// // Level and time since the last call
+/// let g = gain.update(0.1, 1.0 / 30.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AutoGain {
+    target: analyzer::SignalStrength,
+    release: f32,
+    max_gain: f32,
+    initial_gain: f32,
+    warmup: f32,
+
+    level: analyzer::SignalStrength,
+    elapsed: f32,
+    gain: f32,
+}
+
+impl AutoGain {
+    /// Create an AutoGain from a builder config
+    pub fn from_builder(build: &AutoGainBuilder) -> AutoGain {
+        let initial_gain = build
+            .initial_gain
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.gain.initial", 1.0));
+
+        AutoGain {
+            target: build
+                .target
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.gain.target", 1.0)),
+            release: build
+                .release
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.gain.release", 0.5)),
+            max_gain: build
+                .max_gain
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.gain.max", 100.0)),
+            initial_gain,
+            warmup: build
+                .warmup
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.gain.warmup", 2.0)),
+
+            level: 0.0,
+            elapsed: 0.0,
+            gain: initial_gain,
+        }
+    }
+
+    /// Return the gain computed during the last update
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Return whether the warm-up period is over
+    pub fn warmed_up(&self) -> bool {
+        self.elapsed >= self.warmup
+    }
+
+    /// Feed the current level and the time in seconds since the last call
+    ///
+    /// Returns the new gain.
+    pub fn update(&mut self, level: analyzer::SignalStrength, dt: f32) -> f32 {
+        self.elapsed += dt;
+        self.level = level.max(self.level * self.release.powf(dt));
+
+        let steady = if self.level > 0.0 {
+            (self.target / self.level).min(self.max_gain)
+        } else {
+            self.max_gain
+        };
+
+        self.gain = if self.warmed_up() {
+            steady
+        } else {
+            let progress = self.elapsed / self.warmup;
+            steady.min(self.initial_gain + (steady - self.initial_gain) * progress)
+        };
+
+        self.gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warmup() {
+        let mut gain = AutoGainBuilder::new()
+            .target(1.0)
+            .release(0.5)
+            .max_gain(100.0)
+            .initial_gain(1.0)
+            .warmup(2.0)
+            .build();
+
+        let dt = 0.1;
+        let mut last = 0.0;
+        for _ in 0..19 {
+            let g = gain.update(0.02, dt);
+            assert!(g < 50.0, "{}", g);
+            assert!(g > last, "{} > {}", g, last);
+            last = g;
+        }
+
+        for _ in 0..10 {
+            gain.update(0.02, dt);
+        }
+        assert!(gain.warmed_up());
+        assert!((gain.gain() - 50.0).abs() < 1e-3, "{}", gain.gain());
+    }
+
+    #[test]
+    fn test_max_gain() {
+        let mut gain = AutoGainBuilder::new()
+            .target(1.0)
+            .release(0.5)
+            .max_gain(10.0)
+            .initial_gain(1.0)
+            .warmup(0.0)
+            .build();
+
+        assert_eq!(gain.update(0.0, 0.1), 10.0);
+        assert!((gain.update(0.5, 0.1) - 2.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,205 @@
+//! Single Frequency Detection
+use crate::analyzer;
+
+/// Builder for Goertzel
+#[derive(Debug, Default)]
+pub struct GoertzelBuilder {
+    /// Frequencies to detect
+    ///
+    /// Can also be set from config as `"audio.goertzel.frequencies"`.
+    pub frequencies: Vec<analyzer::Frequency>,
+
+    /// Number of samples per detection
+    ///
+    /// Longer blocks separate closer frequencies, but react slower.  Defaults to `205`, the
+    /// classic choice for DTMF at 8 kHz, can also be set from config as
+    /// `"audio.goertzel.block_size"`.
+    pub block_size: Option<usize>,
+}
+
+impl GoertzelBuilder {
+    /// Create a new GoertzelBuilder
+    pub fn new() -> GoertzelBuilder {
+        Default::default()
+    }
+
+    /// Add a frequency to detect
+    pub fn frequency(&mut self, frequency: analyzer::Frequency) -> &mut GoertzelBuilder {
+        self.frequencies.push(frequency);
+        self
+    }
+
+    /// Set the block size
+    pub fn block_size(&mut self, block_size: usize) -> &mut GoertzelBuilder {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    /// Build the detector
+    pub fn build(&mut self) -> Goertzel {
+        Goertzel::from_builder(self)
+    }
+}
+
+/// Goertzel filters for a few target frequencies
+///
+/// Computes the magnitude of single frequencies from the newest block of samples,
+/// downmixed to mono with the buffer's
+/// [`downmix_weights`](../samples/struct.SampleBuffer.html#method.downmix_weights).  Much
+/// cheaper than a full transform when only a handful of frequencies matter, eg. for DTMF
+/// tones or specific notes.  Targets do not need to lie on a bin of the block size.
+///
+/// Magnitudes are normalized so a sine at a target frequency yields its amplitude.
+/// Coefficients are computed for the rate of the `SampleBuffer` and recomputed if it
+/// changes.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let samples = analyzer::SampleBuffer::new(8000, 8000);
+/// let mut goertzel = analyzer::GoertzelBuilder::new()
+///     .frequency(440.0)
+///     .frequency(880.0)
+///     .block_size(400)
+///     .build();
+///
+/// goertzel.analyze(&samples);
+/// let [a4, a5] = [goertzel.magnitudes()[0], goertzel.magnitudes()[1]];
+/// ```
+#[derive(Debug, Clone)]
+pub struct Goertzel {
+    frequencies: Vec<analyzer::Frequency>,
+    block_size: usize,
+
+    rate: usize,
+    coefficients: Vec<analyzer::Sample>,
+    magnitudes: Vec<analyzer::Sample>,
+}
+
+impl Goertzel {
+    /// Create a Goertzel detector from a builder config
+    pub fn from_builder(build: &GoertzelBuilder) -> Goertzel {
+        let frequencies = if build.frequencies.is_empty() {
+            crate::CONFIG
+                .get::<Vec<analyzer::Frequency>>("audio.goertzel.frequencies")
+                .unwrap_or_default()
+        } else {
+            build.frequencies.clone()
+        };
+
+        Goertzel {
+            block_size: build
+                .block_size
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.goertzel.block_size", 205)),
+
+            rate: 0,
+            coefficients: Vec::new(),
+            magnitudes: vec![0.0; frequencies.len()],
+            frequencies,
+        }
+    }
+
+    /// Return the target frequencies, in the order of the magnitudes
+    #[inline]
+    pub fn frequencies(&self) -> &[analyzer::Frequency] {
+        &self.frequencies
+    }
+
+    /// Compute the coefficients for `rate`
+    fn plan(&mut self, rate: usize) {
+        self.rate = rate;
+        self.coefficients = self
+            .frequencies
+            .iter()
+            .map(|&f| {
+                let omega = 2.0 * std::f64::consts::PI * f as f64 / rate as f64;
+                (2.0 * omega.cos()) as analyzer::Sample
+            })
+            .collect();
+    }
+
+    /// Detect the target frequencies in the newest block of `buf`
+    pub fn analyze(&mut self, buf: &analyzer::SampleBuffer) -> &[analyzer::Sample] {
+        if buf.rate() != self.rate {
+            self.plan(buf.rate());
+        }
+
+        let block = buf.mono(self.block_size);
+        let scale = 2.0 / block.len() as analyzer::Sample;
+        for (magnitude, coefficient) in self.magnitudes.iter_mut().zip(self.coefficients.iter()) {
+            let (mut s1, mut s2) = (0.0, 0.0);
+            for x in block.iter() {
+                let s = x + coefficient * s1 - s2;
+                s2 = s1;
+                s1 = s;
+            }
+
+            let power = s1 * s1 + s2 * s2 - coefficient * s1 * s2;
+            *magnitude = power.max(0.0).sqrt() * scale;
+        }
+
+        &self.magnitudes
+    }
+
+    /// Return the magnitude of each target frequency from the last analysis
+    #[inline]
+    pub fn magnitudes(&self) -> &[analyzer::Sample] {
+        &self.magnitudes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DTMF: [analyzer::Frequency; 8] =
+        [697.0, 770.0, 852.0, 941.0, 1209.0, 1336.0, 1477.0, 1633.0];
+
+    fn dtmf(
+        rate: usize,
+        low: analyzer::Frequency,
+        high: analyzer::Frequency,
+    ) -> Vec<analyzer::Sample> {
+        let buf = analyzer::SampleBuffer::new(rate / 10, rate);
+        let low = crate::helpers::signal::sine(rate, low, 0.1);
+        let high = crate::helpers::signal::sine(rate, high, 0.1);
+        buf.push(
+            &low.iter()
+                .zip(high.iter())
+                .map(|(l, h)| [0.5 * (l[0] + h[0]), 0.5 * (l[1] + h[1])])
+                .collect::<Vec<_>>(),
+        );
+
+        let mut builder = GoertzelBuilder::new();
+        for &f in DTMF.iter() {
+            builder.frequency(f);
+        }
+        let mut goertzel = builder.block_size(rate / 8000 * 205).build();
+        goertzel.analyze(&buf).to_vec()
+    }
+
+    #[test]
+    fn test_dtmf() {
+        crate::test_config();
+        // Key "5"
+        let magnitudes = dtmf(8000, 770.0, 1336.0);
+        for (i, m) in magnitudes.iter().enumerate() {
+            if i == 1 || i == 5 {
+                assert!((m - 0.5).abs() < 0.02, "{:?}", magnitudes);
+            } else {
+                assert!(*m < 0.15, "{:?}", magnitudes);
+            }
+        }
+
+        // Key "D" at another rate
+        let magnitudes = dtmf(16000, 941.0, 1633.0);
+        for (i, m) in magnitudes.iter().enumerate() {
+            if i == 3 || i == 7 {
+                assert!((m - 0.5).abs() < 0.02, "{:?}", magnitudes);
+            } else {
+                assert!(*m < 0.15, "{:?}", magnitudes);
+            }
+        }
+    }
+}
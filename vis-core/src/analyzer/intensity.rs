@@ -0,0 +1,271 @@
+//! Combined Intensity
+use crate::analyzer;
+use crate::helpers;
+
+/// Builder for Intensity
+#[derive(Debug, Default)]
+pub struct IntensityBuilder {
+    /// Weight of the overall loudness
+    ///
+    /// Defaults to `1.0`, can also be set from config as `"audio.intensity.loudness"`.
+    pub loudness: Option<f32>,
+
+    /// Weight of the spectral flux, ie. how much the spectrum grew since the last update
+    ///
+    /// Defaults to `1.0`, can also be set from config as `"audio.intensity.flux"`.
+    pub flux: Option<f32>,
+
+    /// Weight of the bass energy
+    ///
+    /// Defaults to `1.0`, can also be set from config as `"audio.intensity.bass"`.
+    pub bass: Option<f32>,
+
+    /// Fraction of the tracked peaks that remains after one second
+    ///
+    /// Components are normalized to these peaks.  Defaults to `0.5`, can also be set from
+    /// config as `"audio.intensity.release"`.
+    pub release: Option<f32>,
+
+    /// Smoothing factor of the output, see [`Smoothing`](../../helpers/struct.Smoothing.html)
+    ///
+    /// Defaults to `0.5`, can also be set from config as `"audio.intensity.smoothing"`.
+    pub smoothing: Option<f32>,
+}
+
+impl IntensityBuilder {
+    /// Create a new IntensityBuilder
+    pub fn new() -> IntensityBuilder {
+        Default::default()
+    }
+
+    /// Set the weights of loudness, flux and bass
+    pub fn weights(&mut self, loudness: f32, flux: f32, bass: f32) -> &mut IntensityBuilder {
+        self.loudness = Some(loudness);
+        self.flux = Some(flux);
+        self.bass = Some(bass);
+        self
+    }
+
+    /// Set the release of the tracked peaks
+    pub fn release(&mut self, release: f32) -> &mut IntensityBuilder {
+        self.release = Some(release);
+        self
+    }
+
+    /// Set the smoothing factor
+    pub fn smoothing(&mut self, smoothing: f32) -> &mut IntensityBuilder {
+        self.smoothing = Some(smoothing);
+        self
+    }
+
+    /// Build the analyzer
+    pub fn build(&mut self) -> Intensity {
+        Intensity::from_builder(self)
+    }
+}
+
+/// Energy of the moment, combining loudness, spectral flux and bass energy
+///
+/// Each component is normalized to its tracked peak (bass to the loudness peak, so a
+/// track without bass stays low), weighted, and the weighted mean is smoothed into a
+/// single value from `0.0` to `1.0`.  Meant for driving one parameter off the music.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// let mut intensity = analyzer::IntensityBuilder::new()
+///     .weights(1.0, 2.0, 1.0)
+///     .release(0.5)
+///     .smoothing(0.5)
+///     .build();
+///
+/// # let spectrum = analyzer::Spectrum::new(vec![0.0; 100], 0.0, 4000.0);
+/// // Spectrum and time since the last call
+/// let value = intensity.update(&spectrum, 1.0 / 30.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Intensity {
+    weights: [f32; 3],
+    release: f32,
+    smoothing: helpers::Smoothing,
+
+    previous: Vec<analyzer::SignalStrength>,
+    peaks: [analyzer::SignalStrength; 2],
+    components: [f32; 3],
+    value: f32,
+}
+
+impl Intensity {
+    /// Create an Intensity from a builder config
+    pub fn from_builder(build: &IntensityBuilder) -> Intensity {
+        Intensity {
+            weights: [
+                build
+                    .loudness
+                    .unwrap_or_else(|| crate::CONFIG.get_or("audio.intensity.loudness", 1.0)),
+                build
+                    .flux
+                    .unwrap_or_else(|| crate::CONFIG.get_or("audio.intensity.flux", 1.0)),
+                build
+                    .bass
+                    .unwrap_or_else(|| crate::CONFIG.get_or("audio.intensity.bass", 1.0)),
+            ],
+            release: build
+                .release
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.intensity.release", 0.5)),
+            smoothing: helpers::Smoothing::new(
+                build
+                    .smoothing
+                    .unwrap_or_else(|| crate::CONFIG.get_or("audio.intensity.smoothing", 0.5)),
+            ),
+
+            previous: Vec::new(),
+            peaks: [0.0; 2],
+            components: [0.0; 3],
+            value: 0.0,
+        }
+    }
+
+    /// Return the intensity computed during the last update
+    #[inline]
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Return the normalized loudness of the last update
+    #[inline]
+    pub fn loudness(&self) -> f32 {
+        self.components[0]
+    }
+
+    /// Return the normalized spectral flux of the last update
+    #[inline]
+    pub fn flux(&self) -> f32 {
+        self.components[1]
+    }
+
+    /// Return the normalized bass energy of the last update
+    #[inline]
+    pub fn bass(&self) -> f32 {
+        self.components[2]
+    }
+
+    /// Feed a new spectrum, `dt` seconds after the last update
+    ///
+    /// Returns the new intensity.
+    pub fn update<S: analyzer::spectrum::Storage>(
+        &mut self,
+        spectrum: &analyzer::Spectrum<S>,
+        dt: f32,
+    ) -> f32 {
+        let mut dummy = helpers::Smoothing::new(0.0);
+        let loudness = helpers::energy_scalar(spectrum, helpers::Weighting::Flat, &mut dummy);
+        let bass = helpers::energy_scalar(spectrum, helpers::Weighting::Bass, &mut dummy);
+
+        let flux = if self.previous.len() == spectrum.len() {
+            spectrum
+                .iter()
+                .zip(self.previous.iter())
+                .map(|(v, p)| (v - p).max(0.0))
+                .sum()
+        } else {
+            loudness
+        };
+        self.previous.clear();
+        self.previous.extend(spectrum.iter());
+
+        let decay = self.release.powf(dt);
+        self.peaks[0] = loudness.max(self.peaks[0] * decay);
+        self.peaks[1] = flux.max(self.peaks[1] * decay);
+
+        let normalize = |v: f32, peak: f32| if peak > 0.0 { v / peak } else { 0.0 };
+        self.components = [
+            normalize(loudness, self.peaks[0]),
+            normalize(flux, self.peaks[1]),
+            normalize(bass, self.peaks[0]),
+        ];
+
+        let total = self.weights.iter().sum::<f32>();
+        let mixed = if total > 0.0 {
+            self.components
+                .iter()
+                .zip(self.weights.iter())
+                .map(|(c, w)| c * w)
+                .sum::<f32>()
+                / total
+        } else {
+            0.0
+        };
+
+        self.value = self.smoothing.update(mixed.clamp(0.0, 1.0));
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(f: analyzer::Frequency, v: analyzer::SignalStrength) -> analyzer::Spectrum<Vec<f32>> {
+        let mut spectrum = analyzer::Spectrum::new(vec![0.0; 401], 0.0, 4000.0);
+        spectrum[f] = v;
+        spectrum
+    }
+
+    fn weighted(loudness: f32, flux: f32, bass: f32) -> Intensity {
+        IntensityBuilder::new()
+            .weights(loudness, flux, bass)
+            .release(0.5)
+            .smoothing(0.0)
+            .build()
+    }
+
+    #[test]
+    fn test_loudness() {
+        let mut intensity = weighted(1.0, 0.0, 0.0);
+        assert_eq!(intensity.update(&tone(3000.0, 1.0), 0.01), 1.0);
+        assert_eq!(intensity.update(&tone(3000.0, 1.0), 0.01), 1.0);
+
+        // Half the peak, which decays slightly
+        let half = intensity.update(&tone(3000.0, 0.5), 0.01);
+        assert!((half - 0.5 / 0.5f32.powf(0.01)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_flux() {
+        let mut intensity = weighted(0.0, 1.0, 0.0);
+        assert_eq!(intensity.update(&tone(3000.0, 1.0), 0.01), 1.0);
+
+        // A steady spectrum has no flux, whatever its loudness
+        assert_eq!(intensity.update(&tone(3000.0, 1.0), 0.01), 0.0);
+        assert_eq!(intensity.update(&tone(3000.0, 0.5), 0.01), 0.0);
+        assert!(intensity.update(&tone(1000.0, 1.0), 0.01) > 0.9);
+    }
+
+    #[test]
+    fn test_bass() {
+        let mut intensity = weighted(0.0, 0.0, 1.0);
+        assert!(intensity.update(&tone(3000.0, 1.0), 0.01) < 0.01);
+        assert!(intensity.update(&tone(50.0, 1.0), 0.01) > 0.85);
+    }
+
+    #[test]
+    fn test_weights() {
+        // Steady treble: full loudness, no flux, no bass
+        let treble = tone(3000.0, 1.0);
+
+        let mut intensity = weighted(1.0, 1.0, 0.0);
+        intensity.update(&treble, 0.01);
+        assert_eq!(intensity.update(&treble, 0.01), 0.5);
+
+        let mut intensity = weighted(3.0, 1.0, 0.0);
+        intensity.update(&treble, 0.01);
+        assert_eq!(intensity.update(&treble, 0.01), 0.75);
+
+        let mut intensity = weighted(1.0, 0.0, 1.0);
+        intensity.update(&treble, 0.01);
+        assert!((intensity.update(&treble, 0.01) - 0.5).abs() < 0.01);
+        assert_eq!(intensity.loudness(), 1.0);
+        assert_eq!(intensity.flux(), 0.0);
+    }
+}
@@ -0,0 +1,249 @@
+//! Signal Level Metering
+use crate::analyzer;
+
+/// Builder for LevelAnalyzer
+#[derive(Debug, Default)]
+pub struct LevelBuilder {
+    /// Number of samples measured per update
+    ///
+    /// Defaults to `1024`, can also be set from config as `"audio.level.length"`.
+    pub length: Option<usize>,
+
+    /// Whether to apply K-weighting (ITU-R BS.1770) before measuring
+    ///
+    /// Shapes the signal like perceived loudness, which makes
+    /// [`lufs`](struct.LevelAnalyzer.html#method.lufs) a rough LUFS estimate.  Defaults to
+    /// `false`, can also be set from config as `"audio.level.k_weighting"`.
+    pub k_weighting: Option<bool>,
+}
+
+impl LevelBuilder {
+    /// Create a new LevelBuilder
+    pub fn new() -> LevelBuilder {
+        Default::default()
+    }
+
+    /// Set the measured length
+    pub fn length(&mut self, length: usize) -> &mut LevelBuilder {
+        self.length = Some(length);
+        self
+    }
+
+    /// Enable or disable K-weighting
+    pub fn k_weighting(&mut self, k_weighting: bool) -> &mut LevelBuilder {
+        self.k_weighting = Some(k_weighting);
+        self
+    }
+
+    /// Build the analyzer
+    pub fn build(&mut self) -> LevelAnalyzer {
+        LevelAnalyzer::from_builder(self)
+    }
+}
+
+/// Biquad filter in direct form I
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The two stages of the K-weighting filter at `rate`
+///
+/// A high shelf of about +4 dB above 1.5 kHz modelling the head, followed by a highpass
+/// at 38 Hz.  Coefficients follow BS.1770 and are recomputed for rates other than 48 kHz.
+fn k_weighting(rate: usize) -> [Biquad; 2] {
+    let rate = rate as f64;
+
+    let k = (std::f64::consts::PI * 1681.974450955533 / rate).tan();
+    let q = 0.7071752369554196;
+    let vh = 10.0f64.powf(3.999843853973347 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        ..Default::default()
+    };
+
+    let k = (std::f64::consts::PI * 38.13547087602444 / rate).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1.0 + k / q + k * k;
+    let highpass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        ..Default::default()
+    };
+
+    [shelf, highpass]
+}
+
+/// RMS and peak level of the newest samples, per channel
+///
+/// Each call to [`analyze`](#method.analyze) measures the last `length` samples of a
+/// `SampleBuffer`, eg. for a VU meter.  With K-weighting enabled, the filter runs over
+/// each window from a silent state and only the RMS is weighted, the peak is always
+/// measured on the raw samples.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let buffer = analyzer::SampleBuffer::new(1024, 8000);
+/// let mut level = analyzer::LevelBuilder::new()
+///     .length(512)
+///     .build();
+///
+/// level.analyze(&buffer);
+/// let db = level.rms_db(0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LevelAnalyzer {
+    length: usize,
+    k_weighting: bool,
+
+    rms: [f32; 2],
+    peak: [f32; 2],
+}
+
+impl LevelAnalyzer {
+    /// Create a LevelAnalyzer from a builder config
+    pub fn from_builder(build: &LevelBuilder) -> LevelAnalyzer {
+        LevelAnalyzer {
+            length: build
+                .length
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.level.length", 1024)),
+            k_weighting: build
+                .k_weighting
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.level.k_weighting", false)),
+
+            rms: [0.0; 2],
+            peak: [0.0; 2],
+        }
+    }
+
+    /// Measure the newest samples of `buf`
+    pub fn analyze(&mut self, buf: &analyzer::SampleBuffer) {
+        let mut filters = [k_weighting(buf.rate()); 2];
+        let k_weighting = self.k_weighting;
+
+        let (squares, peak, len) = buf.with_latest(self.length, |samples| {
+            let mut squares = [0.0f64; 2];
+            let mut peak = [0.0f32; 2];
+            for s in samples.iter() {
+                for c in 0..2 {
+                    let raw = analyzer::samples::to_f32(s[c]);
+                    peak[c] = peak[c].max(raw.abs());
+
+                    let mut x = raw as f64;
+                    if k_weighting {
+                        for f in filters[c].iter_mut() {
+                            x = f.process(x);
+                        }
+                    }
+                    squares[c] += x * x;
+                }
+            }
+            (squares, peak, samples.len())
+        });
+
+        for (rms, squares) in self.rms.iter_mut().zip(squares.iter()) {
+            *rms = if len > 0 {
+                (squares / len as f64).sqrt() as f32
+            } else {
+                0.0
+            };
+        }
+        self.peak = peak;
+    }
+
+    /// Return the RMS level of `channel` (`0` is left) from the last update
+    #[inline]
+    pub fn rms(&self, channel: usize) -> f32 {
+        self.rms[channel]
+    }
+
+    /// Return the largest absolute sample of `channel` from the last update
+    #[inline]
+    pub fn peak(&self, channel: usize) -> f32 {
+        self.peak[channel]
+    }
+
+    /// Return the [`rms`](#method.rms) of `channel` in dBFS
+    ///
+    /// A full scale square is `0.0` dB, a full scale sine about `-3.0` dB.  Silence is
+    /// negative infinity.
+    pub fn rms_db(&self, channel: usize) -> f32 {
+        20.0 * self.rms[channel].log10()
+    }
+
+    /// Return the loudness of both channels from the last update
+    ///
+    /// Computed like the BS.1770 loudness of a stereo signal, but over a single window
+    /// without gating.  Only a LUFS estimate if K-weighting is enabled.
+    pub fn lufs(&self) -> f32 {
+        -0.691 + 10.0 * self.rms.iter().map(|r| r * r).sum::<f32>().log10()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sine() {
+        crate::test_config();
+        let buf = analyzer::SampleBuffer::new(4000, 8000);
+        buf.push(&crate::helpers::signal::sine(8000, 500.0, 0.5));
+
+        let mut level = LevelBuilder::new().length(1600).k_weighting(false).build();
+        level.analyze(&buf);
+
+        for c in 0..2 {
+            assert!(
+                (level.rms(c) - 0.5f32.sqrt()).abs() < 1e-3,
+                "{}",
+                level.rms(c)
+            );
+            assert!((level.peak(c) - 1.0).abs() < 1e-3, "{}", level.peak(c));
+            assert!((level.rms_db(c) + 3.01).abs() < 0.01, "{}", level.rms_db(c));
+        }
+    }
+
+    #[test]
+    fn test_k_weighting() {
+        crate::test_config();
+        let measure = |freq| {
+            let buf = analyzer::SampleBuffer::new(48000, 48000);
+            buf.push(&crate::helpers::signal::sine(48000, freq, 1.0));
+
+            let mut level = LevelBuilder::new().length(48000).k_weighting(true).build();
+            level.analyze(&buf);
+            level.lufs()
+        };
+
+        // BS.1770 is calibrated so a full scale 1 kHz sine on both channels is 0 LUFS
+        let lufs = measure(997.0);
+        assert!(lufs.abs() < 0.1, "{}", lufs);
+        // Bass is attenuated, treble boosted
+        assert!(measure(20.0) < -10.0);
+        assert!(measure(4000.0) > 3.0);
+    }
+}
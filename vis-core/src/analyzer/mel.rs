@@ -0,0 +1,304 @@
+//! Mel Bands
+use crate::analyzer;
+
+/// Convert a frequency to mels
+pub fn hz_to_mel(f: analyzer::Frequency) -> f32 {
+    2595.0 * (1.0 + f / 700.0).log10()
+}
+
+/// Convert mels to a frequency
+pub fn mel_to_hz(mel: f32) -> analyzer::Frequency {
+    700.0 * (10.0f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// Builder for MelAnalyzer
+#[derive(Debug, Default)]
+pub struct MelBuilder {
+    /// Number of mel filters
+    ///
+    /// Defaults to `40`, can also be set from config as `"audio.mel.count"`.
+    pub count: Option<usize>,
+
+    /// Lower edge of the first filter
+    ///
+    /// Defaults to `20.0`, can also be set from config as `"audio.mel.lowest"`.
+    pub lowest: Option<analyzer::Frequency>,
+
+    /// Upper edge of the last filter
+    ///
+    /// Defaults to the Nyquist frequency of the analyzer, can also be set from config as
+    /// `"audio.mel.highest"`.
+    pub highest: Option<analyzer::Frequency>,
+}
+
+impl MelBuilder {
+    /// Create a new MelBuilder
+    pub fn new() -> MelBuilder {
+        Default::default()
+    }
+
+    /// Set the number of mel filters
+    pub fn count(&mut self, count: usize) -> &mut MelBuilder {
+        self.count = Some(count);
+        self
+    }
+
+    /// Set the frequency range covered by the filters
+    pub fn range(
+        &mut self,
+        lowest: analyzer::Frequency,
+        highest: analyzer::Frequency,
+    ) -> &mut MelBuilder {
+        self.lowest = Some(lowest);
+        self.highest = Some(highest);
+        self
+    }
+
+    /// Build the MelAnalyzer around a fourier analyzer
+    pub fn build(&mut self, analyzer: analyzer::FourierAnalyzer) -> MelAnalyzer {
+        MelAnalyzer::from_builder(self, analyzer)
+    }
+}
+
+/// A single triangular filter, `weights[i]` belongs to bin `start + i`
+#[derive(Debug, Clone)]
+struct Filter {
+    start: usize,
+    weights: Vec<analyzer::Sample>,
+}
+
+/// Mel-scale filterbank on top of a fourier analyzer
+///
+/// The filters are triangles with edges equally spaced on the mel scale, each one peaks
+/// at the lower edge of the next.  Bands are weighted sums of the power of the transform
+/// bins, independent of the analyzer's
+/// [`MagnitudeMode`](../fourier/enum.MagnitudeMode.html).  Filters narrower than a bin
+/// take the bin nearest their center.
+///
+/// The filterbank is computed once from the analyzer's
+/// [`frequencies`](../fourier/struct.FourierAnalyzer.html#method.frequencies) and again
+/// only if they change, eg. after a change of the downsampling factor.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let samples = analyzer::SampleBuffer::new(32000, 8000);
+/// let fourier = analyzer::FourierBuilder::new().plan();
+/// let mut mel = analyzer::MelBuilder::new()
+///     .count(24)
+///     .range(50.0, 4000.0)
+///     .build(fourier);
+///
+/// mel.analyze(&samples);
+/// assert_eq!(mel.bands(0).len(), 24);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MelAnalyzer {
+    lowest: analyzer::Frequency,
+    highest: Option<analyzer::Frequency>,
+
+    analyzer: analyzer::FourierAnalyzer,
+    frequencies: Vec<analyzer::Frequency>,
+    filters: Vec<Filter>,
+    bands: [Vec<analyzer::Sample>; 2],
+}
+
+impl MelAnalyzer {
+    /// Create a MelAnalyzer from a builder config
+    pub fn from_builder(build: &MelBuilder, analyzer: analyzer::FourierAnalyzer) -> MelAnalyzer {
+        let count = build
+            .count
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.mel.count", 40));
+
+        let mut mel = MelAnalyzer {
+            lowest: build
+                .lowest
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.mel.lowest", 20.0)),
+            highest: build
+                .highest
+                .or_else(|| crate::CONFIG.get("audio.mel.highest")),
+
+            analyzer,
+            frequencies: Vec::new(),
+            filters: Vec::new(),
+            bands: [vec![0.0; count], vec![0.0; count]],
+        };
+        mel.plan();
+        mel
+    }
+
+    /// Compute the filterbank for the current bin frequencies
+    fn plan(&mut self) {
+        self.frequencies = self.analyzer.frequencies();
+        let count = self.bands[0].len();
+
+        self.filters = (0..count)
+            .map(|i| {
+                let (low, high) = self.edges(i);
+                let center = self.center(i);
+
+                let weight = |f: analyzer::Frequency| {
+                    if f > low && f <= center {
+                        (f - low) / (center - low)
+                    } else if f > center && f < high {
+                        (high - f) / (high - center)
+                    } else {
+                        0.0
+                    }
+                };
+
+                let bins = self
+                    .frequencies
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &f)| weight(f) > 0.0)
+                    .map(|(j, _)| j)
+                    .collect::<Vec<_>>();
+
+                match (bins.first(), bins.last()) {
+                    (Some(&start), Some(&end)) => Filter {
+                        start,
+                        weights: self.frequencies[start..=end]
+                            .iter()
+                            .map(|&f| weight(f) as analyzer::Sample)
+                            .collect(),
+                    },
+                    _ => {
+                        let nearest = (0..self.frequencies.len())
+                            .min_by(|&a, &b| {
+                                (self.frequencies[a] - center)
+                                    .abs()
+                                    .total_cmp(&(self.frequencies[b] - center).abs())
+                            })
+                            .expect("Analyzer has no bins!");
+                        Filter {
+                            start: nearest,
+                            weights: vec![1.0],
+                        }
+                    }
+                }
+            })
+            .collect();
+    }
+
+    /// Return the number of mel bands
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.bands[0].len()
+    }
+
+    /// Return the wrapped fourier analyzer
+    #[inline]
+    pub fn analyzer(&self) -> &analyzer::FourierAnalyzer {
+        &self.analyzer
+    }
+
+    /// Return the wrapped fourier analyzer mutably, eg. to change the downsampling
+    #[inline]
+    pub fn analyzer_mut(&mut self) -> &mut analyzer::FourierAnalyzer {
+        &mut self.analyzer
+    }
+
+    /// Return the lower and upper edge of filter `i`
+    pub fn edges(&self, i: usize) -> (analyzer::Frequency, analyzer::Frequency) {
+        let highest = self.highest.unwrap_or_else(|| self.analyzer.highest());
+        let (low, high) = (hz_to_mel(self.lowest), hz_to_mel(highest));
+        let step = (high - low) / (self.count() + 1) as f32;
+
+        // The outer edges are exact, so the range is covered without rounding errors
+        let lower = if i == 0 {
+            self.lowest
+        } else {
+            mel_to_hz(low + i as f32 * step)
+        };
+        let upper = if i + 1 == self.count() {
+            highest
+        } else {
+            mel_to_hz(low + (i + 2) as f32 * step)
+        };
+        (lower, upper)
+    }
+
+    /// Return the center frequency of filter `i`
+    pub fn center(&self, i: usize) -> analyzer::Frequency {
+        let (low, high) = self.edges(i);
+        mel_to_hz((hz_to_mel(low) + hz_to_mel(high)) / 2.0)
+    }
+
+    /// Analyze a `SampleBuffer` and compute the mel bands of both channels
+    pub fn analyze(&mut self, buf: &analyzer::SampleBuffer) -> [&[analyzer::Sample]; 2] {
+        self.analyzer.analyze(buf);
+        if self.analyzer.fft_len() / 2 + 1 != self.frequencies.len()
+            || self.analyzer.bin_frequency(1) != self.frequencies[1]
+        {
+            self.plan();
+        }
+
+        let (left, right) = self.analyzer.output();
+        for (bands, output) in self.bands.iter_mut().zip([left, right]) {
+            for (band, filter) in bands.iter_mut().zip(self.filters.iter()) {
+                *band = filter
+                    .weights
+                    .iter()
+                    .zip(output[filter.start..].iter())
+                    .map(|(w, c)| w * c.norm_sqr())
+                    .sum();
+            }
+        }
+
+        [&self.bands[0], &self.bands[1]]
+    }
+
+    /// Return the mel bands of a channel computed during the last analysis
+    ///
+    /// Band `0` is the lowest, `channel` is `0` for left and `1` for right.
+    #[inline]
+    pub fn bands(&self, channel: usize) -> &[analyzer::Sample] {
+        &self.bands[channel]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mel_scale() {
+        assert!((hz_to_mel(1000.0) - 1000.0).abs() < 0.1);
+        assert!((mel_to_hz(hz_to_mel(440.0)) - 440.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_broadband() {
+        crate::test_config();
+        let buf = analyzer::SampleBuffer::new(1024, 8000);
+        buf.push(&crate::helpers::signal::white_noise(8000, 0.128, 3));
+
+        let fourier = analyzer::FourierBuilder::new()
+            .rate(8000)
+            .length(1024)
+            .downsample(1)
+            .window(analyzer::window::hanning)
+            .plan();
+        let mut mel = MelBuilder::new()
+            .count(20)
+            .range(100.0, 3000.0)
+            .build(fourier);
+        mel.analyze(&buf);
+
+        // Filters cover the requested range in increasing order
+        assert_eq!(mel.edges(0).0, 100.0);
+        assert_eq!(mel.edges(19).1, 3000.0);
+        for i in 1..20 {
+            assert!(mel.center(i) > mel.center(i - 1));
+            assert!(mel.edges(i).0 < mel.edges(i - 1).1);
+        }
+
+        // Every band sees some of the noise, wider high bands collect more of it
+        let bands = mel.bands(0);
+        assert_eq!(bands.len(), 20);
+        assert!(bands.iter().all(|&b| b > 0.0));
+        assert!(bands[19] > bands[0]);
+    }
+}
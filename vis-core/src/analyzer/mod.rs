@@ -1,13 +1,70 @@
+pub mod bands;
+pub mod bars;
 pub mod beat;
+pub mod chroma;
+#[cfg(feature = "dasp")]
+pub mod dasp;
+pub mod envelope;
+pub mod features;
 pub mod fourier;
+pub mod gain;
+pub mod goertzel;
+pub mod intensity;
+pub mod level;
+pub mod mel;
+pub mod note;
+pub mod peaks;
+pub mod pitch;
+pub mod queue;
 pub mod samples;
+pub mod smooth;
+pub mod spectrogram;
 pub mod spectrum;
+pub mod weighting;
 
 #[doc(inline)]
-pub use self::beat::{BeatBuilder, BeatDetector};
+pub use self::bands::{LogBands, LogBandsBuilder};
 #[doc(inline)]
-pub use self::fourier::{window, FourierAnalyzer, FourierBuilder};
+pub use self::bars::{BarState, BarsBuilder};
 #[doc(inline)]
-pub use self::samples::{Sample, SampleBuffer};
+pub use self::beat::{
+    BeatBuilder, BeatDetector, OnsetBuilder, OnsetDetector, OnsetThreshold, TempoBuilder,
+    TempoEstimator,
+};
+#[doc(inline)]
+pub use self::chroma::{ChromaAnalyzer, ChromaBuilder};
+#[doc(inline)]
+pub use self::envelope::{EnvelopeBuilder, EnvelopeFollower};
+#[doc(inline)]
+pub use self::features::SpectralFeatures;
+#[doc(inline)]
+pub use self::fourier::{
+    window, Alignment, ChannelFn, FourierAnalyzer, FourierBuilder, InverseBuilder, InverseFourier,
+    MagnitudeMode, Normalization, StereoMode, UnsupportedLength,
+};
+#[doc(inline)]
+pub use self::gain::{AutoGain, AutoGainBuilder};
+#[doc(inline)]
+pub use self::goertzel::{Goertzel, GoertzelBuilder};
+#[doc(inline)]
+pub use self::intensity::{Intensity, IntensityBuilder};
+#[doc(inline)]
+pub use self::level::{LevelAnalyzer, LevelBuilder};
+#[doc(inline)]
+pub use self::mel::{MelAnalyzer, MelBuilder};
+#[doc(inline)]
+pub use self::note::{Note, NoteBuilder, NoteTracker, PitchClass};
+#[doc(inline)]
+pub use self::peaks::{PeakPicker, PeakPickerBuilder};
+#[doc(inline)]
+pub use self::pitch::{PitchBuilder, PitchDetector};
+#[doc(inline)]
+pub use self::queue::BatchQueue;
+#[doc(inline)]
+pub use self::samples::{BudgetError, Calibration, Sample, SampleBuffer, SampleStats};
+#[doc(inline)]
+pub use self::smooth::{SpectrumSmoother, SpectrumSmootherBuilder};
+#[doc(inline)]
+pub use self::spectrogram::Spectrogram;
 #[doc(inline)]
 pub use self::spectrum::{average_spectrum, Frequency, SignalStrength, Spectrum};
@@ -0,0 +1,316 @@
+//! Sustained Note Tracking
+use crate::analyzer;
+
+const NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// A pitch class, ie. a note without octave
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PitchClass {
+    /// Semitones above C, from `0` to `11`
+    pub index: u8,
+}
+
+impl PitchClass {
+    /// Return the pitch class `index` semitones above C, wrapping around
+    pub fn new(index: usize) -> PitchClass {
+        PitchClass {
+            index: (index % 12) as u8,
+        }
+    }
+
+    /// Return the name of this pitch class, eg. `"C#"`
+    pub fn name(self) -> &'static str {
+        NAMES[self.index as usize]
+    }
+}
+
+impl std::fmt::Display for PitchClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A note in twelve-tone equal temperament, identified by its MIDI number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Note {
+    /// MIDI note number, `69` is A4 (440 Hz)
+    pub midi: u8,
+}
+
+impl Note {
+    /// Return the note closest to frequency `f`
+    ///
+    /// Returns `None` if `f` is outside of the MIDI range.
+    pub fn from_frequency(f: analyzer::Frequency) -> Option<Note> {
+        let midi = (69.0 + 12.0 * (f / 440.0).log2()).round();
+        if (0.0..=127.0).contains(&midi) {
+            Some(Note { midi: midi as u8 })
+        } else {
+            None
+        }
+    }
+
+    /// Return the frequency of this note
+    pub fn frequency(self) -> analyzer::Frequency {
+        440.0 * 2.0f32.powf((self.midi as f32 - 69.0) / 12.0)
+    }
+
+    /// Return the pitch class of this note
+    pub fn pitch_class(self) -> PitchClass {
+        PitchClass::new(self.midi as usize)
+    }
+
+    /// Return the name of this note without octave, eg. `"C#"`
+    pub fn name(self) -> &'static str {
+        self.pitch_class().name()
+    }
+
+    /// Return the octave of this note, middle C is in octave `4`
+    pub fn octave(self) -> i32 {
+        self.midi as i32 / 12 - 1
+    }
+}
+
+impl std::fmt::Display for Note {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", self.name(), self.octave())
+    }
+}
+
+/// Builder for NoteTracker
+#[derive(Debug, Default)]
+pub struct NoteBuilder {
+    /// Time in seconds a note must be dominant before it is reported
+    ///
+    /// Defaults to `0.1`, can also be set from config as `"audio.note.min_duration"`.
+    pub min_duration: Option<f32>,
+
+    /// Frequency range to search for notes in
+    ///
+    /// Defaults to `50 Hz - 2000 Hz`, can also be set from config as `"audio.note.low"`
+    /// and `"audio.note.high"`.
+    pub range: Option<(analyzer::Frequency, analyzer::Frequency)>,
+
+    /// Minimum strength of the dominant bucket, quieter frames count as no note
+    ///
+    /// Defaults to `0.0`, can also be set from config as `"audio.note.threshold"`.
+    pub threshold: Option<analyzer::SignalStrength>,
+}
+
+impl NoteBuilder {
+    /// Create a new NoteBuilder
+    pub fn new() -> NoteBuilder {
+        Default::default()
+    }
+
+    /// Set the minimum duration
+    pub fn min_duration(&mut self, min_duration: f32) -> &mut NoteBuilder {
+        self.min_duration = Some(min_duration);
+        self
+    }
+
+    /// Set frequency range
+    pub fn range(
+        &mut self,
+        low: analyzer::Frequency,
+        high: analyzer::Frequency,
+    ) -> &mut NoteBuilder {
+        self.range = Some((low, high));
+        self
+    }
+
+    /// Set the threshold
+    pub fn threshold(&mut self, threshold: analyzer::SignalStrength) -> &mut NoteBuilder {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Build the tracker
+    pub fn build(&mut self) -> NoteTracker {
+        NoteTracker::from_builder(self)
+    }
+}
+
+/// Tracker for the loudest sustained note
+///
+/// Each update, the strongest bucket in range is taken as the pitch (refined with
+/// parabolic interpolation).  A note is only reported once it has been dominant for
+/// `min_duration`, which smooths out rapid flickers between adjacent notes.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// let mut tracker = analyzer::NoteBuilder::new()
+///     .min_duration(0.1)
+///     .range(50.0, 2000.0)
+///     .threshold(0.0)
+///     .build();
+///
+/// # let spectrum = analyzer::Spectrum::new(vec![0.0; 100], 0.0, 4000.0);
+/// // Spectrum and time since the last call
+/// tracker.update(&spectrum, 1.0 / 30.0);
+/// if let Some(note) = tracker.current_note() {
+///     println!("Playing {}", note);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct NoteTracker {
+    min_duration: f32,
+    range: (analyzer::Frequency, analyzer::Frequency),
+    threshold: analyzer::SignalStrength,
+
+    candidate: Option<Note>,
+    candidate_time: f32,
+    current: Option<Note>,
+}
+
+impl NoteTracker {
+    /// Create a NoteTracker from a builder config
+    pub fn from_builder(build: &NoteBuilder) -> NoteTracker {
+        NoteTracker {
+            min_duration: build
+                .min_duration
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.note.min_duration", 0.1)),
+            range: build.range.unwrap_or_else(|| {
+                (
+                    crate::CONFIG.get_or("audio.note.low", 50.0),
+                    crate::CONFIG.get_or("audio.note.high", 2000.0),
+                )
+            }),
+            threshold: build
+                .threshold
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.note.threshold", 0.0)),
+
+            candidate: None,
+            candidate_time: 0.0,
+            current: None,
+        }
+    }
+
+    /// Return the sustained note, `None` during silence or before a note was sustained
+    #[inline]
+    pub fn current_note(&self) -> Option<Note> {
+        self.current
+    }
+
+    /// Find the dominant note of `spectrum` in range
+    fn dominant<S: analyzer::spectrum::Storage>(
+        &self,
+        spectrum: &analyzer::Spectrum<S>,
+    ) -> Option<Note> {
+        let (i, max) = (0..spectrum.len())
+            .filter(|&i| {
+                let f = spectrum.id_to_freq(i);
+                f >= self.range.0 && f <= self.range.1
+            })
+            .map(|i| (i, spectrum[i]))
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))?;
+
+        if max <= self.threshold {
+            return None;
+        }
+
+        let offset = if i > 0 && i + 1 < spectrum.len() {
+            let (a, b, c) = (spectrum[i - 1], max, spectrum[i + 1]);
+            let denom = a - 2.0 * b + c;
+            if denom != 0.0 {
+                0.5 * (a - c) / denom
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        };
+        let width = spectrum.id_to_freq(1) - spectrum.id_to_freq(0);
+
+        Note::from_frequency(spectrum.id_to_freq(i) + offset * width)
+    }
+
+    /// Feed a new spectrum, `dt` seconds after the last update
+    ///
+    /// Returns the sustained note.
+    pub fn update<S: analyzer::spectrum::Storage>(
+        &mut self,
+        spectrum: &analyzer::Spectrum<S>,
+        dt: f32,
+    ) -> Option<Note> {
+        let note = self.dominant(spectrum);
+
+        if note == self.candidate {
+            self.candidate_time += dt;
+        } else {
+            self.candidate = note;
+            self.candidate_time = 0.0;
+        }
+
+        if self.candidate_time >= self.min_duration {
+            self.current = self.candidate;
+        }
+
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(f: analyzer::Frequency) -> analyzer::Spectrum<Vec<analyzer::SignalStrength>> {
+        let mut spectrum = analyzer::Spectrum::new(vec![0.0; 4001], 0.0, 4000.0);
+        spectrum[f] = 1.0;
+        spectrum
+    }
+
+    #[test]
+    fn test_note() {
+        let a4 = Note::from_frequency(440.0).unwrap();
+        assert_eq!(a4.midi, 69);
+        assert_eq!(a4.to_string(), "A4");
+        assert_eq!(Note::from_frequency(261.63).unwrap().to_string(), "C4");
+        assert_eq!(Note::from_frequency(452.0), Some(a4));
+        assert!((Note { midi: 81 }.frequency() - 880.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_sustained() {
+        let mut tracker = NoteBuilder::new()
+            .min_duration(0.1)
+            .range(50.0, 2000.0)
+            .threshold(0.5)
+            .build();
+
+        // A4 is reported after 100ms
+        for i in 0..20 {
+            tracker.update(&tone(440.0), 0.01);
+            if i < 10 {
+                assert_eq!(tracker.current_note(), None);
+            }
+        }
+        assert_eq!(tracker.current_note().unwrap().to_string(), "A4");
+
+        // A short A#4 glitch is ignored
+        for _ in 0..5 {
+            tracker.update(&tone(466.16), 0.01);
+        }
+        assert_eq!(tracker.current_note().unwrap().to_string(), "A4");
+        for _ in 0..5 {
+            tracker.update(&tone(440.0), 0.01);
+        }
+        assert_eq!(tracker.current_note().unwrap().to_string(), "A4");
+
+        // A sustained C5 takes over
+        for _ in 0..12 {
+            tracker.update(&tone(523.25), 0.01);
+        }
+        assert_eq!(tracker.current_note().unwrap().to_string(), "C5");
+
+        // Silence is sustained as well
+        let silence = analyzer::Spectrum::new(vec![0.0; 4001], 0.0, 4000.0);
+        for _ in 0..12 {
+            tracker.update(&silence, 0.01);
+        }
+        assert_eq!(tracker.current_note(), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! Peak Picking
+use crate::analyzer;
+
+/// Builder for PeakPicker
+#[derive(Debug, Default)]
+pub struct PeakPickerBuilder {
+    /// Maximum number of peaks to return
+    ///
+    /// Defaults to `5`, can also be set from config as `"audio.peaks.count"`.
+    pub count: Option<usize>,
+
+    /// Multiple of the median absolute deviation a peak needs to rise above the median
+    ///
+    /// Defaults to `10.0`, can also be set from config as `"audio.peaks.threshold"`.
+    pub threshold: Option<f32>,
+
+    /// Minimum distance between two peaks in buckets
+    ///
+    /// Defaults to `3`, can also be set from config as `"audio.peaks.separation"`.
+    pub separation: Option<usize>,
+}
+
+impl PeakPickerBuilder {
+    /// Create a new PeakPickerBuilder
+    pub fn new() -> PeakPickerBuilder {
+        Default::default()
+    }
+
+    /// Set the maximum number of peaks
+    pub fn count(&mut self, count: usize) -> &mut PeakPickerBuilder {
+        self.count = Some(count);
+        self
+    }
+
+    /// Set the noise floor threshold
+    pub fn threshold(&mut self, threshold: f32) -> &mut PeakPickerBuilder {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Set the minimum distance between peaks
+    pub fn separation(&mut self, separation: usize) -> &mut PeakPickerBuilder {
+        self.separation = Some(separation);
+        self
+    }
+
+    /// Build the PeakPicker
+    pub fn build(&mut self) -> PeakPicker {
+        PeakPicker::from_builder(self)
+    }
+}
+
+/// Picks the strongest peaks of a spectrum
+///
+/// Only local maxima rising above an adaptive noise floor of
+/// `median + threshold * MAD` (median absolute deviation) are considered, so the
+/// fluctuations of noise between tones are ignored no matter the overall level.  Of those,
+/// the strongest ones at least `separation` buckets apart are returned.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// let mut picker = analyzer::PeakPickerBuilder::new()
+///     .count(5)
+///     .threshold(10.0)
+///     .separation(3)
+///     .build();
+///
+/// let mut spectrum = analyzer::Spectrum::new(vec![0.01; 100], 0.0, 4000.0);
+/// spectrum[20] = 1.0;
+/// spectrum[60] = 0.5;
+///
+/// assert_eq!(picker.pick(&spectrum), &[20, 60]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PeakPicker {
+    count: usize,
+    threshold: f32,
+    separation: usize,
+
+    scratch: Vec<analyzer::SignalStrength>,
+    candidates: Vec<usize>,
+    peaks: Vec<usize>,
+}
+
+fn median(values: &mut [analyzer::SignalStrength]) -> analyzer::SignalStrength {
+    let mid = values.len() / 2;
+    *values
+        .select_nth_unstable_by(mid, |a, b| a.partial_cmp(b).unwrap())
+        .1
+}
+
+impl PeakPicker {
+    /// Create a PeakPicker from a builder config
+    pub fn from_builder(build: &PeakPickerBuilder) -> PeakPicker {
+        PeakPicker {
+            count: build
+                .count
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.peaks.count", 5)),
+            threshold: build
+                .threshold
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.peaks.threshold", 10.0)),
+            separation: build
+                .separation
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.peaks.separation", 3)),
+
+            scratch: Vec::new(),
+            candidates: Vec::new(),
+            peaks: Vec::new(),
+        }
+    }
+
+    /// Return the noise floor of a spectrum
+    pub fn noise_floor<S: analyzer::spectrum::Storage>(
+        &mut self,
+        spectrum: &analyzer::Spectrum<S>,
+    ) -> analyzer::SignalStrength {
+        if spectrum.len() == 0 {
+            return 0.0;
+        }
+
+        self.scratch.clear();
+        self.scratch.extend(spectrum.iter().cloned());
+        let med = median(&mut self.scratch);
+
+        for v in self.scratch.iter_mut() {
+            *v = (*v - med).abs();
+        }
+        let mad = median(&mut self.scratch);
+
+        med + self.threshold * mad
+    }
+
+    /// Find the peaks of a spectrum
+    ///
+    /// Returns the indices of the peaks, starting with the strongest.
+    pub fn pick<S: analyzer::spectrum::Storage>(
+        &mut self,
+        spectrum: &analyzer::Spectrum<S>,
+    ) -> &[usize] {
+        let floor = self.noise_floor(spectrum);
+
+        self.candidates.clear();
+        self.candidates
+            .extend((1..spectrum.len().saturating_sub(1)).filter(|&i| {
+                spectrum[i] > floor
+                    && spectrum[i] > spectrum[i - 1]
+                    && spectrum[i] >= spectrum[i + 1]
+            }));
+        self.candidates
+            .sort_by(|&a, &b| spectrum[b].partial_cmp(&spectrum[a]).unwrap());
+
+        self.peaks.clear();
+        for &i in self.candidates.iter() {
+            if self.peaks.len() == self.count {
+                break;
+            }
+            if self
+                .peaks
+                .iter()
+                .all(|&p| (p as isize - i as isize).unsigned_abs() >= self.separation)
+            {
+                self.peaks.push(i);
+            }
+        }
+
+        &self.peaks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_tone() {
+        crate::test_config();
+        let mut analyzer = analyzer::FourierBuilder::new()
+            .length(512)
+            .window(analyzer::window::hanning)
+            .downsample(1)
+            .rate(8000)
+            .plan();
+
+        // Tones exactly on buckets 32, 96 and 160
+        let noise = crate::helpers::signal::white_noise(8000, 0.064, 7);
+        let tones = [(500.0, 1.0), (1500.0, 0.5), (2500.0, 0.25)];
+        let samples = noise
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                let t = i as f32 / 8000.0;
+                let s = tones
+                    .iter()
+                    .map(|(f, a)| a * (t * f * 2.0 * std::f32::consts::PI).sin())
+                    .sum::<f32>() as analyzer::Sample
+                    + n[0] * 0.01;
+                [s, s]
+            })
+            .collect::<Vec<_>>();
+
+        let buf = analyzer::SampleBuffer::new(512, 8000);
+        buf.push(&samples);
+        analyzer.analyze(&buf);
+
+        let mut picker = PeakPickerBuilder::new()
+            .count(8)
+            .threshold(20.0)
+            .separation(3)
+            .build();
+        assert_eq!(picker.pick(&analyzer.average()), &[32, 96, 160]);
+
+        picker.count = 2;
+        assert_eq!(picker.pick(&analyzer.average()), &[32, 96]);
+    }
+
+    #[test]
+    fn test_separation() {
+        let mut spectrum = analyzer::Spectrum::new(vec![0.0; 50], 0.0, 1000.0);
+        spectrum[10] = 1.0;
+        spectrum[12] = 0.9;
+        spectrum[20] = 0.5;
+
+        let mut picker = PeakPickerBuilder::new()
+            .count(5)
+            .threshold(10.0)
+            .separation(3)
+            .build();
+        assert_eq!(picker.pick(&spectrum), &[10, 20]);
+
+        picker.separation = 1;
+        assert_eq!(picker.pick(&spectrum), &[10, 12, 20]);
+    }
+}
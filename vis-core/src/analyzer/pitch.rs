@@ -0,0 +1,234 @@
+//! Pitch Detection
+use crate::analyzer;
+
+/// Builder for PitchDetector
+#[derive(Debug, Default)]
+pub struct PitchBuilder {
+    /// Number of samples per detection
+    ///
+    /// Must hold at least two periods of the lowest frequency.  Defaults to `2048`, can
+    /// also be set from config as `"audio.pitch.length"`.
+    pub length: Option<usize>,
+
+    /// Range of fundamental frequencies considered
+    ///
+    /// Defaults to `50 Hz - 1000 Hz`, can also be set from config as `"audio.pitch.low"`
+    /// and `"audio.pitch.high"`.
+    pub range: Option<(analyzer::Frequency, analyzer::Frequency)>,
+
+    /// Clarity threshold
+    ///
+    /// Periods are only accepted if the normalized difference drops below this value,
+    /// lower is stricter.  Defaults to `0.15`, can also be set from config as
+    /// `"audio.pitch.threshold"`.
+    pub threshold: Option<f32>,
+}
+
+impl PitchBuilder {
+    /// Create a new PitchBuilder
+    pub fn new() -> PitchBuilder {
+        Default::default()
+    }
+
+    /// Set the number of samples per detection
+    pub fn length(&mut self, length: usize) -> &mut PitchBuilder {
+        self.length = Some(length);
+        self
+    }
+
+    /// Set the frequency range
+    pub fn range(
+        &mut self,
+        low: analyzer::Frequency,
+        high: analyzer::Frequency,
+    ) -> &mut PitchBuilder {
+        self.range = Some((low, high));
+        self
+    }
+
+    /// Set the clarity threshold
+    pub fn threshold(&mut self, threshold: f32) -> &mut PitchBuilder {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Build the detector
+    pub fn build(&mut self) -> PitchDetector {
+        PitchDetector::from_builder(self)
+    }
+}
+
+/// Fundamental frequency of monophonic signals
+///
+/// Implements YIN: the period is the first lag at which the cumulative mean normalized
+/// difference of the newest `length` samples, downmixed to mono, drops below the clarity
+/// threshold, refined by parabolic interpolation.  Unlike a transform this resolves low
+/// frequencies precisely and is not fooled by a weak fundamental.
+///
+/// The confidence is `1.0` minus the normalized difference at the detected period, so
+/// perfectly periodic signals approach `1.0`.  Frames without a clear period, including
+/// silence, yield no pitch.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let samples = analyzer::SampleBuffer::new(8000, 8000);
+/// let mut pitch = analyzer::PitchBuilder::new()
+///     .length(1024)
+///     .range(80.0, 800.0)
+///     .build();
+///
+/// if let Some(f) = pitch.analyze(&samples) {
+///     println!("{} Hz ({:.0}%)", f, pitch.confidence() * 100.0);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PitchDetector {
+    length: usize,
+    range: (analyzer::Frequency, analyzer::Frequency),
+    threshold: f32,
+
+    difference: Vec<f64>,
+    pitch: Option<analyzer::Frequency>,
+    confidence: f32,
+}
+
+impl PitchDetector {
+    /// Create a PitchDetector from a builder config
+    pub fn from_builder(build: &PitchBuilder) -> PitchDetector {
+        PitchDetector {
+            length: build
+                .length
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.pitch.length", 2048)),
+            range: build.range.unwrap_or_else(|| {
+                (
+                    crate::CONFIG.get_or("audio.pitch.low", 50.0),
+                    crate::CONFIG.get_or("audio.pitch.high", 1000.0),
+                )
+            }),
+            threshold: build
+                .threshold
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.pitch.threshold", 0.15)),
+
+            difference: Vec::new(),
+            pitch: None,
+            confidence: 0.0,
+        }
+    }
+
+    /// Detect the pitch of the newest samples of `buf`
+    pub fn analyze(&mut self, buf: &analyzer::SampleBuffer) -> Option<analyzer::Frequency> {
+        let frame = buf
+            .mono(self.length)
+            .iter()
+            .map(|&x| analyzer::samples::to_f32(x) as f64)
+            .collect::<Vec<_>>();
+        let rate = buf.rate() as f64;
+
+        let window = frame.len() / 2;
+        let max_lag = ((rate / self.range.0 as f64).ceil() as usize + 1).min(window);
+        let min_lag = ((rate / self.range.1 as f64).floor() as usize).max(2);
+
+        self.pitch = None;
+        self.confidence = 0.0;
+        if min_lag + 1 >= max_lag {
+            return None;
+        }
+
+        // Cumulative mean normalized difference
+        self.difference.clear();
+        self.difference.push(1.0);
+        let mut sum = 0.0;
+        for lag in 1..=max_lag {
+            let d = frame[..window]
+                .iter()
+                .zip(frame[lag..lag + window].iter())
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum::<f64>();
+            sum += d;
+            self.difference
+                .push(if sum > 0.0 { d * lag as f64 / sum } else { 1.0 });
+        }
+
+        let d = &self.difference;
+        let threshold = self.threshold as f64;
+        let mut lag = (min_lag..max_lag).find(|&lag| d[lag] < threshold)?;
+        while lag + 1 < max_lag && d[lag + 1] < d[lag] {
+            lag += 1;
+        }
+
+        let (a, b, c) = (d[lag - 1], d[lag], d[lag + 1]);
+        let denom = a - 2.0 * b + c;
+        let offset = if denom > 0.0 {
+            (0.5 * (a - c) / denom).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+
+        self.pitch = Some((rate / (lag as f64 + offset)) as analyzer::Frequency);
+        self.confidence = (1.0 - b).clamp(0.0, 1.0) as f32;
+        self.pitch
+    }
+
+    /// Return the pitch found by the last analysis
+    #[inline]
+    pub fn pitch(&self) -> Option<analyzer::Frequency> {
+        self.pitch
+    }
+
+    /// Return the confidence of the last pitch, `0.0` if there was none
+    #[inline]
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> PitchDetector {
+        crate::test_config();
+        PitchBuilder::new().length(1024).range(60.0, 1000.0).build()
+    }
+
+    #[test]
+    fn test_sawtooth() {
+        let mut pitch = detector();
+        let buf = analyzer::SampleBuffer::new(1024, 8000);
+        for freq in [220.0, 82.4, 659.3] {
+            buf.push(&crate::helpers::signal::sawtooth(8000, freq, 0.128));
+            let detected = pitch.analyze(&buf).unwrap();
+            assert!(
+                (detected - freq).abs() < freq * 0.01,
+                "{}: {}",
+                freq,
+                detected
+            );
+            assert!(pitch.confidence() > 0.9, "{}", pitch.confidence());
+        }
+
+        // A weak fundamental is not mistaken for the overtone
+        let mut signal = crate::helpers::signal::sine(8000, 220.0, 0.128);
+        let overtone = crate::helpers::signal::sine(8000, 440.0, 0.128);
+        for (s, o) in signal.iter_mut().zip(overtone.iter()) {
+            *s = [0.3 * s[0] + o[0], 0.3 * s[1] + o[1]];
+        }
+        buf.push(&signal);
+        let detected = pitch.analyze(&buf).unwrap();
+        assert!((detected - 220.0).abs() < 2.2, "{}", detected);
+    }
+
+    #[test]
+    fn test_unclear() {
+        let mut pitch = detector();
+        let buf = analyzer::SampleBuffer::new(1024, 8000);
+        assert_eq!(pitch.analyze(&buf), None);
+
+        buf.push(&crate::helpers::signal::white_noise(8000, 0.128, 7));
+        assert_eq!(pitch.analyze(&buf), None);
+        assert_eq!(pitch.pitch(), None);
+        assert_eq!(pitch.confidence(), 0.0);
+    }
+}
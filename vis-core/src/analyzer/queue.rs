@@ -0,0 +1,212 @@
+//! Batch Queue
+use crate::analyzer;
+use std::sync;
+
+/// Bounded lock-free queue of pushed samples, drained in fixed-size batches
+///
+/// The ring buffer in a [`SampleBuffer`](../struct.SampleBuffer.html) always holds the
+/// newest samples, so how much audio arrived between two analyses depends on timing.
+/// A `BatchQueue` instead receives every pushed sample through a
+/// [`tap`](../struct.SampleBuffer.html#method.tap) and hands them out in batches of exactly
+/// `hop` samples.  As the queue never holds more than `capacity` samples, the latency
+/// it adds is bounded by [`max_latency`](#method.max_latency).  Samples arriving while
+/// the queue is full are dropped and counted as [`overruns`](#method.overruns).
+///
+/// Other taps on the buffer, eg. session recording, keep working.  Dropping the queue
+/// removes its tap again.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// let recorder_buffer = analyzer::SampleBuffer::new(8000, 8000);
+/// let mut queue = analyzer::BatchQueue::attach(&recorder_buffer, 1024, 256);
+///
+/// let analysis_buffer = analyzer::SampleBuffer::new(8000, 8000);
+/// let mut analyzer = analyzer::FourierBuilder::new().plan();
+/// let mut batch = Vec::new();
+/// # recorder_buffer.push(&[[0.0; 2]; 1100]);
+///
+/// // Analyze once per hop
+/// while queue.next_batch(&mut batch) {
+///     analysis_buffer.push(&batch);
+///     analyzer.analyze(&analysis_buffer);
+/// }
+/// assert_eq!(queue.overruns(), 76);
+/// ```
+#[derive(Debug)]
+pub struct BatchQueue {
+    buf: analyzer::SampleBuffer,
+    tap: analyzer::samples::TapId,
+    consumer: rtrb::Consumer<[analyzer::Sample; 2]>,
+    capacity: usize,
+    hop: usize,
+    rate: usize,
+    overruns: sync::Arc<sync::atomic::AtomicU64>,
+}
+
+impl BatchQueue {
+    /// Attach a queue holding up to `capacity` samples to a buffer
+    pub fn attach(buf: &analyzer::SampleBuffer, capacity: usize, hop: usize) -> BatchQueue {
+        assert!(
+            hop > 0 && hop <= capacity,
+            "Hop must be between 1 and the capacity!"
+        );
+
+        let (mut producer, consumer) = rtrb::RingBuffer::new(capacity);
+        let overruns = sync::Arc::new(sync::atomic::AtomicU64::new(0));
+
+        let counter = overruns.clone();
+        let tap = buf.tap(Box::new(move |samples| {
+            for (i, sample) in samples.iter().enumerate() {
+                if producer.push(*sample).is_err() {
+                    counter.fetch_add((samples.len() - i) as u64, sync::atomic::Ordering::Relaxed);
+                    break;
+                }
+            }
+        }));
+
+        BatchQueue {
+            buf: buf.clone(),
+            tap,
+            consumer,
+            capacity,
+            hop,
+            rate: buf.rate(),
+            overruns,
+        }
+    }
+
+    /// Pop the next batch of `hop` samples into `batch`
+    ///
+    /// Returns false and leaves `batch` empty while less than `hop` samples are queued.
+    pub fn next_batch(&mut self, batch: &mut Vec<[analyzer::Sample; 2]>) -> bool {
+        batch.clear();
+        let chunk = match self.consumer.read_chunk(self.hop) {
+            Ok(chunk) => chunk,
+            Err(_) => return false,
+        };
+
+        batch.extend(chunk);
+        true
+    }
+
+    /// Return the number of queued samples
+    pub fn depth(&self) -> usize {
+        self.consumer.slots()
+    }
+
+    /// Return the maximum number of queued samples
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Return the size of each batch
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    /// Return the time in seconds the queued samples take to play
+    ///
+    /// This is the latency added by the queue right now.
+    pub fn latency(&self) -> f32 {
+        self.depth() as f32 / self.rate as f32
+    }
+
+    /// Return the highest latency in seconds the queue can add
+    pub fn max_latency(&self) -> f32 {
+        self.capacity as f32 / self.rate as f32
+    }
+
+    /// Return the number of samples dropped because the queue was full
+    ///
+    /// Increasing overruns mean the consumer does not keep up.
+    pub fn overruns(&self) -> u64 {
+        self.overruns.load(sync::atomic::Ordering::Relaxed)
+    }
+}
+
+impl Drop for BatchQueue {
+    fn drop(&mut self) {
+        self.buf.untap(self.tap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batches() {
+        let buf = analyzer::SampleBuffer::new(16, 8000);
+        let mut queue = BatchQueue::attach(&buf, 100, 40);
+        let mut batch = Vec::new();
+
+        buf.push(
+            &(0..30)
+                .map(|i| [i as analyzer::Sample; 2])
+                .collect::<Vec<_>>(),
+        );
+        assert!(!queue.next_batch(&mut batch));
+        assert!(batch.is_empty());
+
+        buf.push(
+            &(30..150)
+                .map(|i| [i as analyzer::Sample; 2])
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(queue.depth(), 100);
+        assert_eq!(queue.overruns(), 50);
+        assert_eq!(queue.latency(), queue.max_latency());
+
+        assert!(queue.next_batch(&mut batch));
+        assert_eq!(batch.len(), 40);
+        assert_eq!(batch[0], [0.0; 2]);
+        assert!(queue.next_batch(&mut batch));
+        assert_eq!(batch[39], [79.0; 2]);
+        assert!(!queue.next_batch(&mut batch));
+        assert_eq!(queue.depth(), 20);
+    }
+
+    #[test]
+    fn test_steady_latency() {
+        let buf = analyzer::SampleBuffer::new(512, 8000);
+        let mut queue = BatchQueue::attach(&buf, 256, 64);
+        let mut batch = Vec::new();
+
+        // Pushes arrive in bursts of two hops every other frame, one batch is taken per
+        // frame.  The queue never holds more than the burst.
+        let mut batches = 0;
+        for frame in 0..40 {
+            if frame % 2 == 0 {
+                buf.push(&[[frame as analyzer::Sample; 2]; 128]);
+            }
+            assert!(queue.depth() <= 128);
+            assert!(queue.latency() <= 128.0 / 8000.0);
+
+            assert!(queue.next_batch(&mut batch));
+            assert_eq!(batch[0][0], (frame - frame % 2) as analyzer::Sample);
+            batches += 1;
+        }
+
+        assert_eq!(batches, 40);
+        assert_eq!(queue.overruns(), 0);
+        assert_eq!(queue.depth(), 0);
+    }
+
+    #[test]
+    fn test_multiple_taps() {
+        let buf = analyzer::SampleBuffer::new(16, 8000);
+        let first = BatchQueue::attach(&buf, 100, 10);
+        let second = BatchQueue::attach(&buf, 100, 10);
+
+        buf.push(&[[1.0; 2]; 30]);
+        assert_eq!(first.depth(), 30);
+        assert_eq!(second.depth(), 30);
+
+        // Dropping a queue only removes its own tap
+        drop(first);
+        buf.push(&[[1.0; 2]; 30]);
+        assert_eq!(second.depth(), 60);
+    }
+}
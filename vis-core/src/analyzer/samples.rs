@@ -3,10 +3,157 @@ use std::collections;
 use std::sync;
 
 /// Type Alias for Samples
+///
+/// `f32` by default, the `f64-samples` feature switches to `f64` for more precision in
+/// long transforms.
+#[cfg(not(feature = "f64-samples"))]
 pub type Sample = f32;
+/// Type Alias for Samples
+///
+/// `f32` by default, the `f64-samples` feature switches to `f64` for more precision in
+/// long transforms.
+#[cfg(feature = "f64-samples")]
+pub type Sample = f64;
+
+/// Convert a sample to `f32`, a no-op unless `f64-samples` is enabled
+#[inline]
+#[allow(clippy::unnecessary_cast)]
+pub(crate) fn to_f32(s: Sample) -> f32 {
+    s as f32
+}
 
 type _SampleBuf = sync::Arc<parking_lot::Mutex<collections::VecDeque<[Sample; 2]>>>;
 
+/// Callback which sees every push to a `SampleBuffer`
+pub type Tap = Box<dyn FnMut(&[[Sample; 2]]) + Send>;
+
+/// Wall-clock times of the first and the latest push
+#[derive(Debug, Clone, Copy)]
+struct Arrivals {
+    first: std::time::Instant,
+    first_pushed: u64,
+    last: std::time::Instant,
+    last_pushed: u64,
+}
+
+/// Handle of a tap installed with [`SampleBuffer::tap`](struct.SampleBuffer.html#method.tap)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TapId(u64);
+
+#[derive(Default)]
+struct Taps {
+    next: u64,
+    taps: Vec<(TapId, Tap)>,
+}
+
+#[derive(Clone, Default)]
+struct _Tap(sync::Arc<parking_lot::Mutex<Taps>>);
+
+impl std::fmt::Debug for _Tap {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} Taps", self.0.lock().taps.len())
+    }
+}
+
+/// Statistics about the samples pushed to a `SampleBuffer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SampleStats {
+    /// Number of non-finite (NaN or infinite) channel values which were replaced
+    /// with `0.0`
+    pub non_finite: u64,
+    /// Total number of samples pushed
+    pub pushed: u64,
+}
+
+/// Error when a history would exceed its memory budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetError {
+    /// Bytes the history would need
+    pub required: usize,
+    /// Bytes the caller allowed
+    pub budget: usize,
+}
+
+impl std::fmt::Display for BudgetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "History needs {} bytes, exceeding the budget of {} bytes",
+            self.required, self.budget
+        )
+    }
+}
+
+impl std::error::Error for BudgetError {}
+
+/// Per-channel calibration applied to samples before they enter a `SampleBuffer`
+///
+/// Multiplies each channel by its gain and delays one channel by a possibly
+/// fractional number of samples.  The integer part of the delay uses a delay line,
+/// the fractional part a first-order Thiran allpass.
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    gain: [Sample; 2],
+    /// Index of the delayed channel
+    channel: usize,
+    line: collections::VecDeque<Sample>,
+    /// Allpass coefficient, `None` for integer delays
+    coeff: Option<Sample>,
+    x1: Sample,
+    y1: Sample,
+}
+
+impl Calibration {
+    /// Create a new calibration
+    ///
+    /// A positive `delay_samples` delays the right channel, a negative one the left.
+    pub fn new(gain_l: Sample, gain_r: Sample, delay_samples: f32) -> Calibration {
+        let channel = if delay_samples < 0.0 { 0 } else { 1 };
+        let delay = delay_samples.abs();
+
+        // Keep the fractional part in [0.5, 1.5) where the allpass is most accurate
+        let mut int = delay.floor() as usize;
+        let mut frac = delay - int as f32;
+        if frac < 0.5 && frac > 0.0 && int > 0 {
+            int -= 1;
+            frac += 1.0;
+        }
+
+        Calibration {
+            gain: [gain_l, gain_r],
+            channel,
+            line: collections::VecDeque::from(vec![0.0; int]),
+            coeff: if frac > 0.0 {
+                Some(((1.0 - frac) / (1.0 + frac)) as Sample)
+            } else {
+                None
+            },
+            x1: 0.0,
+            y1: 0.0,
+        }
+    }
+
+    /// Calibrate a single sample
+    pub fn process(&mut self, sample: [Sample; 2]) -> [Sample; 2] {
+        let mut out = [sample[0] * self.gain[0], sample[1] * self.gain[1]];
+
+        let mut x = out[self.channel];
+        if !self.line.is_empty() {
+            self.line.push_back(x);
+            x = self.line.pop_front().unwrap();
+        }
+        if let Some(a) = self.coeff {
+            let y = a * x + self.x1 - a * self.y1;
+            self.x1 = x;
+            self.y1 = y;
+            x = y;
+        }
+        out[self.channel] = x;
+
+        out
+    }
+}
+
 /// A Sample Buffer
 ///
 /// The sample buffer is a synchronized ring-buffer.  During analyzation, it will
@@ -16,6 +163,7 @@ type _SampleBuf = sync::Arc<parking_lot::Mutex<collections::VecDeque<[Sample; 2]
 /// # Example
 /// ```
 /// # use vis_core::analyzer;
+/// # vis_core::default_config();
 /// # let mut beat = analyzer::BeatBuilder::new()
 /// #     .decay(2000.0)
 /// #     .trigger(0.4)
@@ -39,6 +187,18 @@ type _SampleBuf = sync::Arc<parking_lot::Mutex<collections::VecDeque<[Sample; 2]
 pub struct SampleBuffer {
     buf: _SampleBuf,
     rate: usize,
+    tap: _Tap,
+    calibration: sync::Arc<parking_lot::Mutex<Option<Calibration>>>,
+    downmix: sync::Arc<parking_lot::Mutex<[Sample; 2]>>,
+    sanitize: sync::Arc<sync::atomic::AtomicBool>,
+    non_finite: sync::Arc<sync::atomic::AtomicU64>,
+    pushed: sync::Arc<sync::atomic::AtomicU64>,
+    arrivals: sync::Arc<parking_lot::Mutex<Option<Arrivals>>>,
+    /// Number of samples pushed up to the end of the last overlapped frame
+    cursor: sync::Arc<parking_lot::Mutex<Option<u64>>>,
+    /// Number of samples pushed when the buffer was last read, `None` before the first read
+    read: sync::Arc<parking_lot::Mutex<Option<u64>>>,
+    overruns: sync::Arc<sync::atomic::AtomicUsize>,
 }
 
 impl SampleBuffer {
@@ -49,7 +209,43 @@ impl SampleBuffer {
         SampleBuffer {
             buf: sync::Arc::new(parking_lot::Mutex::new(buf)),
             rate,
+            tap: Default::default(),
+            calibration: Default::default(),
+            downmix: sync::Arc::new(parking_lot::Mutex::new([0.5; 2])),
+            sanitize: sync::Arc::new(sync::atomic::AtomicBool::new(true)),
+            non_finite: Default::default(),
+            pushed: Default::default(),
+            arrivals: Default::default(),
+            cursor: Default::default(),
+            read: Default::default(),
+            overruns: Default::default(),
+        }
+    }
+
+    /// Create a new sample buffer, failing if it would need more than `budget` bytes
+    pub fn with_budget(
+        size: usize,
+        rate: usize,
+        budget: usize,
+    ) -> Result<SampleBuffer, BudgetError> {
+        let required = size * std::mem::size_of::<[Sample; 2]>();
+        if required > budget {
+            return Err(BudgetError { required, budget });
         }
+
+        Ok(SampleBuffer::new(size, rate))
+    }
+
+    /// Return the number of bytes used for samples, including the calibration's delay line
+    pub fn memory_bytes(&self) -> usize {
+        let calibration = self
+            .calibration
+            .lock()
+            .as_ref()
+            .map(|c| c.line.len() * std::mem::size_of::<Sample>())
+            .unwrap_or(0);
+
+        self.buf.lock().len() * std::mem::size_of::<[Sample; 2]>() + calibration
     }
 
     #[inline]
@@ -57,20 +253,215 @@ impl SampleBuffer {
         self.rate
     }
 
+    /// Return the number of samples the buffer holds
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.buf.lock().len()
+    }
+
+    /// Return the total number of samples pushed to the buffer
+    ///
+    /// Divided by the [`rate`](#method.rate) this is the stream time in seconds.  Unlike
+    /// reading, this does not affect the [`overruns`](#method.overruns).
+    #[inline]
+    pub fn pushed(&self) -> u64 {
+        self.pushed.load(sync::atomic::Ordering::Acquire)
+    }
+
     /// Push a slice of interleaved samples to the buffer
+    ///
+    /// Unless disabled with [`sanitize`](#method.sanitize), non-finite samples are replaced
+    /// with `0.0`.  If a calibration is set, it is applied before the samples are stored.
     pub fn push(&self, new: &[[Sample; 2]]) {
-        let mut lock = self.buf.lock();
+        self.push_at(new, std::time::Instant::now())
+    }
 
-        #[cfg(debug_assertions)]
-        let debug_size = lock.len();
+    /// Push a slice of interleaved samples which arrived at `time`
+    ///
+    /// Same as [`push`](#method.push), but [`measured_rate`](#method.measured_rate) uses
+    /// `time` instead of the wall clock, eg. for a timestamp reported by the device.
+    pub fn push_at(&self, new: &[[Sample; 2]], time: std::time::Instant) {
+        let mut owned = None;
+
+        if self.sanitize.load(sync::atomic::Ordering::Relaxed)
+            && new.iter().flatten().any(|s| !s.is_finite())
+        {
+            let mut count = 0;
+            owned = Some(
+                new.iter()
+                    .map(|s| {
+                        s.map(|c| {
+                            if c.is_finite() {
+                                c
+                            } else {
+                                count += 1;
+                                0.0
+                            }
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            self.non_finite
+                .fetch_add(count, sync::atomic::Ordering::Relaxed);
+        }
 
-        for sample in new.iter() {
-            lock.pop_front().expect("Failed to pop sample!");
-            lock.push_back(*sample);
+        if let Some(ref mut calibration) = *self.calibration.lock() {
+            let calibrated = owned
+                .as_deref()
+                .unwrap_or(new)
+                .iter()
+                .map(|s| calibration.process(*s))
+                .collect();
+            owned = Some(calibrated);
         }
 
-        #[cfg(debug_assertions)]
-        assert_eq!(debug_size, lock.len(), "Sample buffer size differs!");
+        let new = owned.as_deref().unwrap_or(new);
+
+        {
+            let mut lock = self.buf.lock();
+
+            #[cfg(debug_assertions)]
+            let debug_size = lock.len();
+
+            for sample in new.iter() {
+                lock.pop_front().expect("Failed to pop sample!");
+                lock.push_back(*sample);
+            }
+
+            #[cfg(debug_assertions)]
+            assert_eq!(debug_size, lock.len(), "Sample buffer size differs!");
+
+            // Counted while locked, so `since` sees samples and count consistently
+            let pushed = self
+                .pushed
+                .fetch_add(new.len() as u64, sync::atomic::Ordering::Release)
+                + new.len() as u64;
+
+            if let Some(read) = *self.read.lock() {
+                if pushed - read > lock.len() as u64 {
+                    self.overruns.fetch_add(1, sync::atomic::Ordering::Relaxed);
+                }
+            }
+
+            let mut arrivals = self.arrivals.lock();
+            match *arrivals {
+                Some(ref mut a) => {
+                    a.last = time;
+                    a.last_pushed = pushed;
+                }
+                None => {
+                    *arrivals = Some(Arrivals {
+                        first: time,
+                        first_pushed: pushed,
+                        last: time,
+                        last_pushed: pushed,
+                    })
+                }
+            }
+        }
+
+        for (_, tap) in self.tap.0.lock().taps.iter_mut() {
+            tap(new);
+        }
+    }
+
+    /// Install a tap which is called with every slice pushed to this buffer
+    ///
+    /// Taps are called in the order they were installed.  Used for eg. session recording.
+    pub fn tap(&self, tap: Tap) -> TapId {
+        let mut taps = self.tap.0.lock();
+        let id = TapId(taps.next);
+        taps.next += 1;
+        taps.taps.push((id, tap));
+        id
+    }
+
+    /// Remove the tap with handle `id`, dropping it
+    pub fn untap(&self, id: TapId) {
+        self.tap.0.lock().taps.retain(|(i, _)| *i != id);
+    }
+
+    /// Enable or disable replacing non-finite samples with `0.0`
+    ///
+    /// Enabled by default, a single NaN would otherwise poison every transform it is part
+    /// of.  Replaced values are counted in [`stats`](#method.stats).
+    pub fn sanitize(&self, enabled: bool) {
+        self.sanitize
+            .store(enabled, sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Return statistics about the pushed samples
+    pub fn stats(&self) -> SampleStats {
+        SampleStats {
+            non_finite: self.non_finite.load(sync::atomic::Ordering::Relaxed),
+            pushed: self.pushed.load(sync::atomic::Ordering::Acquire),
+        }
+    }
+
+    /// Return the number of pushes which overwrote samples that were never read
+    ///
+    /// Reading is any of [`iter`](#method.iter), [`iter_overlap`](#method.iter_overlap),
+    /// [`latest`](#method.latest), [`since`](#method.since) or
+    /// [`snapshot`](#method.snapshot).  Counting starts with
+    /// the first read, so a recorder filling the buffer before the analyzer starts does
+    /// not count.  A growing number means the analyzer can't keep up and loses samples.
+    pub fn overruns(&self) -> usize {
+        self.overruns.load(sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Reset the [`overruns`](#method.overruns) counter to `0`
+    pub fn reset_overruns(&self) {
+        self.overruns.store(0, sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Return the rate samples actually arrive at
+    ///
+    /// Measured from the number of samples pushed between the first and the latest push
+    /// and the wall-clock time in between.  Device clocks drift from their nominal rate,
+    /// comparing this with [`rate`](#method.rate) tells how far.  Returns the nominal rate
+    /// until two pushes happened.
+    pub fn measured_rate(&self) -> f32 {
+        match *self.arrivals.lock() {
+            Some(a) if a.last > a.first => {
+                (a.last_pushed - a.first_pushed) as f32 / (a.last - a.first).as_secs_f32()
+            }
+            _ => self.rate as f32,
+        }
+    }
+
+    /// Calibrate all following pushes
+    ///
+    /// Applies per-channel gains and delays one channel by `delay_samples`, which can
+    /// be fractional.  A positive delay delays the right channel, a negative one the
+    /// left.  Replaces a previous calibration.  See [`Calibration`](struct.Calibration.html).
+    pub fn calibrate(&self, gain_l: Sample, gain_r: Sample, delay_samples: f32) {
+        *self.calibration.lock() = Some(Calibration::new(gain_l, gain_r, delay_samples));
+    }
+
+    /// Remove the calibration
+    pub fn uncalibrate(&self) {
+        self.calibration.lock().take();
+    }
+
+    /// Set the channel weights used when downmixing to mono
+    ///
+    /// The weights are normalized, so the mono sum is
+    /// `(w_l * l + w_r * r) / (w_l + w_r)`.  Defaults to equal weights.
+    pub fn downmix_weights(&self, w_l: Sample, w_r: Sample) {
+        assert!(w_l + w_r > 0.0, "Downmix weights must not sum to zero!");
+        *self.downmix.lock() = [w_l / (w_l + w_r), w_r / (w_l + w_r)];
+    }
+
+    /// Downmix a sample to mono using the [`downmix_weights`](#method.downmix_weights)
+    pub fn downmix(&self, sample: [Sample; 2]) -> Sample {
+        let [w_l, w_r] = *self.downmix.lock();
+        w_l * sample[0] + w_r * sample[1]
+    }
+
+    /// Copy the last `size` samples downmixed to mono, oldest first
+    pub fn mono(&self, size: usize) -> Vec<Sample> {
+        let [w_l, w_r] = *self.downmix.lock();
+        self.iter(size, 1).map(|[l, r]| w_l * l + w_r * r).collect()
     }
 
     /// Lock the buffer and iterate over the last `size` samples (with downsampling)
@@ -78,20 +469,128 @@ impl SampleBuffer {
     /// Set downsampling to `1` if you do not want to use it.
     pub fn iter<'a>(&'a self, size: usize, downsample: usize) -> SampleIterator<'a> {
         let lock = self.buf.lock();
+        let pushed = self.pushed.load(sync::atomic::Ordering::Acquire);
+        *self.read.lock() = Some(pushed);
 
         SampleIterator {
             index: lock.len() - (size * downsample),
+            end: lock.len(),
+            pushed,
             buf: lock,
             downsample,
         }
     }
 
+    /// Lock the buffer and iterate over the frame `hop` samples after the previous one
+    ///
+    /// Like [`iter`](#method.iter), but successive frames advance by exactly `hop`
+    /// (downsampled) samples instead of always reading the newest ones, so frames overlap
+    /// by `size - hop` samples.  The first frame ends at the newest sample.  Returns `None`
+    /// until enough new samples were pushed for the next frame.  If the next frame was
+    /// already dropped from the buffer, it skips ahead to the newest samples.
+    ///
+    /// The position is stored in the buffer and shared by all clones, so only one
+    /// consumer should use this.
+    pub fn iter_overlap<'a>(
+        &'a self,
+        size: usize,
+        downsample: usize,
+        hop: usize,
+    ) -> Option<SampleIterator<'a>> {
+        let lock = self.buf.lock();
+        let total = self.pushed.load(sync::atomic::Ordering::Acquire);
+        let span = size * downsample;
+        let mut cursor = self.cursor.lock();
+
+        let mut end = cursor.map_or(total, |c| c + (hop * downsample) as u64);
+        if end > total {
+            return None;
+        }
+        if (total - end) as usize + span > lock.len() {
+            log::warn!(
+                "SampleBuffer({:p}): Overlapping frames fell behind by {} samples, skipping",
+                &self,
+                total - end,
+            );
+            end = total;
+        }
+        *cursor = Some(end);
+        *self.read.lock() = Some(end);
+
+        let end_index = lock.len() - (total - end) as usize;
+        Some(SampleIterator {
+            index: end_index - span,
+            end: end_index,
+            pushed: end,
+            buf: lock,
+            downsample,
+        })
+    }
+
+    /// Copy all samples currently in the buffer, oldest first
+    pub fn snapshot(&self) -> Vec<[Sample; 2]> {
+        let lock = self.buf.lock();
+        *self.read.lock() = Some(self.pushed.load(sync::atomic::Ordering::Acquire));
+
+        lock.iter().cloned().collect()
+    }
+
+    /// Copy the newest `n` samples into `out`, oldest first
+    ///
+    /// Unlike [`iter`](#method.iter) there is no downsampling, eg. for an oscilloscope.
+    /// `out` is cleared first, so it can be reused across frames.  `n` is limited to the
+    /// size of the buffer.
+    pub fn latest(&self, n: usize, out: &mut Vec<[Sample; 2]>) {
+        out.clear();
+        self.with_latest(n, |samples| out.extend_from_slice(samples));
+    }
+
+    /// Call `f` with the newest `n` samples, oldest first
+    ///
+    /// The buffer stays locked while `f` runs, so keep it short.  If the samples wrapped
+    /// around the end of the ring-buffer, they are copied into a temporary first,
+    /// otherwise `f` sees them in place.  `n` is limited to the size of the buffer.
+    pub fn with_latest<R, F>(&self, n: usize, f: F) -> R
+    where
+        F: FnOnce(&[[Sample; 2]]) -> R,
+    {
+        let lock = self.buf.lock();
+        *self.read.lock() = Some(self.pushed.load(sync::atomic::Ordering::Acquire));
+
+        let n = n.min(lock.len());
+        let (front, back) = lock.as_slices();
+        if n <= back.len() {
+            f(&back[back.len() - n..])
+        } else if back.is_empty() {
+            f(&front[front.len() - n..])
+        } else {
+            let mut samples = Vec::with_capacity(n);
+            samples.extend_from_slice(&front[front.len() - (n - back.len())..]);
+            samples.extend_from_slice(back);
+            f(&samples)
+        }
+    }
+
+    /// Copy the samples pushed after the buffer had seen `pushed` samples, oldest first
+    ///
+    /// Returns the new total number of pushed samples along with the samples, which are
+    /// limited to the size of the buffer.  Use this to consume a buffer incrementally.
+    pub fn since(&self, pushed: u64) -> (u64, Vec<[Sample; 2]>) {
+        let lock = self.buf.lock();
+        let total = self.pushed.load(sync::atomic::Ordering::Acquire);
+        let new = (total.saturating_sub(pushed) as usize).min(lock.len());
+        *self.read.lock() = Some(total);
+
+        (total, lock.iter().skip(lock.len() - new).cloned().collect())
+    }
+
     /// Calculate the RMS Volume over the last `length` seconds
     ///
     /// Keep `length` short to avoid performance issues
     pub fn volume(&self, length: f32) -> super::SignalStrength {
         use super::SignalStrength;
 
+        let [w_l, w_r] = *self.downmix.lock();
         let lock = self.buf.lock();
         let len = lock.len();
 
@@ -102,23 +601,61 @@ impl SampleBuffer {
             // Only look at the last tenth of a second
             .skip(len - self.rate / div)
             // RMS
-            .map(|s| ((s[0] + s[1]) / 2.0).powi(2) as SignalStrength)
+            .map(|s| (w_l * s[0] + w_r * s[1]).powi(2) as SignalStrength)
             .sum::<SignalStrength>()
             / len as SignalStrength)
             .sqrt()
     }
+
+    /// Calculate the correlation between left and right channel over the last
+    /// `length` seconds
+    ///
+    /// `+1.0` means both channels are in phase, `-1.0` that they are inverted and `0.0`
+    /// that they are unrelated (or silent).
+    pub fn correlation(&self, length: f32) -> f32 {
+        let lock = self.buf.lock();
+        let len = lock.len();
+        let size = ((self.rate as f32 * length) as usize).min(len);
+
+        let (lr, ll, rr) = lock
+            .iter()
+            .skip(len - size)
+            .fold((0.0, 0.0, 0.0), |(lr, ll, rr), [l, r]| {
+                (lr + l * r, ll + l * l, rr + r * r)
+            });
+
+        if ll == 0.0 || rr == 0.0 {
+            0.0
+        } else {
+            to_f32(lr / (ll * rr).sqrt())
+        }
+    }
 }
 
 pub struct SampleIterator<'a> {
     buf: parking_lot::MutexGuard<'a, collections::VecDeque<[Sample; 2]>>,
     index: usize,
+    end: usize,
     downsample: usize,
+    pushed: u64,
+}
+
+impl SampleIterator<'_> {
+    /// Return the number of samples pushed to the buffer up to the newest sample
+    ///
+    /// Consistent with the iterated samples, as the buffer stays locked.
+    pub fn pushed(&self) -> u64 {
+        self.pushed
+    }
 }
 
 impl Iterator for SampleIterator<'_> {
-    type Item = [f32; 2];
+    type Item = [Sample; 2];
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.end {
+            return None;
+        }
         let res = self.buf.get(self.index).cloned();
         self.index += self.downsample;
         res
@@ -164,6 +701,161 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_overruns() {
+        let buf = SampleBuffer::new(16, 8000);
+
+        // Nothing was read yet, so filling the buffer is not an overrun
+        buf.push(&[[0.0; 2]; 32]);
+        assert_eq!(buf.overruns(), 0);
+
+        buf.iter(16, 1).count();
+        buf.push(&[[0.0; 2]; 10]);
+        buf.push(&[[0.0; 2]; 6]);
+        assert_eq!(buf.overruns(), 0);
+
+        // Laps the last read
+        buf.push(&[[0.0; 2]; 1]);
+        buf.push(&[[0.0; 2]; 20]);
+        assert_eq!(buf.overruns(), 2);
+
+        buf.since(0);
+        buf.push(&[[0.0; 2]; 8]);
+        assert_eq!(buf.overruns(), 2);
+
+        buf.reset_overruns();
+        assert_eq!(buf.overruns(), 0);
+    }
+
+    #[test]
+    fn test_latest() {
+        let buf = SampleBuffer::new(16, 8000);
+        let ramp = |range: std::ops::Range<usize>| {
+            range
+                .map(|i| [i as Sample, -(i as Sample)])
+                .collect::<Vec<_>>()
+        };
+        buf.push(&ramp(0..16));
+
+        // Freshly filled, all samples are in one piece
+        let mut out = vec![[9.0; 2]; 3];
+        buf.latest(4, &mut out);
+        assert_eq!(out, ramp(12..16));
+        buf.with_latest(16, |s| assert_eq!(s, &ramp(0..16)[..]));
+
+        // Now the newest 5 samples start the ring-buffer, older ones end it
+        buf.push(&ramp(16..21));
+        buf.with_latest(3, |s| assert_eq!(s, &ramp(18..21)[..]));
+        buf.latest(5, &mut out);
+        assert_eq!(out, ramp(16..21));
+        buf.latest(8, &mut out);
+        assert_eq!(out, ramp(13..21));
+        let len = buf.with_latest(100, |s| {
+            assert_eq!(s, &ramp(5..21)[..]);
+            s.len()
+        });
+        assert_eq!(len, 16);
+    }
+
+    #[test]
+    fn test_overlap() {
+        let buf = SampleBuffer::new(32, 8000);
+        let ramp = |range: std::ops::Range<usize>| {
+            range
+                .map(|i| [i as Sample, -(i as Sample)])
+                .collect::<Vec<_>>()
+        };
+
+        buf.push(&ramp(0..16));
+        let first = buf.iter_overlap(16, 1, 8).unwrap().collect::<Vec<_>>();
+        assert_eq!(first, ramp(0..16));
+
+        // Not enough new samples for the next frame
+        buf.push(&ramp(16..20));
+        assert!(buf.iter_overlap(16, 1, 8).is_none());
+
+        // Frames advance by exactly one hop, even if more samples are available
+        buf.push(&ramp(20..30));
+        let second = buf.iter_overlap(16, 1, 8).unwrap();
+        assert_eq!(second.pushed(), 24);
+        let second = second.collect::<Vec<_>>();
+        assert_eq!(second.len(), 16);
+        assert_eq!(first[8..], second[..8]);
+        assert_eq!(second, ramp(8..24));
+
+        // Falling behind skips to the newest samples
+        buf.push(&ramp(30..80));
+        let third = buf.iter_overlap(16, 1, 8).unwrap().collect::<Vec<_>>();
+        assert_eq!(third, ramp(64..80));
+    }
+
+    #[test]
+    fn test_calibrate() {
+        let rate = 8000;
+        let mono = (0..4000)
+            .map(|i| (i as f32 / rate as f32 * 500.0 * 2.0 * std::f32::consts::PI).sin() as Sample)
+            .collect::<Vec<_>>();
+
+        // Right channel is 6 dB quieter and lags by 1.5 samples
+        let delay = 1.5;
+        let mismatched = (0..mono.len())
+            .map(|i| {
+                let t = i as f32 - delay;
+                let r = (t / rate as f32 * 500.0 * 2.0 * std::f32::consts::PI).sin();
+                [mono[i], r as Sample * 0.5]
+            })
+            .collect::<Vec<_>>();
+
+        let buf = SampleBuffer::new(4000, rate);
+        buf.push(&mismatched);
+        let before = buf.correlation(0.1);
+        assert!(before < 0.9, "{}", before);
+
+        buf.calibrate(1.0, 2.0, -delay);
+        buf.push(&mismatched);
+        let after = buf.correlation(0.1);
+        assert!((after - 1.0).abs() < 1e-3, "{}", after);
+
+        let rms = buf
+            .iter(800, 1)
+            .fold([0.0; 2], |[l, r], [sl, sr]| [l + sl * sl, r + sr * sr]);
+        assert!((rms[0] / rms[1] - 1.0).abs() < 1e-2, "{:?}", rms);
+
+        buf.uncalibrate();
+        buf.push(&mismatched);
+        assert!((buf.correlation(0.1) - before).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_sanitize() {
+        crate::test_config();
+        let mut analyzer = crate::analyzer::FourierBuilder::new()
+            .length(64)
+            .window(crate::analyzer::window::hanning)
+            .downsample(1)
+            .rate(8000)
+            .plan();
+
+        let buf = SampleBuffer::new(64, 8000);
+        let mut samples = crate::helpers::signal::sine(8000, 1000.0, 64.0 / 8000.0);
+        samples[10] = [Sample::NAN, 0.5];
+        samples[20] = [Sample::INFINITY, Sample::NEG_INFINITY];
+
+        buf.push(&samples);
+        assert_eq!(buf.stats().non_finite, 3);
+        assert_eq!(buf.iter(64, 1).nth(10).unwrap(), [0.0, 0.5]);
+        assert!(analyzer.analyze(&buf)[0].iter().all(|s| s.is_finite()));
+
+        buf.push(&crate::helpers::signal::sine(8000, 1000.0, 32.0 / 8000.0));
+        assert_eq!(buf.stats().non_finite, 3);
+
+        buf.sanitize(false);
+        buf.push(&[[Sample::NAN; 2]]);
+        assert_eq!(buf.stats().non_finite, 3);
+        assert!(buf.iter(64, 1).last().unwrap()[0].is_nan());
+        assert_eq!(buf.stats().pushed, 97);
+    }
+
     #[test]
     fn test_downsample() {
         let buf = SampleBuffer::new(32, 8000);
@@ -179,4 +871,41 @@ mod tests {
             &[[4.0; 2], [8.0; 2], [12.0; 2], [16.0; 2], [20.0; 2], [24.0; 2], [28.0; 2],]
         );
     }
+
+    #[test]
+    fn test_budget() {
+        let frame = std::mem::size_of::<[Sample; 2]>();
+        let err = SampleBuffer::with_budget(1024, 8000, 4096).unwrap_err();
+        assert_eq!(
+            err,
+            BudgetError {
+                required: 1024 * frame,
+                budget: 4096,
+            }
+        );
+
+        let buf = SampleBuffer::with_budget(4096 / frame, 8000, 4096).unwrap();
+        assert_eq!(buf.memory_bytes(), 4096);
+
+        buf.calibrate(1.0, 1.0, 10.0);
+        assert!(buf.memory_bytes() > 4096);
+    }
+
+    #[test]
+    fn test_downmix() {
+        let buf = SampleBuffer::new(4, 8000);
+        buf.push(&[[1.0, 0.0], [0.5, -1.0], [0.0, 0.25], [-1.0, 1.0]]);
+
+        assert_eq!(buf.mono(4), vec![0.5, -0.25, 0.125, 0.0]);
+
+        buf.downmix_weights(1.0, 0.0);
+        assert_eq!(buf.mono(4), vec![1.0, 0.5, 0.0, -1.0]);
+
+        buf.downmix_weights(0.0, 2.0);
+        assert_eq!(buf.mono(4), vec![0.0, -1.0, 0.25, 1.0]);
+        assert_eq!(buf.downmix([0.3, 0.7]), 0.7);
+
+        buf.downmix_weights(3.0, 1.0);
+        assert_eq!(buf.downmix([1.0, 1.0]), 1.0);
+    }
 }
@@ -0,0 +1,168 @@
+//! Spectral Smoothing
+use crate::analyzer;
+
+/// Builder for SpectrumSmoother
+#[derive(Debug, Default)]
+pub struct SpectrumSmootherBuilder {
+    /// Fraction of the previous value that remains when a bin rises
+    ///
+    /// `0.0` follows rises immediately.  Defaults to `0.0`, can also be set from config as
+    /// `"audio.smooth.attack"`.
+    pub attack: Option<f32>,
+
+    /// Fraction of the previous value that remains when a bin falls
+    ///
+    /// The closer to `1.0`, the slower bins fall.  Defaults to `0.8`, can also be set from
+    /// config as `"audio.smooth.release"`.
+    pub release: Option<f32>,
+}
+
+impl SpectrumSmootherBuilder {
+    /// Create a new SpectrumSmootherBuilder
+    pub fn new() -> SpectrumSmootherBuilder {
+        Default::default()
+    }
+
+    /// Set the attack coefficient
+    pub fn attack(&mut self, attack: f32) -> &mut SpectrumSmootherBuilder {
+        self.attack = Some(attack);
+        self
+    }
+
+    /// Set the release coefficient
+    pub fn release(&mut self, release: f32) -> &mut SpectrumSmootherBuilder {
+        self.release = Some(release);
+        self
+    }
+
+    /// Build the SpectrumSmoother
+    pub fn build(&mut self) -> SpectrumSmoother {
+        SpectrumSmoother::from_builder(self)
+    }
+}
+
+/// Per-bin attack/release smoothing of magnitudes
+///
+/// Reduces the frame-to-frame flicker of raw transform output.  A rising bin moves towards
+/// the new value by `1 - attack`, a falling bin decays by `release` per frame but never below
+/// the new value:
+///
+/// ```text
+/// out[i] = max(new[i], out[i] * release)
+/// ```
+///
+/// Works on any slice of magnitudes, eg. from
+/// [`FourierAnalyzer::magnitudes`](struct.FourierAnalyzer.html#method.magnitudes).  If the
+/// number of bins changes, the state starts over.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// # vis_core::default_config();
+/// # let mut analyzer = analyzer::FourierBuilder::new().length(64).downsample(1).plan();
+/// # let buffer = analyzer::SampleBuffer::new(64, 8000);
+/// let mut smoother = analyzer::SpectrumSmootherBuilder::new()
+///     .attack(0.0)
+///     .release(0.8)
+///     .build();
+///
+/// analyzer.analyze(&buffer);
+/// let smoothed = smoother.process(&analyzer.magnitudes(0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SpectrumSmoother {
+    attack: f32,
+    release: f32,
+
+    state: Vec<analyzer::SignalStrength>,
+}
+
+impl SpectrumSmoother {
+    /// Create a SpectrumSmoother from a builder config
+    pub fn from_builder(build: &SpectrumSmootherBuilder) -> SpectrumSmoother {
+        SpectrumSmoother {
+            attack: build
+                .attack
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.smooth.attack", 0.0)),
+            release: build
+                .release
+                .unwrap_or_else(|| crate::CONFIG.get_or("audio.smooth.release", 0.8)),
+
+            state: Vec::new(),
+        }
+    }
+
+    /// Smooth the next frame of magnitudes and return the smoothed bins
+    pub fn process(&mut self, input: &[analyzer::SignalStrength]) -> &[analyzer::SignalStrength] {
+        if self.state.len() != input.len() {
+            self.state.clear();
+            self.state.extend_from_slice(input);
+            return &self.state;
+        }
+
+        for (out, new) in self.state.iter_mut().zip(input.iter()) {
+            *out = if *new > *out {
+                *out * self.attack + new * (1.0 - self.attack)
+            } else {
+                new.max(*out * self.release)
+            };
+        }
+
+        &self.state
+    }
+
+    /// Return the smoothed bins of the last frame
+    pub fn output(&self) -> &[analyzer::SignalStrength] {
+        &self.state
+    }
+
+    /// Forget the smoothing state
+    pub fn reset(&mut self) {
+        self.state.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_impulse() {
+        crate::test_config();
+        let mut smoother = SpectrumSmootherBuilder::new()
+            .attack(0.0)
+            .release(0.5)
+            .build();
+
+        assert_eq!(smoother.process(&[0.0; 4]), &[0.0; 4]);
+        assert_eq!(
+            smoother.process(&[0.0, 8.0, 0.0, 0.0]),
+            &[0.0, 8.0, 0.0, 0.0]
+        );
+        for expected in [4.0, 2.0, 1.0, 0.5] {
+            assert_eq!(
+                smoother.process(&[0.0, 0.0, 0.0, 1.0]),
+                &[0.0, expected, 0.0, 1.0]
+            );
+        }
+
+        // A new frame size starts over
+        assert_eq!(smoother.process(&[3.0; 2]), &[3.0; 2]);
+    }
+
+    #[test]
+    fn test_attack() {
+        crate::test_config();
+        let mut smoother = SpectrumSmootherBuilder::new()
+            .attack(0.75)
+            .release(0.5)
+            .build();
+
+        smoother.process(&[0.0]);
+        assert_eq!(smoother.process(&[4.0]), &[1.0]);
+        assert_eq!(smoother.process(&[4.0]), &[1.75]);
+
+        smoother.reset();
+        assert!(smoother.output().is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+//! Spectral History
+use crate::analyzer;
+
+/// Mapping of normalized levels to colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Colormap {
+    /// Black to white
+    #[default]
+    Grayscale,
+    /// Black through red and yellow to white
+    Heat,
+}
+
+impl Colormap {
+    /// Return the color of `value`, from `0.0` (black) to `1.0`, as RGBA
+    pub fn color(self, value: f32) -> [u8; 4] {
+        let v = value.clamp(0.0, 1.0);
+        let byte = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        match self {
+            Colormap::Grayscale => [byte(v), byte(v), byte(v), 255],
+            Colormap::Heat => [byte(v * 3.0), byte(v * 3.0 - 1.0), byte(v * 3.0 - 2.0), 255],
+        }
+    }
+}
+
+impl std::str::FromStr for Colormap {
+    type Err = ();
+
+    /// Get the colormap for the specified name
+    fn from_str(name: &str) -> Result<Colormap, ()> {
+        match name {
+            "grayscale" | "gray" => Ok(Colormap::Grayscale),
+            "heat" => Ok(Colormap::Heat),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Ring of the last `columns` spectra with `bins` magnitudes each
+///
+/// Each [`push`](#method.push) adds a column and evicts the oldest one once the ring is
+/// full.  Unlike the [`DisplaySpectrogram`](../../helpers/struct.DisplaySpectrogram.html)
+/// the magnitudes are kept as they are, eg. for analyses over time or custom rendering.
+///
+/// # Example
+/// ```
+/// # use vis_core::analyzer;
+/// let mut spectrogram = analyzer::Spectrogram::new(128, 40);
+///
+/// # let bands = vec![0.0; 40];
+/// // Eg. the bands of a MelAnalyzer, once per frame
+/// spectrogram.push(&bands);
+///
+/// let image = spectrogram.to_image_rgba(analyzer::spectrogram::Colormap::Heat);
+/// assert_eq!(image.len(), 128 * 40 * 4);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Spectrogram {
+    columns: usize,
+    bins: usize,
+
+    data: Vec<analyzer::Sample>,
+    /// Column holding the oldest spectrum
+    start: usize,
+    len: usize,
+}
+
+impl Spectrogram {
+    /// Create an empty spectrogram
+    pub fn new(columns: usize, bins: usize) -> Spectrogram {
+        assert!(columns > 0, "Spectrogram needs at least 1 column!");
+
+        Spectrogram {
+            columns,
+            bins,
+
+            data: vec![0.0; columns * bins],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// Return the maximum number of columns
+    #[inline]
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Return the number of bins per column
+    #[inline]
+    pub fn bins(&self) -> usize {
+        self.bins
+    }
+
+    /// Return the number of columns pushed so far, at most `columns`
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return whether no column was pushed yet
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add the newest column
+    pub fn push(&mut self, column: &[analyzer::Sample]) {
+        assert_eq!(
+            column.len(),
+            self.bins,
+            "Spectrogram columns must have {} bins!",
+            self.bins
+        );
+
+        let index = if self.len < self.columns {
+            self.len += 1;
+            (self.start + self.len - 1) % self.columns
+        } else {
+            let oldest = self.start;
+            self.start = (self.start + 1) % self.columns;
+            oldest
+        };
+        self.data[index * self.bins..(index + 1) * self.bins].copy_from_slice(column);
+    }
+
+    /// Forget all columns
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+
+    /// Iterate over the pushed columns, oldest first
+    pub fn data(&self) -> impl Iterator<Item = &[analyzer::Sample]> + '_ {
+        (0..self.len).map(move |i| {
+            let index = (self.start + i) % self.columns;
+            &self.data[index * self.bins..(index + 1) * self.bins]
+        })
+    }
+
+    /// Render the spectrogram as a `columns` x `bins` RGBA image
+    ///
+    /// The image is row-major with the oldest column on the left and the highest bin in
+    /// the top row.  Levels are normalized to the largest magnitude, columns not pushed yet
+    /// are black.
+    pub fn to_image_rgba(&self, colormap: Colormap) -> Vec<u8> {
+        let max = self
+            .data()
+            .flat_map(|c| c.iter())
+            .cloned()
+            .fold(0.0, analyzer::Sample::max);
+        let scale = if max > 0.0 { 1.0 / max } else { 0.0 };
+
+        let mut image = [0, 0, 0, 255].repeat(self.columns * self.bins);
+        for (x, column) in self.data().enumerate() {
+            for (bin, v) in column.iter().enumerate() {
+                let y = self.bins - 1 - bin;
+                let offset = (y * self.columns + x) * 4;
+                image[offset..offset + 4]
+                    .copy_from_slice(&colormap.color(analyzer::samples::to_f32(v * scale)));
+            }
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring() {
+        let mut spectrogram = Spectrogram::new(3, 2);
+        assert!(spectrogram.is_empty());
+        assert_eq!(spectrogram.data().count(), 0);
+
+        for i in 0..5 {
+            spectrogram.push(&[i as analyzer::Sample, -(i as analyzer::Sample)]);
+            assert_eq!(spectrogram.len(), (i + 1).min(3));
+        }
+
+        // The two oldest were evicted, order is preserved
+        let columns = spectrogram.data().map(|c| c.to_vec()).collect::<Vec<_>>();
+        assert_eq!(columns, [[2.0, -2.0], [3.0, -3.0], [4.0, -4.0]]);
+
+        spectrogram.clear();
+        spectrogram.push(&[7.0, 8.0]);
+        assert_eq!(spectrogram.data().collect::<Vec<_>>(), [&[7.0, 8.0]]);
+    }
+
+    #[test]
+    fn test_image() {
+        let mut spectrogram = Spectrogram::new(3, 2);
+        spectrogram.push(&[0.0, 2.0]);
+        spectrogram.push(&[1.0, 0.0]);
+
+        let image = spectrogram.to_image_rgba(Colormap::Grayscale);
+        let pixel = |x: usize, y: usize| &image[(y * 3 + x) * 4..(y * 3 + x + 1) * 4];
+        // Highest bin on top, oldest column left
+        assert_eq!(pixel(0, 0), [255, 255, 255, 255]);
+        assert_eq!(pixel(0, 1), [0, 0, 0, 255]);
+        assert_eq!(pixel(1, 1), [128, 128, 128, 255]);
+        // Not pushed yet
+        assert_eq!(pixel(2, 0), [0, 0, 0, 255]);
+
+        assert_eq!(Colormap::Heat.color(1.0), [255, 255, 255, 255]);
+        assert_eq!(Colormap::Heat.color(0.5), [255, 128, 0, 255]);
+    }
+}
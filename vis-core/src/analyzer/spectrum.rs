@@ -352,6 +352,17 @@ impl<S: StorageMut> Spectrum<S> {
     }
 }
 
+impl Spectrum<Vec<SignalStrength>> {
+    /// Resize this spectrum to `len` buckets spanning `low` to `high`
+    ///
+    /// All buckets are reset to `0.0`.  The allocation is reused if it is big enough.
+    pub fn resize(&mut self, len: usize, low: Frequency, high: Frequency) {
+        self.buckets.clear();
+        self.buckets.resize(len, 0.0);
+        self.respan(low, high);
+    }
+}
+
 /// Compute the average of multiple spectra
 pub fn average_spectrum<'a, S: Storage, SMut: StorageMut>(
     out: &'a mut Spectrum<SMut>,
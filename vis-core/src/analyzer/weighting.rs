@@ -0,0 +1,142 @@
+//! Perceptual Frequency Weighting
+//!
+//! Gain curves of the standard sound level meter weightings (IEC 61672), for meter-style
+//! visuals or loudness-aware scaling.  All curves are linear amplitude gains normalized to
+//! `1.0` at 1 kHz.  Compute them from the analyzer's bin frequencies and apply them with
+//! [`FourierAnalyzer::apply_weighting`](../fourier/struct.FourierAnalyzer.html#method.apply_weighting):
+//!
+//! ```
+//! # use vis_core::analyzer;
+//! # vis_core::default_config();
+//! # let samples = analyzer::SampleBuffer::new(32000, 8000);
+//! let mut analyzer = analyzer::FourierBuilder::new().plan();
+//! let weights = analyzer::weighting::a_weighting(&analyzer.frequencies());
+//!
+//! analyzer.analyze(&samples);
+//! analyzer.apply_weighting(&weights);
+//! ```
+use crate::analyzer;
+
+/// Pole frequencies of the weighting curves in Hz
+const F1: f64 = 20.598997;
+const F2: f64 = 107.65265;
+const F3: f64 = 737.86223;
+const F4: f64 = 12194.217;
+
+/// Unnormalized A-weighting response at `f`
+fn a_response(f: f64) -> f64 {
+    let f2 = f * f;
+    F4 * F4 * f2 * f2 / ((f2 + F1 * F1) * ((f2 + F2 * F2) * (f2 + F3 * F3)).sqrt() * (f2 + F4 * F4))
+}
+
+/// Unnormalized C-weighting response at `f`
+fn c_response(f: f64) -> f64 {
+    let f2 = f * f;
+    F4 * F4 * f2 / ((f2 + F1 * F1) * (f2 + F4 * F4))
+}
+
+/// Response at `f` relative to the one at 1 kHz
+fn gain(f: analyzer::Frequency, response: fn(f64) -> f64) -> analyzer::SignalStrength {
+    (response(f as f64) / response(1000.0)) as analyzer::SignalStrength
+}
+
+fn curve(
+    frequencies: &[analyzer::Frequency],
+    response: fn(f64) -> f64,
+) -> Vec<analyzer::SignalStrength> {
+    frequencies.iter().map(|&f| gain(f, response)).collect()
+}
+
+/// Return the A-weighting gain at a single frequency `f`
+pub fn a_gain(f: analyzer::Frequency) -> analyzer::SignalStrength {
+    gain(f, a_response)
+}
+
+/// Return the A-weighting gain for each of `frequencies`
+///
+/// Follows the ear's sensitivity at low levels, strongly attenuating low frequencies
+/// (about -40 dB at 30 Hz) and slightly the highest ones.
+pub fn a_weighting(frequencies: &[analyzer::Frequency]) -> Vec<analyzer::SignalStrength> {
+    curve(frequencies, a_response)
+}
+
+/// Return the C-weighting gain for each of `frequencies`
+///
+/// Almost flat, only the extremes below 50 Hz and above 5 kHz are attenuated.  Used for
+/// loud sounds and peak levels.
+pub fn c_weighting(frequencies: &[analyzer::Frequency]) -> Vec<analyzer::SignalStrength> {
+    curve(frequencies, c_response)
+}
+
+/// Return the Z-weighting (no weighting) gain for each of `frequencies`
+pub fn z_weighting(frequencies: &[analyzer::Frequency]) -> Vec<analyzer::SignalStrength> {
+    vec![1.0; frequencies.len()]
+}
+
+/// Convert a linear gain to dB
+pub fn to_db(gain: analyzer::SignalStrength) -> analyzer::SignalStrength {
+    20.0 * gain.log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curves() {
+        let frequencies = [31.5, 100.0, 1000.0, 10000.0];
+        let a = a_weighting(&frequencies)
+            .into_iter()
+            .map(to_db)
+            .collect::<Vec<_>>();
+        let c = c_weighting(&frequencies)
+            .into_iter()
+            .map(to_db)
+            .collect::<Vec<_>>();
+
+        // Table values of IEC 61672
+        for (db, expected) in a.iter().zip([-39.4, -19.1, 0.0, -2.5]) {
+            assert!((db - expected).abs() < 0.2, "{:?}", a);
+        }
+        for (db, expected) in c.iter().zip([-3.0, -0.3, 0.0, -4.4]) {
+            assert!((db - expected).abs() < 0.2, "{:?}", c);
+        }
+        assert_eq!(z_weighting(&frequencies), [1.0; 4]);
+        assert_eq!(a_gain(100.0), a_weighting(&frequencies)[1]);
+    }
+
+    #[test]
+    fn test_apply_weighting() {
+        crate::test_config();
+        let mut analyzer = analyzer::FourierBuilder::new()
+            .rate(8000)
+            .length(2048)
+            .window(analyzer::window::hanning)
+            .downsample(1)
+            .plan();
+        let buf = analyzer::SampleBuffer::new(2048, 8000);
+        let weights = a_weighting(&analyzer.frequencies());
+
+        let mut level = |freq| {
+            buf.push(&crate::helpers::signal::sine(8000, freq, 2048.0 / 8000.0));
+            analyzer.analyze(&buf);
+            let plain = analyzer.magnitudes(0);
+            let plain = plain
+                .iter()
+                .cloned()
+                .fold(0.0, analyzer::SignalStrength::max);
+            analyzer.apply_weighting(&weights);
+            let weighted = analyzer.magnitudes(0);
+            let weighted = weighted
+                .iter()
+                .cloned()
+                .fold(0.0, analyzer::SignalStrength::max);
+            to_db(weighted / plain)
+        };
+
+        let low = level(30.0);
+        let mid = level(1000.0);
+        assert!(mid.abs() < 0.5, "{}", mid);
+        assert!(low < mid - 30.0, "{} {}", low, mid);
+    }
+}
@@ -1,5 +1,122 @@
 use crate::{analyzer, recorder};
-use std::{cell, rc, time};
+use std::{cell, collections, rc, time};
+
+/// Kind of analysis results carried by [`FrameData`](enum.FrameData.html)
+#[derive(Debug, Clone)]
+pub enum FrameKind {
+    /// The average spectrum of both channels
+    Magnitudes,
+    /// Bars computed from the average spectrum
+    Bars(analyzer::BarState),
+    /// Scalar features
+    Features,
+}
+
+/// A bundle of scalar features
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Features {
+    /// RMS volume of the last tenth of a second
+    pub volume: analyzer::SignalStrength,
+    /// Sum of all buckets of the average spectrum
+    pub energy: analyzer::SignalStrength,
+    /// Frequency of the strongest maximum of the average spectrum
+    pub peak: analyzer::Frequency,
+}
+
+/// Typed analysis results
+///
+/// Created by [`Visualizer::with_data`](../visualizer/struct.Visualizer.html#method.with_data),
+/// the variant is selected by a [`FrameKind`](enum.FrameKind.html).
+#[derive(Debug, Clone)]
+pub enum FrameData {
+    /// The average spectrum of both channels
+    Magnitudes(analyzer::Spectrum<Vec<analyzer::SignalStrength>>),
+    /// Bars computed from the average spectrum
+    Bars(Vec<analyzer::SignalStrength>),
+    /// Scalar features
+    Features(Features),
+}
+
+/// Analyzer closure producing [`FrameData`](enum.FrameData.html)
+pub type DataAnalyzer =
+    Box<dyn for<'r> FnMut(&'r mut FrameData, &analyzer::SampleBuffer) -> &'r mut FrameData + Send>;
+
+/// Source of the time passed to the recorder each frame
+///
+/// Replaying and synthetic recorders push samples based on this time, so a manual clock
+/// makes frame boundaries reproducible, eg. for tests.  Live recorders ignore it and stay
+/// real-time.
+#[derive(Debug, Clone, Default)]
+pub enum Clock {
+    /// Seconds since iteration started
+    #[default]
+    Real,
+    /// Advanced by hand, one frame per tick
+    Manual(ManualClock),
+}
+
+#[derive(Debug, Default)]
+struct ManualState {
+    time: f32,
+    ticks: collections::VecDeque<f32>,
+}
+
+/// Handle for advancing a [`Clock::Manual`](enum.Clock.html#variant.Manual)
+///
+/// Each [`tick`](#method.tick) queues one frame, the frames iterator ends once all ticks
+/// are consumed and continues when more are queued.  Clones share the same clock.
+///
+/// # Example
+/// ```
+/// # use vis_core::frames;
+/// # vis_core::default_config();
+/// let clock = frames::ManualClock::new();
+/// # let recorder = vis_core::recorder::callback::CallbackBuilder::new().build(|_| ());
+/// let mut frames = vis_core::Visualizer::new(0.0, |i, _s| i)
+///     .recorder(recorder)
+///     .clock(frames::Clock::Manual(clock.clone()))
+///     .frames();
+///
+/// for _ in 0..3 {
+///     clock.tick(1.0 / 60.0);
+/// }
+/// assert_eq!(frames.iter().count(), 3);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    state: std::sync::Arc<parking_lot::Mutex<ManualState>>,
+}
+
+impl ManualClock {
+    /// Create a new clock at time `0.0`
+    pub fn new() -> ManualClock {
+        Default::default()
+    }
+
+    /// Queue a frame `dt` seconds after the previously queued one
+    pub fn tick(&self, dt: f32) {
+        let mut state = self.state.lock();
+        let last = state.ticks.back().cloned().unwrap_or(state.time);
+        state.ticks.push_back(last + dt);
+    }
+
+    /// Return the time of the last consumed tick
+    pub fn time(&self) -> f32 {
+        self.state.lock().time
+    }
+
+    /// Return the number of queued ticks
+    pub fn pending(&self) -> usize {
+        self.state.lock().ticks.len()
+    }
+
+    /// Consume the next tick, returning its time
+    fn next(&self) -> Option<f32> {
+        let mut state = self.state.lock();
+        state.time = state.ticks.pop_front()?;
+        Some(state.time)
+    }
+}
 
 /// Data for one Frame
 #[derive(Debug)]
@@ -51,6 +168,9 @@ where
     info: rc::Rc<cell::RefCell<triple_buffer::Output<R>>>,
     analyzer: Option<(A, triple_buffer::Input<R>)>,
     recorder: Box<dyn recorder::Recorder>,
+    /// Number of samples to buffer before the first frame
+    pre_roll: u64,
+    clock: Clock,
 }
 
 impl<R, A> Frames<R, A>
@@ -60,14 +180,26 @@ where
 {
     pub fn from_vis(vis: crate::Visualizer<R, A>) -> Frames<R, A> {
         let (inp, outp) = triple_buffer::TripleBuffer::new(&vis.initial).split();
+        let recorder = vis
+            .recorder
+            .unwrap_or_else(|| recorder::RecorderBuilder::new().build());
+        let pre_roll = vis
+            .pre_roll
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.pre_roll", 0));
+        let pre_roll = (pre_roll * recorder.sample_buffer().rate() / 1000) as u64;
         let mut f = Frames {
             info: rc::Rc::new(cell::RefCell::new(outp)),
             analyzer: Some((vis.analyzer, inp)),
-            recorder: vis
-                .recorder
-                .unwrap_or_else(|| recorder::RecorderBuilder::new().build()),
+            recorder,
+            pre_roll,
+            clock: vis.clock.unwrap_or_default(),
         };
 
+        if let Some(path) = crate::CONFIG.get::<String>("audio.session.record") {
+            recorder::session::record(f.recorder.sample_buffer(), path)
+                .expect("Failed to record session");
+        }
+
         if let Some(num) = vis.async_analyzer {
             if num != 0 {
                 f.detach_analyzer(num);
@@ -85,25 +217,32 @@ where
     pub fn detach_analyzer(&mut self, num: usize) {
         let (mut analyzer, mut info) = self.analyzer.take().unwrap();
         let buffer = self.recorder.sample_buffer().clone();
+        let pre_roll = self.pre_roll;
 
         let conv_time = std::time::Duration::new(0, (1000000000 / num) as u32);
         log::debug!("Conversion Time: {:?}", conv_time);
 
         std::thread::Builder::new()
             .name("analyzer".into())
-            .spawn(move || loop {
-                let start = std::time::Instant::now();
-                analyzer(info.input_buffer(), &buffer);
-                info.publish();
-
-                let now = std::time::Instant::now();
-                let duration = now - start;
-                log::trace!("Conversion Time (real): {:?}", duration);
-
-                if duration < conv_time {
-                    let sleep = conv_time - duration;
-                    log::trace!("Sleeping for {:?}", sleep);
-                    std::thread::sleep(sleep);
+            .spawn(move || {
+                while buffer.stats().pushed < pre_roll {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+
+                loop {
+                    let start = std::time::Instant::now();
+                    analyzer(info.input_buffer(), &buffer);
+                    info.publish();
+
+                    let now = std::time::Instant::now();
+                    let duration = now - start;
+                    log::trace!("Conversion Time (real): {:?}", duration);
+
+                    if duration < conv_time {
+                        let sleep = conv_time - duration;
+                        log::trace!("Sleeping for {:?}", sleep);
+                        std::thread::sleep(sleep);
+                    }
                 }
             })
             .unwrap();
@@ -140,6 +279,44 @@ where
     type Item = Frame<R>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let mut time = match self.visualizer.clock {
+            Clock::Real => crate::helpers::time(self.start_time),
+            Clock::Manual(ref clock) => clock.next()?,
+        };
+        if !self.visualizer.recorder.sync(time) {
+            return None;
+        }
+
+        if self.frame == 0 && self.visualizer.pre_roll > 0 {
+            // Wait for the pre-roll, syncing the recorder in case it only pushes then
+            while self.buffer.stats().pushed < self.visualizer.pre_roll {
+                let pushed = self.buffer.stats().pushed;
+                if let Clock::Real = self.visualizer.clock {
+                    time = crate::helpers::time(self.start_time);
+                }
+                if !self.visualizer.recorder.sync(time) {
+                    return None;
+                }
+                if self.buffer.stats().pushed == pushed {
+                    if let Clock::Manual(_) = self.visualizer.clock {
+                        // Time does not pass while waiting
+                        break;
+                    }
+                    std::thread::sleep(time::Duration::from_millis(1));
+                }
+            }
+
+            // A detached analyzer starts once the pre-roll is buffered, which a manual
+            // clock might not have ticked far enough for yet
+            if self.visualizer.analyzer.is_none()
+                && self.buffer.stats().pushed >= self.visualizer.pre_roll
+            {
+                while !self.visualizer.info.borrow_mut().updated() {
+                    std::thread::sleep(time::Duration::from_millis(1));
+                }
+            }
+        }
+
         if let Some((ref mut analyzer, ref mut info)) = self.visualizer.analyzer {
             analyzer(info.input_buffer(), &self.buffer);
             info.publish();
@@ -149,7 +326,7 @@ where
         self.frame += 1;
 
         Some(Frame {
-            time: crate::helpers::time(self.start_time),
+            time,
             frame,
             info: self.visualizer.info.clone(),
         })
@@ -0,0 +1,176 @@
+//! Onset Envelopes
+/// Shape of an attack-decay-sustain-release envelope
+///
+/// All times are in seconds, the sustain level is held for `hold` seconds before the
+/// release starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adsr {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub hold: f32,
+    pub release: f32,
+}
+
+impl Adsr {
+    /// Return the total duration of the envelope
+    pub fn duration(&self) -> f32 {
+        self.attack + self.decay + self.hold + self.release
+    }
+
+    /// Return the value of the envelope `t` seconds after it was triggered
+    pub fn value(&self, t: f32) -> f32 {
+        let mut t = t;
+        if t < 0.0 {
+            return 0.0;
+        }
+        if t < self.attack {
+            return t / self.attack;
+        }
+        t -= self.attack;
+        if t < self.decay {
+            return 1.0 - (1.0 - self.sustain) * t / self.decay;
+        }
+        t -= self.decay;
+        if t < self.hold {
+            return self.sustain;
+        }
+        t -= self.hold;
+        if t < self.release {
+            return self.sustain * (1.0 - t / self.release);
+        }
+        0.0
+    }
+}
+
+/// Envelope generators triggered by onsets
+///
+/// Each [`trigger`](#method.trigger) starts a new envelope, envelopes overlap freely and
+/// are dropped once they finished.  Turns discrete beats into continuous intensities,
+/// eg. one per particle burst.
+///
+/// # Example
+/// ```
+/// # use vis_core::{analyzer, helpers};
+/// # vis_core::default_config();
+/// # let samples = analyzer::SampleBuffer::new(32000, 8000);
+/// # let mut beat = analyzer::BeatBuilder::new().build();
+/// let mut envelopes = helpers::Envelopes::new(helpers::Adsr {
+///     attack: 0.01,
+///     decay: 0.1,
+///     sustain: 0.5,
+///     hold: 0.1,
+///     release: 0.3,
+/// });
+///
+/// // Once per frame
+/// if beat.detect(&samples) {
+///     envelopes.trigger(1.0);
+/// }
+/// envelopes.update(1.0 / 60.0);
+/// for intensity in envelopes.active_envelopes() {
+///     // Draw a burst
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Envelopes {
+    shape: Adsr,
+    /// Age and peak of each active envelope
+    active: Vec<(f32, f32)>,
+}
+
+impl Envelopes {
+    /// Create a new set of envelopes with the given shape
+    pub fn new(shape: Adsr) -> Envelopes {
+        Envelopes {
+            shape,
+            active: Vec::new(),
+        }
+    }
+
+    /// Start a new envelope peaking at `intensity`
+    pub fn trigger(&mut self, intensity: f32) {
+        self.active.push((0.0, intensity));
+    }
+
+    /// Advance all envelopes by `dt` seconds, dropping finished ones
+    pub fn update(&mut self, dt: f32) {
+        let duration = self.shape.duration();
+        for (age, _) in self.active.iter_mut() {
+            *age += dt;
+        }
+        self.active.retain(|&(age, _)| age < duration);
+    }
+
+    /// Return the number of active envelopes
+    pub fn len(&self) -> usize {
+        self.active.len()
+    }
+
+    /// Return whether no envelope is active
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Return the current value of all active envelopes, oldest first
+    pub fn active_envelopes(&self) -> impl Iterator<Item = f32> + '_ {
+        self.active
+            .iter()
+            .map(move |&(age, intensity)| self.shape.value(age) * intensity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHAPE: Adsr = Adsr {
+        attack: 0.0,
+        decay: 0.2,
+        sustain: 0.5,
+        hold: 0.0,
+        release: 0.2,
+    };
+
+    #[test]
+    fn test_adsr() {
+        let shape = Adsr {
+            attack: 0.1,
+            hold: 0.1,
+            ..SHAPE
+        };
+
+        let close = |t: f32, v: f32| (shape.value(t) - v).abs() < 1e-5;
+        assert!(close(0.05, 0.5));
+        assert!(close(0.1, 1.0));
+        assert!(close(0.2, 0.75));
+        assert!(close(0.35, 0.5));
+        assert!(close(0.5, 0.25));
+        assert_eq!(shape.value(0.6), 0.0);
+    }
+
+    #[test]
+    fn test_overlapping() {
+        let mut envelopes = Envelopes::new(SHAPE);
+
+        envelopes.trigger(1.0);
+        envelopes.update(0.05);
+        envelopes.trigger(0.5);
+        envelopes.update(0.05);
+
+        // Both envelopes decay at the same time
+        let first = envelopes.active_envelopes().collect::<Vec<_>>();
+        assert_eq!(first.len(), 2);
+        envelopes.update(0.05);
+        let second = envelopes.active_envelopes().collect::<Vec<_>>();
+        assert!(second[0] < first[0]);
+        assert!(second[1] < first[1]);
+        assert!(second[1] > 0.0);
+
+        // The older one finishes first
+        envelopes.update(0.27);
+        assert_eq!(envelopes.len(), 1);
+        envelopes.update(0.05);
+        assert!(envelopes.is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+//! Spectrum Equalizer
+use crate::analyzer;
+
+/// A single peaking band of a [`SpectrumEq`](struct.SpectrumEq.html)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqBand {
+    /// Center frequency
+    pub center: analyzer::Frequency,
+    /// Gain at the center frequency in dB
+    pub gain: f32,
+    /// Quality, the higher the narrower the band
+    pub q: f32,
+}
+
+impl EqBand {
+    /// Return the linear gain of this band at `f`
+    ///
+    /// Uses the magnitude response of an analog peaking filter.
+    pub fn gain_at(&self, f: analyzer::Frequency) -> f32 {
+        let a = 10.0f32.powf(self.gain / 40.0);
+        let w = f / self.center;
+        let w2 = (1.0 - w * w).powi(2);
+
+        ((w2 + (w * a / self.q).powi(2)) / (w2 + (w / (a * self.q)).powi(2))).sqrt()
+    }
+}
+
+/// Parametric EQ for shaping the displayed spectrum
+///
+/// Unlike a tilt, each band only affects the range around its center.  Gains apply to
+/// amplitudes, so a `+6 dB` band doubles the buckets at its center.  This is purely for
+/// visual emphasis, the audio is not touched.
+///
+/// # Example
+/// ```
+/// # use vis_core::helpers;
+/// let mut eq = helpers::SpectrumEq::new();
+/// eq.add_band(60.0, 6.0, 1.0).add_band(3000.0, -3.0, 2.0);
+///
+/// let mut magnitudes = vec![1.0; 256];
+/// eq.apply(&mut magnitudes, 15.625);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SpectrumEq {
+    bands: Vec<EqBand>,
+}
+
+impl SpectrumEq {
+    /// Create a new EQ without any bands
+    pub fn new() -> SpectrumEq {
+        Default::default()
+    }
+
+    /// Add a band at `center` with `gain` dB and quality `q`
+    pub fn add_band(&mut self, center: analyzer::Frequency, gain: f32, q: f32) -> &mut SpectrumEq {
+        assert!(
+            center > 0.0 && q > 0.0,
+            "EQ bands need a positive center and Q!"
+        );
+        self.bands.push(EqBand { center, gain, q });
+        self
+    }
+
+    /// Return all bands
+    pub fn bands(&self) -> &[EqBand] {
+        &self.bands
+    }
+
+    /// Return the combined linear gain of all bands at `f`
+    pub fn gain_at(&self, f: analyzer::Frequency) -> f32 {
+        self.bands.iter().map(|b| b.gain_at(f)).product()
+    }
+
+    /// Apply the EQ to magnitudes where bucket `i` has the frequency `i * bin_hz`
+    pub fn apply(&self, magnitudes: &mut [analyzer::SignalStrength], bin_hz: analyzer::Frequency) {
+        for (i, m) in magnitudes.iter_mut().enumerate() {
+            *m *= self.gain_at(i as analyzer::Frequency * bin_hz);
+        }
+    }
+
+    /// Apply the EQ to a spectrum
+    pub fn apply_spectrum<S: analyzer::spectrum::StorageMut>(
+        &self,
+        spectrum: &mut analyzer::Spectrum<S>,
+    ) {
+        for i in 0..spectrum.len() {
+            let gain = self.gain_at(spectrum.id_to_freq(i));
+            spectrum[i] *= gain;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_band() {
+        let mut eq = SpectrumEq::new();
+        eq.add_band(1000.0, 12.0, 2.0);
+
+        // 10 Hz bins
+        let mut magnitudes = vec![1.0; 2001];
+        eq.apply(&mut magnitudes, 10.0);
+
+        let factor = 10.0f32.powf(12.0 / 20.0);
+        assert!((magnitudes[100] - factor).abs() < 1e-4);
+        assert!(magnitudes[90] > 1.0 && magnitudes[90] < factor);
+        assert!((magnitudes[5] - 1.0).abs() < 0.01);
+        assert!((magnitudes[2000] - 1.0).abs() < 0.01);
+
+        // The same on a spectrum
+        let mut spectrum = analyzer::Spectrum::new(vec![1.0; 2001], 0.0, 20000.0);
+        eq.apply_spectrum(&mut spectrum);
+        assert_eq!(spectrum[1000.0], magnitudes[100]);
+    }
+
+    #[test]
+    fn test_cut() {
+        let mut eq = SpectrumEq::new();
+        eq.add_band(100.0, -6.0, 1.0).add_band(100.0, 6.0, 1.0);
+
+        assert!((eq.gain_at(100.0) - 1.0).abs() < 1e-5);
+        assert!((eq.bands()[0].gain_at(100.0) - 0.5).abs() < 0.01);
+    }
+}
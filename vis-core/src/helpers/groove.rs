@@ -0,0 +1,132 @@
+//! Rhythmic Regularity
+use std::collections::VecDeque;
+
+/// Tracks how steady the intervals between onsets are
+///
+/// The beat period is estimated as the median interval.  Each interval is compared to
+/// the nearest multiple of the period, so a missed onset does not count as irregular.
+///
+/// # Example
+/// ```
+/// # use vis_core::helpers;
+/// let mut groove = helpers::Groove::new(16);
+///
+/// for i in 0..8 {
+///     // Time of each detected beat
+///     groove.onset(i as f32 * 0.5);
+/// }
+/// assert_eq!(groove.period(), Some(0.5));
+/// assert_eq!(groove.groove_stability(), 1.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Groove {
+    history: usize,
+    last: Option<f32>,
+    intervals: VecDeque<f32>,
+}
+
+impl Groove {
+    /// Create a tracker remembering the last `history` intervals
+    pub fn new(history: usize) -> Groove {
+        Groove {
+            history,
+            last: None,
+            intervals: VecDeque::with_capacity(history),
+        }
+    }
+
+    /// Register an onset at `time` seconds
+    pub fn onset(&mut self, time: f32) {
+        if let Some(last) = self.last.replace(time) {
+            if self.intervals.len() == self.history {
+                self.intervals.pop_front();
+            }
+            self.intervals.push_back(time - last);
+        }
+    }
+
+    /// Return the remembered intervals, oldest first
+    pub fn intervals(&self) -> impl Iterator<Item = f32> + '_ {
+        self.intervals.iter().cloned()
+    }
+
+    /// Return the estimated beat period in seconds, `None` before the first interval
+    ///
+    /// Starts from the median interval and averages all intervals close to a multiple of
+    /// it.
+    pub fn period(&self) -> Option<f32> {
+        let mut sorted = self.intervals.iter().cloned().collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let median = *sorted.get(sorted.len() / 2)?;
+
+        let (sum, n) = sorted
+            .iter()
+            .map(|&i| (i, (i / median).round().max(1.0)))
+            .filter(|&(i, multiple)| (i / multiple - median).abs() < median / 4.0)
+            .fold((0.0, 0.0), |(sum, n), (i, multiple)| {
+                (sum + i, n + multiple)
+            });
+
+        Some(sum / n)
+    }
+
+    /// Return how steady the tempo is, from `0.0` (no regularity) to `1.0` (periodic)
+    ///
+    /// Based on the RMS deviation of the intervals from multiples of the period, relative
+    /// to the period.  Less than three intervals yield `0.0`.
+    pub fn groove_stability(&self) -> f32 {
+        let period = match self.period() {
+            Some(period) if self.intervals.len() >= 3 && period > 0.0 => period,
+            _ => return 0.0,
+        };
+
+        let deviation = (self
+            .intervals
+            .iter()
+            .map(|&i| {
+                let multiple = (i / period).round().max(1.0);
+                ((i - multiple * period) / period).powi(2)
+            })
+            .sum::<f32>()
+            / self.intervals.len() as f32)
+            .sqrt();
+
+        // Deviations of a quarter period are as irregular as it gets
+        (1.0 - 4.0 * deviation).max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stability() {
+        let mut groove = Groove::new(16);
+        assert_eq!(groove.groove_stability(), 0.0);
+
+        // Periodic, with one missed beat
+        for &t in [0.0, 0.5, 1.0, 1.5, 2.5, 3.0, 3.5].iter() {
+            groove.onset(t);
+        }
+        assert!(groove.groove_stability() > 0.99);
+
+        // Slight jitter keeps it high
+        let mut jittery = Groove::new(16);
+        for i in 0..16 {
+            jittery.onset(i as f32 * 0.5 + if i % 2 == 0 { 0.01 } else { -0.01 });
+        }
+        assert!(jittery.groove_stability() > 0.8);
+
+        // Random intervals between 0.2 and 1.0 seconds
+        let mut random = Groove::new(16);
+        let mut seed = 12345u32;
+        let mut time = 0.0;
+        for _ in 0..17 {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            time += 0.2 + 0.8 * (seed >> 8) as f32 / (1 << 24) as f32;
+            random.onset(time);
+        }
+        assert!(random.groove_stability() < 0.3);
+    }
+}
@@ -0,0 +1,187 @@
+//! Key Detection
+use crate::analyzer;
+use std::collections::VecDeque;
+
+/// Krumhansl-Kessler major key profile, starting at the tonic
+const MAJOR: [f32; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+
+/// Krumhansl-Kessler minor key profile, starting at the tonic
+const MINOR: [f32; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// Mode of a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Mode::Major => write!(f, "major"),
+            Mode::Minor => write!(f, "minor"),
+        }
+    }
+}
+
+fn correlation(a: &[f32; 12], b: &[f32; 12]) -> f32 {
+    let mean_a = a.iter().sum::<f32>() / 12.0;
+    let mean_b = b.iter().sum::<f32>() / 12.0;
+
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b.iter()) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+
+    if var_a == 0.0 || var_b == 0.0 {
+        0.0
+    } else {
+        cov / (var_a * var_b).sqrt()
+    }
+}
+
+/// Key detection from accumulated chroma
+///
+/// Sums the chroma (energy per pitch class, starting at C) of the last `window` updates and
+/// correlates it against the Krumhansl-Schmuckler key profiles of all 24 major and minor
+/// keys.
+///
+/// # Example
+/// ```
+/// # use vis_core::helpers;
+/// let mut key = helpers::KeyDetector::new(64);
+///
+/// // C major triad
+/// key.update(&[1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+/// if let Some((tonic, mode)) = key.key() {
+///     println!("{} {}", tonic, mode);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyDetector {
+    window: usize,
+    history: VecDeque<[f32; 12]>,
+    accumulated: [f32; 12],
+}
+
+impl KeyDetector {
+    /// Create a new key detector accumulating the last `window` updates
+    pub fn new(window: usize) -> KeyDetector {
+        assert!(
+            window > 0,
+            "Key detection needs a window of at least 1 update!"
+        );
+
+        KeyDetector {
+            window,
+            history: VecDeque::with_capacity(window),
+            accumulated: [0.0; 12],
+        }
+    }
+
+    /// Feed the chroma of a new frame
+    pub fn update(&mut self, chroma: &[f32; 12]) {
+        if self.history.len() == self.window {
+            let old = self.history.pop_front().unwrap();
+            for (a, o) in self.accumulated.iter_mut().zip(old.iter()) {
+                *a -= o;
+            }
+        }
+
+        self.history.push_back(*chroma);
+        for (a, c) in self.accumulated.iter_mut().zip(chroma.iter()) {
+            *a += c;
+        }
+    }
+
+    /// Return the chroma accumulated over the window
+    pub fn accumulated(&self) -> &[f32; 12] {
+        &self.accumulated
+    }
+
+    /// Return all 24 keys with their correlation, best match first
+    pub fn candidates(&self) -> Vec<(analyzer::PitchClass, Mode, f32)> {
+        let mut candidates = Vec::with_capacity(24);
+
+        for tonic in 0..12 {
+            for (mode, profile) in [(Mode::Major, &MAJOR), (Mode::Minor, &MINOR)].iter() {
+                let mut rotated = [0.0; 12];
+                for (i, r) in rotated.iter_mut().enumerate() {
+                    *r = profile[(i + 12 - tonic) % 12];
+                }
+
+                candidates.push((
+                    analyzer::PitchClass::new(tonic),
+                    *mode,
+                    correlation(&self.accumulated, &rotated),
+                ));
+            }
+        }
+
+        candidates.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+        candidates
+    }
+
+    /// Return the best matching key
+    ///
+    /// Returns `None` until any chroma was accumulated.
+    pub fn key(&self) -> Option<(analyzer::PitchClass, Mode)> {
+        if self.accumulated.iter().all(|&c| c <= 0.0) {
+            return None;
+        }
+
+        self.candidates()
+            .first()
+            .map(|&(tonic, mode, _)| (tonic, mode))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_c_major() {
+        let mut key = KeyDetector::new(16);
+        assert_eq!(key.key(), None);
+
+        // C major scale up to the octave
+        for &note in [0, 2, 4, 5, 7, 9, 11, 0].iter() {
+            let mut chroma = [0.0; 12];
+            chroma[note] = 1.0;
+            key.update(&chroma);
+        }
+
+        let (tonic, mode) = key.key().unwrap();
+        assert_eq!(format!("{} {}", tonic, mode), "C major");
+
+        let candidates = key.candidates();
+        let rank = |name: &str, mode: Mode| {
+            candidates
+                .iter()
+                .position(|&(t, m, _)| t.name() == name && m == mode)
+                .unwrap()
+        };
+        assert_eq!(rank("C", Mode::Major), 0);
+        assert!(rank("A", Mode::Minor) > 0);
+        assert!(rank("C", Mode::Minor) > rank("A", Mode::Minor));
+    }
+
+    #[test]
+    fn test_window() {
+        let mut key = KeyDetector::new(2);
+        let mut chroma = [0.0; 12];
+        chroma[3] = 1.0;
+
+        for _ in 0..5 {
+            key.update(&chroma);
+        }
+        assert_eq!(key.accumulated()[3], 2.0);
+    }
+}
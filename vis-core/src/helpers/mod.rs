@@ -0,0 +1,892 @@
+use crate::analyzer;
+use std::time;
+
+mod envelope;
+mod eq;
+mod groove;
+mod key;
+mod modulation;
+mod pitch;
+pub mod signal;
+mod spectrogram;
+
+#[doc(inline)]
+pub use self::envelope::{Adsr, Envelopes};
+#[doc(inline)]
+pub use self::eq::{EqBand, SpectrumEq};
+#[doc(inline)]
+pub use self::groove::Groove;
+#[doc(inline)]
+pub use self::key::{KeyDetector, Mode};
+#[doc(inline)]
+pub use self::modulation::ModulationSpectrum;
+#[doc(inline)]
+pub use self::pitch::PitchTracker;
+#[doc(inline)]
+pub use self::spectrogram::DisplaySpectrogram;
+
+pub fn time(start: time::Instant) -> f32 {
+    let elapsed = time::Instant::now() - start;
+
+    elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 * 1e-9
+}
+
+/// Frequency weighting curves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weighting {
+    /// All frequencies are weighted equally
+    Flat,
+    /// Emphasize bass, rolling off above roughly 150 Hz
+    Bass,
+    /// A-weighting, approximating the ear's sensitivity
+    ///
+    /// See [`weighting::a_gain`](../analyzer/weighting/fn.a_gain.html).
+    A,
+}
+
+impl Weighting {
+    /// Return the gain of this curve at frequency `f`
+    pub fn gain(self, f: analyzer::Frequency) -> f32 {
+        match self {
+            Weighting::Flat => 1.0,
+            Weighting::Bass => 1.0 / (1.0 + (f / 150.0).powi(2)),
+            Weighting::A => analyzer::weighting::a_gain(f),
+        }
+    }
+}
+
+/// Exponentially smoothed value
+///
+/// `factor` is the weight of the previous value, `0.0` disables smoothing.
+#[derive(Debug, Clone)]
+pub struct Smoothing {
+    factor: f32,
+    value: f32,
+}
+
+impl Smoothing {
+    /// Create a new smoothed value starting at `0.0`
+    pub fn new(factor: f32) -> Smoothing {
+        Smoothing { factor, value: 0.0 }
+    }
+
+    /// Feed a new value and return the smoothed one
+    pub fn update(&mut self, value: f32) -> f32 {
+        self.value = self.value * self.factor + value * (1.0 - self.factor);
+        self.value
+    }
+
+    /// Return the current smoothed value
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+}
+
+/// Attack preserving smoothing with look-ahead
+///
+/// Smooths releases exponentially like [`Smoothing`](struct.Smoothing.html) while attacks
+/// pass through at full amplitude.  Frames are delayed by `lookahead` updates, a release
+/// is held while a value at least as loud is coming up, so short dips do not flicker.
+/// Attacks only ever use the delayed value, so there is no pre-ring.
+///
+/// # Example
+/// ```
+/// # use vis_core::helpers;
+/// let mut smoothing = helpers::TransientSmoothing::new(0.8, 2);
+///
+/// let smoothed = smoothing.update(&[0.0, 1.0, 0.5]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TransientSmoothing {
+    release: f32,
+    lookahead: usize,
+    frames: std::collections::VecDeque<Vec<f32>>,
+    output: Vec<f32>,
+}
+
+impl TransientSmoothing {
+    /// Create a new smoothing with release factor `release` and `lookahead` frames of delay
+    pub fn new(release: f32, lookahead: usize) -> TransientSmoothing {
+        TransientSmoothing {
+            release,
+            lookahead,
+            frames: std::collections::VecDeque::with_capacity(lookahead + 1),
+            output: Vec::new(),
+        }
+    }
+
+    /// Return the look-ahead in frames, which is also the added latency
+    pub fn lookahead(&self) -> usize {
+        self.lookahead
+    }
+
+    /// Feed a new frame and return the smoothed frame from `lookahead` updates ago
+    ///
+    /// All frames should have the same length, the output is zero until the first frame
+    /// made it through the look-ahead.
+    pub fn update(&mut self, values: &[f32]) -> &[f32] {
+        self.output.resize(values.len(), 0.0);
+        self.frames.push_back(values.to_vec());
+        if self.frames.len() <= self.lookahead {
+            return &self.output;
+        }
+
+        let delayed = self.frames.pop_front().unwrap();
+        for (i, (out, &value)) in self.output.iter_mut().zip(delayed.iter()).enumerate() {
+            let upcoming = self
+                .frames
+                .iter()
+                .filter_map(|f| f.get(i))
+                .fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+
+            *out = if value >= *out {
+                value
+            } else if upcoming >= *out {
+                *out
+            } else {
+                *out * self.release + value * (1.0 - self.release)
+            };
+        }
+
+        &self.output
+    }
+}
+
+/// Compute a single smoothed energy value from a spectrum
+///
+/// Buckets are weighted with `weighting`, summed and fed into `smoothing`, which keeps
+/// the state between calls.
+///
+/// # Example
+/// ```
+/// # use vis_core::{analyzer, helpers};
+/// let spectrum = analyzer::Spectrum::new(vec![1.0; 100], 0.0, 4000.0);
+/// let mut smoothing = helpers::Smoothing::new(0.8);
+///
+/// let energy = helpers::energy_scalar(&spectrum, helpers::Weighting::Bass, &mut smoothing);
+/// ```
+pub fn energy_scalar<S: analyzer::spectrum::Storage>(
+    spectrum: &analyzer::Spectrum<S>,
+    weighting: Weighting,
+    smoothing: &mut Smoothing,
+) -> f32 {
+    let energy = spectrum
+        .iter()
+        .enumerate()
+        .map(|(i, v)| v * weighting.gain(spectrum.id_to_freq(i)))
+        .sum();
+
+    smoothing.update(energy)
+}
+
+/// Perceptual color axes of a sound, see [`color_features`](fn.color_features.html)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ColorFeatures {
+    /// Spectral centroid on a logarithmic scale from 20 Hz (`0.0`) to the highest
+    /// frequency of the spectrum (`1.0`)
+    pub brightness: f32,
+    /// Share of the energy below 250 Hz, `1.0` for pure bass
+    pub warmth: f32,
+}
+
+/// Crossover between bass and treble for [`ColorFeatures::warmth`](struct.ColorFeatures.html#structfield.warmth)
+const WARMTH_CROSSOVER: analyzer::Frequency = 250.0;
+
+/// Compute brightness and warmth of a spectrum for color mapping
+///
+/// Both are normalized to `0.0..=1.0`, a silent spectrum yields `0.0` for both.
+///
+/// # Example
+/// ```
+/// # use vis_core::{analyzer, helpers};
+/// let mut spectrum = analyzer::Spectrum::new(vec![0.0; 401], 0.0, 4000.0);
+/// spectrum[80.0] = 1.0;
+///
+/// let color = helpers::color_features(&spectrum);
+/// assert_eq!(color.warmth, 1.0);
+/// ```
+pub fn color_features<S: analyzer::spectrum::Storage>(
+    spectrum: &analyzer::Spectrum<S>,
+) -> ColorFeatures {
+    let (total, weighted, bass) =
+        spectrum
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0, 0.0), |(total, weighted, bass), (i, &v)| {
+                let f = spectrum.id_to_freq(i);
+                (
+                    total + v,
+                    weighted + v * f,
+                    if f < WARMTH_CROSSOVER { bass + v } else { bass },
+                )
+            });
+
+    if total <= 0.0 {
+        return ColorFeatures::default();
+    }
+
+    let centroid = (weighted / total).max(20.0);
+    ColorFeatures {
+        brightness: ((centroid / 20.0).ln() / (spectrum.highest() / 20.0).ln()).clamp(0.0, 1.0),
+        warmth: bass / total,
+    }
+}
+
+/// Fraction of the strongest magnitude a bin needs to count for [`spectral_fullness`](fn.spectral_fullness.html)
+const FULLNESS_THRESHOLD: f32 = 0.1;
+
+/// Compute how busy a spectrum is, from `0.0` (sparse) to `1.0` (full)
+///
+/// This is the fraction of bins whose magnitude is within 20 dB of the strongest bin, eg.
+/// from [`FourierAnalyzer::magnitudes`](../analyzer/fourier/struct.FourierAnalyzer.html#method.magnitudes).
+/// A solo instrument reads low, a dense mix or noise high.  Silence yields `0.0`.
+///
+/// # Example
+/// ```
+/// # use vis_core::helpers;
+/// assert_eq!(helpers::spectral_fullness(&[0.0, 1.0, 0.5, 0.01]), 0.5);
+/// ```
+pub fn spectral_fullness(mags: &[f32]) -> f32 {
+    let max = mags.iter().cloned().fold(0.0, f32::max);
+    if max <= 0.0 {
+        return 0.0;
+    }
+
+    let threshold = max * FULLNESS_THRESHOLD;
+    mags.iter().filter(|&&m| m >= threshold).count() as f32 / mags.len() as f32
+}
+
+/// Compute the spectral slope in dB per octave
+///
+/// Fits a line to `10 * log10(level)` over the logarithmic frequency, so the buckets are
+/// expected to hold powers, eg. from
+/// [`MagnitudeMode::Squared`](../analyzer/fourier/enum.MagnitudeMode.html#variant.Squared).
+/// White noise then has a slope of about `0.0`, pink noise about `-3.0`; plain magnitudes
+/// halve the slope.  Empty buckets and buckets at or below 0 Hz are skipped, if less than
+/// two buckets remain the slope is `0.0`.
+///
+/// # Example
+/// ```
+/// # use vis_core::{analyzer, helpers};
+/// let spectrum = analyzer::Spectrum::new(vec![1.0; 100], 10.0, 1000.0);
+///
+/// assert_eq!(helpers::spectral_slope(&spectrum), 0.0);
+/// ```
+pub fn spectral_slope<S: analyzer::spectrum::Storage>(spectrum: &analyzer::Spectrum<S>) -> f32 {
+    let points = spectrum
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| (spectrum.id_to_freq(i), v))
+        .filter(|&(f, v)| f > 0.0 && v > 0.0)
+        .map(|(f, v)| (f.log2(), 10.0 * v.log10()));
+
+    let (n, sx, sy, sxx, sxy) = points.fold(
+        (0.0, 0.0, 0.0, 0.0, 0.0),
+        |(n, sx, sy, sxx, sxy), (x, y)| (n + 1.0, sx + x, sy + y, sxx + x * x, sxy + x * y),
+    );
+
+    let denominator = n * sxx - sx * sx;
+    if n < 2.0 || denominator == 0.0 {
+        0.0
+    } else {
+        (n * sxy - sx * sy) / denominator
+    }
+}
+
+/// Shape of a [`beat_flash`](fn.beat_flash.html) envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashShape {
+    /// Falls off linearly
+    Linear,
+    /// Falls off exponentially, bright for the shortest time
+    Exponential,
+    /// Falls off along the right half of a gaussian bell, staying bright the longest
+    Gaussian,
+}
+
+/// Duration of a flash in seconds, shortened at tempos with shorter beats
+const FLASH_LENGTH: f32 = 0.25;
+
+/// Compute a flash envelope synced to a tempo
+///
+/// `phase` is the position in beats, its integer part is ignored.  The envelope is `1.0`
+/// exactly on each beat and decays to (almost) `0.0` within a quarter of a second or one
+/// beat, whatever is shorter.  Without a tempo, ie. if `bpm` is not a positive number,
+/// this is always `0.0`.
+///
+/// # Example
+/// ```
+/// # use vis_core::helpers;
+/// let bpm = 120.0;
+/// let time = 0.1;
+///
+/// let brightness = helpers::beat_flash(bpm, time * bpm / 60.0, helpers::FlashShape::Exponential);
+/// ```
+pub fn beat_flash(bpm: f32, phase: f32, shape: FlashShape) -> f32 {
+    if !bpm.is_finite() || bpm <= 0.0 {
+        return 0.0;
+    }
+
+    let period = 60.0 / bpm;
+    let length = period.min(FLASH_LENGTH);
+    let x = phase.rem_euclid(1.0) * period / length;
+
+    match shape {
+        FlashShape::Linear => (1.0 - x).max(0.0),
+        FlashShape::Exponential => (-5.0 * x).exp(),
+        FlashShape::Gaussian => (-(3.0 * x).powi(2) / 2.0).exp(),
+    }
+}
+
+/// Compute the stereo balance over the last `window` seconds
+///
+/// Returns `-1.0` for a signal only on the left channel, `+1.0` for one only on the right
+/// and `0.0` for a centered signal or silence.  Based on the ratio of per-channel RMS.
+///
+/// # Example
+/// ```
+/// # use vis_core::{analyzer, helpers};
+/// let buffer = analyzer::SampleBuffer::new(800, 8000);
+/// buffer.push(&[[1.0, 0.0]; 800]);
+///
+/// assert_eq!(helpers::balance(&buffer, 0.1), -1.0);
+/// ```
+pub fn balance(buf: &analyzer::SampleBuffer, window: f32) -> f32 {
+    let size = ((buf.rate() as f32 * window) as usize).min(buf.size());
+
+    let (ll, rr) = buf
+        .iter(size, 1)
+        .fold((0.0, 0.0), |(ll, rr), [l, r]| (ll + l * l, rr + r * r));
+    let (l, r) = (ll.sqrt(), rr.sqrt());
+
+    if l + r == 0.0 {
+        0.0
+    } else {
+        analyzer::samples::to_f32((r - l) / (r + l))
+    }
+}
+
+/// Estimate the delay of `b` relative to `a` in samples
+///
+/// Cross-correlates the mono downmix of both buffers in the frequency domain and returns
+/// the lag of the peak, searched within `-max_lag..=max_lag`.  A positive delay means `b`
+/// lags behind `a`, eg. a measurement mic further away from the source than the
+/// reference.  Both buffers are assumed to have the same rate, only their common length
+/// is used.  Returns `0` if either buffer is silent.
+///
+/// # Example
+/// ```
+/// # use vis_core::{analyzer, helpers};
+/// let noise = helpers::signal::white_noise(8000, 0.1, 1);
+/// let reference = analyzer::SampleBuffer::new(800, 8000);
+/// let measurement = analyzer::SampleBuffer::new(800, 8000);
+/// reference.push(&noise);
+/// measurement.push(&[[0.0; 2]; 12]);
+/// measurement.push(&noise[..788]);
+///
+/// assert_eq!(helpers::delay_samples(&reference, &measurement, 100), 12);
+/// ```
+pub fn delay_samples(
+    a: &analyzer::SampleBuffer,
+    b: &analyzer::SampleBuffer,
+    max_lag: usize,
+) -> i32 {
+    let len = a.size().min(b.size());
+    let max_lag = max_lag.min(len.saturating_sub(1));
+    // Zero padded, so the circular correlation does not wrap around
+    let size = (len + max_lag).next_power_of_two();
+    let (forward, inverse) = analyzer::fourier::with_planner(|planner| {
+        (
+            planner.plan_fft_forward(size),
+            planner.plan_fft_inverse(size),
+        )
+    });
+
+    let spectrum = |buf: &analyzer::SampleBuffer| {
+        let samples = buf.mono(len);
+        let mean = samples.iter().sum::<analyzer::Sample>() / len as analyzer::Sample;
+        let mut signal = samples
+            .iter()
+            .map(|s| s - mean)
+            .chain(std::iter::repeat(0.0))
+            .take(size)
+            .collect::<Vec<_>>();
+        let mut spectrum = forward.make_output_vec();
+        forward.process(&mut signal, &mut spectrum).unwrap();
+        spectrum
+    };
+
+    let mut correlation = spectrum(a)
+        .iter()
+        .zip(spectrum(b).iter())
+        .map(|(x, y)| x.conj() * y)
+        .collect::<Vec<_>>();
+    // Real inputs have real DC and Nyquist terms, up to rounding
+    correlation[0].im = 0.0;
+    if let Some(nyquist) = correlation.last_mut() {
+        nyquist.im = 0.0;
+    }
+    let mut lags = inverse.make_output_vec();
+    inverse.process(&mut correlation, &mut lags).unwrap();
+
+    // Negative lags are at the end of the correlation
+    let (lag, peak) = (-(max_lag as i32)..=max_lag as i32)
+        .map(|lag| (lag, lags[lag.rem_euclid(size as i32) as usize]))
+        .fold((0, 0.0), |best, c| if c.1 > best.1 { c } else { best });
+
+    if peak > 0.0 {
+        lag
+    } else {
+        0
+    }
+}
+
+/// Quantize magnitudes (or bars) into a row of bytes, eg. for uploading a 1D texture
+///
+/// Values are mapped linearly from `range` to `0 - 255` and clamped, `None` uses the
+/// minimum and maximum of `values`.  If `out` has a different length than `values`, the
+/// nearest value is used for each byte.
+///
+/// # Example
+/// ```
+/// # use vis_core::helpers;
+/// let mut row = [0; 4];
+/// helpers::to_u8_row(&[0.0, 0.5, 1.0, 2.0], Some((0.0, 1.0)), &mut row);
+///
+/// assert_eq!(row, [0, 128, 255, 255]);
+/// ```
+pub fn to_u8_row(values: &[f32], range: Option<(f32, f32)>, out: &mut [u8]) {
+    if values.is_empty() {
+        out.iter_mut().for_each(|o| *o = 0);
+        return;
+    }
+
+    let (min, max) = range.unwrap_or_else(|| {
+        values
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| {
+                (min.min(v), max.max(v))
+            })
+    });
+    let scale = if max > min { 255.0 / (max - min) } else { 0.0 };
+
+    let len = out.len();
+    for (i, o) in out.iter_mut().enumerate() {
+        let v = values[i * values.len() / len];
+        *o = ((v - min) * scale).round().clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Render bars as a multi-line ASCII chart, eg. for checking the pipeline over SSH
+///
+/// Each bar is one column of `#`, scaled so the highest bar spans all `height` rows.
+/// Returns exactly `height` lines separated by newlines, the top row first.
+///
+/// # Example
+/// ```
+/// # use vis_core::helpers;
+/// let chart = helpers::ascii_spectrum(&[0.5, 1.0, 0.0, 0.25], 4);
+///
+/// assert_eq!(chart, " #  \n #  \n##  \n## #");
+/// ```
+pub fn ascii_spectrum(bars: &[f32], height: usize) -> String {
+    let max = bars.iter().cloned().fold(0.0, f32::max);
+    let scale = if max > 0.0 { height as f32 / max } else { 0.0 };
+    let heights = bars
+        .iter()
+        .map(|b| (b * scale).round() as usize)
+        .collect::<Vec<_>>();
+
+    (0..height)
+        .rev()
+        .map(|row| {
+            heights
+                .iter()
+                .map(|&h| if h > row { '#' } else { ' ' })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Convert a frequency to the Bark scale
+pub fn bark(f: analyzer::Frequency) -> f32 {
+    13.0 * (0.00076 * f).atan() + 3.5 * (f / 7500.0).powi(2).atan()
+}
+
+/// Remove buckets which are masked by stronger neighbours
+///
+/// Uses a simplified spreading function on the Bark scale: a masker hides weaker
+/// buckets whose level is below its own level minus 6 dB, falling off by a further
+/// 27 dB per Bark towards lower and 12 dB per Bark towards higher frequencies.  The
+/// thresholds are in power dB, so pass a spectrum analyzed with
+/// [`MagnitudeMode::Squared`](../analyzer/fourier/enum.MagnitudeMode.html#variant.Squared);
+/// with plain magnitudes masking reaches twice as far.  Masked buckets are set to `0.0`,
+/// all others keep their level.
+///
+/// # Example
+/// ```
+/// # use vis_core::{analyzer, helpers};
+/// let mut spectrum = analyzer::Spectrum::new(vec![0.0; 401], 0.0, 4000.0);
+/// spectrum[1000.0] = 1.0;
+/// spectrum[1050.0] = 0.01;
+///
+/// let masked = helpers::masking(&spectrum);
+/// assert_eq!(masked[1050.0], 0.0);
+/// ```
+pub fn masking<S: analyzer::spectrum::Storage>(
+    spectrum: &analyzer::Spectrum<S>,
+) -> analyzer::Spectrum<Vec<analyzer::SignalStrength>> {
+    let barks = (0..spectrum.len())
+        .map(|i| bark(spectrum.id_to_freq(i)))
+        .collect::<Vec<_>>();
+    let levels = spectrum
+        .iter()
+        .map(|&level| 10.0 * level.log10())
+        .collect::<Vec<_>>();
+
+    // The threshold falls off linearly per Bark, so the strongest masker below (above)
+    // each bucket is carried along in one sweep upwards (downwards)
+    let mut threshold = vec![analyzer::SignalStrength::NEG_INFINITY; levels.len()];
+    let mut carried = analyzer::SignalStrength::NEG_INFINITY;
+    for j in 1..levels.len() {
+        carried = carried.max(levels[j - 1] - 6.0) - 12.0 * (barks[j] - barks[j - 1]);
+        threshold[j] = carried;
+    }
+    carried = analyzer::SignalStrength::NEG_INFINITY;
+    for j in (0..levels.len().saturating_sub(1)).rev() {
+        carried = carried.max(levels[j + 1] - 6.0) - 27.0 * (barks[j + 1] - barks[j]);
+        threshold[j] = threshold[j].max(carried);
+    }
+
+    let buckets = spectrum
+        .iter()
+        .zip(levels.iter().zip(threshold.iter()))
+        .map(|(&level, (db, threshold))| if db < threshold { 0.0 } else { level })
+        .collect();
+
+    analyzer::Spectrum::new(buckets, spectrum.lowest(), spectrum.highest())
+}
+
+/// Compare a spectrum against a reference template
+///
+/// Returns the cosine similarity of both: `1.0` if the spectrum has the same shape as the
+/// template (regardless of its level), `0.0` if they share no energy at all.  The template
+/// needs one value per bucket, eg. captured from a spectrum of the sound to detect.
+///
+/// # Example
+/// ```
+/// # use vis_core::{analyzer, helpers};
+/// let mut snare = analyzer::Spectrum::new(vec![0.0; 100], 0.0, 4000.0);
+/// snare[200.0] = 1.0;
+/// snare[3000.0] = 0.5;
+/// let template = snare.iter().cloned().collect::<Vec<_>>();
+///
+/// let similarity = helpers::template_match(&snare, &template);
+/// assert!((similarity - 1.0).abs() < 1e-6);
+/// ```
+pub fn template_match<S: analyzer::spectrum::Storage>(
+    spectrum: &analyzer::Spectrum<S>,
+    template: &[analyzer::SignalStrength],
+) -> f32 {
+    assert_eq!(
+        spectrum.len(),
+        template.len(),
+        "Template does not match the spectrum's size!"
+    );
+
+    let (dot, ss, tt) = spectrum
+        .iter()
+        .zip(template.iter())
+        .fold((0.0, 0.0, 0.0), |(dot, ss, tt), (s, t)| {
+            (dot + s * t, ss + s * s, tt + t * t)
+        });
+
+    if ss == 0.0 || tt == 0.0 {
+        0.0
+    } else {
+        dot / (ss * tt).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighting() {
+        assert!((Weighting::A.gain(1000.0) - 1.0).abs() < 0.01);
+        assert!(Weighting::A.gain(50.0) < 0.1);
+        assert_eq!(Weighting::Flat.gain(50.0), Weighting::Flat.gain(8000.0));
+    }
+
+    #[test]
+    fn test_energy_bass() {
+        let mut kick = analyzer::Spectrum::new(vec![0.0; 201], 0.0, 10000.0);
+        let mut hihat = kick.clone();
+        kick[60.0] = 1.0;
+        hihat[8000.0] = 1.0;
+
+        let energy =
+            |s: &analyzer::Spectrum<Vec<f32>>, w| energy_scalar(s, w, &mut Smoothing::new(0.0));
+
+        assert_eq!(
+            energy(&kick, Weighting::Flat),
+            energy(&hihat, Weighting::Flat)
+        );
+        assert!(energy(&kick, Weighting::Bass) > 100.0 * energy(&hihat, Weighting::Bass));
+    }
+
+    #[test]
+    fn test_masking() {
+        let mut spectrum = analyzer::Spectrum::new(vec![0.0; 401], 0.0, 4000.0);
+        spectrum[1000.0] = 1.0;
+        spectrum[1050.0] = 0.01;
+        spectrum[950.0] = 0.01;
+        spectrum[3000.0] = 0.01;
+
+        let masked = masking(&spectrum);
+
+        assert_eq!(masked[1000.0], 1.0);
+        assert_eq!(masked[1050.0], 0.0);
+        assert_eq!(masked[950.0], 0.0);
+        assert_eq!(masked[3000.0], 0.01);
+    }
+
+    #[test]
+    fn test_template_match() {
+        let mut spectrum = analyzer::Spectrum::new(vec![0.0; 100], 0.0, 4000.0);
+        spectrum[200.0] = 1.0;
+        spectrum[1000.0] = 0.3;
+
+        let template = spectrum.iter().map(|v| v * 4.0).collect::<Vec<_>>();
+        assert!((template_match(&spectrum, &template) - 1.0).abs() < 1e-6);
+
+        let mut orthogonal = vec![0.0; 100];
+        orthogonal[50] = 1.0;
+        assert!(template_match(&spectrum, &orthogonal).abs() < 1e-6);
+
+        assert_eq!(template_match(&spectrum, &[0.0; 100]), 0.0);
+    }
+
+    #[test]
+    fn test_color_features() {
+        let mut spectrum = analyzer::Spectrum::new(vec![0.0; 401], 0.0, 8000.0);
+        assert_eq!(color_features(&spectrum), ColorFeatures::default());
+
+        spectrum[100.0] = 1.0;
+        spectrum[2000.0] = 1.0;
+        let base = color_features(&spectrum);
+
+        let mut bright = spectrum.clone();
+        bright[6000.0] = 2.0;
+        let mut bassy = spectrum.clone();
+        bassy[60.0] = 2.0;
+
+        assert!(color_features(&bright).brightness > base.brightness);
+        assert!(color_features(&bright).warmth < base.warmth);
+        assert!(color_features(&bassy).warmth > base.warmth);
+        assert!(color_features(&bassy).brightness < base.brightness);
+
+        for color in [base, color_features(&bright), color_features(&bassy)].iter() {
+            assert!(color.brightness >= 0.0 && color.brightness <= 1.0);
+            assert!(color.warmth >= 0.0 && color.warmth <= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_spectral_slope() {
+        crate::test_config();
+        let mut analyzer = analyzer::FourierBuilder::new()
+            .length(512)
+            .window(analyzer::window::hanning)
+            .downsample(1)
+            .rate(8000)
+            .magnitude_mode(analyzer::MagnitudeMode::Squared)
+            .plan();
+
+        let mut slope = |noise: Vec<[analyzer::Sample; 2]>| {
+            let buf = analyzer::SampleBuffer::new(512, 8000);
+            let mut sum =
+                analyzer::Spectrum::new(vec![0.0; 256], analyzer.lowest(), analyzer.highest());
+            for block in noise.chunks(512) {
+                buf.push(block);
+                analyzer.analyze(&buf);
+                let average = analyzer.average();
+                for (s, v) in sum.iter_mut().zip(average.iter()) {
+                    *s += v;
+                }
+            }
+            spectral_slope(&sum)
+        };
+
+        let white = slope(signal::white_noise(8000, 4.0, 3));
+        let pink = slope(signal::pink_noise(8000, 4.0, 3));
+
+        assert!(white.abs() < 1.0, "{}", white);
+        assert!(pink < -2.0 && pink > -4.0, "{}", pink);
+
+        let mut gaps = analyzer::Spectrum::new(vec![1.0; 100], 0.0, 1000.0);
+        gaps[50] = 0.0;
+        assert_eq!(spectral_slope(&gaps), 0.0);
+    }
+
+    #[test]
+    fn test_beat_flash() {
+        for shape in [
+            FlashShape::Linear,
+            FlashShape::Exponential,
+            FlashShape::Gaussian,
+        ]
+        .iter()
+        {
+            for beat in 0..4 {
+                let beat = beat as f32;
+                assert_eq!(beat_flash(120.0, beat, *shape), 1.0);
+                assert!(beat_flash(120.0, beat + 0.05, *shape) < 1.0);
+                assert!(
+                    beat_flash(120.0, beat + 0.05, *shape) > beat_flash(120.0, beat + 0.2, *shape)
+                );
+                assert!(beat_flash(120.0, beat + 0.5, *shape) < 0.02, "{:?}", shape);
+                assert!(beat_flash(120.0, beat + 0.99, *shape) < 0.02, "{:?}", shape);
+            }
+
+            // No flash without a tempo
+            for bpm in [0.0, -120.0, f32::NAN, f32::INFINITY].iter() {
+                assert_eq!(beat_flash(*bpm, 0.0, *shape), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_smoothing() {
+        let mut smoothing = Smoothing::new(0.5);
+
+        assert_eq!(smoothing.update(1.0), 0.5);
+        assert_eq!(smoothing.update(1.0), 0.75);
+        assert_eq!(smoothing.value(), 0.75);
+    }
+
+    #[test]
+    fn test_transient_smoothing() {
+        let mut smoothing = TransientSmoothing::new(0.5, 2);
+        let input = [0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+
+        let output = input
+            .iter()
+            .map(|&v| smoothing.update(&[v])[0])
+            .collect::<Vec<_>>();
+
+        // No pre-ring before the delayed onset, which arrives at full amplitude
+        assert_eq!(&output[..4], &[0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(output[4], 1.0);
+        // Smooth release
+        assert_eq!(&output[5..], &[0.5, 0.25, 0.125]);
+
+        // A short dip is held
+        let mut smoothing = TransientSmoothing::new(0.5, 2);
+        let output = [1.0, 1.0, 0.0, 1.0, 1.0, 1.0]
+            .iter()
+            .map(|&v| smoothing.update(&[v])[0])
+            .collect::<Vec<_>>();
+        assert_eq!(&output[2..], &[1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_balance() {
+        let buf = analyzer::SampleBuffer::new(800, 8000);
+        assert_eq!(balance(&buf, 0.1), 0.0);
+
+        let tone = signal::sine(8000, 440.0, 0.1);
+        buf.push(&tone.iter().map(|[l, _]| [*l, 0.0]).collect::<Vec<_>>());
+        assert_eq!(balance(&buf, 0.1), -1.0);
+
+        buf.push(&tone.iter().map(|[_, r]| [0.0, *r]).collect::<Vec<_>>());
+        assert_eq!(balance(&buf, 0.1), 1.0);
+
+        buf.push(&tone);
+        assert!(balance(&buf, 0.1).abs() < 1e-6);
+
+        buf.push(&tone.iter().map(|[l, r]| [*l, r * 0.5]).collect::<Vec<_>>());
+        assert!((balance(&buf, 0.1) + 1.0 / 3.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_delay_samples() {
+        let noise = signal::white_noise(8000, 0.5, 3);
+        let a = analyzer::SampleBuffer::new(2048, 8000);
+        let b = analyzer::SampleBuffer::new(2048, 8000);
+        assert_eq!(delay_samples(&a, &b, 100), 0);
+
+        // b is a delayed and attenuated copy of a
+        a.push(&noise[100..]);
+        b.push(
+            &noise[100 - 37..noise.len() - 37]
+                .iter()
+                .map(|[l, r]| [l * 0.5, r * 0.5])
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(delay_samples(&a, &b, 100), 37);
+        assert_eq!(delay_samples(&b, &a, 100), -37);
+
+        // Delays beyond max_lag are not found
+        assert_ne!(delay_samples(&a, &b, 20), 37);
+    }
+
+    #[test]
+    fn test_spectral_fullness() {
+        crate::test_config();
+        let mut analyzer = analyzer::FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .downsample(1)
+            .plan();
+        let buf = analyzer::SampleBuffer::new(256, 8000);
+
+        // 1000 Hz falls onto bin 32, so there is no leakage
+        buf.push(&signal::sine(8000, 1000.0, 0.032));
+        analyzer.analyze(&buf);
+        assert!(spectral_fullness(&analyzer.magnitudes(0)) < 0.05);
+
+        buf.push(&signal::white_noise(8000, 0.032, 5));
+        analyzer.analyze(&buf);
+        assert!(spectral_fullness(&analyzer.magnitudes(0)) > 0.8);
+
+        assert_eq!(spectral_fullness(&[0.0; 4]), 0.0);
+    }
+
+    #[test]
+    fn test_ascii_spectrum() {
+        let chart = ascii_spectrum(&[0.1, 0.2, 2.0, 0.2, 0.1], 10);
+        let lines = chart.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 10);
+        assert!(lines.iter().all(|l| l.len() == 5));
+
+        let heights = (0..5)
+            .map(|i| lines.iter().filter(|l| l.as_bytes()[i] == b'#').count())
+            .collect::<Vec<_>>();
+        assert_eq!(heights, [1, 1, 10, 1, 1]);
+
+        // Silence renders blank
+        assert_eq!(ascii_spectrum(&[0.0; 3], 2), "   \n   ");
+    }
+
+    #[test]
+    fn test_to_u8_row() {
+        let values = [3.0, -1.0, 0.0, 7.0, 5.0];
+        let mut row = [0; 5];
+        to_u8_row(&values, None, &mut row);
+        assert_eq!(row[1], 0);
+        assert_eq!(row[3], 255);
+        assert_eq!(row[0], 128);
+
+        // Resampled to a wider texture
+        let mut wide = [0; 10];
+        to_u8_row(&values, None, &mut wide);
+        assert_eq!(&wide[..4], &[row[0], row[0], row[1], row[1]]);
+
+        // Constant input
+        to_u8_row(&[2.0; 5], None, &mut row);
+        assert_eq!(row, [0; 5]);
+    }
+}
@@ -0,0 +1,210 @@
+//! Modulation Spectrum
+use crate::analyzer;
+use std::collections::VecDeque;
+
+/// Spectrum of a band's energy envelope
+///
+/// Tracks the energy of a frequency band over time and transforms the envelope, which
+/// reveals modulation rates like tremolo or rhythmic patterns.  The resulting spectrum
+/// spans `0 Hz` to half the update rate.
+///
+/// # Example
+/// ```
+/// # use vis_core::{analyzer, helpers};
+/// let spectrum = analyzer::Spectrum::new(vec![1.0; 100], 0.0, 4000.0);
+/// let mut modulation = helpers::ModulationSpectrum::new((50.0, 200.0), 128);
+///
+/// // Once per frame, with the time since the last frame
+/// modulation.update(&spectrum, 1.0 / 60.0);
+/// let rates = modulation.spectrum();
+/// ```
+pub struct ModulationSpectrum {
+    band: (analyzer::Frequency, analyzer::Frequency),
+    history: usize,
+    envelope: VecDeque<(analyzer::SignalStrength, f32)>,
+    duration: f32,
+
+    window: Vec<analyzer::Sample>,
+    fft: std::sync::Arc<dyn rustfft::Fft<f32>>,
+    buffer: Vec<rustfft::num_complex::Complex<f32>>,
+    spectrum: analyzer::Spectrum<Vec<analyzer::SignalStrength>>,
+}
+
+impl std::fmt::Debug for ModulationSpectrum {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "ModulationSpectrum {{ band: {:?}, history: {:?} }}",
+            self.band, self.history,
+        )
+    }
+}
+
+impl ModulationSpectrum {
+    /// Create a new modulation spectrum for `band` over the last `history` updates
+    pub fn new(
+        band: (analyzer::Frequency, analyzer::Frequency),
+        history: usize,
+    ) -> ModulationSpectrum {
+        assert!(history >= 2, "Modulation history needs at least 2 updates!");
+
+        ModulationSpectrum {
+            band,
+            history,
+            envelope: VecDeque::with_capacity(history),
+            duration: 0.0,
+
+            window: analyzer::window::hanning(history),
+            fft: rustfft::FftPlanner::new().plan_fft_forward(history),
+            buffer: Vec::with_capacity(history),
+            spectrum: analyzer::Spectrum::new(vec![0.0; history / 2 + 1], 0.0, 1.0),
+        }
+    }
+
+    /// Create a new modulation spectrum, failing if it would need more than `budget` bytes
+    pub fn with_budget(
+        band: (analyzer::Frequency, analyzer::Frequency),
+        history: usize,
+        budget: usize,
+    ) -> Result<ModulationSpectrum, analyzer::BudgetError> {
+        let required = Self::required_bytes(history);
+        if required > budget {
+            return Err(analyzer::BudgetError { required, budget });
+        }
+
+        Ok(ModulationSpectrum::new(band, history))
+    }
+
+    fn required_bytes(history: usize) -> usize {
+        // Envelope, window and fourier buffer, plus the resulting spectrum
+        history
+            * (std::mem::size_of::<(analyzer::SignalStrength, f32)>()
+                + std::mem::size_of::<f32>()
+                + std::mem::size_of::<rustfft::num_complex::Complex<f32>>())
+            + (history / 2 + 1) * std::mem::size_of::<analyzer::SignalStrength>()
+    }
+
+    /// Return the number of bytes used for the history and transform buffers
+    pub fn memory_bytes(&self) -> usize {
+        Self::required_bytes(self.history)
+    }
+
+    /// Feed the band energy of `spectrum`, `dt` seconds after the last update
+    ///
+    /// Until `history` updates were made, the missing part of the envelope is filled with
+    /// its mean.
+    pub fn update<S: analyzer::spectrum::Storage>(
+        &mut self,
+        spectrum: &analyzer::Spectrum<S>,
+        dt: f32,
+    ) {
+        let energy = spectrum.slice(self.band.0, self.band.1).mean();
+
+        if self.envelope.len() == self.history {
+            let (_, old) = self.envelope.pop_front().unwrap();
+            self.duration -= old;
+        }
+        self.envelope.push_back((energy, dt));
+        self.duration += dt;
+
+        let mean = self.envelope.iter().map(|(e, _)| e).sum::<f32>() / self.envelope.len() as f32;
+        let missing = self.history - self.envelope.len();
+
+        self.buffer.clear();
+        self.buffer.extend(
+            std::iter::repeat_n(mean, missing)
+                .chain(self.envelope.iter().map(|(e, _)| *e))
+                .zip(self.window.iter())
+                .map(|(e, w)| {
+                    rustfft::num_complex::Complex::new(
+                        (e - mean) * analyzer::samples::to_f32(*w),
+                        0.0,
+                    )
+                }),
+        );
+        self.fft.process(&mut self.buffer);
+
+        // Average update rate over the tracked envelope
+        let rate = if self.duration > 0.0 {
+            self.envelope.len() as f32 / self.duration
+        } else {
+            1.0
+        };
+        let len = self.history / 2 + 1;
+        self.spectrum.resize(
+            len,
+            0.0,
+            (len - 1) as analyzer::Frequency * rate / self.history as analyzer::Frequency,
+        );
+        for (s, c) in self.spectrum.iter_mut().zip(self.buffer.iter()) {
+            *s = c.norm() / self.history as f32;
+        }
+    }
+
+    /// Return the current modulation spectrum
+    pub fn spectrum(&self) -> analyzer::Spectrum<&[analyzer::SignalStrength]> {
+        self.spectrum.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tremolo() {
+        crate::test_config();
+        let mut analyzer = analyzer::FourierBuilder::new()
+            .rate(8000)
+            .length(256)
+            .window(analyzer::window::hanning)
+            .downsample(1)
+            .plan();
+        let buf = analyzer::SampleBuffer::new(256, 8000);
+        let mut modulation = ModulationSpectrum::new((900.0, 1100.0), 128);
+
+        // 1 kHz tone, amplitude modulated at 4 Hz, analyzed at 100 frames per second
+        let tone = crate::helpers::signal::sine(8000, 1000.0, 2.0);
+        for (frame, chunk) in tone.chunks(80).enumerate() {
+            let chunk = chunk
+                .iter()
+                .enumerate()
+                .map(|(i, [l, r])| {
+                    let t = (frame * 80 + i) as f32 / 8000.0;
+                    let gain = (1.0 + 0.8 * (2.0 * std::f32::consts::PI * 4.0 * t).sin())
+                        as analyzer::Sample;
+                    [l * gain, r * gain]
+                })
+                .collect::<Vec<_>>();
+            buf.push(&chunk);
+            analyzer.analyze(&buf);
+            modulation.update(&analyzer.average(), 0.01);
+        }
+
+        let spectrum = modulation.spectrum();
+        assert!((spectrum.highest() - 50.0).abs() < 0.5);
+
+        let peak = spectrum
+            .iter()
+            .enumerate()
+            .skip(1)
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .map(|(i, _)| spectrum.id_to_freq(i))
+            .unwrap();
+        assert!(
+            (peak - 4.0).abs() < spectrum.id_to_freq(1),
+            "peak at {}",
+            peak
+        );
+    }
+
+    #[test]
+    fn test_budget() {
+        let modulation = ModulationSpectrum::new((50.0, 200.0), 128);
+        let required = modulation.memory_bytes();
+
+        let err = ModulationSpectrum::with_budget((50.0, 200.0), 128, required - 1).unwrap_err();
+        assert_eq!(err.required, required);
+        assert!(ModulationSpectrum::with_budget((50.0, 200.0), 128, required).is_ok());
+    }
+}
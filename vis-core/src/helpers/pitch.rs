@@ -0,0 +1,164 @@
+//! Dominant Frequency Tracking
+use crate::analyzer;
+use std::collections::VecDeque;
+
+/// Number of spectral peaks considered as candidates each update
+const CANDIDATES: usize = 5;
+
+/// Follows the dominant frequency with glissando smoothing
+///
+/// Each update, the strongest peaks are scored by their level and by how far they leap
+/// from the current pitch, `leap_cost` is the penalty per octave relative to a peak at
+/// `1/e` of the strongest one.  The winner is smoothed on a logarithmic scale, so the
+/// output glides between notes.  Brief noise or octave flips are mostly ignored while
+/// the previous pitch is still present in the spectrum.
+///
+/// # Example
+/// ```
+/// # use vis_core::helpers;
+/// let mut tracker = helpers::PitchTracker::new(2.0, 0.5, 64);
+///
+/// # let magnitudes = vec![0.0; 257];
+/// // Once per frame, with the bucket spacing of the magnitudes
+/// let pitch = tracker.track(&magnitudes, 15.625);
+/// let line = tracker.trajectory().collect::<Vec<_>>();
+/// ```
+#[derive(Debug, Clone)]
+pub struct PitchTracker {
+    leap_cost: f32,
+    smoothing: f32,
+    history: usize,
+
+    /// Smoothed pitch as `log2` of the frequency
+    pitch: Option<f32>,
+    trajectory: VecDeque<analyzer::Frequency>,
+}
+
+impl PitchTracker {
+    /// Create a new tracker
+    ///
+    /// `smoothing` is the weight of the previous pitch, `0.0` disables smoothing.  The last
+    /// `history` pitches are kept as the trajectory.
+    pub fn new(leap_cost: f32, smoothing: f32, history: usize) -> PitchTracker {
+        PitchTracker {
+            leap_cost,
+            smoothing,
+            history,
+
+            pitch: None,
+            trajectory: VecDeque::with_capacity(history),
+        }
+    }
+
+    /// Return the current smoothed pitch, `0.0` before anything was tracked
+    pub fn pitch(&self) -> analyzer::Frequency {
+        self.pitch.map_or(0.0, f32::exp2)
+    }
+
+    /// Return the smoothed pitches of the last updates, oldest first
+    pub fn trajectory(&self) -> impl Iterator<Item = analyzer::Frequency> + '_ {
+        self.trajectory.iter().cloned()
+    }
+
+    /// Track the magnitudes of a new frame, buckets are `bin_hz` apart starting at DC
+    ///
+    /// Returns the smoothed pitch.  Silent frames keep the previous pitch.
+    pub fn track(
+        &mut self,
+        mags: &[analyzer::SignalStrength],
+        bin_hz: analyzer::Frequency,
+    ) -> analyzer::Frequency {
+        let max = mags.iter().cloned().fold(0.0, f32::max);
+
+        if max > 0.0 {
+            let mut peaks = (1..mags.len().saturating_sub(1))
+                .filter(|&i| mags[i] > 0.0 && mags[i] >= mags[i - 1] && mags[i] > mags[i + 1])
+                .collect::<Vec<_>>();
+            peaks.sort_by(|&a, &b| {
+                mags[b]
+                    .partial_cmp(&mags[a])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            peaks.truncate(CANDIDATES);
+
+            let cost = |i: usize, f: f32| {
+                let leap = self.pitch.map_or(0.0, |p| (f.log2() - p).abs());
+                -(mags[i] / max).ln() + self.leap_cost * leap
+            };
+            let best = peaks
+                .iter()
+                .map(|&i| {
+                    // Parabolic interpolation between the neighbouring buckets
+                    let (a, b, c) = (mags[i - 1], mags[i], mags[i + 1]);
+                    let denom = a - 2.0 * b + c;
+                    let offset = if denom != 0.0 {
+                        0.5 * (a - c) / denom
+                    } else {
+                        0.0
+                    };
+                    let f = (i as f32 + offset) * bin_hz;
+                    (f, cost(i, f))
+                })
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+            if let Some((f, _)) = best {
+                let target = f.log2();
+                self.pitch = Some(match self.pitch {
+                    Some(p) => self.smoothing * p + (1.0 - self.smoothing) * target,
+                    None => target,
+                });
+            }
+        }
+
+        let pitch = self.pitch();
+        if self.pitch.is_some() {
+            if self.trajectory.len() == self.history {
+                self.trajectory.pop_front();
+            }
+            self.trajectory.push_back(pitch);
+        }
+        pitch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Magnitudes with peaks at the given frequencies, 10 Hz per bucket
+    fn frame(peaks: &[(f32, f32)]) -> Vec<f32> {
+        let mut mags = vec![0.01; 201];
+        for &(f, v) in peaks.iter() {
+            let i = (f / 10.0) as usize;
+            mags[i] = v;
+            mags[i - 1] = v / 2.0;
+            mags[i + 1] = v / 2.0;
+        }
+        mags
+    }
+
+    #[test]
+    fn test_octave_jump() {
+        let mut tracker = PitchTracker::new(2.0, 0.5, 16);
+        for _ in 0..10 {
+            tracker.track(&frame(&[(220.0, 1.0), (440.0, 0.3)]), 10.0);
+        }
+        assert!((tracker.pitch() - 220.0).abs() < 1.0);
+
+        // The octave briefly gets louder, the fundamental is still there
+        for _ in 0..3 {
+            let pitch = tracker.track(&frame(&[(220.0, 0.5), (440.0, 1.0)]), 10.0);
+            assert!(pitch < 250.0, "Followed the jump to {}", pitch);
+        }
+        tracker.track(&frame(&[(220.0, 1.0), (440.0, 0.3)]), 10.0);
+        assert!((tracker.pitch() - 220.0).abs() < 1.0);
+
+        // A real new note is followed, gliding
+        let glide = (0..10)
+            .map(|_| tracker.track(&frame(&[(660.0, 1.0)]), 10.0))
+            .collect::<Vec<_>>();
+        assert!(glide[0] > 250.0 && glide[0] < 600.0);
+        assert!((glide[9] - 660.0).abs() < 1.0);
+        assert_eq!(tracker.trajectory().count(), 16);
+    }
+}
@@ -0,0 +1,211 @@
+//! Reference Test Signals
+//!
+//! Generators for known inputs, eg. to test an analyzer without an audio device.  All
+//! signals are full-scale, mono (both channels carry the same samples) and
+//! `(rate * duration).round()` samples long.
+//!
+//! # Example
+//! ```
+//! # use vis_core::helpers::signal;
+//! let buffer = vis_core::analyzer::SampleBuffer::new(8000, 8000);
+//!
+//! buffer.push(&signal::sine(8000, 440.0, 0.5));
+//! buffer.push(&signal::pink_noise(8000, 0.5, 42));
+//! ```
+use crate::analyzer::{Frequency, Sample};
+use std::f32::consts::PI;
+
+fn len(rate: usize, duration: f32) -> usize {
+    (rate as f32 * duration).round() as usize
+}
+
+fn mono<I: Iterator<Item = Sample>>(samples: I) -> Vec<[Sample; 2]> {
+    samples.map(|s| [s, s]).collect()
+}
+
+/// Xorshift generator, good enough for noise and reproducible from a seed
+#[derive(Debug, Clone)]
+pub(crate) struct Noise(u64);
+
+impl Noise {
+    pub(crate) fn new(seed: u64) -> Noise {
+        // Xorshift must not start at 0
+        Noise(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    /// Uniform sample in `[-1, 1)`
+    pub(crate) fn next(&mut self) -> Sample {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 >> 40) as Sample / (1u64 << 23) as Sample - 1.0
+    }
+}
+
+/// Sine at `phase`, given in periods
+pub(crate) fn sine_at(phase: Sample) -> Sample {
+    (phase * 2.0 * std::f64::consts::PI as Sample).sin()
+}
+
+/// Square wave at `phase`, given in periods
+pub(crate) fn square_at(phase: Sample) -> Sample {
+    if phase.fract() < 0.5 {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+/// Rising sawtooth at `phase`, given in periods
+pub(crate) fn sawtooth_at(phase: Sample) -> Sample {
+    2.0 * phase.fract() - 1.0
+}
+
+/// Sine at `freq`
+pub fn sine(rate: usize, freq: Frequency, duration: f32) -> Vec<[Sample; 2]> {
+    mono((0..len(rate, duration)).map(|i| sine_at((i as f32 / rate as f32 * freq) as Sample)))
+}
+
+/// Square wave at `freq`
+pub fn square(rate: usize, freq: Frequency, duration: f32) -> Vec<[Sample; 2]> {
+    mono((0..len(rate, duration)).map(|i| square_at((i as f32 / rate as f32 * freq) as Sample)))
+}
+
+/// Rising sawtooth at `freq`
+pub fn sawtooth(rate: usize, freq: Frequency, duration: f32) -> Vec<[Sample; 2]> {
+    mono((0..len(rate, duration)).map(|i| sawtooth_at((i as f32 / rate as f32 * freq) as Sample)))
+}
+
+/// Uniform white noise
+///
+/// The same `seed` always produces the same noise.
+pub fn white_noise(rate: usize, duration: f32, seed: u64) -> Vec<[Sample; 2]> {
+    let mut noise = Noise::new(seed);
+
+    mono((0..len(rate, duration)).map(|_| noise.next()))
+}
+
+/// Pink noise, falling off by 3 dB per octave
+///
+/// White noise shaped by Paul Kellet's economy filter, which is accurate to about
+/// 0.5 dB above `rate / 4800`.  The same `seed` always produces the same noise.
+pub fn pink_noise(rate: usize, duration: f32, seed: u64) -> Vec<[Sample; 2]> {
+    let mut noise = Noise::new(seed);
+    let mut b = [0.0; 3];
+
+    mono((0..len(rate, duration)).map(|_| {
+        let white = noise.next();
+        b[0] = 0.99765 * b[0] + white * 0.0990460;
+        b[1] = 0.96300 * b[1] + white * 0.2965164;
+        b[2] = 0.57000 * b[2] + white * 1.0526913;
+
+        // Scale the sum (peaking around 4.5) back into full-scale
+        ((b[0] + b[1] + b[2] + white * 0.1848) / 4.5).clamp(-1.0, 1.0)
+    }))
+}
+
+/// Logarithmic sweep from `from` to `to`
+///
+/// The instantaneous frequency at time `t` is `from * (to / from).powf(t / duration)`,
+/// so every octave takes the same time.
+pub fn sweep(rate: usize, from: Frequency, to: Frequency, duration: f32) -> Vec<[Sample; 2]> {
+    let k = (to / from).ln() / duration;
+
+    mono((0..len(rate, duration)).map(|i| {
+        let t = i as f32 / rate as f32;
+        (2.0 * PI * from * ((k * t).exp() - 1.0) / k).sin() as Sample
+    }))
+}
+
+/// Silence with a single full-scale sample `at` seconds in
+pub fn impulse(rate: usize, duration: f32, at: f32) -> Vec<[Sample; 2]> {
+    let at = len(rate, at);
+
+    mono((0..len(rate, duration)).map(|i| if i == at { 1.0 } else { 0.0 }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep() {
+        let rate = 48000;
+        let (from, to, duration) = (100.0, 10000.0, 2.0);
+        let sweep = sweep(rate, from, to, duration);
+        assert_eq!(sweep.len(), 96000);
+
+        // Estimate the instantaneous frequency by counting zero crossings
+        let window = 0.05;
+        for step in 1..8 {
+            let t = step as f32 * 0.25;
+            let start = ((t - window / 2.0) * rate as f32) as usize;
+            let end = ((t + window / 2.0) * rate as f32) as usize;
+
+            let crossings = sweep[start..end]
+                .windows(2)
+                .filter(|w| (w[0][0] < 0.0) != (w[1][0] < 0.0))
+                .count();
+            let measured = crossings as f32 / window / 2.0;
+            let expected = from * (to / from).powf(t / duration);
+
+            assert!(
+                (measured / expected - 1.0).abs() < 0.05,
+                "{}s: {} != {}",
+                t,
+                measured,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_noise() {
+        let white = white_noise(8000, 1.0, 1);
+        assert_eq!(white, white_noise(8000, 1.0, 1));
+        assert_ne!(white, white_noise(8000, 1.0, 2));
+        let pink = pink_noise(8000, 1.0, 1);
+
+        // Lag-1 autocorrelation, pink noise is dominated by low frequencies
+        let autocorrelation = |noise: &[[Sample; 2]]| {
+            let lag = noise.windows(2).map(|w| w[0][0] * w[1][0]).sum::<Sample>();
+            lag / noise.iter().map(|s| s[0] * s[0]).sum::<Sample>()
+        };
+
+        for noise in [&white, &pink].iter() {
+            assert!(noise
+                .iter()
+                .all(|s| s[0] >= -1.0 && s[0] <= 1.0 && s[0] == s[1]));
+        }
+        assert!(autocorrelation(&white).abs() < 0.05);
+        assert!(autocorrelation(&pink) > 0.5);
+    }
+
+    #[test]
+    fn test_shapes() {
+        assert_eq!(impulse(1000, 0.01, 0.005)[5], [1.0; 2]);
+        assert_eq!(
+            impulse(1000, 0.01, 0.005)
+                .iter()
+                .filter(|s| s[0] != 0.0)
+                .count(),
+            1
+        );
+
+        let square = square(1000, 100.0, 0.01);
+        assert_eq!(
+            &square[..10].iter().map(|s| s[0]).collect::<Vec<_>>(),
+            &[1.0, 1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0, -1.0]
+        );
+
+        let sine = sine(1000, 250.0, 0.004);
+        assert!((sine[1][0] - 1.0).abs() < 1e-6);
+
+        let sawtooth = sawtooth(1000, 250.0, 0.008);
+        assert_eq!(
+            &sawtooth.iter().map(|s| s[0]).collect::<Vec<_>>(),
+            &[-1.0, -0.5, 0.0, 0.5, -1.0, -0.5, 0.0, 0.5]
+        );
+    }
+}
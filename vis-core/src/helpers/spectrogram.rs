@@ -0,0 +1,189 @@
+//! Display Spectrogram
+use crate::analyzer;
+
+/// Scrolling spectrogram image, ready for display
+///
+/// Each [`push`](#method.push) remaps a spectrum to `height` logarithmically spaced rows,
+/// converts them to dB, smooths them over time and appends the result as the rightmost
+/// column of a `width` x `height` image, scrolling older columns to the left.  The image
+/// is stored row-major with the highest frequency in the top row.  Levels are converted
+/// with `10 * log10`, then `floor` dB maps to `0` and `0 dB` to `255`.  For power spectra,
+/// eg. from
+/// [`MagnitudeMode::Squared`](../analyzer/fourier/enum.MagnitudeMode.html#variant.Squared),
+/// that is the true level in dB; plain magnitudes use only half of the range down to
+/// `floor`.
+///
+/// # Example
+/// ```
+/// # use vis_core::{analyzer, helpers};
+/// let mut spectrogram = helpers::DisplaySpectrogram::new(256, 64, (50.0, 4000.0), -60.0);
+/// spectrogram.set_smoothing(0.5);
+///
+/// # let spectrum = analyzer::Spectrum::new(vec![0.0; 100], 0.0, 4000.0);
+/// // Once per frame, then upload as a single channel texture
+/// let image: &[u8] = spectrogram.push(&spectrum);
+/// assert_eq!(image.len(), 256 * 64);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DisplaySpectrogram {
+    width: usize,
+    height: usize,
+    range: (analyzer::Frequency, analyzer::Frequency),
+    floor: f32,
+    smoothing: f32,
+
+    column: Vec<f32>,
+    image: Vec<u8>,
+}
+
+impl DisplaySpectrogram {
+    /// Create a new, black spectrogram
+    ///
+    /// `range` is the frequency range spanned by the rows, `floor` the level in dB (below
+    /// `0.0`) which is shown as black.
+    pub fn new(
+        width: usize,
+        height: usize,
+        range: (analyzer::Frequency, analyzer::Frequency),
+        floor: f32,
+    ) -> DisplaySpectrogram {
+        assert!(
+            range.0 > 0.0 && range.1 > range.0,
+            "Spectrogram range must be positive and ascending!"
+        );
+        assert!(floor < 0.0, "Spectrogram floor must be below 0 dB!");
+
+        DisplaySpectrogram {
+            width,
+            height,
+            range,
+            floor,
+            smoothing: 0.0,
+
+            column: vec![0.0; height],
+            image: vec![0; width * height],
+        }
+    }
+
+    /// Set the weight of the previous frame for temporal smoothing, `0.0` disables it
+    pub fn set_smoothing(&mut self, smoothing: f32) {
+        self.smoothing = smoothing;
+    }
+
+    /// Return the width of the image in columns
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Return the height of the image in rows
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Return the image as it was after the last push
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+
+    /// Return the smoothed levels of the last column from `0.0` to `1.0`, top row first
+    pub fn column(&self) -> &[f32] {
+        &self.column
+    }
+
+    /// Return the frequency range covered by `row`
+    pub fn row_range(&self, row: usize) -> (analyzer::Frequency, analyzer::Frequency) {
+        let ratio = self.range.1 / self.range.0;
+        let k = (self.height - 1 - row) as f32;
+
+        (
+            self.range.0 * ratio.powf(k / self.height as f32),
+            self.range.0 * ratio.powf((k + 1.0) / self.height as f32),
+        )
+    }
+
+    /// Append a new column computed from `spectrum` and return the image
+    pub fn push<S: analyzer::spectrum::Storage>(
+        &mut self,
+        spectrum: &analyzer::Spectrum<S>,
+    ) -> &[u8] {
+        let (lowest, highest) = (spectrum.lowest(), spectrum.highest());
+
+        for row in 0..self.height {
+            let (low, high) = self.row_range(row);
+
+            // Strongest bucket in the row, or the closest one if the row is narrower
+            // than a bucket
+            let level = spectrum
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| {
+                    let f = spectrum.id_to_freq(i);
+                    f >= low && f < high
+                })
+                .map(|(_, &v)| v)
+                .fold(None, |max: Option<f32>, v| {
+                    Some(max.map_or(v, |m| m.max(v)))
+                })
+                .unwrap_or_else(|| {
+                    let center = (low * high).sqrt();
+                    if center < lowest || center > highest {
+                        0.0
+                    } else {
+                        spectrum[spectrum.freq_to_id(center)]
+                    }
+                });
+
+            let db = 10.0 * level.max(1e-30).log10();
+            let value = (1.0 - db / self.floor).clamp(0.0, 1.0);
+            self.column[row] = self.smoothing * self.column[row] + (1.0 - self.smoothing) * value;
+        }
+
+        for (row, line) in self.image.chunks_exact_mut(self.width.max(1)).enumerate() {
+            line.rotate_left(1);
+            if let Some(last) = line.last_mut() {
+                *last = (self.column[row] * 255.0).round() as u8;
+            }
+        }
+
+        &self.image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steady_tone() {
+        let mut spectrogram = DisplaySpectrogram::new(64, 32, (50.0, 4000.0), -60.0);
+        spectrogram.set_smoothing(0.5);
+
+        let mut spectrum = analyzer::Spectrum::new(vec![0.0; 801], 0.0, 4000.0);
+        spectrum[440.0] = 1.0;
+
+        let row = (0..32)
+            .find(|&r| {
+                let (low, high) = spectrogram.row_range(r);
+                low <= 440.0 && 440.0 < high
+            })
+            .unwrap();
+        for _ in 0..64 {
+            spectrogram.push(&spectrum);
+        }
+
+        // A horizontal line at the tone's row once smoothing settled
+        let image = spectrogram.image();
+        for x in 16..64 {
+            for y in 0..32 {
+                let expected = if y == row { 255 } else { 0 };
+                assert_eq!(image[y * 64 + x], expected, "Pixel at ({}, {})", x, y);
+            }
+        }
+
+        // The image scrolls, so older columns are still fading in
+        assert!(image[row * 64] < 255);
+        let before = spectrogram.column().to_vec();
+        spectrogram.push(&spectrum);
+        assert_eq!(spectrogram.column(), &before[..]);
+    }
+}
@@ -70,7 +70,10 @@
 pub mod analyzer;
 pub mod frames;
 pub mod helpers;
+pub mod output;
 pub mod recorder;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 pub mod visualizer;
 
 #[doc(inline)]
@@ -116,6 +119,14 @@ pub fn default_config() {
         .expect("Can't load config");
 }
 
+/// Initialize an empty config for unit tests
+///
+/// Can be called from any number of tests, only the first call has an effect.
+#[cfg(test)]
+pub(crate) fn test_config() {
+    test_support::init_config();
+}
+
 /// Initialize logger
 ///
 /// By default, enable debug output in debug-builds.
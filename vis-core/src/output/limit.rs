@@ -0,0 +1,189 @@
+//! Output Rate Limiting
+//!
+//! Streaming every analysis frame can flood a downstream consumer.  A
+//! [`RateLimiter`](struct.RateLimiter.html) sits in front of a sink and lets at most
+//! `max_rate` frames per second through, independently of the analysis rate.  Each sink
+//! should get its own limiter.
+
+/// What happens to frames arriving faster than the limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitMode {
+    /// Drop frames in between, only the frame at the emission time is passed on
+    Decimate,
+    /// Average all frames since the last emission
+    Coalesce,
+}
+
+/// Builder for a RateLimiter
+#[derive(Debug, Default)]
+pub struct RateLimiterBuilder {
+    /// Maximum number of frames per second
+    ///
+    /// Defaults to `30.0`, can also be set from config as `"output.limit.rate"`.
+    pub max_rate: Option<f32>,
+
+    /// How frames above the limit are handled
+    ///
+    /// Defaults to `Decimate`, can also be set from config as `"output.limit.mode"`
+    /// (`"decimate"` or `"coalesce"`).
+    pub mode: Option<LimitMode>,
+}
+
+impl RateLimiterBuilder {
+    /// Create a new RateLimiterBuilder
+    pub fn new() -> RateLimiterBuilder {
+        Default::default()
+    }
+
+    /// Set the maximum number of frames per second
+    pub fn max_rate(&mut self, max_rate: f32) -> &mut RateLimiterBuilder {
+        self.max_rate = Some(max_rate);
+        self
+    }
+
+    /// Set the limit mode
+    pub fn mode(&mut self, mode: LimitMode) -> &mut RateLimiterBuilder {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Build the RateLimiter
+    pub fn build(&mut self) -> RateLimiter {
+        RateLimiter::from_builder(self)
+    }
+}
+
+/// Rate limiter for an output sink
+///
+/// # Example
+/// ```
+/// # use vis_core::output::limit;
+/// let mut limiter = limit::RateLimiterBuilder::new()
+///     .max_rate(20.0)
+///     .mode(limit::LimitMode::Coalesce)
+///     .build();
+///
+/// // Once per analysis frame, with the current time in seconds
+/// if let Some(frame) = limiter.offer(&[0.0; 16], 0.0) {
+///     // Send frame to the sink
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    interval: f32,
+    mode: LimitMode,
+
+    next: Option<f32>,
+    pending: Vec<f32>,
+    pending_count: usize,
+    output: Vec<f32>,
+}
+
+impl RateLimiter {
+    /// Create a RateLimiter from a builder config
+    pub fn from_builder(build: &RateLimiterBuilder) -> RateLimiter {
+        let max_rate = build
+            .max_rate
+            .unwrap_or_else(|| crate::CONFIG.get_or("output.limit.rate", 30.0));
+        assert!(max_rate > 0.0, "Rate limit must be positive!");
+
+        let mode = build.mode.unwrap_or_else(|| {
+            match &*crate::CONFIG.get_or("output.limit.mode", "decimate".to_string()) {
+                "decimate" => LimitMode::Decimate,
+                "coalesce" => LimitMode::Coalesce,
+                _ => panic!("Limit mode does not exist!"),
+            }
+        });
+
+        log::debug!("Output Rate Limit:");
+        log::debug!("    Max Rate    = {:6}", max_rate);
+        log::debug!("    Mode        = {:?}", mode);
+
+        RateLimiter {
+            interval: 1.0 / max_rate,
+            mode,
+
+            next: None,
+            pending: Vec::new(),
+            pending_count: 0,
+            output: Vec::new(),
+        }
+    }
+
+    /// Offer a frame at `time` seconds
+    ///
+    /// Returns the frame to emit, or `None` if the sink should skip this one.
+    pub fn offer(&mut self, frame: &[f32], time: f32) -> Option<&[f32]> {
+        if self.mode == LimitMode::Coalesce {
+            if self.pending_count == 0 {
+                self.pending.clear();
+                self.pending.extend_from_slice(frame);
+            } else {
+                for (p, v) in self.pending.iter_mut().zip(frame.iter()) {
+                    *p += v;
+                }
+            }
+            self.pending_count += 1;
+        }
+
+        let next = *self.next.get_or_insert(time);
+        if time < next {
+            return None;
+        }
+        // Keep the emission grid unless we fell behind by more than one interval
+        self.next = Some(if time - next < self.interval {
+            next + self.interval
+        } else {
+            time + self.interval
+        });
+
+        self.output.clear();
+        match self.mode {
+            LimitMode::Decimate => self.output.extend_from_slice(frame),
+            LimitMode::Coalesce => {
+                let count = self.pending_count as f32;
+                self.output.extend(self.pending.iter().map(|p| p / count));
+                self.pending_count = 0;
+            }
+        }
+
+        Some(&self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitted(mode: LimitMode) -> Vec<Vec<f32>> {
+        let mut limiter = RateLimiterBuilder::new().max_rate(20.0).mode(mode).build();
+
+        // One simulated second of analysis at 60 Hz
+        (0..60)
+            .filter_map(|i| {
+                limiter
+                    .offer(&[i as f32], i as f32 / 60.0)
+                    .map(|f| f.to_vec())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decimate() {
+        let frames = emitted(LimitMode::Decimate);
+
+        assert!((19..=21).contains(&frames.len()), "{} frames", frames.len());
+        assert_eq!(frames[0], vec![0.0]);
+        assert_eq!(frames[1], vec![3.0]);
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let frames = emitted(LimitMode::Coalesce);
+
+        assert!((19..=21).contains(&frames.len()), "{} frames", frames.len());
+        assert_eq!(frames[0], vec![0.0]);
+        // Frames 1, 2 and 3 averaged
+        assert_eq!(frames[1], vec![2.0]);
+    }
+}
@@ -0,0 +1,7 @@
+//! Output sinks
+//!
+//! Sinks publish analysis results to consumers outside of this process.
+pub mod frames_file;
+pub mod limit;
+#[cfg(feature = "shm")]
+pub mod shm;
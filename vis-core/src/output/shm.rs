@@ -0,0 +1,357 @@
+//! Shared Memory Output
+//!
+//! Publishes the latest frames into a shared memory ring which other processes can map
+//! without any serialization overhead.  The mapping has the following layout (native
+//! endianness):
+//!
+//! | Offset | Type                   | Content                                      |
+//! |--------|------------------------|----------------------------------------------|
+//! | 0      | `u32`                  | Magic, [`MAGIC`](constant.MAGIC.html)        |
+//! | 4      | `u32`                  | Frame length (number of `f32`)               |
+//! | 8      | `u32`                  | Number of slots                              |
+//! | 12     | `u32`                  | Reserved                                     |
+//! | 16     | `u64`                  | Sequence number of the latest frame          |
+//! | 24     | `[f32; length * slots]`| Frames, frame `n` is stored in slot `n % slots` |
+//!
+//! A sequence number of `0` means no frame has been written yet.
+use crate::analyzer;
+use std::sync::atomic;
+
+/// Magic number at the start of the mapping
+pub const MAGIC: u32 = u32::from_le_bytes(*b"VIS2");
+
+const HEADER_SIZE: usize = 24;
+
+/// Errors when creating or opening a shared memory output
+#[derive(Debug)]
+pub enum ShmError {
+    /// The mapping could not be created or opened
+    Shmem(shared_memory::ShmemError),
+    /// The mapping does not contain a valid header
+    InvalidHeader,
+}
+
+impl From<shared_memory::ShmemError> for ShmError {
+    fn from(e: shared_memory::ShmemError) -> ShmError {
+        ShmError::Shmem(e)
+    }
+}
+
+impl std::fmt::Display for ShmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShmError::Shmem(e) => write!(f, "Shared memory error: {}", e),
+            ShmError::InvalidHeader => write!(f, "Shared memory has an invalid header"),
+        }
+    }
+}
+
+impl std::error::Error for ShmError {}
+
+/// Builder for a ShmWriter
+#[derive(Debug, Default)]
+pub struct ShmBuilder {
+    /// OS identifier of the mapping
+    ///
+    /// Defaults to `"/visualizer2"`, can also be set from config as `"output.shm.name"`.
+    pub name: Option<String>,
+
+    /// Number of values per frame
+    ///
+    /// Defaults to `256`, can also be set from config as `"output.shm.length"`.
+    pub length: Option<usize>,
+
+    /// Number of frames kept in the ring
+    ///
+    /// Must be at least 2.  Defaults to `4`, can also be set from config as
+    /// `"output.shm.slots"`.
+    pub slots: Option<usize>,
+}
+
+impl ShmBuilder {
+    /// Create a new ShmBuilder
+    pub fn new() -> ShmBuilder {
+        Default::default()
+    }
+
+    /// Set the OS identifier of the mapping
+    pub fn name<S: Into<String>>(&mut self, name: S) -> &mut ShmBuilder {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the number of values per frame
+    pub fn length(&mut self, length: usize) -> &mut ShmBuilder {
+        self.length = Some(length);
+        self
+    }
+
+    /// Set the number of frames kept in the ring
+    pub fn slots(&mut self, slots: usize) -> &mut ShmBuilder {
+        self.slots = Some(slots);
+        self
+    }
+
+    /// Create the shared memory mapping
+    pub fn create(&self) -> Result<ShmWriter, ShmError> {
+        ShmWriter::from_builder(self)
+    }
+}
+
+fn header(shmem: &shared_memory::Shmem) -> (u32, usize, usize) {
+    let ptr = shmem.as_ptr() as *const u32;
+
+    // SAFETY: Every mapping is at least HEADER_SIZE bytes and page aligned
+    unsafe {
+        (
+            ptr.read_volatile(),
+            ptr.add(1).read_volatile() as usize,
+            ptr.add(2).read_volatile() as usize,
+        )
+    }
+}
+
+fn sequence(shmem: &shared_memory::Shmem) -> &atomic::AtomicU64 {
+    // SAFETY: The sequence number lives at an 8-byte aligned offset inside the mapping
+    // which stays valid for the lifetime of `shmem`
+    unsafe { &*(shmem.as_ptr().add(16) as *const atomic::AtomicU64) }
+}
+
+fn slot(shmem: &shared_memory::Shmem, length: usize, index: usize) -> *mut f32 {
+    // SAFETY: `index < slots`, so the slot lies inside the mapping
+    unsafe { (shmem.as_ptr().add(HEADER_SIZE) as *mut f32).add(index * length) }
+}
+
+/// Writing end of a shared memory output
+///
+/// # Example
+/// ```no_run
+/// # use vis_core::output::shm;
+/// let mut writer = shm::ShmBuilder::new()
+///     .name("/visualizer2")
+///     .length(256)
+///     .create()
+///     .unwrap();
+///
+/// writer.write(&[0.0; 256]);
+/// ```
+pub struct ShmWriter {
+    shmem: shared_memory::Shmem,
+    length: usize,
+    slots: usize,
+    sequence: u64,
+}
+
+// SAFETY: The mapping is shared memory of the whole process, moving the handle to
+// another thread (eg. into the analyzer) is fine.
+unsafe impl Send for ShmWriter {}
+
+impl std::fmt::Debug for ShmWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "ShmWriter {{ name: {:?}, length: {:?}, slots: {:?}, sequence: {:?} }}",
+            self.shmem.get_os_id(),
+            self.length,
+            self.slots,
+            self.sequence,
+        )
+    }
+}
+
+impl ShmWriter {
+    fn from_builder(build: &ShmBuilder) -> Result<ShmWriter, ShmError> {
+        let name = build
+            .name
+            .clone()
+            .unwrap_or_else(|| crate::CONFIG.get_or("output.shm.name", "/visualizer2".to_string()));
+        let length = build
+            .length
+            .unwrap_or_else(|| crate::CONFIG.get_or("output.shm.length", 256));
+        let slots = build
+            .slots
+            .unwrap_or_else(|| crate::CONFIG.get_or("output.shm.slots", 4));
+        assert!(slots >= 2, "Shared memory needs at least 2 slots!");
+
+        let shmem = shared_memory::ShmemConf::new()
+            .os_id(&name)
+            .size(HEADER_SIZE + length * slots * std::mem::size_of::<f32>())
+            .create()?;
+
+        let ptr = shmem.as_ptr() as *mut u32;
+        // SAFETY: See `header`
+        unsafe {
+            ptr.write_volatile(MAGIC);
+            ptr.add(1).write_volatile(length as u32);
+            ptr.add(2).write_volatile(slots as u32);
+        }
+        sequence(&shmem).store(0, atomic::Ordering::Release);
+
+        log::debug!("Shared Memory Output:");
+        log::debug!("    Name        = \"{}\"", name);
+        log::debug!("    Length      = {:6}", length);
+        log::debug!("    Slots       = {:6}", slots);
+
+        Ok(ShmWriter {
+            shmem,
+            length,
+            slots,
+            sequence: 0,
+        })
+    }
+
+    /// Return the number of values per frame
+    #[inline]
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Publish a new frame
+    ///
+    /// Frames shorter than `length` are padded with zeros, longer ones are truncated.
+    pub fn write(&mut self, frame: &[f32]) {
+        self.write_iter(frame.iter().cloned())
+    }
+
+    /// Publish the buckets of a spectrum as a new frame
+    pub fn write_spectrum<S: analyzer::spectrum::Storage>(
+        &mut self,
+        spectrum: &analyzer::Spectrum<S>,
+    ) {
+        self.write_iter(spectrum.iter().cloned())
+    }
+
+    fn write_iter<I: Iterator<Item = f32>>(&mut self, frame: I) {
+        let sequence = self.sequence + 1;
+        let slot = slot(
+            &self.shmem,
+            self.length,
+            (sequence % self.slots as u64) as usize,
+        );
+
+        let mut values = frame.chain(std::iter::repeat(0.0));
+        for i in 0..self.length {
+            // SAFETY: `i < length`
+            unsafe { slot.add(i).write_volatile(values.next().unwrap()) };
+        }
+
+        self::sequence(&self.shmem).store(sequence, atomic::Ordering::Release);
+        self.sequence = sequence;
+    }
+}
+
+/// Reading end of a shared memory output
+pub struct ShmReader {
+    shmem: shared_memory::Shmem,
+    length: usize,
+    slots: usize,
+}
+
+impl std::fmt::Debug for ShmReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "ShmReader {{ name: {:?}, length: {:?}, slots: {:?} }}",
+            self.shmem.get_os_id(),
+            self.length,
+            self.slots,
+        )
+    }
+}
+
+impl ShmReader {
+    /// Open an existing mapping
+    pub fn open(name: &str) -> Result<ShmReader, ShmError> {
+        let shmem = shared_memory::ShmemConf::new().os_id(name).open()?;
+
+        if shmem.len() < HEADER_SIZE {
+            return Err(ShmError::InvalidHeader);
+        }
+        let (magic, length, slots) = header(&shmem);
+        if magic != MAGIC
+            || slots < 2
+            || shmem.len() < HEADER_SIZE + length * slots * std::mem::size_of::<f32>()
+        {
+            return Err(ShmError::InvalidHeader);
+        }
+
+        Ok(ShmReader {
+            shmem,
+            length,
+            slots,
+        })
+    }
+
+    /// Return the number of values per frame
+    #[inline]
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Copy the latest frame into `out`
+    ///
+    /// Returns the frame's sequence number or `None` if no frame was written yet.
+    pub fn latest(&self, out: &mut Vec<f32>) -> Option<u64> {
+        let sequence = self::sequence(&self.shmem);
+
+        loop {
+            let seq = sequence.load(atomic::Ordering::Acquire);
+            if seq == 0 {
+                return None;
+            }
+
+            let slot = slot(&self.shmem, self.length, (seq % self.slots as u64) as usize);
+            out.clear();
+            // SAFETY: `i < length`
+            out.extend((0..self.length).map(|i| unsafe { slot.add(i).read_volatile() }));
+
+            // The slot is only overwritten once the writer has moved on by `slots - 1` frames
+            atomic::fence(atomic::Ordering::Acquire);
+            if sequence.load(atomic::Ordering::Relaxed) - seq < self.slots as u64 - 1 {
+                return Some(seq);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_frame() {
+        let name = format!("/visualizer2-test-{}", std::process::id());
+        let mut writer = ShmBuilder::new()
+            .name(name.clone())
+            .length(8)
+            .slots(3)
+            .create()
+            .unwrap();
+
+        let reader = std::thread::spawn(move || {
+            let reader = ShmReader::open(&name).unwrap();
+            assert_eq!(reader.length(), 8);
+
+            let mut frame = Vec::new();
+            loop {
+                if let Some(seq) = reader.latest(&mut frame) {
+                    assert_eq!(frame, vec![seq as f32; 8]);
+                    if seq == 100 {
+                        break;
+                    }
+                }
+            }
+        });
+
+        for i in 0..100 {
+            writer.write(&[(i + 1) as f32; 8]);
+        }
+
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        assert!(ShmReader::open("/visualizer2-does-not-exist").is_err());
+    }
+}
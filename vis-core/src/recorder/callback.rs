@@ -0,0 +1,132 @@
+//! Callback Recorder
+//!
+//! A recorder which asks a callback for a new block of samples each time it is synced.
+//! Together with [`Visualizer::synchronous`](../../visualizer/struct.Visualizer.html#method.synchronous)
+//! generating, buffering and analyzing all happens on one thread, one block per frame.
+//! This is meant for deterministic offline rendering and small single-core targets.
+use crate::analyzer;
+
+/// Callback filling a block with new samples
+pub type Callback = Box<dyn FnMut(&mut [[analyzer::Sample; 2]]) + Send>;
+
+/// Builder for CallbackRecorder
+#[derive(Debug, Default)]
+pub struct CallbackBuilder {
+    pub rate: Option<usize>,
+    pub buffer_size: Option<usize>,
+
+    /// Number of samples generated per sync
+    ///
+    /// Can also be set from config as `"audio.read_size"`.
+    pub block_size: Option<usize>,
+}
+
+impl CallbackBuilder {
+    pub fn new() -> CallbackBuilder {
+        Default::default()
+    }
+
+    pub fn rate(&mut self, rate: usize) -> &mut CallbackBuilder {
+        self.rate = Some(rate);
+        self
+    }
+
+    pub fn buffer_size(&mut self, buffer_size: usize) -> &mut CallbackBuilder {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    pub fn block_size(&mut self, block_size: usize) -> &mut CallbackBuilder {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    pub fn create<F>(&self, callback: F) -> CallbackRecorder
+    where
+        F: FnMut(&mut [[analyzer::Sample; 2]]) + Send + 'static,
+    {
+        CallbackRecorder::from_builder(self, Box::new(callback))
+    }
+
+    pub fn build<F>(&self, callback: F) -> Box<dyn super::Recorder>
+    where
+        F: FnMut(&mut [[analyzer::Sample; 2]]) + Send + 'static,
+    {
+        Box::new(self.create(callback))
+    }
+}
+
+/// Recorder generating a block of samples on each sync
+///
+/// # Example
+/// ```
+/// # use vis_core::{helpers, recorder};
+/// # vis_core::default_config();
+/// let sine = helpers::signal::sine(8000, 440.0, 1.0);
+/// let mut position = 0;
+///
+/// let recorder = recorder::callback::CallbackBuilder::new()
+///     .rate(8000)
+///     .buffer_size(4000)
+///     .block_size(160)
+///     .build(move |block| {
+///         for s in block.iter_mut() {
+///             *s = sine[position % sine.len()];
+///             position += 1;
+///         }
+///     });
+/// ```
+pub struct CallbackRecorder {
+    callback: Callback,
+    block: Vec<[analyzer::Sample; 2]>,
+    buffer: analyzer::SampleBuffer,
+}
+
+impl std::fmt::Debug for CallbackRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "CallbackRecorder {{ block_size: {:?}, buffer: {:?} }}",
+            self.block.len(),
+            self.buffer,
+        )
+    }
+}
+
+impl CallbackRecorder {
+    fn from_builder(build: &CallbackBuilder, callback: Callback) -> CallbackRecorder {
+        let rate = build
+            .rate
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.rate", 8000));
+        let buffer_size = build
+            .buffer_size
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.buffer", 16000));
+        let block_size = build
+            .block_size
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.read_size", 256));
+
+        log::debug!("Callback:");
+        log::debug!("    Sample Rate = {:6}", rate);
+        log::debug!("    Buffer Size = {:6}", buffer_size);
+        log::debug!("    Block Size  = {:6}", block_size);
+
+        CallbackRecorder {
+            callback,
+            block: vec![[0.0; 2]; block_size],
+            buffer: analyzer::SampleBuffer::new(buffer_size, rate),
+        }
+    }
+}
+
+impl super::Recorder for CallbackRecorder {
+    fn sample_buffer(&self) -> &analyzer::SampleBuffer {
+        &self.buffer
+    }
+
+    fn sync(&mut self, _time: f32) -> bool {
+        (self.callback)(&mut self.block);
+        self.buffer.push(&self.block);
+
+        true
+    }
+}
@@ -1,13 +1,52 @@
 use crate::analyzer;
-use std::thread;
-use cpal::HostId;
 use cpal::traits::*;
+use cpal::HostId;
 
 #[derive(Debug, Default)]
 pub struct CPalBuilder {
     pub rate: Option<usize>,
     pub buffer_size: Option<usize>,
     pub read_size: Option<usize>,
+    /// Capture at the device's native rate instead of forcing `rate`
+    ///
+    /// The analyzers adapt their downsampling to the buffer's rate.  Can also be
+    /// set from config as `"audio.native_rate"`.
+    pub native_rate: Option<bool>,
+    /// Device channels used as left and right
+    ///
+    /// Mono devices are always duplicated to both channels, see
+    /// [`ChannelMap`](../pcm/struct.ChannelMap.html).  Defaults to the first two channels,
+    /// can also be set from config as `"audio.channel_map.left"` and
+    /// `"audio.channel_map.right"`.
+    pub channel_map: Option<(u16, u16)>,
+    /// Number of device channels to capture
+    ///
+    /// The channels are mixed down to stereo, see
+    /// [`ChannelMix`](../pcm/enum.ChannelMix.html).  Can also be set from config as
+    /// `"audio.channels"`, by default only the channels of the channel map are captured.
+    pub channels: Option<u16>,
+    /// Sample format requested from the device
+    ///
+    /// Defaults to the first of `F32`, `I32`, `I16` and `U16` the device supports, can also
+    /// be set from config as `"audio.sample_format"` (`"f32"`, `"i32"`, `"i16"` or `"u16"`).
+    pub sample_format: Option<cpal::SampleFormat>,
+    /// Name of the audio host
+    ///
+    /// Defaults to the system's default host, can also be set from config as
+    /// `"capl.host"`.  Unknown hosts are an error, see [`list_hosts`](#method.list_hosts).
+    pub host: Option<String>,
+}
+
+/// Sample formats the recorder converts, in order of preference
+const FORMATS: [cpal::SampleFormat; 4] = [
+    cpal::SampleFormat::F32,
+    cpal::SampleFormat::I32,
+    cpal::SampleFormat::I16,
+    cpal::SampleFormat::U16,
+];
+
+fn parse_format(s: &str) -> Option<cpal::SampleFormat> {
+    FORMATS.iter().find(|f| f.to_string() == s).cloned()
 }
 
 impl CPalBuilder {
@@ -30,24 +69,126 @@ impl CPalBuilder {
         self
     }
 
-    pub fn create(&self) -> CPalRecorder {
+    pub fn native_rate(&mut self, native_rate: bool) -> &mut CPalBuilder {
+        self.native_rate = Some(native_rate);
+        self
+    }
+
+    pub fn channel_map(&mut self, left: u16, right: u16) -> &mut CPalBuilder {
+        self.channel_map = Some((left, right));
+        self
+    }
+
+    pub fn channels(&mut self, channels: u16) -> &mut CPalBuilder {
+        self.channels = Some(channels);
+        self
+    }
+
+    pub fn sample_format(&mut self, format: cpal::SampleFormat) -> &mut CPalBuilder {
+        self.sample_format = Some(format);
+        self
+    }
+
+    pub fn host<S: Into<String>>(&mut self, host: S) -> &mut CPalBuilder {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Return the names of the available audio hosts, as used for `"capl.host"`
+    pub fn list_hosts() -> Vec<String> {
+        cpal::available_hosts()
+            .iter()
+            .map(|id| id.name().to_string())
+            .collect()
+    }
+
+    /// Return the input devices of all available hosts
+    pub fn list_devices() -> Vec<super::DeviceInfo> {
+        let mut devices = Vec::new();
+        for id in cpal::available_hosts() {
+            let host = match cpal::host_from_id(id) {
+                Ok(host) => host,
+                Err(err) => {
+                    log::debug!("Host {} is not available: {}", id.name(), err);
+                    continue;
+                }
+            };
+            let default = host
+                .default_input_device()
+                .and_then(|device| device.name().ok());
+            let input_devices = match host.input_devices() {
+                Ok(input_devices) => input_devices,
+                Err(err) => {
+                    log::warn!("Could not get input devices of {}: {}", id.name(), err);
+                    continue;
+                }
+            };
+
+            for device in input_devices {
+                let name = match device.name() {
+                    Ok(name) => name,
+                    Err(_) => continue,
+                };
+                let mut info = super::DeviceInfo {
+                    host: id.name().to_string(),
+                    is_default: default.as_ref() == Some(&name),
+                    name,
+                    channels: (u16::MAX, 0),
+                    rates: (u32::MAX, 0),
+                };
+                for config in device.supported_input_configs().into_iter().flatten() {
+                    info.channels = (
+                        info.channels.0.min(config.channels()),
+                        info.channels.1.max(config.channels()),
+                    );
+                    info.rates = (
+                        info.rates.0.min(config.min_sample_rate().0),
+                        info.rates.1.max(config.max_sample_rate().0),
+                    );
+                }
+                if info.channels.1 == 0 {
+                    info.channels = (0, 0);
+                    info.rates = (0, 0);
+                }
+                devices.push(info);
+            }
+        }
+        devices
+    }
+
+    pub fn try_create(&self) -> Result<CPalRecorder, super::RecorderError> {
         CPalRecorder::from_builder(self)
     }
 
+    pub fn create(&self) -> CPalRecorder {
+        self.try_create().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Build the recorder, returning an error instead of panicking if no device is usable
+    pub fn try_build(&self) -> Result<Box<dyn super::Recorder>, super::RecorderError> {
+        Ok(Box::new(self.try_create()?))
+    }
+
     pub fn build(&self) -> Box<dyn super::Recorder> {
-        Box::new(self.create())
+        self.try_build().unwrap_or_else(|err| panic!("{}", err))
     }
 }
 
+/// Recorder capturing from a cpal input device
+///
+/// Capturing stops when the recorder is dropped.
 #[derive(Debug)]
 pub struct CPalRecorder {
-    #[allow(unused)]
     rate: usize,
+    /// Channels and sample format of the opened stream
+    channels: u16,
+    format: cpal::SampleFormat,
     buffer: analyzer::SampleBuffer,
+    thread: super::RecorderThread,
 }
 
 impl CPalRecorder {
-    fn from_builder(build: &CPalBuilder) -> CPalRecorder {
+    fn from_builder(build: &CPalBuilder) -> Result<CPalRecorder, super::RecorderError> {
         let rate = build
             .rate
             .unwrap_or_else(|| crate::CONFIG.get_or("audio.rate", 8000));
@@ -57,27 +198,51 @@ impl CPalRecorder {
         let read_size = build
             .buffer_size
             .unwrap_or_else(|| crate::CONFIG.get_or("audio.read_size", 256));
+        let native_rate = build
+            .native_rate
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.native_rate", false));
+        let channel_map = build.channel_map.or_else(|| {
+            Some((
+                crate::CONFIG.get("audio.channel_map.left")?,
+                crate::CONFIG.get("audio.channel_map.right")?,
+            ))
+        });
+        let capture_channels = build
+            .channels
+            .or_else(|| crate::CONFIG.get("audio.channels"));
+        let forced_format = build.sample_format.or_else(|| {
+            let name = crate::CONFIG.get::<String>("audio.sample_format")?;
+            let format = parse_format(&name);
+            if format.is_none() {
+                log::warn!(
+                    "Unknown sample format \"{}\", picking one from the device",
+                    name
+                );
+            }
+            format
+        });
+        let host_name = build
+            .host
+            .clone()
+            .or_else(|| crate::CONFIG.get::<String>("capl.host"));
+
+        // The buffer is created by the recorder thread, because the rate might
+        // depend on the selected device
+        let (buf_tx, buf_rx) = std::sync::mpsc::channel();
 
-        let buf = analyzer::SampleBuffer::new(buffer_size, rate);
-
-        {
-            let buf = buf.clone();
-            let mut chunk_buffer = vec![[0.0; 2]; read_size];
-
-            thread::Builder::new()
-                .name("cpal-recorder".into())
-                .spawn(move || {
-                    let host = if let Some(hostname) = crate::CONFIG.get::<String>("capl.host"){
-                        cpal::available_hosts()
-                            .into_iter()
-                            .filter(|host|host.name() == hostname)
-                            .next()
-                            .map(|id|cpal::host_from_id(id).ok())
-                            .flatten()
-                            .unwrap_or_else(||{
-                                log::debug!("The specified Host could not be found. Using default.");
-                                cpal::default_host()
-                            })
+        let thread = {
+            let mut chunk_buffer = Vec::with_capacity(read_size);
+
+            super::RecorderThread::spawn("cpal-recorder", move |running| {
+                let open = || -> Result<(cpal::Stream, analyzer::SampleBuffer, cpal::StreamConfig, cpal::SampleFormat), super::RecorderError> {
+                    let host = if let Some(hostname) = host_name.clone() {
+                        match cpal::available_hosts().into_iter().find(|host|host.name() == hostname) {
+                            Some(id) => cpal::host_from_id(id).map_err(|_| super::RecorderError::HostNotFound(hostname))?,
+                            None => {
+                                log::warn!("Unknown audio host \"{}\", available hosts: {}", hostname, CPalBuilder::list_hosts().join(", "));
+                                return Err(super::RecorderError::HostNotFound(hostname));
+                            }
+                        }
                     } else {
                         let hosts = cpal::available_hosts()
                             .iter()
@@ -89,8 +254,8 @@ impl CPalRecorder {
 
                     let device = {
                         if let Some(name) = crate::CONFIG.get::<String>("capl.device") {
-                            if let Ok(input_devices) = host.input_devices() {
-                                input_devices.filter(|item|if let Ok(device_name) = item.name() {device_name == name} else {false}).next()
+                            if let Ok(mut input_devices) = host.input_devices() {
+                                input_devices.find(|item|if let Ok(device_name) = item.name() {device_name == name} else {false})
                             }else{
                                 log::warn!("Could not get input devices");
                                 None
@@ -106,61 +271,203 @@ impl CPalRecorder {
                             }
                             None
                         }
-                        .unwrap_or_else(||{
+                        .or_else(||{
                             log::warn!("Could not get default input device");
-                            host.default_input_device().expect("No Input device found")
+                            host.default_input_device()
+                        })
+                        .ok_or(super::RecorderError::NoDevice)?
+                    };
+
+                    let rate = if native_rate {
+                        match device.default_input_config() {
+                            Ok(config) => config.sample_rate().0 as usize,
+                            Err(err) => {
+                                log::warn!("Could not get native rate ({err}), using {rate}");
+                                rate
+                            }
+                        }
+                    } else {
+                        rate
+                    };
+
+                    let supported = match device.supported_input_configs() {
+                        Ok(configs) => configs.map(|c| c.channels()).max().unwrap_or(2),
+                        Err(err) => {
+                            log::warn!("Could not get supported channels ({err}), assuming stereo");
+                            2
+                        }
+                    };
+                    let mix = super::pcm::ChannelMix::for_device(supported, capture_channels, channel_map);
+
+                    let format = forced_format.unwrap_or_else(|| {
+                        let formats = match device.supported_input_configs() {
+                            Ok(configs) => configs.filter(|c| c.channels() >= mix.channels()).map(|c| c.sample_format()).collect(),
+                            Err(_) => Vec::new(),
+                        };
+                        FORMATS.iter().find(|f| formats.contains(f)).cloned().unwrap_or_else(|| {
+                            log::warn!("Device supports none of {:?}, trying F32", FORMATS);
+                            cpal::SampleFormat::F32
                         })
+                    });
+                    if !FORMATS.contains(&format) {
+                        return Err(super::RecorderError::StreamBuild(format!("Unsupported sample format {format}")));
+                    }
+
+                    // Capture at the supported rate nearest to the requested one and resample
+                    let device_rate = match device.supported_input_configs() {
+                        Ok(configs) => configs
+                            .filter(|c| c.channels() >= mix.channels() && c.sample_format() == format)
+                            .map(|c| (rate as u32).clamp(c.min_sample_rate().0, c.max_sample_rate().0) as usize)
+                            .min_by_key(|supported| supported.abs_diff(rate))
+                            .unwrap_or(rate),
+                        Err(_) => rate,
                     };
+                    let mut resampler = if device_rate != rate {
+                        log::info!("Device does not support {rate} Hz, resampling from {device_rate} Hz");
+                        Some(super::resample::Resampler::new(device_rate, rate))
+                    } else {
+                        None
+                    };
+                    let mut resampled = std::collections::VecDeque::new();
+
+                    let buf = analyzer::SampleBuffer::new(buffer_size, rate);
 
                     let config = cpal::StreamConfig {
-                        channels: 2,
-                        sample_rate: cpal::SampleRate(rate as u32),
+                        channels: mix.channels(),
+                        sample_rate: cpal::SampleRate(device_rate as u32),
                         buffer_size: cpal::BufferSize::Fixed(read_size as u32),
                     };
 
-                    let stream = device.build_input_stream_raw(
-                        &config,
-                        cpal::SampleFormat::F32,
-                        move |data, _info| {
-                            let slice = data.as_slice::<f32>().expect("Wrong sample buffer data type!");
-                            for chunk in slice.chunks(chunk_buffer.len() * 2) {
-                                let len = chunk.len() / 2;
-                                for p in chunk_buffer.iter_mut().zip(chunk.chunks_exact(2)) {
-                                    match p {
-                                        (b, [l, r]) => *b = [*l, *r],
-                                        _ => unreachable!(),
-                                    }
+                    let stream = {
+                        let buf = buf.clone();
+                        let mut converted = Vec::new();
+                        device.build_input_stream_raw(
+                            &config,
+                            format,
+                            move |data, _info| {
+                                let slice = match format {
+                                    #[cfg(not(feature = "f64-samples"))]
+                                    cpal::SampleFormat::F32 => data.as_slice::<f32>().expect("Wrong sample buffer data type!"),
+                                    #[cfg(feature = "f64-samples")]
+                                    cpal::SampleFormat::F32 => super::pcm::convert(data.as_slice().expect("Wrong sample buffer data type!"), super::pcm::from_f32, &mut converted),
+                                    cpal::SampleFormat::I32 => super::pcm::convert(data.as_slice().expect("Wrong sample buffer data type!"), super::pcm::from_i32, &mut converted),
+                                    cpal::SampleFormat::I16 => super::pcm::convert(data.as_slice().expect("Wrong sample buffer data type!"), super::pcm::from_i16, &mut converted),
+                                    cpal::SampleFormat::U16 => super::pcm::convert(data.as_slice().expect("Wrong sample buffer data type!"), super::pcm::from_u16, &mut converted),
+                                    _ => unreachable!(),
+                                };
+                                chunk_buffer.clear();
+                                mix.deinterleave(slice, &mut chunk_buffer);
+                                if let Some(resampler) = resampler.as_mut() {
+                                    resampler.process(&chunk_buffer, &mut resampled);
+                                    chunk_buffer.clear();
+                                    chunk_buffer.extend(resampled.drain(..));
                                 }
-                                buf.push(&chunk_buffer[..len]);
-                            }
-                        },
-                        |err| {
-                            panic!("Stream Error: {err:?}");
-                        },
-                        None,
-                    ).expect("Failed to build input stream");
+                                for chunk in chunk_buffer.chunks(read_size) {
+                                    buf.push(chunk);
+                                }
+                            },
+                            |err| {
+                                panic!("Stream Error: {err:?}");
+                            },
+                            None,
+                        ).map_err(|err| super::RecorderError::StreamBuild(err.to_string()))?
+                    };
 
                     log::debug!("CPal:");
-                    log::debug!("    Sample Rate = {:6}", rate);
+                    log::debug!("    Sample Rate = {:6} (device {})", rate, device_rate);
                     log::debug!("    Read Size   = {:6}", read_size);
+                    log::debug!("    Format      = {:>6}", format.to_string());
                     log::debug!("    Buffer Size = {:6}", buffer_size);
+                    match mix {
+                        super::pcm::ChannelMix::Map(map) => log::debug!("    Channels    = {:6} (L = {}, R = {})", map.channels, map.left, map.right),
+                        super::pcm::ChannelMix::Downmix(channels) => log::debug!("    Channels    = {:6} (downmixed)", channels),
+                    }
                     log::debug!("    Device      = \"{}\"", device.name().as_deref().unwrap_or("unknown"));
 
-                    stream.play().unwrap();
+                    stream.play().map_err(|err| super::RecorderError::StreamBuild(err.to_string()))?;
 
-                    loop {
-                        std::thread::park();
+                    Ok((stream, buf, config, format))
+                };
+
+                // Keep the stream alive until the recorder is stopped
+                let _stream = match open() {
+                    Ok((stream, buf, config, format)) => {
+                        buf_tx.send(Ok((buf, config.channels, format))).unwrap();
+                        stream
                     }
-                })
-                .unwrap();
-        }
+                    Err(err) => {
+                        buf_tx.send(Err(err)).unwrap();
+                        return;
+                    }
+                };
+
+                super::RecorderThread::park_while(running);
+            })
+        };
+
+        let (buf, channels, format): (analyzer::SampleBuffer, u16, cpal::SampleFormat) =
+            buf_rx.recv().expect("CPal recorder thread died")?;
 
-        CPalRecorder { rate, buffer: buf }
+        Ok(CPalRecorder {
+            rate: buf.rate(),
+            channels,
+            format,
+            buffer: buf,
+            thread,
+        })
+    }
+
+    /// Stop capturing and wait for the recorder thread to terminate
+    pub fn stop(mut self) {
+        self.thread.stop();
     }
 }
 
 impl super::Recorder for CPalRecorder {
-    fn sample_buffer<'a>(&'a self) -> &'a analyzer::SampleBuffer {
+    fn sample_buffer(&self) -> &analyzer::SampleBuffer {
         &self.buffer
     }
+
+    fn metadata(&self) -> super::CaptureMetadata {
+        super::CaptureMetadata {
+            layout: super::ChannelLayout::from_channels(self.channels),
+            rate: self.rate,
+            bit_depth: Some(self.format.sample_size() as u16 * 8),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_devices() {
+        let devices = CPalBuilder::list_devices();
+        let hosts = CPalBuilder::list_hosts();
+        assert!(devices.iter().all(|d| hosts.contains(&d.host)));
+        assert!(devices
+            .iter()
+            .all(|d| d.channels.0 <= d.channels.1 && d.rates.0 <= d.rates.1));
+
+        // The default device is listed, if the system has one
+        if let Some(default) = cpal::default_host().default_input_device() {
+            let name = default.name().unwrap();
+            assert!(devices.iter().any(|d| d.is_default && d.name == name));
+        }
+    }
+
+    #[test]
+    fn test_unknown_host() {
+        crate::test_config();
+        let err = CPalBuilder::new()
+            .host("NoSuchHost")
+            .try_create()
+            .unwrap_err();
+        assert_eq!(
+            err,
+            super::super::RecorderError::HostNotFound("NoSuchHost".to_string())
+        );
+    }
 }
@@ -0,0 +1,632 @@
+//! Audio File Playback
+//!
+//! Feeds a visualizer from an audio file instead of live audio, eg. for reproducible demos.
+//! To use it from the config, set `"audio.recorder"` to `"file"` and `"audio.file.path"`
+//! to the file.
+//!
+//! WAV files are always supported.  FLAC, MP3 and Ogg/Vorbis files need the `symphonia`
+//! feature.
+use crate::analyzer;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+/// Errors when loading an audio file
+#[derive(Debug)]
+pub enum FileError {
+    /// The file could not be opened
+    Io(std::io::Error),
+    /// The WAV file could not be decoded
+    Wav(hound::Error),
+    /// The file could not be probed or decoded
+    #[cfg(feature = "symphonia")]
+    Decode(symphonia::core::errors::Error),
+    /// No decoder supports the file
+    Unsupported(PathBuf),
+}
+
+impl From<std::io::Error> for FileError {
+    fn from(e: std::io::Error) -> FileError {
+        FileError::Io(e)
+    }
+}
+
+impl From<hound::Error> for FileError {
+    fn from(e: hound::Error) -> FileError {
+        FileError::Wav(e)
+    }
+}
+
+#[cfg(feature = "symphonia")]
+impl From<symphonia::core::errors::Error> for FileError {
+    fn from(e: symphonia::core::errors::Error) -> FileError {
+        FileError::Decode(e)
+    }
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FileError::Io(e) => write!(f, "Could not open audio file: {}", e),
+            FileError::Wav(e) => write!(f, "Could not decode WAV file: {}", e),
+            #[cfg(feature = "symphonia")]
+            FileError::Decode(e) => write!(f, "Could not decode audio file: {}", e),
+            FileError::Unsupported(path) => write!(f, "Unsupported audio file {:?}", path),
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+/// Builder for a FileRecorder playing any supported file
+///
+/// The decoder is picked from the file itself: WAV files are read directly, everything
+/// else is probed by symphonia.
+#[derive(Debug, Default)]
+pub struct FileBuilder {
+    /// Path of the audio file
+    ///
+    /// Can also be set from config as `"audio.file.path"`.
+    pub path: Option<PathBuf>,
+    /// Rate the file is resampled to
+    ///
+    /// Can also be set from config as `"audio.rate"`.
+    pub rate: Option<usize>,
+    pub buffer_size: Option<usize>,
+    /// Start over once the end of the file is reached
+    ///
+    /// Defaults to `false`, can also be set from config as `"audio.file.loop"`.
+    pub looping: Option<bool>,
+}
+
+impl FileBuilder {
+    pub fn new() -> FileBuilder {
+        Default::default()
+    }
+
+    pub fn path<P: Into<PathBuf>>(&mut self, path: P) -> &mut FileBuilder {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn rate(&mut self, rate: usize) -> &mut FileBuilder {
+        self.rate = Some(rate);
+        self
+    }
+
+    pub fn buffer_size(&mut self, buffer_size: usize) -> &mut FileBuilder {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    pub fn looping(&mut self, looping: bool) -> &mut FileBuilder {
+        self.looping = Some(looping);
+        self
+    }
+
+    pub fn create(&self) -> Result<FileRecorder, FileError> {
+        FileRecorder::from_parts(
+            self.path.clone(),
+            self.rate,
+            self.buffer_size,
+            self.looping,
+            |path| {
+                let is_wav = path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+                if is_wav {
+                    decode_wav(path)
+                } else {
+                    decode_probed(path)
+                }
+            },
+        )
+    }
+
+    pub fn build(&self) -> Box<dyn super::Recorder> {
+        Box::new(self.create().expect("Failed to load audio file"))
+    }
+}
+
+/// Builder for a FileRecorder playing a WAV file
+#[derive(Debug, Default)]
+pub struct WavBuilder {
+    /// Path of the WAV file
+    ///
+    /// Can also be set from config as `"audio.file.path"`.
+    pub path: Option<PathBuf>,
+    /// Rate the file is resampled to
+    ///
+    /// Can also be set from config as `"audio.rate"`.
+    pub rate: Option<usize>,
+    pub buffer_size: Option<usize>,
+    /// Start over once the end of the file is reached
+    ///
+    /// Defaults to `false`, can also be set from config as `"audio.file.loop"`.
+    pub looping: Option<bool>,
+}
+
+impl WavBuilder {
+    pub fn new() -> WavBuilder {
+        Default::default()
+    }
+
+    pub fn path<P: Into<PathBuf>>(&mut self, path: P) -> &mut WavBuilder {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn rate(&mut self, rate: usize) -> &mut WavBuilder {
+        self.rate = Some(rate);
+        self
+    }
+
+    pub fn buffer_size(&mut self, buffer_size: usize) -> &mut WavBuilder {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    pub fn looping(&mut self, looping: bool) -> &mut WavBuilder {
+        self.looping = Some(looping);
+        self
+    }
+
+    pub fn create(&self) -> Result<FileRecorder, FileError> {
+        FileRecorder::from_parts(
+            self.path.clone(),
+            self.rate,
+            self.buffer_size,
+            self.looping,
+            decode_wav,
+        )
+    }
+
+    pub fn build(&self) -> Box<dyn super::Recorder> {
+        Box::new(self.create().expect("Failed to load WAV file"))
+    }
+}
+
+/// Fully decoded file at its own rate
+#[derive(Debug)]
+struct Decoded {
+    samples: Vec<[analyzer::Sample; 2]>,
+    rate: usize,
+    channels: u16,
+    bit_depth: Option<u16>,
+}
+
+fn decode_wav(path: &Path) -> Result<Decoded, FileError> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let interleaved = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .map(|s| s.map(|s| s as analyzer::Sample))
+            .collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u32 << (spec.bits_per_sample - 1)) as analyzer::Sample;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as analyzer::Sample * scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    let map = super::pcm::ChannelMap {
+        channels: spec.channels,
+        left: 0,
+        right: spec.channels.min(2) - 1,
+    };
+    let mut samples = Vec::with_capacity(interleaved.len() / spec.channels as usize);
+    map.deinterleave(&interleaved, &mut samples);
+
+    Ok(Decoded {
+        samples,
+        rate: spec.sample_rate as usize,
+        channels: spec.channels,
+        bit_depth: Some(spec.bits_per_sample),
+    })
+}
+
+#[cfg(not(feature = "symphonia"))]
+fn decode_probed(path: &Path) -> Result<Decoded, FileError> {
+    Err(FileError::Unsupported(path.to_owned()))
+}
+
+#[cfg(feature = "symphonia")]
+fn decode_probed(path: &Path) -> Result<Decoded, FileError> {
+    use symphonia::core::errors::Error;
+
+    let file = std::fs::File::open(path)?;
+    let stream = symphonia::core::io::MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = symphonia::core::formats::probe::Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let mut format = symphonia::default::get_probe().probe(
+        &hint,
+        stream,
+        Default::default(),
+        Default::default(),
+    )?;
+    let track = format
+        .default_track(symphonia::core::formats::TrackType::Audio)
+        .ok_or_else(|| FileError::Unsupported(path.to_owned()))?;
+    let params = track
+        .codec_params
+        .as_ref()
+        .and_then(|params| params.audio())
+        .ok_or_else(|| FileError::Unsupported(path.to_owned()))?;
+    let mut decoder =
+        symphonia::default::get_codecs().make_audio_decoder(params, &Default::default())?;
+    let track_id = track.id;
+    let bit_depth = params.bits_per_sample.map(|bits| bits as u16);
+
+    let mut samples = Vec::new();
+    let mut interleaved = Vec::new();
+    let mut spec = None;
+    while let Some(packet) = format.next_packet()? {
+        if packet.track_id != track_id {
+            continue;
+        }
+
+        let buf = match decoder.decode(&packet) {
+            Ok(buf) => buf,
+            Err(Error::DecodeError(err)) => {
+                log::warn!("Skipping undecodable packet in {:?}: {}", path, err);
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let (rate, downmix) = spec.get_or_insert_with(|| {
+            (
+                buf.spec().rate() as usize,
+                Downmix::for_channels(buf.spec().channels()),
+            )
+        });
+        if buf.spec().rate() as usize != *rate {
+            log::warn!("Sample rate of {:?} changes, ignoring the rest", path);
+            break;
+        }
+
+        buf.copy_to_vec_interleaved(&mut interleaved);
+        downmix.process(&interleaved, &mut samples);
+    }
+
+    let (rate, downmix) = spec.ok_or_else(|| FileError::Unsupported(path.to_owned()))?;
+    Ok(Decoded {
+        samples,
+        rate,
+        channels: downmix.weights.len() as u16,
+        bit_depth,
+    })
+}
+
+/// Weights of each source channel in the left and right output
+///
+/// Stereo passes through, mono is duplicated and surround channels are folded into the
+/// side they belong to.  Centered channels go to both sides at -3 dB, LFE is dropped.
+#[cfg(feature = "symphonia")]
+#[derive(Debug, Clone, PartialEq)]
+struct Downmix {
+    weights: Vec<[analyzer::Sample; 2]>,
+}
+
+#[cfg(feature = "symphonia")]
+impl Downmix {
+    fn for_channels(channels: &symphonia::core::audio::Channels) -> Downmix {
+        use symphonia::core::audio::Channels;
+
+        let count = channels.count();
+        let weights = match channels {
+            _ if count == 1 => vec![[1.0, 1.0]],
+            Channels::Positioned(positions) => positions
+                .iter_names()
+                .map(|(name, _)| {
+                    if name.starts_with("LFE") {
+                        [0.0, 0.0]
+                    } else if name.contains("LEFT") {
+                        [1.0, 0.0]
+                    } else if name.contains("RIGHT") {
+                        [0.0, 1.0]
+                    } else {
+                        [std::f64::consts::FRAC_1_SQRT_2 as analyzer::Sample; 2]
+                    }
+                })
+                .collect(),
+            // Without positions, use the first two channels like a capture device
+            _ => (0..count)
+                .map(|i| match i {
+                    0 => [1.0, 0.0],
+                    1 => [0.0, 1.0],
+                    _ => [0.0, 0.0],
+                })
+                .collect(),
+        };
+        Downmix { weights }
+    }
+
+    fn process(&self, data: &[analyzer::Sample], out: &mut Vec<[analyzer::Sample; 2]>) {
+        out.extend(data.chunks_exact(self.weights.len()).map(|frame| {
+            frame
+                .iter()
+                .zip(self.weights.iter())
+                .fold([0.0; 2], |[l, r], (s, w)| [l + s * w[0], r + s * w[1]])
+        }));
+    }
+}
+
+/// Recorder playing an audio file
+///
+/// The file is decoded and resampled when the recorder is created.  During
+/// [`sync`](../trait.Recorder.html#method.sync) the samples up to the given time are
+/// pushed, so the file plays in real time with the visualizer's clock.  Mono files are
+/// duplicated to both channels, WAV files with more channels use the first two.
+#[derive(Debug)]
+pub struct FileRecorder {
+    path: PathBuf,
+    rate: usize,
+    channels: u16,
+    bit_depth: Option<u16>,
+    looping: bool,
+
+    samples: Vec<[analyzer::Sample; 2]>,
+    /// Number of samples pushed so far, including previous loops
+    pushed: u64,
+    buffer: analyzer::SampleBuffer,
+}
+
+impl FileRecorder {
+    fn from_parts<D>(
+        path: Option<PathBuf>,
+        rate: Option<usize>,
+        buffer_size: Option<usize>,
+        looping: Option<bool>,
+        decode: D,
+    ) -> Result<FileRecorder, FileError>
+    where
+        D: FnOnce(&Path) -> Result<Decoded, FileError>,
+    {
+        let path = path.unwrap_or_else(|| {
+            crate::CONFIG
+                .get::<String>("audio.file.path")
+                .expect("No audio file given")
+                .into()
+        });
+        let rate = rate.unwrap_or_else(|| crate::CONFIG.get_or("audio.rate", 8000));
+        let buffer_size =
+            buffer_size.unwrap_or_else(|| crate::CONFIG.get_or("audio.buffer", 16000));
+        let looping = looping.unwrap_or_else(|| crate::CONFIG.get_or("audio.file.loop", false));
+
+        let decoded = decode(&path)?;
+        let mut resampled = VecDeque::with_capacity(decoded.samples.len());
+        super::resample::Resampler::new(decoded.rate, rate)
+            .process(&decoded.samples, &mut resampled);
+
+        log::debug!("File:");
+        log::debug!("    Sample Rate = {:6} (file {})", rate, decoded.rate);
+        log::debug!("    Buffer Size = {:6}", buffer_size);
+        log::debug!("    Channels    = {:6}", decoded.channels);
+        log::debug!(
+            "    Duration    = {:6.2} s",
+            resampled.len() as f32 / rate as f32
+        );
+        log::debug!("    File        = {:?}", path);
+
+        Ok(FileRecorder {
+            path,
+            rate,
+            channels: decoded.channels,
+            bit_depth: decoded.bit_depth,
+            looping,
+
+            samples: resampled.into(),
+            pushed: 0,
+            buffer: analyzer::SampleBuffer::new(buffer_size, rate),
+        })
+    }
+
+    /// Return the duration of the file in seconds
+    pub fn duration(&self) -> f32 {
+        self.samples.len() as f32 / self.rate as f32
+    }
+}
+
+impl super::Recorder for FileRecorder {
+    fn sample_buffer(&self) -> &analyzer::SampleBuffer {
+        &self.buffer
+    }
+
+    fn metadata(&self) -> super::CaptureMetadata {
+        super::CaptureMetadata {
+            layout: super::ChannelLayout::from_channels(self.channels),
+            rate: self.rate,
+            bit_depth: self.bit_depth,
+            title: self
+                .path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned()),
+        }
+    }
+
+    fn sync(&mut self, time: f32) -> bool {
+        let len = self.samples.len() as u64;
+        if len == 0 {
+            return false;
+        }
+
+        let mut target = (time as f64 * self.rate as f64) as u64;
+        if !self.looping {
+            target = target.min(len);
+        }
+
+        while self.pushed < target {
+            let position = (self.pushed % len) as usize;
+            let end = (position + (target - self.pushed) as usize).min(self.samples.len());
+            self.buffer.push(&self.samples[position..end]);
+            self.pushed += (end - position) as u64;
+
+            if !self.looping && self.pushed == len {
+                log::info!("End of file {:?}", self.path);
+            }
+        }
+
+        self.looping || self.pushed < len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::Recorder;
+
+    fn temp_path(name: &str, ext: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vis-file-{}-{}.{}", name, std::process::id(), ext))
+    }
+
+    fn write_wav(name: &str, rate: u32, channels: u16, samples: &[i16]) -> PathBuf {
+        let path = temp_path(name, "wav");
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate: rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for s in samples {
+            writer.write_sample(*s).unwrap();
+        }
+        writer.finalize().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_playback() {
+        crate::test_config();
+        // 0.1 s of a stereo ramp
+        let samples = (0..800)
+            .flat_map(|i| [i as i16 * 16, -(i as i16) * 16])
+            .collect::<Vec<_>>();
+        let path = write_wav("playback", 8000, 2, &samples);
+
+        let mut recorder = WavBuilder::new()
+            .path(&path)
+            .rate(8000)
+            .buffer_size(1000)
+            .create()
+            .unwrap();
+        assert_eq!(recorder.duration(), 0.1);
+        assert_eq!(recorder.metadata().bit_depth, Some(16));
+        assert_eq!(recorder.metadata().layout.channels(), 2);
+
+        // Half the file after half its duration
+        assert!(recorder.sync(0.05));
+        assert_eq!(recorder.sample_buffer().stats().pushed, 400);
+        let snapshot = recorder.sample_buffer().snapshot();
+        assert_eq!(
+            snapshot[999],
+            [399.0 * 16.0 / 32768.0, -399.0 * 16.0 / 32768.0]
+        );
+
+        // Stops at the end of the file
+        assert!(!recorder.sync(0.2));
+        assert_eq!(recorder.sample_buffer().stats().pushed, 800);
+        std::fs::remove_file(&path).unwrap();
+
+        // Interleaved channels are split, the first two are played
+        let samples = (0..100)
+            .flat_map(|i| (0..4).map(move |c| (i * 4 + c) as i16 * 16))
+            .collect::<Vec<_>>();
+        let path = write_wav("quad", 8000, 4, &samples);
+        let mut recorder = WavBuilder::new()
+            .path(&path)
+            .rate(8000)
+            .buffer_size(100)
+            .create()
+            .unwrap();
+        assert_eq!(recorder.metadata().layout.channels(), 4);
+
+        assert!(!recorder.sync(0.1));
+        let snapshot = recorder.sample_buffer().snapshot();
+        for (i, [l, r]) in snapshot.iter().enumerate() {
+            assert_eq!(*l, (i * 4) as analyzer::Sample * 16.0 / 32768.0);
+            assert_eq!(*r, (i * 4 + 1) as analyzer::Sample * 16.0 / 32768.0);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_looping_resampled() {
+        // Mono at 16 kHz, played at 8 kHz
+        let samples = (0..1600).map(|i| (i % 2) as i16 * 1000).collect::<Vec<_>>();
+        let path = write_wav("looping", 16000, 1, &samples);
+
+        let mut recorder = FileBuilder::new()
+            .path(&path)
+            .rate(8000)
+            .buffer_size(2000)
+            .looping(true)
+            .create()
+            .unwrap();
+        assert!((recorder.duration() - 0.1).abs() < 1e-3);
+
+        assert!(recorder.sync(0.25));
+        assert_eq!(recorder.sample_buffer().stats().pushed, 2000);
+        let snapshot = recorder.sample_buffer().snapshot();
+        assert!(snapshot.iter().all(|[l, r]| l == r));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "symphonia")]
+    #[test]
+    fn test_ogg() {
+        crate::test_config();
+        // 0.25 s of a 440 Hz stereo tone at 8 kHz with an amplitude of 0.5
+        let path = temp_path("ogg", "ogg");
+        std::fs::write(&path, include_bytes!("../../tests/data/tone.ogg")).unwrap();
+
+        let mut recorder = FileBuilder::new()
+            .path(&path)
+            .rate(8000)
+            .buffer_size(1000)
+            .create()
+            .unwrap();
+        assert!((recorder.duration() - 0.25).abs() < 0.02);
+        assert_eq!(
+            recorder.metadata().layout,
+            super::super::ChannelLayout::Stereo
+        );
+
+        recorder.sync(0.2);
+        let snapshot = recorder.sample_buffer().snapshot();
+        let peak = snapshot
+            .iter()
+            .map(|[l, _]| l.abs())
+            .fold(0.0, analyzer::Sample::max);
+        assert!(peak > 0.4 && peak < 0.6, "{}", peak);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "symphonia")]
+    #[test]
+    fn test_downmix() {
+        use symphonia::core::audio::{Channels, Position};
+
+        let surround = Downmix::for_channels(&Channels::Positioned(
+            Position::FRONT_LEFT
+                | Position::FRONT_RIGHT
+                | Position::FRONT_CENTER
+                | Position::LFE1
+                | Position::REAR_LEFT
+                | Position::REAR_RIGHT,
+        ));
+        let mut out = Vec::new();
+        surround.process(&[0.5, 0.25, 0.0, 1.0, 0.125, -0.25], &mut out);
+        assert_eq!(out, vec![[0.625, 0.0]]);
+
+        let mono = Downmix::for_channels(&Channels::Positioned(Position::FRONT_CENTER));
+        out.clear();
+        mono.process(&[0.5, -0.5], &mut out);
+        assert_eq!(out, vec![[0.5, 0.5], [-0.5, -0.5]]);
+    }
+}
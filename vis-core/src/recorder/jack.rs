@@ -0,0 +1,250 @@
+use crate::analyzer;
+
+#[derive(Debug, Default)]
+pub struct JackBuilder {
+    pub buffer_size: Option<usize>,
+    pub name: Option<String>,
+    /// Output ports connected to the left and right input
+    ///
+    /// Defaults to `"system:capture_1"` and `"system:capture_2"`, can also be set from
+    /// config as `"jack.ports"`, eg. `["system:monitor_1", "system:monitor_2"]`.
+    pub ports: Option<(String, String)>,
+}
+
+impl JackBuilder {
+    pub fn new() -> JackBuilder {
+        Default::default()
+    }
+
+    pub fn buffer_size(&mut self, size: usize) -> &mut JackBuilder {
+        self.buffer_size = Some(size);
+        self
+    }
+
+    pub fn name<S: Into<String>>(&mut self, name: S) -> &mut JackBuilder {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn ports<S1, S2>(&mut self, left: S1, right: S2) -> &mut JackBuilder
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.ports = Some((left.into(), right.into()));
+        self
+    }
+
+    pub fn try_create(&self) -> Result<JackRecorder, super::RecorderError> {
+        JackRecorder::from_builder(self)
+    }
+
+    pub fn create(&self) -> JackRecorder {
+        self.try_create().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Build the recorder, returning an error instead of panicking if JACK is unusable
+    pub fn try_build(&self) -> Result<Box<dyn super::Recorder>, super::RecorderError> {
+        Ok(Box::new(self.try_create()?))
+    }
+
+    pub fn build(&self) -> Box<dyn super::Recorder> {
+        self.try_build().unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+/// Recorder capturing from two JACK input ports
+///
+/// JACK dictates the sample rate, so the buffer has the server's rate instead of
+/// `"audio.rate"`.  The analyzers adapt their downsampling to it.  Capturing stops when
+/// the recorder is dropped.
+#[derive(Debug)]
+pub struct JackRecorder {
+    rate: usize,
+    buffer: analyzer::SampleBuffer,
+    thread: super::RecorderThread,
+}
+
+impl JackRecorder {
+    fn from_builder(build: &JackBuilder) -> Result<JackRecorder, super::RecorderError> {
+        let buffer_size = build
+            .buffer_size
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.buffer", 16000));
+        let name = build
+            .name
+            .clone()
+            .unwrap_or_else(|| "visualizer2".to_string());
+        let ports = build.ports.clone().unwrap_or_else(|| {
+            match crate::CONFIG.get::<Vec<String>>("jack.ports").as_deref() {
+                Some([left, right]) => (left.clone(), right.clone()),
+                Some(ports) => {
+                    log::warn!("Expected two JACK ports, got {:?}, using defaults", ports);
+                    (
+                        "system:capture_1".to_string(),
+                        "system:capture_2".to_string(),
+                    )
+                }
+                None => (
+                    "system:capture_1".to_string(),
+                    "system:capture_2".to_string(),
+                ),
+            }
+        });
+
+        // The buffer is created by the recorder thread, because the rate depends on
+        // the server
+        let (buf_tx, buf_rx) = std::sync::mpsc::channel();
+
+        let thread = super::RecorderThread::spawn("jack-recorder", move |running| {
+            let open = || -> Result<_, super::RecorderError> {
+                let (client, _status) =
+                    jack::Client::new(&name, jack::ClientOptions::NO_START_SERVER).map_err(
+                        |err| {
+                            super::RecorderError::StreamBuild(format!(
+                                "Could not connect to the JACK server: {}",
+                                err
+                            ))
+                        },
+                    )?;
+                let register = |port| {
+                    client.register_port(port, jack::AudioIn).map_err(|err| {
+                        super::RecorderError::StreamBuild(format!(
+                            "Could not register JACK port {}: {}",
+                            port, err
+                        ))
+                    })
+                };
+                let in_l = register("in_l")?;
+                let in_r = register("in_r")?;
+                let destinations = [
+                    format!("{}:in_l", client.name()),
+                    format!("{}:in_r", client.name()),
+                ];
+
+                let rate = client.sample_rate();
+                let period = client.buffer_size() as usize;
+
+                // The process callback runs on JACK's realtime thread, where it must
+                // neither lock nor allocate, so it only fills a ring drained below
+                let (mut producer, consumer) = rtrb::RingBuffer::new(buffer_size.max(2 * period));
+                let dropped = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+
+                let counter = dropped.clone();
+                let process = jack::ClosureProcessHandler::new(
+                    move |_: &jack::Client, ps: &jack::ProcessScope| {
+                        let samples = in_l.as_slice(ps).iter().zip(in_r.as_slice(ps).iter());
+                        for (i, (l, r)) in samples.enumerate() {
+                            let sample = [*l as analyzer::Sample, *r as analyzer::Sample];
+                            if producer.push(sample).is_err() {
+                                counter.fetch_add(
+                                    (ps.n_frames() as usize - i) as u64,
+                                    std::sync::atomic::Ordering::Relaxed,
+                                );
+                                break;
+                            }
+                        }
+                        jack::Control::Continue
+                    },
+                );
+
+                let active = client.activate_async((), process).map_err(|err| {
+                    super::RecorderError::StreamBuild(format!(
+                        "Could not activate JACK client: {}",
+                        err
+                    ))
+                })?;
+
+                for (source, destination) in [&ports.0, &ports.1].iter().zip(destinations.iter()) {
+                    if let Err(err) = active
+                        .as_client()
+                        .connect_ports_by_name(source, destination)
+                    {
+                        log::warn!("Could not connect {} to {}: {}", source, destination, err);
+                    }
+                }
+
+                log::debug!("JACK:");
+                log::debug!("    Sample Rate = {:6}", rate);
+                log::debug!("    Period      = {:6}", period);
+                log::debug!("    Buffer Size = {:6}", buffer_size);
+                log::debug!("    Ports       = \"{}\", \"{}\"", ports.0, ports.1);
+
+                Ok((active, consumer, dropped, rate, period))
+            };
+
+            let (active, mut consumer, dropped, rate, period) = match open() {
+                Ok(opened) => opened,
+                Err(err) => {
+                    buf_tx.send(Err(err)).unwrap();
+                    return;
+                }
+            };
+            let buf = analyzer::SampleBuffer::new(buffer_size, rate);
+            buf_tx.send(Ok(buf.clone())).unwrap();
+
+            // Move the captured samples to the buffer about once per period, until the
+            // recorder is stopped
+            let interval = std::time::Duration::from_secs_f64(period as f64 / rate as f64);
+            let mut reported = 0;
+            while running.load(std::sync::atomic::Ordering::Acquire) {
+                match consumer.read_chunk(consumer.slots()) {
+                    Ok(chunk) if !chunk.is_empty() => {
+                        let (first, second) = chunk.as_slices();
+                        buf.push(first);
+                        if !second.is_empty() {
+                            buf.push(second);
+                        }
+                        chunk.commit_all();
+                    }
+                    _ => (),
+                }
+
+                let now = dropped.load(std::sync::atomic::Ordering::Relaxed);
+                if now > reported {
+                    log::warn!("JACK ring overflowed, dropped {} samples", now - reported);
+                    reported = now;
+                }
+
+                std::thread::park_timeout(interval);
+            }
+            if let Err(err) = active.deactivate() {
+                log::warn!("Could not deactivate JACK client: {}", err);
+            }
+        });
+
+        let buffer: analyzer::SampleBuffer = buf_rx.recv().expect("JACK recorder thread died")?;
+        let requested = crate::CONFIG.get_or("audio.rate", 8000);
+        if buffer.rate() != requested {
+            log::info!(
+                "JACK server runs at {} Hz instead of {} Hz",
+                buffer.rate(),
+                requested
+            );
+        }
+
+        Ok(JackRecorder {
+            rate: buffer.rate(),
+            buffer,
+            thread,
+        })
+    }
+
+    /// Stop capturing and wait for the recorder thread to terminate
+    pub fn stop(mut self) {
+        self.thread.stop();
+    }
+}
+
+impl super::Recorder for JackRecorder {
+    fn sample_buffer(&self) -> &analyzer::SampleBuffer {
+        &self.buffer
+    }
+
+    fn metadata(&self) -> super::CaptureMetadata {
+        super::CaptureMetadata {
+            rate: self.rate,
+            bit_depth: Some(32),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! Mix Recorder
+//!
+//! Combines several recorders into one sample buffer, eg. for multi-source installations
+//! or A/B comparisons.  Sources are either summed with a per-source gain or a single one
+//! is selected.  Every source keeps its own buffer, so they can also be analyzed side by
+//! side through [`MixRecorder::source`](struct.MixRecorder.html#method.source).
+//!
+//! Samples are moved from the sources into the mix during
+//! [`sync`](../trait.Recorder.html#method.sync), so the mix lags behind async sources by
+//! up to one frame.  Sources with a different rate are resampled linearly, after a
+//! low-pass when downsampling.
+use crate::analyzer;
+use std::collections::VecDeque;
+
+/// How the sources are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixMode {
+    /// Sum all sources, each scaled by its gain
+    Sum,
+    /// Only pass on the source with this index, scaled by its gain
+    Select(usize),
+}
+
+/// Builder for MixRecorder
+#[derive(Debug, Default)]
+pub struct MixBuilder {
+    pub rate: Option<usize>,
+    pub buffer_size: Option<usize>,
+
+    /// How sources are combined
+    ///
+    /// Defaults to `Sum`.
+    pub mode: Option<MixMode>,
+}
+
+impl MixBuilder {
+    pub fn new() -> MixBuilder {
+        Default::default()
+    }
+
+    pub fn rate(&mut self, rate: usize) -> &mut MixBuilder {
+        self.rate = Some(rate);
+        self
+    }
+
+    pub fn buffer_size(&mut self, buffer_size: usize) -> &mut MixBuilder {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    pub fn mode(&mut self, mode: MixMode) -> &mut MixBuilder {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Create a mix of `sources`, each given with its gain
+    pub fn create(&self, sources: Vec<(Box<dyn super::Recorder>, f32)>) -> MixRecorder {
+        MixRecorder::from_builder(self, sources)
+    }
+
+    pub fn build(&self, sources: Vec<(Box<dyn super::Recorder>, f32)>) -> Box<dyn super::Recorder> {
+        Box::new(self.create(sources))
+    }
+}
+
+#[derive(Debug)]
+struct Source {
+    recorder: Box<dyn super::Recorder>,
+    gain: f32,
+    pushed: u64,
+    resampler: super::resample::Resampler,
+    pending: VecDeque<[analyzer::Sample; 2]>,
+}
+
+/// Recorder mixing several sources
+///
+/// # Example
+/// ```
+/// # use vis_core::{helpers, recorder};
+/// # vis_core::default_config();
+/// let tone = |freq| {
+///     let sine = helpers::signal::sine(8000, freq, 1.0);
+///     let mut position = 0;
+///     recorder::callback::CallbackBuilder::new()
+///         .rate(8000)
+///         .block_size(160)
+///         .build(move |block| {
+///             for s in block.iter_mut() {
+///                 *s = sine[position % sine.len()];
+///                 position += 1;
+///             }
+///         })
+/// };
+///
+/// let recorder = recorder::mix::MixBuilder::new()
+///     .rate(8000)
+///     .buffer_size(4000)
+///     .build(vec![(tone(440.0), 1.0), (tone(660.0), 0.5)]);
+/// ```
+#[derive(Debug)]
+pub struct MixRecorder {
+    sources: Vec<Source>,
+    mode: MixMode,
+    buffer: analyzer::SampleBuffer,
+    block: Vec<[analyzer::Sample; 2]>,
+}
+
+impl MixRecorder {
+    fn from_builder(
+        build: &MixBuilder,
+        sources: Vec<(Box<dyn super::Recorder>, f32)>,
+    ) -> MixRecorder {
+        let rate = build
+            .rate
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.rate", 8000));
+        let buffer_size = build
+            .buffer_size
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.buffer", 16000));
+        let mode = build.mode.unwrap_or(MixMode::Sum);
+
+        log::debug!("Mix:");
+        log::debug!("    Sample Rate = {:6}", rate);
+        log::debug!("    Buffer Size = {:6}", buffer_size);
+        log::debug!("    Sources     = {:6}", sources.len());
+        log::debug!("    Mode        = {:?}", mode);
+
+        MixRecorder {
+            sources: sources
+                .into_iter()
+                .map(|(recorder, gain)| {
+                    let buffer = recorder.sample_buffer();
+                    Source {
+                        pushed: buffer.stats().pushed,
+                        resampler: super::resample::Resampler::new(buffer.rate(), rate),
+                        recorder,
+                        gain,
+                        pending: VecDeque::new(),
+                    }
+                })
+                .collect(),
+            mode,
+            buffer: analyzer::SampleBuffer::new(buffer_size, rate),
+            block: Vec::new(),
+        }
+    }
+
+    /// Return the number of sources
+    pub fn sources(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Return the sample buffer of source `i` for separate analysis
+    pub fn source(&self, i: usize) -> &analyzer::SampleBuffer {
+        self.sources[i].recorder.sample_buffer()
+    }
+
+    /// Return the gain of source `i`
+    pub fn gain(&self, i: usize) -> f32 {
+        self.sources[i].gain
+    }
+
+    /// Set the gain of source `i`
+    pub fn set_gain(&mut self, i: usize, gain: f32) {
+        self.sources[i].gain = gain;
+    }
+
+    /// Select how sources are combined
+    pub fn set_mode(&mut self, mode: MixMode) {
+        self.mode = mode;
+    }
+}
+
+impl super::Recorder for MixRecorder {
+    fn sample_buffer(&self) -> &analyzer::SampleBuffer {
+        &self.buffer
+    }
+
+    fn sync(&mut self, time: f32) -> bool {
+        let mut available = false;
+        for source in self.sources.iter_mut() {
+            available |= source.recorder.sync(time);
+
+            let (pushed, new) = source.recorder.sample_buffer().since(source.pushed);
+            source.pushed = pushed;
+            source.resampler.process(&new, &mut source.pending);
+        }
+
+        // Mix as far as all sources are available, unless one fell behind by a whole
+        // buffer, which is then filled with silence
+        let longest = self
+            .sources
+            .iter()
+            .map(|s| s.pending.len())
+            .max()
+            .unwrap_or(0);
+        let len = if longest > self.buffer.size() {
+            longest
+        } else {
+            self.sources
+                .iter()
+                .map(|s| s.pending.len())
+                .min()
+                .unwrap_or(0)
+        };
+
+        self.block.clear();
+        self.block.resize(len, [0.0; 2]);
+        for (i, source) in self.sources.iter_mut().enumerate() {
+            let selected = match self.mode {
+                MixMode::Sum => true,
+                MixMode::Select(s) => s == i,
+            };
+            let take = len.min(source.pending.len());
+
+            for (b, s) in self.block.iter_mut().zip(source.pending.drain(..take)) {
+                if selected {
+                    b[0] += s[0] * source.gain as analyzer::Sample;
+                    b[1] += s[1] * source.gain as analyzer::Sample;
+                }
+            }
+        }
+
+        if !self.block.is_empty() {
+            self.buffer.push(&self.block);
+        }
+
+        available
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::Recorder;
+
+    fn source(rate: usize, samples: Vec<[analyzer::Sample; 2]>) -> Box<dyn super::super::Recorder> {
+        let mut position = 0;
+        crate::recorder::callback::CallbackBuilder::new()
+            .rate(rate)
+            .buffer_size(1024)
+            .block_size(rate / 100)
+            .build(move |block| {
+                for s in block.iter_mut() {
+                    *s = samples[position % samples.len()];
+                    position += 1;
+                }
+            })
+    }
+
+    #[test]
+    fn test_sum() {
+        let a = crate::helpers::signal::sine(8000, 440.0, 0.1);
+        let b = crate::helpers::signal::sine(8000, 1000.0, 0.1);
+
+        let mut mix = MixBuilder::new().rate(8000).buffer_size(800).create(vec![
+            (source(8000, a.clone()), 1.0),
+            (source(8000, b.clone()), 1.0),
+        ]);
+        mix.set_gain(1, 0.5);
+        assert_eq!(mix.gain(1), 0.5);
+
+        for _ in 0..10 {
+            assert!(mix.sync(0.0));
+        }
+
+        let mixed = mix.sample_buffer().snapshot();
+        for i in 0..800 {
+            let expected = a[i][0] + 0.5 * b[i][0];
+            assert!((mixed[i][0] - expected).abs() < 1e-6);
+        }
+
+        // Sources keep their own, unscaled buffers
+        let last = mix.source(1).snapshot()[1023];
+        assert_eq!(last, b[799]);
+    }
+
+    #[test]
+    fn test_select() {
+        let a = vec![[0.25, 0.5]];
+        let b = vec![[1.0, -1.0]];
+
+        let mut mix = MixBuilder::new()
+            .rate(8000)
+            .buffer_size(80)
+            .mode(MixMode::Select(1))
+            .create(vec![(source(8000, a), 1.0), (source(8000, b), 2.0)]);
+        mix.sync(0.0);
+        assert_eq!(mix.sample_buffer().snapshot(), vec![[2.0, -2.0]; 80]);
+
+        mix.set_mode(MixMode::Select(0));
+        mix.sync(0.0);
+        assert_eq!(mix.sample_buffer().snapshot(), vec![[0.25, 0.5]; 80]);
+    }
+
+    #[test]
+    fn test_resample() {
+        let mut mix = MixBuilder::new().rate(8000).buffer_size(400).create(vec![
+            (source(4000, vec![[0.5, 0.5]]), 1.0),
+            (source(8000, vec![[0.25, 0.25]]), 1.0),
+        ]);
+
+        for _ in 0..10 {
+            mix.sync(0.0);
+        }
+        assert_eq!(mix.sample_buffer().snapshot(), vec![[0.75, 0.75]; 400]);
+        assert!(mix.sample_buffer().stats().pushed >= 700);
+    }
+}
@@ -4,7 +4,110 @@ pub mod pulse;
 #[cfg(feature = "cpalrecord")]
 pub mod cpal;
 
+#[cfg(feature = "jack")]
+pub mod jack;
+
+pub mod callback;
+pub mod file;
+pub mod mix;
+pub mod pcm;
+pub mod session;
+pub mod signal;
+
+mod resample;
+
 use crate::analyzer;
+use std::sync;
+
+/// Errors when starting a live recorder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecorderError {
+    /// The system has no usable input device
+    NoDevice,
+    /// The configured audio host is not available
+    HostNotFound(String),
+    /// The input stream could not be built or started
+    StreamBuild(String),
+}
+
+impl std::fmt::Display for RecorderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RecorderError::NoDevice => write!(f, "No input device found"),
+            RecorderError::HostNotFound(host) => write!(f, "Audio host \"{}\" not available", host),
+            RecorderError::StreamBuild(e) => write!(f, "Failed to build input stream: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RecorderError {}
+
+/// Channel layout of the captured audio
+///
+/// The sample buffer is always stereo, this describes the source before it was mixed
+/// down (or up).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelLayout {
+    Mono,
+    #[default]
+    Stereo,
+    /// 5.1 surround
+    Surround51,
+    /// Any other number of channels
+    Other(u16),
+}
+
+impl ChannelLayout {
+    /// Guess the layout from a number of channels
+    pub fn from_channels(channels: u16) -> ChannelLayout {
+        match channels {
+            1 => ChannelLayout::Mono,
+            2 => ChannelLayout::Stereo,
+            6 => ChannelLayout::Surround51,
+            n => ChannelLayout::Other(n),
+        }
+    }
+
+    /// Return the number of channels
+    pub fn channels(self) -> u16 {
+        match self {
+            ChannelLayout::Mono => 1,
+            ChannelLayout::Stereo => 2,
+            ChannelLayout::Surround51 => 6,
+            ChannelLayout::Other(n) => n,
+        }
+    }
+}
+
+/// Metadata about the captured audio
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CaptureMetadata {
+    /// Channel layout of the source
+    pub layout: ChannelLayout,
+    /// Sample rate of the sample buffer
+    pub rate: usize,
+    /// Bits per sample of the source, if known
+    pub bit_depth: Option<u16>,
+    /// Title of the source, if known
+    pub title: Option<String>,
+}
+
+/// Description of an input device, eg. for a device picker
+///
+/// The name can be passed back as `"capl.device"` (cpal) or `"pulse.device"` (pulse).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DeviceInfo {
+    /// Name of the audio host, eg. `"ALSA"`
+    pub host: String,
+    /// Name of the device
+    pub name: String,
+    /// Whether this is the host's default input device
+    pub is_default: bool,
+    /// Lowest and highest supported number of channels
+    pub channels: (u16, u16),
+    /// Lowest and highest supported sample rate
+    pub rates: (u32, u32),
+}
 
 pub trait Recorder: std::fmt::Debug {
     /// Return the sample buffer where this recorder pushes data into
@@ -19,6 +122,91 @@ pub trait Recorder: std::fmt::Debug {
     fn sync(&mut self, _time: f32) -> bool {
         true
     }
+
+    /// Return metadata about the captured audio
+    ///
+    /// Live devices might only know little, the default reports a stereo source at the
+    /// sample buffer's rate.
+    fn metadata(&self) -> CaptureMetadata {
+        CaptureMetadata {
+            rate: self.sample_buffer().rate(),
+            ..Default::default()
+        }
+    }
+
+    /// Return the rate samples actually arrive at
+    ///
+    /// Measured against the wall clock, see
+    /// [`SampleBuffer::measured_rate`](../analyzer/struct.SampleBuffer.html#method.measured_rate).
+    /// Differs from the nominal rate if the device clock drifts.
+    fn measured_rate(&self) -> f32 {
+        self.sample_buffer().measured_rate()
+    }
+}
+
+/// Background thread of a live recorder
+///
+/// The thread runs as long as the flag passed to it is set.  Stopping clears the flag,
+/// unparks the thread and joins it, which happens at the latest when this is dropped.
+#[derive(Debug)]
+#[cfg_attr(
+    not(any(feature = "cpalrecord", feature = "pulseaudio", feature = "jack")),
+    allow(dead_code)
+)]
+struct RecorderThread {
+    running: sync::Arc<sync::atomic::AtomicBool>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+// Only used by the live recorders
+#[cfg_attr(
+    not(any(feature = "cpalrecord", feature = "pulseaudio", feature = "jack")),
+    allow(dead_code)
+)]
+impl RecorderThread {
+    fn spawn<F>(name: &str, f: F) -> RecorderThread
+    where
+        F: FnOnce(&sync::atomic::AtomicBool) + Send + 'static,
+    {
+        let running = sync::Arc::new(sync::atomic::AtomicBool::new(true));
+        let handle = {
+            let running = running.clone();
+            std::thread::Builder::new()
+                .name(name.into())
+                .spawn(move || f(&running))
+                .unwrap()
+        };
+
+        RecorderThread {
+            running,
+            handle: Some(handle),
+        }
+    }
+
+    /// Park the calling recorder thread until it is stopped
+    #[cfg(any(feature = "cpalrecord", test))]
+    fn park_while(running: &sync::atomic::AtomicBool) {
+        while running.load(sync::atomic::Ordering::Acquire) {
+            std::thread::park();
+        }
+    }
+
+    /// Signal the thread to stop and wait until it terminated
+    fn stop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.running.store(false, sync::atomic::Ordering::Release);
+            handle.thread().unpark();
+            if handle.join().is_err() {
+                log::warn!("Recorder thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for RecorderThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -80,9 +268,109 @@ impl RecorderBuilder {
             }
             .build(),
 
+            #[cfg(feature = "jack")]
+            "jack" => self::jack::JackBuilder {
+                buffer_size: self.buffer_size,
+                ..Default::default()
+            }
+            .build(),
+
+            "file" => self::file::FileBuilder {
+                rate: self.rate,
+                buffer_size: self.buffer_size,
+                ..Default::default()
+            }
+            .build(),
+
+            "wav" => self::file::WavBuilder {
+                rate: self.rate,
+                buffer_size: self.buffer_size,
+                ..Default::default()
+            }
+            .build(),
+
+            "signal" => self::signal::SignalBuilder {
+                rate: self.rate,
+                buffer_size: self.buffer_size,
+                ..Default::default()
+            }
+            .build(),
+
+            "replay" => self::session::ReplayBuilder {
+                buffer_size: self.buffer_size,
+                ..Default::default()
+            }
+            .build(),
+
             _ => {
                 panic!("Recorder type does not exist!");
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Recorder claiming 8000 Hz while pushing at 10000 Hz
+    #[derive(Debug)]
+    struct Fast {
+        buffer: analyzer::SampleBuffer,
+    }
+
+    impl Recorder for Fast {
+        fn sample_buffer(&self) -> &analyzer::SampleBuffer {
+            &self.buffer
+        }
+    }
+
+    #[test]
+    fn test_measured_rate() {
+        let recorder = Fast {
+            buffer: analyzer::SampleBuffer::new(1000, 8000),
+        };
+        assert_eq!(recorder.measured_rate(), 8000.0);
+
+        // 100 samples every 10 ms after the first push
+        let start = std::time::Instant::now();
+        for i in 0..=20 {
+            let time = start + std::time::Duration::from_millis(10 * i);
+            recorder.buffer.push_at(&[[0.0; 2]; 100], time);
+        }
+
+        let rate = recorder.measured_rate();
+        assert!((rate - 10000.0).abs() < 1.0, "{}", rate);
+        assert_eq!(recorder.metadata().rate, 8000);
+    }
+
+    #[test]
+    fn test_thread_stop() {
+        let finished = sync::Arc::new(sync::atomic::AtomicBool::new(false));
+        let mut thread = {
+            let finished = finished.clone();
+            RecorderThread::spawn("test-recorder", move |running| {
+                RecorderThread::park_while(running);
+                finished.store(true, sync::atomic::Ordering::Release);
+            })
+        };
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(!finished.load(sync::atomic::Ordering::Acquire));
+
+        thread.stop();
+        assert!(finished.load(sync::atomic::Ordering::Acquire));
+        assert!(thread.handle.is_none());
+
+        // Dropping a stopped thread is fine, dropping a running one stops it as well
+        drop(thread);
+        let finished = sync::Arc::new(sync::atomic::AtomicBool::new(false));
+        {
+            let finished = finished.clone();
+            RecorderThread::spawn("test-recorder", move |running| {
+                RecorderThread::park_while(running);
+                finished.store(true, sync::atomic::Ordering::Release);
+            });
+        }
+        assert!(finished.load(sync::atomic::Ordering::Acquire));
+    }
+}
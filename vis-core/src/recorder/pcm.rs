@@ -0,0 +1,481 @@
+//! Raw PCM Decoding
+//!
+//! Conversions from device and file sample formats to the analyzer's sample type.  A
+//! [`PcmFormat`](struct.PcmFormat.html) describes a raw interleaved PCM byte stream, eg.
+//! piped in from another program, and [`decode`](struct.PcmFormat.html#method.decode)s it
+//! into stereo frames.  Device recorders use a [`ChannelMap`](struct.ChannelMap.html) to
+//! fit any channel count into the stereo sample buffer.
+use crate::analyzer;
+
+/// Type of a single sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleType {
+    /// Unsigned 8 bit
+    U8,
+    /// Signed 16 bit
+    S16,
+    /// Signed 24 bit, packed into 3 bytes
+    S24,
+    /// Signed 32 bit
+    S32,
+    /// 32 bit float
+    F32,
+}
+
+impl SampleType {
+    /// Return the number of bytes of one sample
+    pub fn bytes(self) -> usize {
+        match self {
+            SampleType::U8 => 1,
+            SampleType::S16 => 2,
+            SampleType::S24 => 3,
+            SampleType::S32 | SampleType::F32 => 4,
+        }
+    }
+}
+
+/// Byte order of multi-byte samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+/// Convert a float sample to the sample type, only a cast with `f64-samples`
+#[allow(clippy::unnecessary_cast)]
+pub fn from_f32(s: f32) -> analyzer::Sample {
+    s as analyzer::Sample
+}
+
+/// Convert a signed 16 bit sample to a float in `-1.0..1.0`
+pub fn from_i16(s: i16) -> analyzer::Sample {
+    s as analyzer::Sample / 32768.0
+}
+
+/// Convert an unsigned 16 bit sample, centered around `32768`, to a float in `-1.0..1.0`
+pub fn from_u16(s: u16) -> analyzer::Sample {
+    (s as analyzer::Sample - 32768.0) / 32768.0
+}
+
+/// Convert a signed 32 bit sample to a float in `-1.0..1.0`
+pub fn from_i32(s: i32) -> analyzer::Sample {
+    s as analyzer::Sample / 2147483648.0
+}
+
+/// Convert samples with `f` into `out`, replacing its previous content
+pub fn convert<'a, T: Copy>(
+    data: &[T],
+    f: fn(T) -> analyzer::Sample,
+    out: &'a mut Vec<analyzer::Sample>,
+) -> &'a [analyzer::Sample] {
+    out.clear();
+    out.extend(data.iter().map(|s| f(*s)));
+    out
+}
+
+/// Description of a raw PCM stream
+///
+/// Can be parsed from strings like `"s16le"`, `"f32be:1"` or `"s24le:6"`, the number after
+/// the colon is the channel count (defaults to `2`).
+///
+/// # Example
+/// ```
+/// # use vis_core::recorder::pcm;
+/// let format: pcm::PcmFormat = "s16le:2".parse().unwrap();
+///
+/// let mut out = Vec::new();
+/// let consumed = format.decode(&[0x00, 0x40, 0x00, 0xc0], &mut out);
+/// assert_eq!(consumed, 4);
+/// assert_eq!(out, vec![[0.5, -0.5]]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcmFormat {
+    pub sample: SampleType,
+    pub endianness: Endianness,
+    pub channels: u16,
+}
+
+/// Error when parsing a [`PcmFormat`](struct.PcmFormat.html)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFormatError(String);
+
+impl std::fmt::Display for ParseFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Invalid PCM format \"{}\"", self.0)
+    }
+}
+
+impl std::error::Error for ParseFormatError {}
+
+impl std::str::FromStr for PcmFormat {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<PcmFormat, ParseFormatError> {
+        let err = || ParseFormatError(s.to_string());
+
+        let (format, channels) = match s.split_once(':') {
+            Some((format, channels)) => (format, channels.parse().map_err(|_| err())?),
+            None => (s, 2),
+        };
+        if channels == 0 {
+            return Err(err());
+        }
+
+        let (sample, endianness) = match format {
+            "u8" => (SampleType::U8, Endianness::Little),
+            _ if format.len() > 2 => {
+                let (sample, endianness) = format.split_at(format.len() - 2);
+                (
+                    match sample {
+                        "s16" => SampleType::S16,
+                        "s24" => SampleType::S24,
+                        "s32" => SampleType::S32,
+                        "f32" => SampleType::F32,
+                        _ => return Err(err()),
+                    },
+                    match endianness {
+                        "le" => Endianness::Little,
+                        "be" => Endianness::Big,
+                        _ => return Err(err()),
+                    },
+                )
+            }
+            _ => return Err(err()),
+        };
+
+        Ok(PcmFormat {
+            sample,
+            endianness,
+            channels,
+        })
+    }
+}
+
+impl PcmFormat {
+    /// Return the number of bytes of one frame (one sample for each channel)
+    pub fn bytes_per_frame(&self) -> usize {
+        self.sample.bytes() * self.channels as usize
+    }
+
+    fn sample(&self, b: &[u8]) -> analyzer::Sample {
+        // Bring the bytes into big endian order, left aligned in 4 bytes
+        let mut be = [0; 4];
+        let n = self.sample.bytes();
+        match self.endianness {
+            Endianness::Big => be[..n].copy_from_slice(b),
+            Endianness::Little => {
+                for (i, byte) in b.iter().rev().enumerate() {
+                    be[i] = *byte;
+                }
+            }
+        }
+
+        (match self.sample {
+            SampleType::U8 => (be[0] as f32 - 128.0) / 128.0,
+            SampleType::F32 => f32::from_be_bytes(be),
+            // Left aligned, so all signed integers scale the same
+            _ => i32::from_be_bytes(be) as f32 / 2147483648.0,
+        }) as analyzer::Sample
+    }
+
+    /// Decode `bytes` and append the samples to `out`
+    ///
+    /// Mono input is copied to both channels, only the first two channels of input with
+    /// more channels are used.  Returns the number of bytes consumed, a trailing partial
+    /// frame is left for the next call.
+    pub fn decode(&self, bytes: &[u8], out: &mut Vec<[analyzer::Sample; 2]>) -> usize {
+        let frame = self.bytes_per_frame();
+        let n = self.sample.bytes();
+
+        for chunk in bytes.chunks_exact(frame) {
+            let l = self.sample(&chunk[..n]);
+            let r = if self.channels > 1 {
+                self.sample(&chunk[n..2 * n])
+            } else {
+                l
+            };
+            out.push([l, r]);
+        }
+
+        bytes.len() / frame * frame
+    }
+}
+
+/// Which device channels end up in the stereo sample buffer
+///
+/// Devices only supporting mono are captured with one channel which is duplicated to both
+/// sides.  For devices with more channels, the first two are used unless a different pair
+/// is configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelMap {
+    /// Number of channels to capture
+    pub channels: u16,
+    /// Index of the channel used as left
+    pub left: u16,
+    /// Index of the channel used as right
+    pub right: u16,
+}
+
+impl ChannelMap {
+    /// Select channels for a device supporting at most `supported` channels
+    ///
+    /// `map` optionally selects the left and right channel, indices the device does not
+    /// have fall back to the first two channels.
+    pub fn for_device(supported: u16, map: Option<(u16, u16)>) -> ChannelMap {
+        match (supported, map) {
+            (0..=1, _) => ChannelMap {
+                channels: 1,
+                left: 0,
+                right: 0,
+            },
+            (_, Some((left, right))) if left < supported && right < supported => ChannelMap {
+                channels: left.max(right).max(1) + 1,
+                left,
+                right,
+            },
+            (_, map) => {
+                if map.is_some() {
+                    log::warn!(
+                        "Channel map {:?} not supported by a device with {} channels",
+                        map,
+                        supported
+                    );
+                }
+                ChannelMap {
+                    channels: 2,
+                    left: 0,
+                    right: 1,
+                }
+            }
+        }
+    }
+
+    /// Convert interleaved samples with `channels` channels and append them to `out`
+    pub fn deinterleave(&self, data: &[analyzer::Sample], out: &mut Vec<[analyzer::Sample; 2]>) {
+        out.extend(
+            data.chunks_exact(self.channels as usize)
+                .map(|frame| [frame[self.left as usize], frame[self.right as usize]]),
+        );
+    }
+}
+
+/// How interleaved device channels are converted to the stereo sample buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelMix {
+    /// Use two of the channels as left and right
+    Map(ChannelMap),
+    /// Capture this many channels and mix them down
+    ///
+    /// Mono is duplicated to both sides.  With more channels, even ones are averaged into
+    /// the left and odd ones into the right side.
+    Downmix(u16),
+}
+
+impl ChannelMix {
+    /// Select the conversion for a device supporting at most `supported` channels
+    ///
+    /// Without a `requested` channel count, this is the [`ChannelMap`](struct.ChannelMap.html)
+    /// for the device.  A requested count the device can't provide is reduced to what it
+    /// supports.  If `map` is set, it selects the channels instead of a downmix.
+    pub fn for_device(
+        supported: u16,
+        requested: Option<u16>,
+        map: Option<(u16, u16)>,
+    ) -> ChannelMix {
+        let requested = match requested {
+            Some(requested) if requested == 0 || requested > supported.max(1) => {
+                log::warn!(
+                    "{} channels requested from a device with {} channels",
+                    requested,
+                    supported
+                );
+                supported.max(1)
+            }
+            Some(requested) => requested,
+            None => return ChannelMix::Map(ChannelMap::for_device(supported, map)),
+        };
+
+        match map {
+            Some(_) => ChannelMix::Map(ChannelMap::for_device(requested, map)),
+            None => ChannelMix::Downmix(requested),
+        }
+    }
+
+    /// Return the number of channels to capture
+    pub fn channels(&self) -> u16 {
+        match self {
+            ChannelMix::Map(map) => map.channels,
+            ChannelMix::Downmix(channels) => *channels,
+        }
+    }
+
+    /// Convert interleaved samples with `channels` channels and append them to `out`
+    pub fn deinterleave(&self, data: &[analyzer::Sample], out: &mut Vec<[analyzer::Sample; 2]>) {
+        let channels = match self {
+            ChannelMix::Map(map) => return map.deinterleave(data, out),
+            ChannelMix::Downmix(1) => return out.extend(data.iter().map(|s| [*s; 2])),
+            ChannelMix::Downmix(channels) => *channels as usize,
+        };
+
+        let left = channels.div_ceil(2) as analyzer::Sample;
+        let right = (channels / 2) as analyzer::Sample;
+        out.extend(data.chunks_exact(channels).map(|frame| {
+            let (l, r) = frame.chunks(2).fold((0.0, 0.0), |(l, r), pair| {
+                (l + pair[0], r + pair.get(1).unwrap_or(&0.0))
+            });
+            [l / left, r / right]
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_i16() {
+        assert_eq!(from_i16(0), 0.0);
+        assert_eq!(from_i16(16384), 0.5);
+        assert_eq!(from_i16(i16::MIN), -1.0);
+        assert_eq!(from_i16(i16::MAX), 32767.0 / 32768.0);
+    }
+
+    #[test]
+    fn test_from_u16() {
+        assert_eq!(from_u16(32768), 0.0);
+        assert_eq!(from_u16(49152), 0.5);
+        assert_eq!(from_u16(0), -1.0);
+        assert_eq!(from_u16(u16::MAX), 32767.0 / 32768.0);
+    }
+
+    #[test]
+    fn test_from_i32() {
+        assert_eq!(from_i32(0), 0.0);
+        assert_eq!(from_i32(-1 << 30), -0.5);
+        assert_eq!(from_i32(i32::MIN), -1.0);
+        assert!(from_i32(i32::MAX) <= 1.0);
+
+        let mut out = vec![1.0; 5];
+        assert_eq!(convert(&[1 << 29, 0], from_i32, &mut out), [0.25, 0.0]);
+    }
+
+    #[test]
+    fn test_s24be() {
+        let format: PcmFormat = "s24be".parse().unwrap();
+        assert_eq!(format.bytes_per_frame(), 6);
+
+        let mut out = Vec::new();
+        let bytes = [
+            0x40, 0x00, 0x00, 0xc0, 0x00, 0x00, // 0.5, -0.5
+            0x7f, 0xff, 0xff, 0x80, 0x00, 0x00, // max, -1.0
+            0x00, 0x00, // Partial frame
+        ];
+        assert_eq!(format.decode(&bytes, &mut out), 12);
+        assert_eq!(out[0], [0.5, -0.5]);
+        assert!((out[1][0] - 1.0).abs() < 1e-6);
+        assert_eq!(out[1][1], -1.0);
+    }
+
+    #[test]
+    fn test_f32le() {
+        let format: PcmFormat = "f32le:1".parse().unwrap();
+
+        let mut out = Vec::new();
+        let bytes = [0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0x80, 0xbe];
+        assert_eq!(format.decode(&bytes, &mut out), 8);
+        assert_eq!(out, vec![[1.0, 1.0], [-0.25, -0.25]]);
+    }
+
+    #[test]
+    fn test_channels() {
+        let format: PcmFormat = "s16le:6".parse().unwrap();
+
+        let mut out = Vec::new();
+        let frame = [
+            0x00, 0x40, 0x00, 0xc0, 0xff, 0x7f, 0xff, 0x7f, 0xff, 0x7f, 0xff, 0x7f,
+        ];
+        format.decode(&frame, &mut out);
+        assert_eq!(out, vec![[0.5, -0.5]]);
+
+        let format: PcmFormat = "u8:1".parse().unwrap();
+        out.clear();
+        format.decode(&[0x80, 0x00], &mut out);
+        assert_eq!(out, vec![[0.0, 0.0], [-1.0, -1.0]]);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "s32be:1".parse(),
+            Ok(PcmFormat {
+                sample: SampleType::S32,
+                endianness: Endianness::Big,
+                channels: 1,
+            })
+        );
+        assert!("s12le".parse::<PcmFormat>().is_err());
+        assert!("s16xx".parse::<PcmFormat>().is_err());
+        assert!("f32le:0".parse::<PcmFormat>().is_err());
+    }
+
+    #[test]
+    fn test_mono_device() {
+        let map = ChannelMap::for_device(1, None);
+        assert_eq!(map.channels, 1);
+
+        let mono = crate::helpers::signal::white_noise(8000, 0.01, 1)
+            .iter()
+            .map(|[l, _]| *l)
+            .collect::<Vec<_>>();
+        let mut stereo = Vec::new();
+        map.deinterleave(&mono, &mut stereo);
+
+        let buf = analyzer::SampleBuffer::new(stereo.len(), 8000);
+        buf.push(&stereo);
+        assert!(buf.iter(stereo.len(), 1).all(|[l, r]| l == r));
+        assert_eq!(buf.snapshot()[3][0], mono[3]);
+    }
+
+    #[test]
+    fn test_channel_map() {
+        let data = [0.0, 1.0, 2.0, 3.0, 4.0, 10.0, 11.0, 12.0, 13.0, 14.0];
+
+        let mut out = Vec::new();
+        let map = ChannelMap::for_device(6, None);
+        assert_eq!(map.channels, 2);
+        map.deinterleave(&data, &mut out);
+        assert_eq!(out[..2], [[0.0, 1.0], [2.0, 3.0]]);
+
+        out.clear();
+        let map = ChannelMap::for_device(6, Some((4, 2)));
+        assert_eq!(map.channels, 5);
+        map.deinterleave(&data, &mut out);
+        assert_eq!(out, vec![[4.0, 2.0], [14.0, 12.0]]);
+
+        // A map the device cannot provide is ignored
+        assert_eq!(ChannelMap::for_device(2, Some((0, 3))).right, 1);
+    }
+
+    #[test]
+    fn test_downmix() {
+        // A mono mic is duplicated, also when stereo was requested
+        let mix = ChannelMix::for_device(1, Some(2), None);
+        assert_eq!(mix, ChannelMix::Downmix(1));
+        let mut out = Vec::new();
+        mix.deinterleave(&[0.5, -0.25], &mut out);
+        assert_eq!(out, vec![[0.5, 0.5], [-0.25, -0.25]]);
+
+        // Extra channels are averaged into their side
+        let mix = ChannelMix::for_device(8, Some(3), None);
+        assert_eq!(mix.channels(), 3);
+        out.clear();
+        mix.deinterleave(&[0.25, 1.0, 0.75, 0.0, 0.5, 1.0], &mut out);
+        assert_eq!(out, vec![[0.5, 1.0], [0.5, 0.5]]);
+
+        // Without a requested count, the channel map is used
+        assert_eq!(
+            ChannelMix::for_device(6, None, None),
+            ChannelMix::Map(ChannelMap::for_device(6, None))
+        );
+    }
+}
@@ -1,5 +1,4 @@
 use crate::analyzer;
-use std::thread;
 
 #[derive(Debug, Default)]
 pub struct PulseBuilder {
@@ -8,6 +7,17 @@ pub struct PulseBuilder {
     pub buffer_size: Option<usize>,
     pub name: Option<(String, String)>,
     pub device: Option<String>,
+    /// Source to record from, eg. `"alsa_output.pci-0000_00_1f.3.analog-stereo.monitor"`
+    ///
+    /// Takes precedence over `device`.  Defaults to the default source, can also be set
+    /// from config as `"pulse.source"`.  `pactl list short sources` lists the names.
+    pub source: Option<String>,
+    /// Record from a sink monitor, ie. whatever is currently playing
+    ///
+    /// If `source` is a sink name, its `.monitor` source is used, otherwise the monitor of
+    /// the default sink.  Defaults to `false`, can also be set from config as
+    /// `"pulse.monitor"`.
+    pub monitor: Option<bool>,
 }
 
 impl PulseBuilder {
@@ -44,23 +54,172 @@ impl PulseBuilder {
         self
     }
 
-    pub fn create(&self) -> PulseRecorder {
+    pub fn source<S: Into<String>>(&mut self, source: S) -> &mut PulseBuilder {
+        self.source = Some(source.into());
+        self
+    }
+
+    pub fn monitor(&mut self, monitor: bool) -> &mut PulseBuilder {
+        self.monitor = Some(monitor);
+        self
+    }
+
+    /// Return the name of the source to record from, `None` for the default source
+    fn source_name(&self) -> Option<String> {
+        let source = self
+            .source
+            .clone()
+            .or_else(|| crate::CONFIG.get("pulse.source"))
+            .or_else(|| self.device.clone())
+            .or_else(|| crate::CONFIG.get("pulse.device"));
+        let monitor = self
+            .monitor
+            .unwrap_or_else(|| crate::CONFIG.get_or("pulse.monitor", false));
+
+        match source {
+            Some(source) if monitor && !source.ends_with(".monitor") => {
+                Some(format!("{}.monitor", source))
+            }
+            None if monitor => Some("@DEFAULT_MONITOR@".to_string()),
+            source => source,
+        }
+    }
+
+    /// Return the pulseaudio sources, eg. to pick a `"pulse.device"`
+    ///
+    /// pulse-simple can't enumerate sources, so this asks `pactl`.  Each source reports
+    /// its current sample spec as the supported range.  Returns an empty list if `pactl`
+    /// is not available.
+    pub fn list_sources() -> Vec<super::DeviceInfo> {
+        let output = match std::process::Command::new("pactl")
+            .args(["list", "short", "sources"])
+            .output()
+        {
+            Ok(output) if output.status.success() => output,
+            Ok(_) | Err(_) => {
+                log::warn!("Could not list pulseaudio sources with pactl");
+                return Vec::new();
+            }
+        };
+        let default = std::process::Command::new("pactl")
+            .arg("get-default-source")
+            .output()
+            .ok()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+
+        parse_sources(&String::from_utf8_lossy(&output.stdout), default.as_deref())
+    }
+
+    pub fn try_create(&self) -> Result<PulseRecorder, super::RecorderError> {
         PulseRecorder::from_builder(self)
     }
 
+    pub fn create(&self) -> PulseRecorder {
+        self.try_create().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Build the recorder, returning an error instead of panicking if pulseaudio is unusable
+    pub fn try_build(&self) -> Result<Box<dyn super::Recorder>, super::RecorderError> {
+        Ok(Box::new(self.try_create()?))
+    }
+
     pub fn build(&self) -> Box<dyn super::Recorder> {
-        Box::new(self.create())
+        self.try_build().unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+/// Parse the output of `pactl list short sources`
+///
+/// Lines look like `"1\talsa_input.usb\tmodule-alsa-card.c\ts16le 2ch 44100Hz\tIDLE"`.
+fn parse_sources(list: &str, default: Option<&str>) -> Vec<super::DeviceInfo> {
+    list.lines()
+        .filter_map(|line| {
+            let mut columns = line.split('\t');
+            let name = columns.nth(1)?.to_string();
+            let spec = columns.nth(1).unwrap_or("");
+
+            let mut info = super::DeviceInfo {
+                host: "PulseAudio".to_string(),
+                is_default: default == Some(name.as_str()),
+                name,
+                ..Default::default()
+            };
+            for field in spec.split_whitespace() {
+                if let Some(Ok(channels)) = field.strip_suffix("ch").map(str::parse) {
+                    info.channels = (channels, channels);
+                } else if let Some(Ok(rate)) = field.strip_suffix("Hz").map(str::parse) {
+                    info.rates = (rate, rate);
+                }
+            }
+            Some(info)
+        })
+        .collect()
+}
+
+/// Record stream of a pulseaudio source
+///
+/// Split out from pulse-simple so tests can run without a server.
+trait Source {
+    fn read(&mut self, buf: &mut [[analyzer::Sample; 2]]);
+}
+
+impl Source for pulse_simple::Record<[f32; 2]> {
+    #[cfg(not(feature = "f64-samples"))]
+    fn read(&mut self, buf: &mut [[analyzer::Sample; 2]]) {
+        pulse_simple::Record::read(self, buf);
+    }
+
+    // pulse-simple only records `f32`
+    #[cfg(feature = "f64-samples")]
+    fn read(&mut self, buf: &mut [[analyzer::Sample; 2]]) {
+        let mut raw = vec![[0.0; 2]; buf.len()];
+        pulse_simple::Record::read(self, &mut raw);
+        for (s, r) in buf.iter_mut().zip(raw) {
+            *s = r.map(super::pcm::from_f32);
+        }
     }
 }
 
+/// Recorder capturing from a pulseaudio source
+///
+/// Capturing stops when the recorder is dropped.
 #[derive(Debug)]
 pub struct PulseRecorder {
     rate: usize,
     buffer: analyzer::SampleBuffer,
+    thread: super::RecorderThread,
 }
 
 impl PulseRecorder {
-    fn from_builder(build: &PulseBuilder) -> PulseRecorder {
+    fn from_builder(build: &PulseBuilder) -> Result<PulseRecorder, super::RecorderError> {
+        PulseRecorder::from_builder_with(build, |name, desc, device, rate| {
+            // pulse-simple panics if the stream can't be created
+            std::panic::catch_unwind(|| {
+                pulse_simple::Record::<[f32; 2]>::new(name, desc, device, rate)
+            })
+            .map_err(|_| {
+                super::RecorderError::StreamBuild(format!(
+                    "Could not connect to pulseaudio device \"{}\"",
+                    device.unwrap_or("default")
+                ))
+            })
+        })
+    }
+
+    /// Create a recorder whose stream is opened by `connect`
+    ///
+    /// `connect` is called on the recorder thread with the client name, description,
+    /// source and rate, its error is returned from here.
+    fn from_builder_with<S, F>(
+        build: &PulseBuilder,
+        connect: F,
+    ) -> Result<PulseRecorder, super::RecorderError>
+    where
+        S: Source,
+        F: FnOnce(&str, &str, Option<&str>, u32) -> Result<S, super::RecorderError>
+            + Send
+            + 'static,
+    {
         let rate = build
             .rate
             .unwrap_or_else(|| crate::CONFIG.get_or("audio.rate", 8000));
@@ -74,50 +233,77 @@ impl PulseRecorder {
             "visualizer2".to_string(),
             "Pulseaudio recorder for visualizer2".to_string(),
         ));
-        let device = build
-            .device
-            .clone()
-            .or_else(|| crate::CONFIG.get("pulse.device"));
+        let device = build.source_name();
 
         let buf = analyzer::SampleBuffer::new(buffer_size, rate);
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
 
-        {
+        let thread = {
             let buf = buf.clone();
 
-            thread::Builder::new()
-                .name("pulse-recorder".into())
-                .spawn(move || {
-                    let rec: pulse_simple::Record<[analyzer::Sample; 2]> =
-                        pulse_simple::Record::new(
-                            &name,
-                            &desc,
-                            device.as_ref().map(|s| s.as_str()),
-                            rate as u32,
-                        );
-
-                    let mut read_buf = vec![[0.0; 2]; read_size];
-
-                    log::debug!("Pulseaudio:");
-                    log::debug!("    Sample Rate = {:6}", rate);
-                    log::debug!("    Read Size   = {:6}", read_size);
-                    log::debug!("    Buffer Size = {:6}", buffer_size);
-                    if let Some(ref name) = device {
-                        log::debug!("    Device      = \"{}\"", name);
-                    } else {
-                        log::debug!("    Device      = \"default\"");
+            super::RecorderThread::spawn("pulse-recorder", move |running| {
+                let mut rec = match connect(&name, &desc, device.as_deref(), rate as u32) {
+                    Ok(rec) => {
+                        ready_tx.send(Ok(())).unwrap();
+                        rec
                     }
+                    Err(err) => {
+                        ready_tx.send(Err(err)).unwrap();
+                        return;
+                    }
+                };
+
+                let mut read_buf = vec![[0.0; 2]; read_size];
 
-                    loop {
-                        rec.read(&mut read_buf);
+                log::debug!("Pulseaudio:");
+                log::debug!("    Sample Rate = {:6}", rate);
+                log::debug!("    Read Size   = {:6}", read_size);
+                log::debug!("    Buffer Size = {:6}", buffer_size);
+                if let Some(ref name) = device {
+                    log::debug!("    Device      = \"{}\"", name);
+                } else {
+                    log::debug!("    Device      = \"default\"");
+                }
 
-                        buf.push(&read_buf);
-                        log::trace!("Pushed {} samples", read_size);
+                // A read blocks for at most `read_size` samples, so stopping is
+                // noticed quickly
+                let mut since_check = 0;
+                let mut overruns = 0;
+                while running.load(std::sync::atomic::Ordering::Acquire) {
+                    rec.read(&mut read_buf);
+
+                    buf.push(&read_buf);
+                    log::trace!("Pushed {} samples", read_size);
+
+                    // Check for overruns about once a second
+                    since_check += read_size;
+                    if since_check >= rate {
+                        since_check = 0;
+                        let now = buf.overruns();
+                        if now > overruns {
+                            log::warn!(
+                                "Analysis can't keep up, {} pushes overwrote unread samples",
+                                now - overruns
+                            );
+                        }
+                        overruns = now;
                     }
-                })
-                .unwrap();
-        }
+                }
+            })
+        };
+
+        ready_rx.recv().expect("Pulseaudio recorder thread died")?;
 
-        PulseRecorder { rate, buffer: buf }
+        Ok(PulseRecorder {
+            rate,
+            buffer: buf,
+            thread,
+        })
+    }
+
+    /// Stop capturing and wait for the recorder thread to terminate
+    pub fn stop(mut self) {
+        self.thread.stop();
     }
 }
 
@@ -125,4 +311,114 @@ impl super::Recorder for PulseRecorder {
     fn sample_buffer<'a>(&'a self) -> &'a analyzer::SampleBuffer {
         &self.buffer
     }
+
+    fn metadata(&self) -> super::CaptureMetadata {
+        super::CaptureMetadata {
+            rate: self.rate,
+            bit_depth: Some(32),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sources() {
+        let list = "0\talsa_output.pci.monitor\tmodule-alsa-card.c\ts16le 2ch 44100Hz\tSUSPENDED\n\
+                    1\talsa_input.usb\tmodule-alsa-card.c\tfloat32le 1ch 48000Hz\tIDLE\n";
+        let sources = parse_sources(list, Some("alsa_input.usb"));
+
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].name, "alsa_output.pci.monitor");
+        assert_eq!(sources[0].channels, (2, 2));
+        assert_eq!(sources[0].rates, (44100, 44100));
+        assert!(!sources[0].is_default);
+        assert_eq!(sources[1].channels, (1, 1));
+        assert!(sources[1].is_default);
+    }
+
+    /// Source producing silence, at roughly 8000 Hz
+    struct Silence;
+
+    impl Source for Silence {
+        fn read(&mut self, buf: &mut [[analyzer::Sample; 2]]) {
+            std::thread::sleep(std::time::Duration::from_micros(125 * buf.len() as u64));
+            buf.iter_mut().for_each(|s| *s = [0.0; 2]);
+        }
+    }
+
+    fn connected_source(build: &PulseBuilder) -> Option<String> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let recorder = PulseRecorder::from_builder_with(build, move |_, _, device, rate| {
+            assert_eq!(rate, 8000);
+            tx.send(device.map(str::to_string)).unwrap();
+            Ok(Silence)
+        })
+        .unwrap();
+        recorder.stop();
+        rx.recv().unwrap()
+    }
+
+    #[test]
+    fn test_source() {
+        crate::test_config();
+
+        let build = PulseBuilder::new();
+        assert_eq!(connected_source(&build), None);
+
+        let mut build = PulseBuilder::new();
+        build
+            .device("alsa_input.usb")
+            .source("alsa_output.pci.monitor");
+        assert_eq!(
+            connected_source(&build).as_deref(),
+            Some("alsa_output.pci.monitor")
+        );
+
+        // Sink names get their monitor, already monitored ones are left alone
+        let mut build = PulseBuilder::new();
+        build.source("alsa_output.pci").monitor(true);
+        assert_eq!(
+            connected_source(&build).as_deref(),
+            Some("alsa_output.pci.monitor")
+        );
+        build.source("alsa_output.pci.monitor");
+        assert_eq!(
+            connected_source(&build).as_deref(),
+            Some("alsa_output.pci.monitor")
+        );
+
+        let mut build = PulseBuilder::new();
+        build.monitor(true);
+        assert_eq!(
+            connected_source(&build).as_deref(),
+            Some("@DEFAULT_MONITOR@")
+        );
+    }
+
+    #[test]
+    fn test_connect_error() {
+        crate::test_config();
+
+        let err = PulseRecorder::from_builder_with(&PulseBuilder::new(), |_, _, _, _| {
+            Err::<Silence, _>(super::super::RecorderError::StreamBuild(
+                "Connection refused".to_string(),
+            ))
+        })
+        .unwrap_err();
+        assert_eq!(
+            err,
+            super::super::RecorderError::StreamBuild("Connection refused".to_string())
+        );
+    }
+
+    #[test]
+    fn test_list_sources() {
+        // Needs a running server, but must never fail without one
+        let sources = PulseBuilder::list_sources();
+        assert!(sources.iter().filter(|s| s.is_default).count() <= 1);
+    }
 }
@@ -0,0 +1,155 @@
+//! Sample Rate Conversion
+//!
+//! Recorders whose source runs at a rate other than the sample buffer's resample
+//! linearly.  When downsampling, the input is low-pass filtered first, so content above
+//! the new Nyquist frequency does not alias into the spectrum.
+use crate::analyzer;
+use std::collections::VecDeque;
+
+/// Linear resampler keeping its state between blocks
+#[derive(Debug)]
+pub(super) struct Resampler {
+    /// Source samples per output sample
+    step: f64,
+    /// Position of the next output sample, relative to `last`
+    position: f64,
+    last: Option<[analyzer::Sample; 2]>,
+    /// Anti-aliasing filter taps, empty unless downsampling
+    lowpass: Vec<analyzer::Sample>,
+    /// Latest input samples, newest first
+    history: VecDeque<[analyzer::Sample; 2]>,
+}
+
+impl Resampler {
+    pub(super) fn new(from: usize, to: usize) -> Resampler {
+        let step = from as f64 / to as f64;
+        let lowpass = if step > 1.0 {
+            let factor = step.ceil() as usize;
+            analyzer::fourier::windowed_sinc((0.5 / step) as analyzer::Sample, 16 * factor + 1)
+        } else {
+            Vec::new()
+        };
+
+        Resampler {
+            step,
+            position: 0.0,
+            last: None,
+            lowpass,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Low-pass filter the next input sample, if downsampling
+    fn filter(&mut self, sample: [analyzer::Sample; 2]) -> [analyzer::Sample; 2] {
+        if self.lowpass.is_empty() {
+            return sample;
+        }
+
+        // Assume the signal held its first value before, so there is no fade in
+        if self.history.is_empty() {
+            self.history.resize(self.lowpass.len(), sample);
+        }
+        self.history.pop_back();
+        self.history.push_front(sample);
+
+        self.lowpass
+            .iter()
+            .zip(self.history.iter())
+            .fold([0.0; 2], |[l, r], (h, s)| [l + h * s[0], r + h * s[1]])
+    }
+
+    pub(super) fn process(
+        &mut self,
+        input: &[[analyzer::Sample; 2]],
+        out: &mut VecDeque<[analyzer::Sample; 2]>,
+    ) {
+        if self.step == 1.0 {
+            out.extend(input.iter().cloned());
+            return;
+        }
+
+        for &next in input.iter() {
+            let next = self.filter(next);
+            let last = match self.last.replace(next) {
+                Some(last) => last,
+                None => {
+                    out.push_back(next);
+                    self.position = self.step;
+                    continue;
+                }
+            };
+
+            // Emit all output samples between `last` (0.0) and `next` (1.0)
+            while self.position <= 1.0 {
+                let t = self.position as analyzer::Sample;
+                out.push_back([
+                    last[0] + (next[0] - last[0]) * t,
+                    last[1] + (next[1] - last[1]) * t,
+                ]);
+                self.position += self.step;
+            }
+            self.position -= 1.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resampler_ramp() {
+        let ramp = |n: usize| {
+            (0..n)
+                .map(|i| [i as analyzer::Sample, -(i as analyzer::Sample)])
+                .collect::<Vec<_>>()
+        };
+
+        // Upsampling interpolates between the input samples
+        let mut out = VecDeque::new();
+        Resampler::new(4000, 8000).process(&ramp(10), &mut out);
+        assert_eq!(out.len(), 19);
+        assert!(out
+            .iter()
+            .enumerate()
+            .all(|(i, s)| *s == [i as analyzer::Sample / 2.0, -(i as analyzer::Sample) / 2.0]));
+
+        // Downsampling in blocks gives the same as a single call
+        let input = ramp(200);
+        out.clear();
+        Resampler::new(8000, 4000).process(&input, &mut out);
+        let mut blocks = VecDeque::new();
+        let mut resampler = Resampler::new(8000, 4000);
+        resampler.process(&input[..7], &mut blocks);
+        resampler.process(&input[7..], &mut blocks);
+        assert_eq!(out.len(), 100);
+        assert_eq!(out, blocks);
+    }
+
+    #[test]
+    fn test_antialias() {
+        let rms = |freq| {
+            let mut out = VecDeque::new();
+            Resampler::new(8000, 4000)
+                .process(&crate::helpers::signal::sine(8000, freq, 0.5), &mut out);
+            let settled = out
+                .iter()
+                .skip(100)
+                .map(|s| s[0] * s[0])
+                .collect::<Vec<_>>();
+            (settled.iter().sum::<analyzer::Sample>() / settled.len() as analyzer::Sample).sqrt()
+        };
+
+        // Passed below the new Nyquist frequency, removed above instead of aliasing
+        assert!((rms(500.0) - 0.5f32.sqrt() as analyzer::Sample).abs() < 0.01);
+        assert!(rms(3000.0) < 0.01);
+
+        // DC passes from the first sample
+        let mut out = VecDeque::new();
+        Resampler::new(48000, 8000).process(&[[0.5, -0.25]; 60], &mut out);
+        assert_eq!(out.len(), 10);
+        assert!(out
+            .iter()
+            .all(|s| (s[0] - 0.5).abs() < 1e-4 && (s[1] + 0.25).abs() < 1e-4));
+    }
+}
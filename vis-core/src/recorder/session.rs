@@ -0,0 +1,292 @@
+//! Session Recording and Replay
+//!
+//! A session file contains every push to a `SampleBuffer` together with the time it
+//! happened.  Replaying it through a [`ReplayRecorder`](struct.ReplayRecorder.html)
+//! reproduces the exact input which produced a visual.
+//!
+//! To record a session of any visualizer, set `"audio.session.record"` to a file path
+//! in the config.  To replay it, set `"audio.recorder"` to `"replay"` and
+//! `"audio.session.replay"` to the same path.
+//!
+//! The file format is little endian:
+//! * Header: magic `b"VIS2SESS"`, rate as `u32`
+//! * Records: timestamp in seconds as `f32`, number of samples as `u32`, followed
+//!   by the samples as pairs of `f32`
+use crate::analyzer;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"VIS2SESS";
+
+/// Record all pushes to `buffer` into the session file at `path`
+///
+/// Installs a tap on the buffer, the file is flushed after each push and closed once
+/// [`SampleBuffer::untap`](../../analyzer/samples/struct.SampleBuffer.html#method.untap)
+/// is called with the returned handle.
+pub fn record<P: AsRef<Path>>(
+    buffer: &analyzer::SampleBuffer,
+    path: P,
+) -> io::Result<analyzer::samples::TapId> {
+    let mut file = io::BufWriter::new(std::fs::File::create(path.as_ref())?);
+
+    file.write_all(MAGIC)?;
+    file.write_all(&(buffer.rate() as u32).to_le_bytes())?;
+    file.flush()?;
+
+    log::debug!("Recording session to {:?}", path.as_ref());
+
+    let start = std::time::Instant::now();
+    let tap = buffer.tap(Box::new(move |samples| {
+        let res = (|| {
+            file.write_all(&crate::helpers::time(start).to_le_bytes())?;
+            file.write_all(&(samples.len() as u32).to_le_bytes())?;
+            // Sessions always store `f32`, independent of the sample type
+            for [l, r] in samples.iter() {
+                file.write_all(&analyzer::samples::to_f32(*l).to_le_bytes())?;
+                file.write_all(&analyzer::samples::to_f32(*r).to_le_bytes())?;
+            }
+            file.flush()
+        })();
+
+        if let Err(e) = res {
+            log::warn!("Failed writing session: {}", e);
+        }
+    }));
+
+    Ok(tap)
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+    read_u32(r).map(f32::from_bits)
+}
+
+/// A recorded session
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// Rate of the recorded buffer
+    pub rate: usize,
+    /// Pushes with their timestamps
+    pub pushes: Vec<(f32, Vec<[analyzer::Sample; 2]>)>,
+}
+
+impl Session {
+    /// Load a session file
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Session> {
+        let mut file = io::BufReader::new(std::fs::File::open(path)?);
+
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a session file",
+            ));
+        }
+        let rate = read_u32(&mut file)? as usize;
+
+        let mut pushes = Vec::new();
+        loop {
+            let time = match read_f32(&mut file) {
+                Ok(t) => t,
+                Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            let len = read_u32(&mut file)? as usize;
+
+            let samples = (0..len)
+                .map(|_| {
+                    Ok([
+                        read_f32(&mut file)? as analyzer::Sample,
+                        read_f32(&mut file)? as analyzer::Sample,
+                    ])
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            pushes.push((time, samples));
+        }
+
+        Ok(Session { rate, pushes })
+    }
+}
+
+/// Builder for ReplayRecorder
+#[derive(Debug, Default)]
+pub struct ReplayBuilder {
+    /// Path of the session file
+    ///
+    /// Can also be set from config as `"audio.session.replay"`.
+    pub path: Option<PathBuf>,
+    pub buffer_size: Option<usize>,
+}
+
+impl ReplayBuilder {
+    pub fn new() -> ReplayBuilder {
+        Default::default()
+    }
+
+    pub fn path<P: Into<PathBuf>>(&mut self, path: P) -> &mut ReplayBuilder {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn buffer_size(&mut self, buffer_size: usize) -> &mut ReplayBuilder {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    pub fn create(&self) -> io::Result<ReplayRecorder> {
+        ReplayRecorder::from_builder(self)
+    }
+
+    pub fn build(&self) -> Box<dyn super::Recorder> {
+        Box::new(self.create().expect("Failed to load session"))
+    }
+}
+
+/// Recorder replaying a session file
+///
+/// Samples are pushed during [`sync`](../trait.Recorder.html#method.sync) once their
+/// timestamp is reached, so replaying is deterministic.
+#[derive(Debug)]
+pub struct ReplayRecorder {
+    session: Session,
+    path: PathBuf,
+    next: usize,
+    buffer: analyzer::SampleBuffer,
+}
+
+impl ReplayRecorder {
+    fn from_builder(build: &ReplayBuilder) -> io::Result<ReplayRecorder> {
+        let path = build.path.clone().unwrap_or_else(|| {
+            crate::CONFIG
+                .get::<String>("audio.session.replay")
+                .expect("No session file given")
+                .into()
+        });
+        let buffer_size = build
+            .buffer_size
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.buffer", 16000));
+
+        let session = Session::load(&path)?;
+
+        log::debug!("Replay:");
+        log::debug!("    Sample Rate = {:6}", session.rate);
+        log::debug!("    Buffer Size = {:6}", buffer_size);
+        log::debug!("    Pushes      = {:6}", session.pushes.len());
+        log::debug!("    File        = {:?}", path);
+
+        Ok(ReplayRecorder {
+            buffer: analyzer::SampleBuffer::new(buffer_size, session.rate),
+            session,
+            path,
+            next: 0,
+        })
+    }
+}
+
+impl super::Recorder for ReplayRecorder {
+    fn sample_buffer(&self) -> &analyzer::SampleBuffer {
+        &self.buffer
+    }
+
+    fn metadata(&self) -> super::CaptureMetadata {
+        super::CaptureMetadata {
+            layout: super::ChannelLayout::Stereo,
+            rate: self.session.rate,
+            bit_depth: Some(32),
+            title: self
+                .path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned()),
+        }
+    }
+
+    fn sync(&mut self, time: f32) -> bool {
+        while let Some((t, samples)) = self.session.pushes.get(self.next) {
+            if *t > time {
+                break;
+            }
+            self.buffer.push(samples);
+            self.next += 1;
+        }
+
+        self.next < self.session.pushes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::Recorder;
+
+    #[test]
+    fn test_replay() {
+        let path = std::env::temp_dir().join(format!("vis-session-{}", std::process::id()));
+
+        crate::test_config();
+        let mut analyzer = analyzer::FourierBuilder::new()
+            .length(64)
+            .window(analyzer::window::hanning)
+            .downsample(2)
+            .rate(8000)
+            .plan();
+
+        let buffer = analyzer::SampleBuffer::new(256, 8000);
+        let tap = record(&buffer, &path).unwrap();
+
+        let mut frames = Vec::new();
+        for i in 0..10 {
+            let chunk = (0..32)
+                .map(|j| {
+                    // Sessions store `f32`, so only use samples it can represent
+                    let s = (((i * 32 + j) as f32 * 0.3).sin() * i as f32) as analyzer::Sample;
+                    [s, -s]
+                })
+                .collect::<Vec<_>>();
+            buffer.push(&chunk);
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            frames.push(
+                analyzer.analyze(&buffer)[0]
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            );
+        }
+        buffer.untap(tap);
+
+        let session = Session::load(&path).unwrap();
+        assert_eq!(session.rate, 8000);
+        assert_eq!(session.pushes.len(), 10);
+
+        let mut replay = ReplayBuilder::new()
+            .path(&path)
+            .buffer_size(256)
+            .create()
+            .unwrap();
+
+        let metadata = replay.metadata();
+        assert_eq!(metadata.layout.channels(), 2);
+        assert_eq!(metadata.rate, 8000);
+        assert_eq!(metadata.bit_depth, Some(32));
+        assert_eq!(
+            metadata.title.as_deref(),
+            path.file_stem().and_then(|s| s.to_str())
+        );
+
+        for (i, (time, _)) in session.pushes.iter().enumerate() {
+            assert_eq!(replay.sync(*time), i < 9);
+            let frame = analyzer.analyze(replay.sample_buffer())[0]
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>();
+            assert_eq!(frame, frames[i]);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
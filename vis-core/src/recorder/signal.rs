@@ -0,0 +1,309 @@
+//! Synthetic Test Signals
+//!
+//! Feeds a visualizer from generated waveforms instead of live audio, eg. to test an
+//! analyzer deterministically or to run an example without an audio device.  To use it
+//! from the config, set `"audio.recorder"` to `"signal"`.
+//!
+//! Like the file recorder, samples are pushed as time passes: every call to
+//! [`sync`](../trait.Recorder.html#method.sync) pushes the samples up to that time stamp.
+//!
+//! # Example
+//! ```
+//! # vis_core::default_config();
+//! use vis_core::recorder::{signal, Recorder};
+//!
+//! // A chord of two sines
+//! let mut recorder = signal::SignalBuilder::new()
+//!     .rate(8000)
+//!     .tone(signal::Waveform::Sine, 440.0, 0.5)
+//!     .tone(signal::Waveform::Sine, 660.0, 0.25)
+//!     .create();
+//!
+//! recorder.sync(0.5);
+//! assert_eq!(recorder.pushed(), 4000);
+//! ```
+use crate::analyzer::{self, Frequency, Sample};
+use crate::helpers::signal::{self, Noise};
+
+/// Shape of a generated tone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Square,
+    /// Rising from -1 to 1 every period
+    Sawtooth,
+    /// Uniform noise, the frequency is ignored
+    WhiteNoise,
+    Silence,
+}
+
+impl std::str::FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Waveform, String> {
+        match s {
+            "sine" => Ok(Waveform::Sine),
+            "square" => Ok(Waveform::Square),
+            "sawtooth" => Ok(Waveform::Sawtooth),
+            "noise" | "white_noise" => Ok(Waveform::WhiteNoise),
+            "silence" => Ok(Waveform::Silence),
+            _ => Err(format!("Unknown waveform \"{}\"", s)),
+        }
+    }
+}
+
+/// A single waveform at a frequency and amplitude
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub waveform: Waveform,
+    pub freq: Frequency,
+    pub amplitude: Sample,
+}
+
+impl Tone {
+    /// Value of this tone at `phase`, the number of periods since the start
+    fn at(&self, phase: f64, noise: &mut Noise) -> Sample {
+        let fract = phase.fract() as Sample;
+        let value = match self.waveform {
+            Waveform::Sine => signal::sine_at(fract),
+            Waveform::Square => signal::square_at(fract),
+            Waveform::Sawtooth => signal::sawtooth_at(fract),
+            Waveform::WhiteNoise => noise.next(),
+            Waveform::Silence => 0.0,
+        };
+
+        value * self.amplitude
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SignalBuilder {
+    pub rate: Option<usize>,
+    pub buffer_size: Option<usize>,
+    /// Tones summed into the signal
+    ///
+    /// Defaults to a single tone, configured as `"audio.signal.waveform"` (`"sine"`),
+    /// `"audio.signal.freq"` (`440.0`) and `"audio.signal.amplitude"` (`0.5`).
+    pub tones: Vec<Tone>,
+    /// Seed of the noise generator
+    ///
+    /// Defaults to `0`, the same seed always produces the same noise.
+    pub seed: Option<u64>,
+}
+
+impl SignalBuilder {
+    pub fn new() -> SignalBuilder {
+        Default::default()
+    }
+
+    pub fn rate(&mut self, rate: usize) -> &mut SignalBuilder {
+        self.rate = Some(rate);
+        self
+    }
+
+    pub fn buffer_size(&mut self, buffer_size: usize) -> &mut SignalBuilder {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    /// Add a tone to the signal
+    pub fn tone(
+        &mut self,
+        waveform: Waveform,
+        freq: Frequency,
+        amplitude: Sample,
+    ) -> &mut SignalBuilder {
+        self.tones.push(Tone {
+            waveform,
+            freq,
+            amplitude,
+        });
+        self
+    }
+
+    pub fn seed(&mut self, seed: u64) -> &mut SignalBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn create(&self) -> SignalRecorder {
+        SignalRecorder::from_builder(self)
+    }
+
+    pub fn build(&self) -> Box<dyn super::Recorder> {
+        Box::new(self.create())
+    }
+}
+
+/// Recorder pushing a synthesized signal
+///
+/// Both channels carry the same samples.
+#[derive(Debug)]
+pub struct SignalRecorder {
+    rate: usize,
+    tones: Vec<Tone>,
+    noise: Noise,
+    pushed: u64,
+    buffer: analyzer::SampleBuffer,
+    chunk: Vec<[Sample; 2]>,
+}
+
+impl SignalRecorder {
+    fn from_builder(build: &SignalBuilder) -> SignalRecorder {
+        let rate = build
+            .rate
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.rate", 8000));
+        let buffer_size = build
+            .buffer_size
+            .unwrap_or_else(|| crate::CONFIG.get_or("audio.buffer", 16000));
+        let tones = if build.tones.is_empty() {
+            let waveform = crate::CONFIG
+                .get_or("audio.signal.waveform", "sine".to_string())
+                .parse()
+                .unwrap_or_else(|err| {
+                    log::warn!("{}, using a sine", err);
+                    Waveform::Sine
+                });
+            vec![Tone {
+                waveform,
+                freq: crate::CONFIG.get_or("audio.signal.freq", 440.0),
+                amplitude: crate::CONFIG.get_or("audio.signal.amplitude", 0.5),
+            }]
+        } else {
+            build.tones.clone()
+        };
+
+        log::debug!("Signal:");
+        log::debug!("    Sample Rate = {:6}", rate);
+        log::debug!("    Buffer Size = {:6}", buffer_size);
+        for tone in tones.iter() {
+            log::debug!(
+                "    Tone        = {:?} {} Hz * {}",
+                tone.waveform,
+                tone.freq,
+                tone.amplitude
+            );
+        }
+
+        SignalRecorder {
+            rate,
+            tones,
+            noise: Noise::new(build.seed.unwrap_or(0)),
+            pushed: 0,
+            buffer: analyzer::SampleBuffer::new(buffer_size, rate),
+            chunk: Vec::new(),
+        }
+    }
+
+    /// Return the number of samples pushed so far
+    pub fn pushed(&self) -> u64 {
+        self.pushed
+    }
+}
+
+impl super::Recorder for SignalRecorder {
+    fn sample_buffer(&self) -> &analyzer::SampleBuffer {
+        &self.buffer
+    }
+
+    fn sync(&mut self, time: f32) -> bool {
+        let target = (time as f64 * self.rate as f64) as u64;
+        if target <= self.pushed {
+            return true;
+        }
+
+        self.chunk.clear();
+        for i in self.pushed..target {
+            let t = i as f64 / self.rate as f64;
+            let noise = &mut self.noise;
+            let s = self
+                .tones
+                .iter()
+                .map(|tone| tone.at(t * tone.freq as f64, noise))
+                .sum::<Sample>();
+            self.chunk.push([s, s]);
+        }
+        self.buffer.push(&self.chunk);
+        self.pushed = target;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::Recorder;
+
+    #[test]
+    fn test_waveforms() {
+        crate::test_config();
+        let samples = |waveform| {
+            let mut recorder = SignalBuilder::new()
+                .rate(8)
+                .buffer_size(8)
+                .tone(waveform, 1.0, 0.5)
+                .create();
+            recorder.sync(1.0);
+            recorder
+                .sample_buffer()
+                .iter(8, 1)
+                .map(|s| s[0])
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            samples(Waveform::Square),
+            [0.5, 0.5, 0.5, 0.5, -0.5, -0.5, -0.5, -0.5]
+        );
+        assert_eq!(
+            samples(Waveform::Sawtooth),
+            [-0.5, -0.375, -0.25, -0.125, 0.0, 0.125, 0.25, 0.375]
+        );
+        assert_eq!(samples(Waveform::Silence), [0.0; 8]);
+        assert!(samples(Waveform::WhiteNoise).iter().all(|s| s.abs() <= 0.5));
+    }
+
+    #[test]
+    fn test_sync() {
+        crate::test_config();
+        let mut recorder = SignalBuilder::new().rate(8000).create();
+
+        // Pushing in steps produces the same signal as pushing at once
+        for step in 1..=10 {
+            assert!(recorder.sync(step as f32 * 0.05));
+        }
+        assert_eq!(recorder.pushed(), 4000);
+        assert!(recorder.sync(0.25));
+        assert_eq!(recorder.pushed(), 4000);
+
+        let expected = crate::helpers::signal::sine(8000, 440.0, 0.5);
+        for (a, b) in recorder.sample_buffer().iter(4000, 1).zip(expected.iter()) {
+            assert!((a[0] - b[0] * 0.5).abs() < 1e-3, "{} != {}", a[0], b[0]);
+        }
+    }
+
+    #[test]
+    fn test_spectrum() {
+        crate::test_config();
+        let mut recorder = SignalBuilder::new()
+            .rate(8000)
+            .tone(Waveform::Sine, 500.0, 0.5)
+            .tone(Waveform::Sine, 1500.0, 0.25)
+            .create();
+        recorder.sync(1.0);
+
+        let mut analyzer = analyzer::FourierBuilder::new()
+            .rate(8000)
+            .length(512)
+            .window(analyzer::window::from_str("hanning").unwrap())
+            .downsample(1)
+            .plan();
+        analyzer.analyze(recorder.sample_buffer());
+
+        let maxima = analyzer.average().find_maxima_alloc();
+        let tolerance = 1.5 * 8000.0 / 512.0;
+        assert!((maxima[0].0 - 500.0).abs() < tolerance, "{:?}", maxima);
+        assert!((maxima[1].0 - 1500.0).abs() < tolerance, "{:?}", maxima);
+    }
+}
@@ -0,0 +1,144 @@
+//! Test Support
+//!
+//! Building blocks for testing analyzers end-to-end without an audio device.  Enable the
+//! `test-support` feature to use this module from your own tests.
+//!
+//! # Example
+//! ```
+//! # use vis_core::{analyzer, test_support};
+//! test_support::init_config();
+//!
+//! let mut harness = test_support::Harness::new(
+//!     8000,
+//!     analyzer::FourierBuilder::new()
+//!         .length(512)
+//!         .window(analyzer::window::hanning)
+//!         .downsample(1),
+//! );
+//!
+//! let spectrum = harness.feed(&test_support::sine(8000, 1000.0, 512));
+//! let (peak, _) = spectrum.find_maxima_alloc()[0];
+//! assert!((peak - 1000.0).abs() < 20.0);
+//! ```
+use crate::analyzer;
+use crate::recorder;
+
+/// Initialize an empty config
+///
+/// Can be called any number of times, only the first call has an effect.  Use this
+/// if your tests rely on config defaults.
+pub fn init_config() {
+    let _ = crate::CONFIG.init(std::iter::empty());
+}
+
+/// A recorder which only pushes samples when asked to
+#[derive(Debug, Clone)]
+pub struct MockRecorder {
+    buffer: analyzer::SampleBuffer,
+}
+
+impl MockRecorder {
+    /// Create a new mock recorder with a silent buffer
+    pub fn new(size: usize, rate: usize) -> MockRecorder {
+        MockRecorder {
+            buffer: analyzer::SampleBuffer::new(size, rate),
+        }
+    }
+
+    /// Push samples into the buffer
+    pub fn push(&self, samples: &[[analyzer::Sample; 2]]) {
+        self.buffer.push(samples);
+    }
+
+    /// Box this recorder, eg. for [`Visualizer::recorder`](../visualizer/struct.Visualizer.html#method.recorder)
+    pub fn build(&self) -> Box<dyn recorder::Recorder> {
+        Box::new(self.clone())
+    }
+}
+
+impl recorder::Recorder for MockRecorder {
+    fn sample_buffer(&self) -> &analyzer::SampleBuffer {
+        &self.buffer
+    }
+}
+
+/// A `MockRecorder` wired to a `FourierAnalyzer`
+#[derive(Debug)]
+pub struct Harness {
+    recorder: MockRecorder,
+    analyzer: analyzer::FourierAnalyzer,
+}
+
+impl Harness {
+    /// Create a harness with a buffer of twice the size the analyzer needs
+    ///
+    /// Missing builder options are taken from the config defaults.
+    pub fn new(rate: usize, builder: &mut analyzer::FourierBuilder) -> Harness {
+        let analyzer = builder.rate(rate).plan();
+        let size = 2 * analyzer.length() * analyzer.downsample();
+
+        Harness {
+            recorder: MockRecorder::new(size, rate),
+            analyzer,
+        }
+    }
+
+    /// Return the recorder
+    pub fn recorder(&self) -> &MockRecorder {
+        &self.recorder
+    }
+
+    /// Return the sample buffer
+    pub fn buffer(&self) -> &analyzer::SampleBuffer {
+        recorder::Recorder::sample_buffer(&self.recorder)
+    }
+
+    /// Return the analyzer
+    pub fn analyzer(&mut self) -> &mut analyzer::FourierAnalyzer {
+        &mut self.analyzer
+    }
+
+    /// Push samples and return the average spectrum of the analysis
+    pub fn feed(
+        &mut self,
+        samples: &[[analyzer::Sample; 2]],
+    ) -> analyzer::Spectrum<Vec<analyzer::SignalStrength>> {
+        self.recorder.push(samples);
+        self.analyzer.analyze(&self.recorder.buffer);
+
+        let average = self.analyzer.average();
+        let mut spectrum = analyzer::Spectrum::new(
+            vec![0.0; average.len()],
+            average.lowest(),
+            average.highest(),
+        );
+        spectrum.fill_from(&average);
+        spectrum
+    }
+}
+
+/// `len` samples of a full-scale sine at `freq`
+///
+/// See [`helpers::signal`](../helpers/signal/index.html) for more reference signals.
+pub fn sine(rate: usize, freq: f32, len: usize) -> Vec<[analyzer::Sample; 2]> {
+    crate::helpers::signal::sine(rate, freq, len as f32 / rate as f32)
+}
+
+/// `len` samples of a full-scale logarithmic sweep from `from` to `to`
+///
+/// See [`helpers::signal::sweep`](../helpers/signal/fn.sweep.html).
+pub fn sweep(rate: usize, from: f32, to: f32, len: usize) -> Vec<[analyzer::Sample; 2]> {
+    crate::helpers::signal::sweep(rate, from, to, len as f32 / rate as f32)
+}
+
+/// `len` samples of silence with a single full-scale sample at `at`
+pub fn impulse(len: usize, at: usize) -> Vec<[analyzer::Sample; 2]> {
+    let mut samples = silence(len);
+    samples[at] = [1.0; 2];
+    samples
+}
+
+/// `len` samples of silence
+pub fn silence(len: usize) -> Vec<[analyzer::Sample; 2]> {
+    vec![[0.0; 2]; len]
+}
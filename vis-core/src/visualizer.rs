@@ -26,6 +26,14 @@ where
     ///
     /// Can also be set from config as `"audio.conversions"`.
     pub async_analyzer: Option<usize>,
+    /// Milliseconds of audio which must be buffered before the first frame
+    ///
+    /// Can also be set from config as `"audio.pre_roll"`.  Defaults to `0`.
+    pub pre_roll: Option<usize>,
+    /// Time source for syncing the recorder
+    ///
+    /// Defaults to [`Clock::Real`](../frames/enum.Clock.html#variant.Real).
+    pub clock: Option<crate::frames::Clock>,
 }
 
 impl<R, A> Visualizer<R, A>
@@ -42,6 +50,8 @@ where
             analyzer,
             recorder: None,
             async_analyzer: None,
+            pre_roll: None,
+            clock: None,
         }
     }
 
@@ -62,6 +72,35 @@ where
         self
     }
 
+    /// Run the analyzer synchronously in the frame loop
+    ///
+    /// The analyzer is run once per frame, right after the recorder was synced, even if
+    /// `"audio.conversions"` is set in the config.  With a recorder producing samples during
+    /// sync (eg. a [`CallbackRecorder`](../recorder/callback/struct.CallbackRecorder.html)),
+    /// everything happens on the calling thread.
+    pub fn synchronous(mut self) -> Visualizer<R, A> {
+        self.async_analyzer = Some(0);
+        self
+    }
+
+    /// Delay the first frame until `ms` milliseconds of audio are buffered
+    ///
+    /// The first frame is then analyzed immediately, so it is not shown empty, eg. when
+    /// starting from a file.
+    pub fn pre_roll(mut self, ms: usize) -> Visualizer<R, A> {
+        self.pre_roll = Some(ms);
+        self
+    }
+
+    /// Drive the frames with a different clock
+    ///
+    /// With a [`ManualClock`](../frames/struct.ManualClock.html), frames happen only when
+    /// ticked, which makes tests with synthetic or replaying recorders deterministic.
+    pub fn clock(mut self, clock: crate::frames::Clock) -> Visualizer<R, A> {
+        self.clock = Some(clock);
+        self
+    }
+
     /// Create a frames iterator from this visualizer config
     ///
     /// The frames iterator should then be iterated over in you main loop:
@@ -82,3 +121,293 @@ where
         crate::Frames::from_vis(self)
     }
 }
+
+impl Visualizer<crate::frames::FrameData, crate::frames::DataAnalyzer> {
+    /// Create a visualizer whose frames carry typed analysis results
+    ///
+    /// `analyzer` transforms the samples, `kind` selects which results are shared with the
+    /// frames.
+    ///
+    /// # Example
+    /// ```
+    /// # use vis_core::{analyzer, frames};
+    /// # vis_core::default_config();
+    /// let fourier = analyzer::FourierBuilder::new().plan();
+    /// let bars = analyzer::BarsBuilder::new().count(16).build();
+    ///
+    /// let visualizer = vis_core::Visualizer::with_data(frames::FrameKind::Bars(bars), fourier);
+    /// ```
+    pub fn with_data(
+        kind: crate::frames::FrameKind,
+        mut analyzer: analyzer::FourierAnalyzer,
+    ) -> Visualizer<crate::frames::FrameData, crate::frames::DataAnalyzer> {
+        use crate::frames::{FrameData, FrameKind};
+
+        let initial = match kind {
+            FrameKind::Magnitudes => FrameData::Magnitudes(analyzer::Spectrum::new(
+                vec![0.0; analyzer.buckets()],
+                analyzer.lowest(),
+                analyzer.highest(),
+            )),
+            FrameKind::Bars(ref bars) => FrameData::Bars(vec![0.0; bars.count()]),
+            FrameKind::Features => FrameData::Features(Default::default()),
+        };
+
+        let mut kind = kind;
+        Visualizer::new(
+            initial,
+            Box::new(move |data, samples| {
+                analyzer.analyze(samples);
+                let average = analyzer.average();
+
+                match (&mut kind, &mut *data) {
+                    (FrameKind::Magnitudes, FrameData::Magnitudes(spectrum)) => {
+                        spectrum.fill_from(&average)
+                    }
+                    (FrameKind::Bars(bars), FrameData::Bars(out)) => {
+                        out.copy_from_slice(bars.update(&average))
+                    }
+                    (FrameKind::Features, FrameData::Features(features)) => {
+                        *features = crate::frames::Features {
+                            volume: samples.volume(0.1),
+                            energy: average.iter().sum(),
+                            peak: average.find_maxima_alloc().first().map_or(0.0, |&(f, _)| f),
+                        }
+                    }
+                    _ => unreachable!("Frame data does not match its kind"),
+                }
+
+                data
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frames::{FrameData, FrameKind};
+    use crate::test_support;
+
+    #[test]
+    fn test_synchronous() {
+        crate::test_config();
+        let fourier = || {
+            analyzer::FourierBuilder::new()
+                .length(256)
+                .window(analyzer::window::hanning)
+                .downsample(2)
+                .rate(8000)
+                .plan()
+        };
+        fn analyze(
+            mut fourier: analyzer::FourierAnalyzer,
+        ) -> impl for<'r> FnMut(&'r mut Vec<f32>, &analyzer::SampleBuffer) -> &'r mut Vec<f32> + Send
+        {
+            move |info, samples| {
+                fourier.analyze(samples);
+                info.clear();
+                info.extend(fourier.average().iter());
+                info
+            }
+        }
+        let sweep = crate::helpers::signal::sweep(8000, 50.0, 3000.0, 0.5);
+
+        // Synchronous: Generating and analyzing one block per frame
+        let blocks = sweep.clone();
+        let mut position = 0;
+        let recorder = recorder::callback::CallbackBuilder::new()
+            .rate(8000)
+            .buffer_size(1024)
+            .block_size(100)
+            .build(move |block| {
+                block.copy_from_slice(&blocks[position..position + 100]);
+                position += 100;
+            });
+        let mut frames = Visualizer::new(Vec::new(), analyze(fourier()))
+            .recorder(recorder)
+            .synchronous()
+            .frames();
+        let synchronous = frames
+            .iter()
+            .take(40)
+            .last()
+            .unwrap()
+            .info(|info| info.clone());
+
+        // Threaded: A recorder thread pushing and a detached analyzer
+        let mock = test_support::MockRecorder::new(1024, 8000);
+        let mut frames = Visualizer::new(Vec::new(), analyze(fourier()))
+            .recorder(mock.build())
+            .async_analyzer(1000)
+            .frames();
+        std::thread::spawn(move || {
+            for block in sweep.chunks(100).take(40) {
+                mock.push(block);
+            }
+        })
+        .join()
+        .unwrap();
+
+        let start = std::time::Instant::now();
+        for frame in frames.iter() {
+            if frame.info(|info| *info == synchronous) {
+                break;
+            }
+            assert!(start.elapsed().as_secs() < 5, "Results differ");
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_pre_roll() {
+        crate::test_config();
+        fn analyze<'r>(
+            info: &'r mut (u64, f32),
+            samples: &analyzer::SampleBuffer,
+        ) -> &'r mut (u64, f32) {
+            *info = (samples.stats().pushed, samples.volume(0.05));
+            info
+        }
+        let feed = |mock: test_support::MockRecorder| {
+            std::thread::spawn(move || {
+                for block in test_support::sine(8000, 440.0, 1600).chunks(100) {
+                    std::thread::sleep(std::time::Duration::from_millis(2));
+                    mock.push(block);
+                }
+            })
+        };
+
+        for &conversions in [0, 1000].iter() {
+            let mock = test_support::MockRecorder::new(1600, 8000);
+            let mut frames = Visualizer::new((0, 0.0), analyze)
+                .recorder(mock.build())
+                .async_analyzer(conversions)
+                .pre_roll(50)
+                .frames();
+            let feeder = feed(mock);
+
+            let (pushed, volume) = frames.iter().next().unwrap().info(|info| *info);
+            assert!(
+                pushed >= 400,
+                "Only {} samples before the first frame",
+                pushed
+            );
+            assert!(volume > 0.0);
+
+            feeder.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_manual_pre_roll() {
+        crate::test_config();
+        let clock = crate::frames::ManualClock::new();
+        // Only pushes as far as the clock went
+        let recorder = recorder::signal::SignalBuilder::new()
+            .rate(8000)
+            .buffer_size(1024)
+            .build();
+        let mut frames = Visualizer::new(0, |info: &mut u64, samples: &analyzer::SampleBuffer| {
+            *info = samples.stats().pushed;
+            info
+        })
+        .recorder(recorder)
+        .async_analyzer(1000)
+        .pre_roll(50)
+        .clock(crate::frames::Clock::Manual(clock.clone()))
+        .frames();
+
+        // One tick is less than the pre-roll, which is not waited for
+        clock.tick(1.0 / 60.0);
+        assert!(frames.iter().next().is_some());
+        assert_eq!(clock.pending(), 0);
+    }
+
+    #[test]
+    fn test_manual_clock() {
+        crate::test_config();
+        fn analyze(
+            count: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+        ) -> impl for<'r> FnMut(&'r mut u64, &analyzer::SampleBuffer) -> &'r mut u64 + Send
+        {
+            move |info, samples| {
+                count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                *info = samples.stats().pushed;
+                info
+            }
+        }
+
+        let count = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let clock = crate::frames::ManualClock::new();
+        let recorder = recorder::callback::CallbackBuilder::new()
+            .rate(8000)
+            .buffer_size(1024)
+            .block_size(100)
+            .build(|_| ());
+        let mut frames = Visualizer::new(0, analyze(count.clone()))
+            .recorder(recorder)
+            .synchronous()
+            .clock(crate::frames::Clock::Manual(clock.clone()))
+            .frames();
+
+        // No ticks, no frames
+        assert!(frames.iter().next().is_none());
+
+        for _ in 0..5 {
+            clock.tick(0.015625);
+        }
+        assert_eq!(clock.pending(), 5);
+        let times = frames.iter().map(|f| f.time).collect::<Vec<_>>();
+        assert_eq!(times.len(), 5);
+        assert_eq!(count.load(std::sync::atomic::Ordering::Relaxed), 5);
+        assert_eq!(clock.time(), 0.078125);
+        assert_eq!(times[4], 0.078125);
+
+        // Resumes with the next ticks, one block per frame
+        clock.tick(0.015625);
+        clock.tick(0.015625);
+        let last = frames.iter().last().unwrap();
+        assert_eq!(last.time, 0.109375);
+        assert_eq!(last.info(|pushed| *pushed), 700);
+        assert_eq!(count.load(std::sync::atomic::Ordering::Relaxed), 7);
+    }
+
+    #[test]
+    fn test_with_data_bars() {
+        crate::test_config();
+        let fourier = || {
+            analyzer::FourierBuilder::new()
+                .length(256)
+                .window(analyzer::window::hanning)
+                .downsample(1)
+                .rate(8000)
+                .plan()
+        };
+        let bars = || {
+            analyzer::BarsBuilder::new()
+                .count(8)
+                .range(50.0, 4000.0)
+                .build()
+        };
+
+        let recorder = test_support::MockRecorder::new(512, 8000);
+        recorder.push(&test_support::sine(8000, 440.0, 512));
+
+        let mut frames = Visualizer::with_data(FrameKind::Bars(bars()), fourier())
+            .recorder(recorder.build())
+            .frames();
+
+        // The same analysis done by hand
+        let mut expected = bars();
+        let mut analyzer = fourier();
+        analyzer.analyze(recorder::Recorder::sample_buffer(&recorder));
+        expected.update(&analyzer.average());
+
+        let frame = frames.iter().next().unwrap();
+        frame.info(|data| match data {
+            FrameData::Bars(b) => assert_eq!(&b[..], expected.bars()),
+            _ => panic!("Expected bars, got {:?}", data),
+        });
+    }
+}
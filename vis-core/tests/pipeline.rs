@@ -0,0 +1,133 @@
+use vis_core::{analyzer, test_support};
+
+#[test]
+fn test_peak_location() {
+    test_support::init_config();
+    let mut harness = test_support::Harness::new(
+        8000,
+        analyzer::FourierBuilder::new()
+            .length(512)
+            .window(analyzer::window::hanning)
+            .downsample(1),
+    );
+
+    for freq in [250.0, 1000.0, 3000.0].iter().cloned() {
+        let spectrum = harness.feed(&test_support::sine(8000, freq, 1024));
+        let (peak, _) = spectrum.find_maxima_alloc()[0];
+
+        assert!(
+            (peak - freq).abs() < 2.0 * 8000.0 / 512.0,
+            "{} != {}",
+            peak,
+            freq
+        );
+    }
+
+    let silent = harness.feed(&test_support::silence(1024));
+    assert_eq!(silent.max(), 0.0);
+}
+
+#[test]
+fn test_onset() {
+    test_support::init_config();
+    let mut beat = analyzer::BeatBuilder::new()
+        .decay(2000.0)
+        .trigger(0.4)
+        .range(50.0, 100.0)
+        .fourier_length(16)
+        .downsample(10)
+        .rate(8000)
+        .build();
+    let recorder = test_support::MockRecorder::new(8000, 8000);
+    let buffer = vis_core::recorder::Recorder::sample_buffer(&recorder);
+
+    let mut chunks = vec![test_support::silence(40); 20];
+    chunks.extend(
+        test_support::sine(8000, 75.0, 400)
+            .chunks(40)
+            .map(|c| c.to_vec()),
+    );
+    chunks.extend(vec![test_support::silence(40); 20]);
+
+    let beats = chunks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, chunk)| {
+            recorder.push(chunk);
+            if beat.detect(buffer) {
+                Some(i)
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    assert!(!beats.is_empty());
+    assert!(beats.iter().all(|&i| i > 20), "{:?}", beats);
+}
+
+#[test]
+fn test_rms() {
+    let recorder = test_support::MockRecorder::new(4000, 8000);
+    let buffer = vis_core::recorder::Recorder::sample_buffer(&recorder);
+
+    recorder.push(&test_support::sine(8000, 440.0, 4000));
+    let volume = buffer.volume(0.5);
+    assert!((volume - 0.5f32.sqrt()).abs() < 0.01, "{}", volume);
+
+    recorder.push(&test_support::silence(4000));
+    assert_eq!(buffer.volume(0.5), 0.0);
+}
+
+/// A 75 Hz hit with a bumpy exponential decay
+fn drum_hit(rate: usize, len: usize) -> Vec<[analyzer::Sample; 2]> {
+    test_support::sine(rate, 75.0, len)
+        .into_iter()
+        .enumerate()
+        .map(|(i, [s, _])| {
+            let t = i as f32 / rate as f32;
+            let attack = (t / 0.005).min(1.0);
+            let bump = 1.0 + (-((t - 0.12) / 0.02).powi(2)).exp();
+            let env = (attack * (-t / 0.08).exp() * bump) as analyzer::Sample;
+            [s * env, s * env]
+        })
+        .collect()
+}
+
+#[test]
+fn test_onset_hysteresis() {
+    test_support::init_config();
+    let onsets = |release, refractory| {
+        let mut beat = analyzer::BeatBuilder::new()
+            .decay(2000.0)
+            .trigger(0.2)
+            .range(50.0, 100.0)
+            .fourier_length(16)
+            .downsample(10)
+            .rate(8000)
+            .release(release)
+            .refractory(refractory)
+            .build();
+        let recorder = test_support::MockRecorder::new(8000, 8000);
+        let buffer = vis_core::recorder::Recorder::sample_buffer(&recorder);
+
+        let mut chunks = vec![test_support::silence(40); 20];
+        chunks.extend(drum_hit(8000, 3200).chunks(40).map(|c| c.to_vec()));
+
+        chunks
+            .iter()
+            .enumerate()
+            .filter(|(i, chunk)| {
+                recorder.push(chunk);
+                beat.detect_at(buffer, *i as f32 * 0.005)
+            })
+            .count()
+    };
+
+    // Without hysteresis or refractory time, the decay triggers a second onset
+    assert_eq!(onsets(1.0, 0.0), 2);
+
+    assert_eq!(onsets(0.1, 0.0), 1);
+    assert_eq!(onsets(1.0, 0.05), 1);
+    assert_eq!(onsets(0.1, 0.05), 1);
+}